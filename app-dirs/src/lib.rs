@@ -0,0 +1,228 @@
+//! Cross-platform directories of the identity hosts, see the README.
+//!
+//! A host resolves its [`AppDirs`] once at startup with [`init`], then reads
+//! them anywhere with [`get`], like it would read the platform's directories.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable overriding the platform directories.
+pub const HOME_ENV: &str = "HYLE_IDENTITY_HOME";
+
+/// Name of the application directory shared by all hosts.
+const APP: &str = "hyle-identity";
+
+static DIRS: OnceLock<AppDirs> = OnceLock::new();
+
+/// Data, state and cache directories of one host. They are created on first
+/// use.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AppDirs {
+    data: PathBuf,
+    state: PathBuf,
+    cache: PathBuf,
+}
+
+impl AppDirs {
+    /// Directories of `host` (`oidc`, `ecdsa`...): under `root` when given,
+    /// else under `$HYLE_IDENTITY_HOME`, else at the platform's locations.
+    pub fn resolve(host: &str, root: Option<&Path>) -> io::Result<Self> {
+        match root.map(Path::to_path_buf).or_else(home_from_env) {
+            Some(root) => Ok(Self::under(&root, host)),
+            None => Self::platform(host),
+        }
+    }
+
+    /// `<root>/{data,state,cache}/<host>`.
+    pub fn under(root: &Path, host: &str) -> Self {
+        AppDirs {
+            data: root.join("data").join(host),
+            state: root.join("state").join(host),
+            cache: root.join("cache").join(host),
+        }
+    }
+
+    fn platform(host: &str) -> io::Result<Self> {
+        let not_found = |kind| io::Error::new(ErrorKind::NotFound, format!("No {kind} directory"));
+        let data = dirs::data_dir().ok_or_else(|| not_found("data"))?;
+        // Only Linux has a state directory; elsewhere state lives with data
+        let state = dirs::state_dir().unwrap_or_else(|| data.clone());
+        let cache = dirs::cache_dir().ok_or_else(|| not_found("cache"))?;
+        Ok(AppDirs {
+            data: data.join(APP).join(host),
+            state: state.join(APP).join(host),
+            cache: cache.join(APP).join(host),
+        })
+    }
+
+    /// Files that must not be lost, such as keys.
+    pub fn data(&self) -> io::Result<PathBuf> {
+        created(self.data.clone())
+    }
+
+    /// Encrypted account keys, one file per account.
+    pub fn keys(&self) -> io::Result<PathBuf> {
+        created(self.data.join("keys"))
+    }
+
+    /// Stores the host keeps updating across runs.
+    pub fn state(&self) -> io::Result<PathBuf> {
+        created(self.state.clone())
+    }
+
+    /// Files that can be fetched again when missing.
+    pub fn cache(&self) -> io::Result<PathBuf> {
+        created(self.cache.clone())
+    }
+}
+
+fn home_from_env() -> Option<PathBuf> {
+    std::env::var_os(HOME_ENV)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn created(dir: PathBuf) -> io::Result<PathBuf> {
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Sets the directories of this process. Only the first call has an effect.
+pub fn init(dirs: AppDirs) -> &'static AppDirs {
+    DIRS.get_or_init(|| dirs)
+}
+
+/// The directories set by [`init`].
+///
+/// # Panics
+///
+/// When [`init`] hasn't been called yet.
+pub fn get() -> &'static AppDirs {
+    DIRS.get()
+        .expect("app_dirs::init must be called at startup")
+}
+
+/// Outcome of moving files to their new location.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Migration {
+    /// New paths of the moved files
+    pub moved: Vec<PathBuf>,
+    /// Old files left in place because the new location already has them
+    pub kept: Vec<PathBuf>,
+}
+
+/// Moves every file of the `legacy` directory into `target`, then removes
+/// `legacy` if nothing is left in it. Does nothing once `legacy` is gone.
+pub fn migrate_dir(legacy: &Path, target: &Path) -> io::Result<Migration> {
+    let mut migration = Migration::default();
+    if !legacy.is_dir() || legacy == target {
+        return Ok(migration);
+    }
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(legacy)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            move_file(
+                &entry.path(),
+                &target.join(entry.file_name()),
+                &mut migration,
+            )?;
+        }
+    }
+    if fs::read_dir(legacy)?.next().is_none() {
+        fs::remove_dir(legacy)?;
+    }
+    Ok(migration)
+}
+
+/// Moves the `legacy` file to `target`. Does nothing once `legacy` is gone.
+pub fn migrate_file(legacy: &Path, target: &Path) -> io::Result<Migration> {
+    let mut migration = Migration::default();
+    if legacy.is_file() && legacy != target {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        move_file(legacy, target, &mut migration)?;
+    }
+    Ok(migration)
+}
+
+fn move_file(from: &Path, to: &Path, migration: &mut Migration) -> io::Result<()> {
+    if to.exists() {
+        migration.kept.push(from.to_path_buf());
+        return Ok(());
+    }
+    // Renaming fails across filesystems, e.g. when the new root is elsewhere
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    migration.moved.push(to.to_path_buf());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("app-dirs-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_root_separates_hosts_and_kinds() {
+        let root = Path::new("/srv/identity");
+        let dirs = AppDirs::resolve("ecdsa", Some(root)).unwrap();
+        assert_eq!(dirs, AppDirs::under(root, "ecdsa"));
+        assert_eq!(dirs.data, root.join("data/ecdsa"));
+        assert_eq!(dirs.state, root.join("state/ecdsa"));
+        assert_ne!(dirs, AppDirs::under(root, "oidc"));
+    }
+
+    #[test]
+    fn test_migrate_dir_moves_files_once() {
+        let root = scratch("dir");
+        let legacy = root.join("ecdsa_keys");
+        let target = AppDirs::under(&root, "ecdsa").keys().unwrap();
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("alice"), b"alice key").unwrap();
+        fs::write(legacy.join("bob"), b"old bob key").unwrap();
+        fs::write(target.join("bob"), b"new bob key").unwrap();
+
+        let migration = migrate_dir(&legacy, &target).unwrap();
+        assert_eq!(migration.moved, vec![target.join("alice")]);
+        assert_eq!(migration.kept, vec![legacy.join("bob")]);
+        assert_eq!(fs::read(target.join("alice")).unwrap(), b"alice key");
+        // Never overwritten
+        assert_eq!(fs::read(target.join("bob")).unwrap(), b"new bob key");
+        assert!(legacy.exists());
+
+        fs::remove_file(legacy.join("bob")).unwrap();
+        assert_eq!(migrate_dir(&legacy, &target).unwrap(), Migration::default());
+        assert!(!legacy.exists());
+        assert_eq!(migrate_dir(&legacy, &target).unwrap(), Migration::default());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_file() {
+        let root = scratch("file");
+        let legacy = root.join("jwks_pins.json");
+        let target = root.join("state/oidc/jwks_pins.json");
+        fs::write(&legacy, b"{}").unwrap();
+
+        let migration = migrate_file(&legacy, &target).unwrap();
+        assert_eq!(migration.moved, vec![target.clone()]);
+        assert!(!legacy.exists());
+        assert_eq!(
+            migrate_file(&legacy, &target).unwrap(),
+            Migration::default()
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,387 @@
+use attacks::accepted;
+use ecdsa_identity::actions::IdentityAction;
+use ecdsa_identity::pause::pause_message;
+use ecdsa_identity::realm::{self, RealmPolicy};
+use ecdsa_identity::recovery::{self, recovery_code_hash};
+use ecdsa_identity::{registration_message, verification_message, IdentityContractState};
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
+use sdk::{Blob, BlobData, BlobIndex, ContractInput, Digestable};
+
+const CONTRACT: &str = "ecdsa_identity";
+
+fn key() -> SigningKey {
+    SigningKey::from_slice(&[7u8; 48]).expect("invalid test key")
+}
+
+fn pub_key(key: &SigningKey) -> String {
+    hex::encode(VerifyingKey::from(key).to_encoded_point(false).as_bytes())
+}
+
+fn identity(key: &SigningKey) -> String {
+    format!("{}.{CONTRACT}", pub_key(key))
+}
+
+fn encode(signature: &Signature) -> String {
+    hex::encode(signature.to_der().as_bytes())
+}
+
+fn input(state: &IdentityContractState, key: &SigningKey, blobs: Vec<Blob>) -> ContractInput {
+    ContractInput {
+        initial_state: state.as_digest(),
+        identity: identity(key).into(),
+        tx_hash: sdk::TxHash("attack".to_string()),
+        private_input: vec![],
+        tx_ctx: None,
+        blobs,
+        index: BlobIndex(0),
+    }
+}
+
+fn registered(key: &SigningKey) -> IdentityContractState {
+    let register = IdentityAction::RegisterIdentity {
+        signature: encode(&key.sign(registration_message(CONTRACT).as_bytes())),
+        recovery_codes: vec![],
+    };
+    let input = input(
+        &IdentityContractState::new(),
+        key,
+        vec![register.as_blob(CONTRACT.into())],
+    );
+    ecdsa_identity::execute(input)
+        .expect("registration failed")
+        .1
+}
+
+fn verify_blob(nonce: u32, signature: &Signature) -> Blob {
+    IdentityAction::VerifyIdentity {
+        nonce,
+        signature: Some(encode(signature)),
+    }
+    .as_blob(CONTRACT.into())
+}
+
+fn app_blob(data: u8) -> Blob {
+    Blob {
+        contract_name: "app".into(),
+        data: BlobData(vec![data]),
+    }
+}
+
+/// Verifies nonce 0, returning the resulting state and the consumed signature.
+fn verified(key: &SigningKey) -> (IdentityContractState, Signature) {
+    let signature = key.sign(verification_message(CONTRACT, 0, &[]).as_bytes());
+    let input = input(&registered(key), key, vec![verify_blob(0, &signature)]);
+    (ecdsa_identity::execute(input).unwrap().1, signature)
+}
+
+#[test]
+fn signature_replay_on_consumed_nonce() {
+    let key = key();
+    let (state, signature) = verified(&key);
+
+    let replayed = input(&state, &key, vec![verify_blob(0, &signature)]);
+    assert!(accepted(|| ecdsa_identity::execute(replayed)).is_none());
+}
+
+#[test]
+fn malleated_signature_on_consumed_nonce() {
+    let key = key();
+    let (state, signature) = verified(&key);
+    // (r, n - s) verifies for the same message and key
+    let (r, s) = signature.split_scalars();
+    let malleated = Signature::from_scalars(r, -*s).unwrap();
+
+    let replayed = input(&state, &key, vec![verify_blob(0, &malleated)]);
+    assert!(accepted(|| ecdsa_identity::execute(replayed)).is_none());
+}
+
+#[test]
+fn counter_rollback() {
+    let key = key();
+    let (state, _) = verified(&key);
+
+    for nonce in [0, 2] {
+        let signature = key.sign(verification_message(CONTRACT, nonce, &[]).as_bytes());
+        let rolled_back = input(&state, &key, vec![verify_blob(nonce, &signature)]);
+        assert!(accepted(|| ecdsa_identity::execute(rolled_back)).is_none());
+    }
+}
+
+#[test]
+fn stale_state_race() {
+    let key = key();
+    let state = registered(&key);
+    let signature = key.sign(verification_message(CONTRACT, 0, &[]).as_bytes());
+
+    // Two provers race on the same snapshot; only the first can settle
+    let first = input(&state, &key, vec![verify_blob(0, &signature)]);
+    let (_, settled, _) = ecdsa_identity::execute(first.clone()).unwrap();
+
+    let rebased = ContractInput {
+        initial_state: settled.as_digest(),
+        ..first
+    };
+    assert!(accepted(|| ecdsa_identity::execute(rebased)).is_none());
+}
+
+#[test]
+fn cross_contract_blob_swap() {
+    let key = key();
+    let state = registered(&key);
+    // Signed for a transaction also carrying `app_blob(1)`...
+    let signature = key.sign(verification_message(CONTRACT, 0, &[app_blob(1)]).as_bytes());
+
+    // ...replayed next to another blob
+    let swapped = input(&state, &key, vec![verify_blob(0, &signature), app_blob(2)]);
+    assert!(accepted(|| ecdsa_identity::execute(swapped)).is_none());
+}
+
+#[test]
+fn cross_contract_signature_reuse() {
+    let key = key();
+    let lookalike = "ecdsa_identity_v2";
+    let pub_key = hex::encode(VerifyingKey::from(&key).to_encoded_point(false).as_bytes());
+    // Registration signed for `CONTRACT`, submitted to a look-alike deployment
+    let register = IdentityAction::RegisterIdentity {
+        signature: encode(&key.sign(registration_message(CONTRACT).as_bytes())),
+        recovery_codes: vec![],
+    };
+    let reused = ContractInput {
+        identity: format!("{pub_key}.{lookalike}").into(),
+        ..input(
+            &IdentityContractState::new(),
+            &key,
+            vec![register.as_blob(lookalike.into())],
+        )
+    };
+    assert!(accepted(|| ecdsa_identity::execute(reused)).is_none());
+}
+
+#[test]
+fn scheduled_pause_dodge() {
+    let key = key();
+    let pub_key = hex::encode(VerifyingKey::from(&key).to_encoded_point(false).as_bytes());
+    let user = SigningKey::from_slice(&[9u8; 48]).unwrap();
+    let state = IdentityContractState::new().with_pause_admin(pub_key, 60);
+    let run = |state: IdentityContractState, signer: &SigningKey, action, timestamp| {
+        ecdsa_identity::execute_action(
+            state,
+            action,
+            &CONTRACT.into(),
+            identity(signer).into(),
+            &[],
+            &[],
+            timestamp,
+        )
+        .map(|(_, state, _)| state)
+    };
+    let schedule = |signer: &SigningKey, paused, nonce| IdentityAction::SchedulePause {
+        paused,
+        signature: encode(&signer.sign(pause_message(CONTRACT, paused, nonce).as_bytes())),
+    };
+    let register = IdentityAction::RegisterIdentity {
+        signature: encode(&user.sign(registration_message(CONTRACT).as_bytes())),
+        recovery_codes: vec![],
+    };
+
+    // Only the pause admin can schedule one
+    assert!(accepted(|| run(state.clone(), &user, schedule(&user, true, 0), Some(0))).is_none());
+    let state = run(state, &key, schedule(&key, true, 0), Some(0)).unwrap();
+
+    // Not yet in effect, but leaving out the tx context doesn't dodge it
+    assert!(run(state.clone(), &user, register.clone(), Some(59)).is_ok());
+    assert!(accepted(|| run(state.clone(), &user, register.clone(), None)).is_none());
+    assert!(accepted(|| run(state.clone(), &user, register.clone(), Some(60))).is_none());
+    // Nor does replaying the admin's signature once consumed
+    assert!(accepted(|| run(state.clone(), &key, schedule(&key, true, 0), Some(1))).is_none());
+
+    // Once paused, leaving out the tx context doesn't bring a pending unpause
+    // forward either
+    let state = run(state, &key, schedule(&key, false, 1), Some(60)).unwrap();
+    assert!(accepted(|| run(state.clone(), &user, register.clone(), None)).is_none());
+    assert!(accepted(|| run(state.clone(), &user, register.clone(), Some(119))).is_none());
+    assert!(run(state, &user, register, Some(120)).is_ok());
+}
+
+#[test]
+fn registration_front_run_with_other_codes() {
+    let key = key();
+    let pub_key = hex::encode(VerifyingKey::from(&key).to_encoded_point(false).as_bytes());
+    let codes = vec![recovery_code_hash(&pub_key, "victim-code")];
+    let signature = key.sign(recovery::registration_message(CONTRACT, &codes).as_bytes());
+
+    // The registration is copied from the mempool with the attacker's codes
+    let register = IdentityAction::RegisterIdentity {
+        signature: encode(&signature),
+        recovery_codes: vec![recovery_code_hash(&pub_key, "attacker-code")],
+    };
+    let front_run = input(
+        &IdentityContractState::new(),
+        &key,
+        vec![register.as_blob(CONTRACT.into())],
+    );
+    assert!(accepted(|| ecdsa_identity::execute(front_run)).is_none());
+}
+
+#[test]
+fn recovery_code_reuse() {
+    let lost = key();
+    let lost_key = hex::encode(VerifyingKey::from(&lost).to_encoded_point(false).as_bytes());
+    let codes = vec![recovery_code_hash(&lost_key, "one-time")];
+    let register = IdentityAction::RegisterIdentity {
+        signature: encode(&lost.sign(recovery::registration_message(CONTRACT, &codes).as_bytes())),
+        recovery_codes: codes,
+    };
+    let state = ecdsa_identity::execute(input(
+        &IdentityContractState::new(),
+        &lost,
+        vec![register.as_blob(CONTRACT.into())],
+    ))
+    .unwrap()
+    .1;
+
+    let recover = |state: &IdentityContractState, new: &SigningKey| ContractInput {
+        private_input: b"one-time".to_vec(),
+        ..input(
+            state,
+            new,
+            vec![IdentityAction::RecoverWithCode {
+                account: lost_key.clone(),
+                signature: encode(
+                    &new.sign(recovery::recovery_message(CONTRACT, &lost_key).as_bytes()),
+                ),
+            }
+            .as_blob(CONTRACT.into())],
+        )
+    };
+    let first = SigningKey::from_slice(&[9u8; 48]).unwrap();
+    let recovered = ecdsa_identity::execute(recover(&state, &first)).unwrap().1;
+    // Relying parties that recorded the account's history see it moved
+    let before = &state.get_account(&lost_key).unwrap().history;
+    let after = &recovered.get_account(&pub_key(&first)).unwrap().history;
+    assert_eq!((before.events, after.events), (1, 2));
+    assert_ne!(before.head, after.head);
+
+    // The consumed code is worthless, whichever key replays it
+    let second = SigningKey::from_slice(&[11u8; 48]).unwrap();
+    assert!(accepted(|| ecdsa_identity::execute(recover(&recovered, &second))).is_none());
+    assert!(accepted(|| ecdsa_identity::execute(recover(&recovered, &first))).is_none());
+}
+
+#[test]
+fn cross_realm_signature_reuse() {
+    let admin = key();
+    let admin_key = pub_key(&admin);
+    let user = SigningKey::from_slice(&[9u8; 48]).unwrap();
+    let run = |state: IdentityContractState, signer: &SigningKey, action| {
+        ecdsa_identity::execute_action(
+            state,
+            action,
+            &CONTRACT.into(),
+            identity(signer).into(),
+            &[],
+            &[],
+            None,
+        )
+        .map(|(_, state, _)| state)
+    };
+    let create = |realm: &str, nonce| {
+        let policy = RealmPolicy::default();
+        let message = realm::creation_message(CONTRACT, realm, &admin_key, 0, &policy, nonce);
+        IdentityAction::CreateRealm {
+            realm: realm.to_string(),
+            admin: admin_key.clone(),
+            pause_delay: 0,
+            policy,
+            signature: encode(&admin.sign(message.as_bytes())),
+        }
+    };
+    let in_realm = |realm: &str, action| IdentityAction::InRealm {
+        realm: realm.to_string(),
+        action: Box::new(action),
+    };
+    let register = |scope: &str| IdentityAction::RegisterIdentity {
+        signature: encode(&user.sign(registration_message(scope).as_bytes())),
+        recovery_codes: vec![],
+    };
+
+    let state = IdentityContractState::new().with_pause_admin(admin_key.clone(), 0);
+    // Only the root admin creates realms
+    assert!(accepted(|| run(state.clone(), &user, create("acme", 0))).is_none());
+    let state = run(state, &admin, create("acme", 0)).unwrap();
+    let state = run(state, &admin, create("globex", 1)).unwrap();
+    // Nor is a consumed creation replayable
+    assert!(accepted(|| run(state.clone(), &admin, create("acme", 0))).is_none());
+
+    let acme = realm::scope(CONTRACT, "acme");
+    let state = run(state, &user, in_realm("acme", register(&acme))).unwrap();
+
+    // A registration signed for one realm doesn't register elsewhere
+    for scope in [acme.clone(), CONTRACT.to_string()] {
+        let reused = in_realm("globex", register(&scope));
+        assert!(accepted(|| run(state.clone(), &user, reused)).is_none());
+    }
+    assert!(accepted(|| run(state.clone(), &user, register(&acme))).is_none());
+    // Realms can't be nested
+    let nested = in_realm("acme", in_realm("globex", register(&acme)));
+    assert!(accepted(|| run(state, &user, nested)).is_none());
+}
+
+#[test]
+fn realm_takeover() {
+    let root = key();
+    let root_key = pub_key(&root);
+    let realm_admin = SigningKey::from_slice(&[9u8; 48]).unwrap();
+    let realm_admin_key = pub_key(&realm_admin);
+    let run = |state: IdentityContractState, signer: &SigningKey, action| {
+        ecdsa_identity::execute_action(
+            state,
+            action,
+            &CONTRACT.into(),
+            identity(signer).into(),
+            &[],
+            &[],
+            Some(0),
+        )
+        .map(|(_, state, _)| state)
+    };
+    let policy = RealmPolicy::default();
+    let message = realm::creation_message(CONTRACT, "acme", &realm_admin_key, 0, &policy, 0);
+    let create = IdentityAction::CreateRealm {
+        realm: "acme".to_string(),
+        admin: realm_admin_key,
+        pause_delay: 0,
+        policy,
+        signature: encode(&root.sign(message.as_bytes())),
+    };
+    let state = IdentityContractState::new().with_pause_admin(root_key, 0);
+    let state = run(state, &root, create).unwrap();
+
+    let open_policy = |scope: &str, nonce| {
+        let policy = RealmPolicy {
+            origins: vec!["https://evil.example".to_string()],
+            allowed_contracts: vec![],
+        };
+        let signature = realm_admin.sign(realm::policy_message(scope, &policy, nonce).as_bytes());
+        IdentityAction::SetPolicy {
+            policy,
+            signature: encode(&signature),
+        }
+    };
+    let pause = IdentityAction::SchedulePause {
+        paused: true,
+        signature: encode(&realm_admin.sign(pause_message(CONTRACT, true, 1).as_bytes())),
+    };
+
+    // The realm's admin key governs the realm...
+    let acme = realm::scope(CONTRACT, "acme");
+    let in_acme = IdentityAction::InRealm {
+        realm: "acme".to_string(),
+        action: Box::new(open_policy(&acme, 0)),
+    };
+    assert!(run(state.clone(), &realm_admin, in_acme).is_ok());
+    // ...but not the root state
+    assert!(accepted(|| run(state.clone(), &realm_admin, open_policy(CONTRACT, 1))).is_none());
+    assert!(accepted(|| run(state, &realm_admin, pause)).is_none());
+}
@@ -0,0 +1,296 @@
+use attacks::accepted;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey};
+use metamask_identity::actions;
+use metamask_identity::realm::{self, RealmPolicy};
+use metamask_identity::{registration_message, verification_message, IdentityContractState};
+use sdk_v07::identity_provider::IdentityAction;
+use sdk_v07::{Blob, BlobData, BlobIndex, ContractInput, Digestable, HyleOutput};
+use sha3::{Digest, Keccak256};
+
+const CONTRACT: &str = "metamask_identity";
+
+fn key() -> SigningKey {
+    SigningKey::from_slice(&[9u8; 32]).expect("invalid test key")
+}
+
+fn account(key: &SigningKey) -> String {
+    account_on(key, CONTRACT)
+}
+
+fn account_on(key: &SigningKey, contract: &str) -> String {
+    let point = key.verifying_key().to_encoded_point(false);
+    let address = &Keccak256::digest(&point.as_bytes()[1..])[12..];
+    format!("{}.{contract}", hex::encode(address))
+}
+
+/// An EIP-191 personal signature, as MetaMask produces it: `r || s || v`.
+fn personal_sign(key: &SigningKey, message: &str) -> (Signature, RecoveryId) {
+    let prefixed = format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message);
+    key.sign_digest_recoverable(Keccak256::new_with_prefix(prefixed))
+        .expect("signing failed")
+}
+
+fn encode(signature: &Signature, recovery_id: RecoveryId) -> String {
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(recovery_id.to_byte() + 27);
+    hex::encode(bytes)
+}
+
+fn execute(
+    state: &IdentityContractState,
+    account: &str,
+    action: impl bincode::Encode,
+    signature: &str,
+) -> Result<IdentityContractState, String> {
+    execute_on(CONTRACT, state, account, action, signature)
+}
+
+fn execute_on(
+    contract: &str,
+    state: &IdentityContractState,
+    account: &str,
+    action: impl bincode::Encode,
+    signature: &str,
+) -> Result<IdentityContractState, String> {
+    let input = ContractInput {
+        initial_state: state.as_digest(),
+        identity: account.into(),
+        tx_hash: sdk_v07::TxHash("attack".to_string()),
+        private_blob: BlobData(signature.as_bytes().to_vec()),
+        blobs: vec![Blob {
+            contract_name: contract.into(),
+            data: BlobData(
+                bincode::encode_to_vec(action, bincode::config::standard())
+                    .expect("failed to encode action"),
+            ),
+        }],
+        index: BlobIndex(0),
+    };
+    let output: HyleOutput = metamask_identity::execute(input);
+    if output.success {
+        Ok(output.next_state.into())
+    } else {
+        Err(String::from_utf8_lossy(&output.program_outputs).into_owned())
+    }
+}
+
+fn registered(key: &SigningKey) -> (IdentityContractState, String) {
+    let (signature, recovery_id) = personal_sign(key, &registration_message(CONTRACT));
+    let signature = encode(&signature, recovery_id);
+    let register = IdentityAction::RegisterIdentity {
+        account: account(key),
+    };
+    let state = execute(
+        &IdentityContractState::new(),
+        &account(key),
+        register,
+        &signature,
+    )
+    .expect("registration failed");
+    (state, signature)
+}
+
+/// The signature verifying `key`'s account at `nonce`.
+fn verification(key: &SigningKey, nonce: u32) -> String {
+    let (signature, recovery_id) = personal_sign(key, &verification_message(CONTRACT, nonce));
+    encode(&signature, recovery_id)
+}
+
+#[test]
+fn registration_replay() {
+    let key = key();
+    let (state, signature) = registered(&key);
+    let register = IdentityAction::RegisterIdentity {
+        account: account(&key),
+    };
+
+    assert!(accepted(|| execute(&state, &account(&key), register, &signature)).is_none());
+}
+
+#[test]
+fn high_s_registration_signature() {
+    let key = key();
+    let (signature, recovery_id) = personal_sign(&key, &registration_message(CONTRACT));
+    // (r, n - s) with the flipped parity recovers the same key on lax verifiers
+    let (r, s) = signature.split_scalars();
+    let malleated = Signature::from_scalars(r, -*s).unwrap();
+    let flipped = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+    let register = IdentityAction::RegisterIdentity {
+        account: account(&key),
+    };
+
+    let signature = encode(&malleated, flipped);
+    let state = IdentityContractState::new();
+    assert!(accepted(|| execute(&state, &account(&key), register, &signature)).is_none());
+}
+
+#[test]
+fn cross_contract_signature_reuse() {
+    let key = key();
+    let lookalike = "metamask_identity_v2";
+    // Registration signed for `CONTRACT`, submitted to a look-alike deployment
+    let (signature, recovery_id) = personal_sign(&key, &registration_message(CONTRACT));
+    let signature = encode(&signature, recovery_id);
+    let register = IdentityAction::RegisterIdentity {
+        account: account_on(&key, lookalike),
+    };
+
+    let state = IdentityContractState::new();
+    assert!(accepted(|| execute_on(
+        lookalike,
+        &state,
+        &account_on(&key, lookalike),
+        register,
+        &signature
+    ))
+    .is_none());
+}
+
+#[test]
+fn counter_rollback() {
+    let key = key();
+    let (state, _) = registered(&key);
+    let verify = |nonce| IdentityAction::VerifyIdentity {
+        account: account(&key),
+        nonce,
+    };
+    let state = execute(&state, &account(&key), verify(0), &verification(&key, 0)).unwrap();
+
+    for nonce in [0, 2] {
+        let signature = verification(&key, nonce);
+        assert!(accepted(|| execute(&state, &account(&key), verify(nonce), &signature)).is_none());
+    }
+}
+
+#[test]
+fn verification_without_signature() {
+    let key = key();
+    let (state, _) = registered(&key);
+    let verify = IdentityAction::VerifyIdentity {
+        account: account(&key),
+        nonce: 0,
+    };
+
+    assert!(accepted(|| execute(&state, &account(&key), verify, "")).is_none());
+}
+
+/// `key` claims realm `acme`, then registers in it.
+fn registered_in_realm(key: &SigningKey) -> IdentityContractState {
+    let policy = RealmPolicy {
+        origins: vec!["https://acme.example".to_string()],
+    };
+    let (signature, recovery_id) =
+        personal_sign(key, &realm::creation_message(CONTRACT, "acme", &policy));
+    let create = actions::IdentityAction::CreateRealm {
+        realm: "acme".to_string(),
+        account: account(key),
+        policy,
+    };
+    let state = execute(
+        &IdentityContractState::new(),
+        &account(key),
+        create,
+        &encode(&signature, recovery_id),
+    )
+    .expect("realm creation failed");
+
+    let scope = realm::scope(CONTRACT, "acme");
+    let (signature, recovery_id) = personal_sign(key, &registration_message(&scope));
+    let register = in_realm(
+        "acme",
+        actions::IdentityAction::RegisterIdentity {
+            account: account(key),
+        },
+    );
+    execute(
+        &state,
+        &account(key),
+        register,
+        &encode(&signature, recovery_id),
+    )
+    .expect("registration failed")
+}
+
+fn in_realm(realm: &str, action: actions::IdentityAction) -> actions::IdentityAction {
+    actions::IdentityAction::InRealm {
+        realm: realm.to_string(),
+        action: Box::new(action),
+    }
+}
+
+#[test]
+fn cross_realm_signature_reuse() {
+    let key = key();
+    let state = registered_in_realm(&key);
+    let other = SigningKey::from_slice(&[11u8; 32]).unwrap();
+    let policy = RealmPolicy::default();
+    let (signature, recovery_id) = personal_sign(
+        &other,
+        &realm::creation_message(CONTRACT, "globex", &policy),
+    );
+    let create = actions::IdentityAction::CreateRealm {
+        realm: "globex".to_string(),
+        account: account(&other),
+        policy,
+    };
+    let state = execute(
+        &state,
+        &account(&other),
+        create,
+        &encode(&signature, recovery_id),
+    )
+    .unwrap();
+
+    // The registration signed for `acme` registers neither in `globex` nor at the root
+    let scope = realm::scope(CONTRACT, "acme");
+    let (signature, recovery_id) = personal_sign(&key, &registration_message(&scope));
+    let signature = encode(&signature, recovery_id);
+    let register = actions::IdentityAction::RegisterIdentity {
+        account: account(&key),
+    };
+    assert!(accepted(|| execute(
+        &state,
+        &account(&key),
+        in_realm("globex", register.clone()),
+        &signature
+    ))
+    .is_none());
+    assert!(accepted(|| execute(&state, &account(&key), register, &signature)).is_none());
+}
+
+#[test]
+fn realm_takeover() {
+    let key = key();
+    let state = registered_in_realm(&key);
+    let attacker = SigningKey::from_slice(&[11u8; 32]).unwrap();
+    let policy = RealmPolicy {
+        origins: vec!["https://evil.example".to_string()],
+    };
+
+    // Claiming the name again, even with a valid signature of one's own
+    let (signature, recovery_id) = personal_sign(
+        &attacker,
+        &realm::creation_message(CONTRACT, "acme", &policy),
+    );
+    let create = actions::IdentityAction::CreateRealm {
+        realm: "acme".to_string(),
+        account: account(&attacker),
+        policy: policy.clone(),
+    };
+    let signature = encode(&signature, recovery_id);
+    assert!(accepted(|| execute(&state, &account(&attacker), create, &signature)).is_none());
+
+    // Changing the policy without being the realm's admin
+    let scope = realm::scope(CONTRACT, "acme");
+    let (signature, recovery_id) =
+        personal_sign(&attacker, &realm::policy_message(&scope, &policy, 0));
+    let set_policy = in_realm(
+        "acme",
+        actions::IdentityAction::SetPolicy {
+            account: account(&attacker),
+            policy,
+        },
+    );
+    let signature = encode(&signature, recovery_id);
+    assert!(accepted(|| execute(&state, &account(&attacker), set_policy, &signature)).is_none());
+}
@@ -0,0 +1,382 @@
+use alias_registry::{AliasAction, AliasRegistry, IdentityScheme};
+use attacks::accepted;
+use attestation_registry::{AttestationAction, AttestationRegistry};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use e2e::TestIdp;
+use oidc_identity::OidcIdentity;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::realm::RealmConfig;
+use oidc_provider::{
+    AdminGovernance, AdminOperation, IdentityAction, IdentityVerification, PrivateInput,
+};
+use sdk::{Blob, BlobIndex, ContractInput, Digestable, StateDigest};
+
+const IDENTITY_CONTRACT: &str = "oidc_identity";
+const ALICE: &str = "alice.oidc_identity";
+
+/// Context of a block before the test tokens expire.
+fn tx_ctx() -> Option<sdk::TxContext> {
+    Some(sdk::TxContext {
+        block_height: sdk::BlockHeight(1),
+        timestamp: 1_700_000_000,
+        ..Default::default()
+    })
+}
+
+fn input(
+    state: &OidcIdentity,
+    identity: &str,
+    action: IdentityAction,
+    token: &str,
+) -> ContractInput {
+    ContractInput {
+        initial_state: state.as_digest(),
+        identity: identity.into(),
+        tx_hash: sdk::TxHash("attack".to_string()),
+        private_input: PrivateInput::Token(token.to_string()).to_bytes(),
+        tx_ctx: tx_ctx(),
+        blobs: vec![action.as_blob(IDENTITY_CONTRACT.into())],
+        index: BlobIndex(0),
+    }
+}
+
+fn register(idp: &TestIdp, account: &str, subject: &str) -> OidcIdentity {
+    let action = IdentityAction::RegisterIdentity {
+        account: account.to_string(),
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+        recovery_codes: vec![],
+        metadata: AccountMetadata::default(),
+        nonce_strategy: NonceStrategy::default(),
+    };
+    let input = input(
+        &OidcIdentity::new(),
+        account,
+        action,
+        &idp.id_token(subject),
+    );
+    let (_, state, _) = oidc_identity::execute(input).expect("registration failed");
+    state
+}
+
+fn verify(idp: &TestIdp, account: &str, nonce: u32) -> IdentityAction {
+    IdentityAction::VerifyIdentity {
+        account: account.to_string(),
+        nonce,
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+    }
+}
+
+/// Swaps the header of a valid token and replaces its signature.
+fn with_header(token: &str, header: &str, signature: &[u8]) -> String {
+    let payload = token.split('.').nth(1).expect("malformed token");
+    format!(
+        "{}.{}.{}",
+        URL_SAFE_NO_PAD.encode(header),
+        payload,
+        URL_SAFE_NO_PAD.encode(signature)
+    )
+}
+
+#[test]
+fn token_replay_on_consumed_nonce() {
+    let idp = TestIdp::new();
+    let state = register(&idp, ALICE, "alice");
+    let replayed = input(
+        &state,
+        ALICE,
+        verify(&idp, ALICE, 0),
+        &idp.id_token("alice"),
+    );
+
+    let (_, state, _) = oidc_identity::execute(replayed.clone()).unwrap();
+    let replayed = ContractInput {
+        initial_state: state.as_digest(),
+        ..replayed
+    };
+    assert!(accepted(|| oidc_identity::execute(replayed)).is_none());
+}
+
+#[test]
+fn alg_none_token() {
+    let idp = TestIdp::new();
+    let state = register(&idp, ALICE, "alice");
+    let token = with_header(&idp.id_token("alice"), r#"{"alg":"none","typ":"JWT"}"#, b"");
+
+    let forged = input(&state, ALICE, verify(&idp, ALICE, 0), &token);
+    assert!(accepted(|| oidc_identity::execute(forged)).is_none());
+}
+
+#[test]
+fn alg_confusion_hs256_token() {
+    let idp = TestIdp::new();
+    let state = register(&idp, ALICE, "alice");
+    // An HMAC keyed with the public modulus, as libraries trusting `alg` would check
+    let token = with_header(
+        &idp.id_token("alice"),
+        r#"{"alg":"HS256","typ":"JWT"}"#,
+        &URL_SAFE_NO_PAD.decode(&idp.jwk().keys[0].n).unwrap()[..32],
+    );
+
+    let forged = input(&state, ALICE, verify(&idp, ALICE, 0), &token);
+    assert!(accepted(|| oidc_identity::execute(forged)).is_none());
+}
+
+#[test]
+fn self_signed_token_for_allowed_issuer() {
+    // The contract stores the provider's key; the attacker signs tokens in
+    // the provider's name with a key of its own and names it in the blob
+    let attacker = TestIdp::new();
+    let mut provider_keys = attacker.jwk();
+    provider_keys.keys[0].n = URL_SAFE_NO_PAD.encode([0xc5; 64]);
+    let state = OidcIdentity::with_allowed_issuers([(attacker.context.clone(), provider_keys)]);
+
+    let register = IdentityAction::RegisterIdentity {
+        account: ALICE.to_string(),
+        context: attacker.context.clone(),
+        jwk_pub_key: attacker.jwk(),
+        recovery_codes: vec![],
+        metadata: AccountMetadata::default(),
+        nonce_strategy: NonceStrategy::default(),
+    };
+    let forged = input(&state, ALICE, register, &attacker.id_token("alice"));
+    assert!(accepted(|| oidc_identity::execute(forged)).is_none());
+}
+
+#[test]
+fn counter_rollback() {
+    let idp = TestIdp::new();
+    let token = idp.id_token("alice");
+    let mut state = register(&idp, ALICE, "alice");
+    for nonce in 0..2 {
+        let input = input(&state, ALICE, verify(&idp, ALICE, nonce), &token);
+        state = oidc_identity::execute(input).unwrap().1;
+    }
+
+    for nonce in [0, 1, 3] {
+        let rolled_back = input(&state, ALICE, verify(&idp, ALICE, nonce), &token);
+        assert!(accepted(|| oidc_identity::execute(rolled_back)).is_none());
+    }
+}
+
+#[test]
+fn counter_rollback_within_reserved_range() {
+    let idp = TestIdp::new();
+    let token = idp.id_token("alice");
+    let state = register(&idp, ALICE, "alice");
+    let reserve = IdentityAction::ReserveNonces {
+        account: ALICE.to_string(),
+        count: 4,
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+    };
+    let state = oidc_identity::execute(input(&state, ALICE, reserve, &token))
+        .unwrap()
+        .1;
+    let state = oidc_identity::execute(input(&state, ALICE, verify(&idp, ALICE, 2), &token))
+        .unwrap()
+        .1;
+
+    let reused = input(&state, ALICE, verify(&idp, ALICE, 2), &token);
+    assert!(accepted(|| oidc_identity::execute(reused)).is_none());
+}
+
+#[test]
+fn stale_state_race() {
+    let idp = TestIdp::new();
+    let token = idp.id_token("alice");
+    let state = register(&idp, ALICE, "alice");
+
+    // Two provers race on the same snapshot; only the first can settle
+    let first = input(&state, ALICE, verify(&idp, ALICE, 0), &token);
+    let second = input(&state, ALICE, verify(&idp, ALICE, 0), &token);
+    let (_, settled, _) = oidc_identity::execute(first).unwrap();
+
+    let rebased = ContractInput {
+        initial_state: settled.as_digest(),
+        ..second
+    };
+    assert!(accepted(|| oidc_identity::execute(rebased)).is_none());
+}
+
+#[test]
+fn cross_realm_token_reuse() {
+    let idp = TestIdp::new();
+    let mut other_app = TestIdp::new();
+    other_app.context.audience = vec!["other-client".to_string()];
+
+    let admin = "admin.oidc_identity";
+    let jwk = idp.jwk();
+    let token = idp.id_token("admin");
+    let auth = (&idp.context, &jwk, token.as_str());
+    let mut state = OidcIdentity::new().with_admin(admin.to_string(), 0);
+    state
+        .register_identity(admin, &idp.context, &jwk, &token)
+        .expect("registration failed");
+    let create = AdminOperation::CreateRealm {
+        realm: "acme".to_string(),
+        config: RealmConfig {
+            admin: admin.to_string(),
+            min_delay: 0,
+            allowed_issuers: vec![(idp.context.clone(), jwk.clone())],
+            origins: vec![],
+            disclosure_policy: Default::default(),
+            max_inactivity: None,
+        },
+    };
+    let id = state.propose_admin_operation(auth, create, 0).unwrap();
+    state.execute_admin_operation(auth, id, 0).unwrap();
+
+    let register = |idp: &TestIdp| IdentityAction::InRealm {
+        realm: "acme".to_string(),
+        action: Box::new(IdentityAction::RegisterIdentity {
+            account: ALICE.to_string(),
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+            recovery_codes: vec![],
+            metadata: AccountMetadata::default(),
+            nonce_strategy: NonceStrategy::default(),
+        }),
+    };
+    // A token the same provider issued to another application of the
+    // deployment is only good in that application's realm
+    let reused = input(
+        &state,
+        ALICE,
+        register(&other_app),
+        &other_app.id_token("alice"),
+    );
+    assert!(accepted(|| oidc_identity::execute(reused)).is_none());
+    let own = input(&state, ALICE, register(&idp), &idp.id_token("alice"));
+    assert!(oidc_identity::execute(own).is_ok());
+}
+
+fn companion_input(state: StateDigest, identity: &str, blobs: Vec<Blob>) -> ContractInput {
+    ContractInput {
+        initial_state: state,
+        identity: identity.into(),
+        tx_hash: sdk::TxHash("attack".to_string()),
+        private_input: vec![],
+        tx_ctx: None,
+        blobs,
+        index: BlobIndex(1),
+    }
+}
+
+#[test]
+fn attestation_from_untrusted_identity_contract() {
+    let idp = TestIdp::new();
+    let registry = AttestationRegistry::new([IDENTITY_CONTRACT.to_string()]);
+    let attest = AttestationAction::Attest {
+        claim: "google-login".to_string(),
+    };
+    let evil = "alice.evil_identity";
+    let blobs = vec![
+        verify(&idp, evil, 0).as_blob("evil_identity".into()),
+        attest.as_blob("attestation_registry".into()),
+    ];
+
+    let swapped = companion_input(registry.as_digest(), evil, blobs);
+    assert!(accepted(|| attestation_registry::execute(swapped)).is_none());
+}
+
+#[test]
+fn attestation_without_identity_blob() {
+    let registry = AttestationRegistry::new([IDENTITY_CONTRACT.to_string()]);
+    let attest = AttestationAction::Attest {
+        claim: "google-login".to_string(),
+    };
+    let blobs = vec![
+        AttestationAction::Attest {
+            claim: "padding".to_string(),
+        }
+        .as_blob("attestation_registry".into()),
+        attest.as_blob("attestation_registry".into()),
+    ];
+
+    let swapped = companion_input(registry.as_digest(), ALICE, blobs);
+    assert!(accepted(|| attestation_registry::execute(swapped)).is_none());
+}
+
+#[test]
+fn attestation_with_another_accounts_verification() {
+    let idp = TestIdp::new();
+    let registry = AttestationRegistry::new([IDENTITY_CONTRACT.to_string()]);
+    let attest = AttestationAction::Attest {
+        claim: "google-login".to_string(),
+    };
+    let blobs = vec![
+        verify(&idp, "mallory.oidc_identity", 0).as_blob(IDENTITY_CONTRACT.into()),
+        attest.as_blob("attestation_registry".into()),
+    ];
+
+    let swapped = companion_input(registry.as_digest(), ALICE, blobs);
+    assert!(accepted(|| attestation_registry::execute(swapped)).is_none());
+}
+
+#[test]
+fn attestation_next_to_a_query() {
+    let registry = AttestationRegistry::new([IDENTITY_CONTRACT.to_string()]);
+    let attest = AttestationAction::Attest {
+        claim: "google-login".to_string(),
+    };
+    // Needs no proof of the account at all
+    let query = IdentityAction::GetIdentityInfo {
+        account: ALICE.to_string(),
+    };
+    let blobs = vec![
+        query.as_blob(IDENTITY_CONTRACT.into()),
+        attest.as_blob("attestation_registry".into()),
+    ];
+
+    let unproven = companion_input(registry.as_digest(), ALICE, blobs);
+    assert!(accepted(|| attestation_registry::execute(unproven)).is_none());
+}
+
+#[test]
+fn alias_link_with_swapped_identity_blob() {
+    let idp = TestIdp::new();
+    let mallory = "mallory.oidc_identity";
+    let state = register(&idp, mallory, "mallory");
+
+    // Mallory proves their own account in a transaction sent as Alice
+    let blobs = vec![
+        verify(&idp, mallory, 0).as_blob(IDENTITY_CONTRACT.into()),
+        AliasAction::Link {
+            handle: "alice".to_string(),
+        }
+        .as_blob("alias_registry".into()),
+    ];
+    let identity_input = ContractInput {
+        index: BlobIndex(0),
+        private_input: PrivateInput::Token(idp.id_token("mallory")).to_bytes(),
+        tx_ctx: tx_ctx(),
+        ..companion_input(state.as_digest(), ALICE, blobs.clone())
+    };
+    let registry = AliasRegistry::new([(IDENTITY_CONTRACT.to_string(), IdentityScheme::Oidc)]);
+    let alias_input = companion_input(registry.as_digest(), ALICE, blobs);
+
+    let identity_settles = accepted(|| oidc_identity::execute(identity_input)).is_some();
+    let alias_settles = accepted(|| alias_registry::execute(alias_input)).is_some();
+    assert!(!(identity_settles && alias_settles));
+}
+
+#[test]
+fn alias_link_next_to_a_query() {
+    let registry = AliasRegistry::new([(IDENTITY_CONTRACT.to_string(), IdentityScheme::Oidc)]);
+    let query = IdentityAction::GetIdentityInfo {
+        account: ALICE.to_string(),
+    };
+    let blobs = vec![
+        query.as_blob(IDENTITY_CONTRACT.into()),
+        AliasAction::Link {
+            handle: "alice".to_string(),
+        }
+        .as_blob("alias_registry".into()),
+    ];
+
+    let unproven = companion_input(registry.as_digest(), ALICE, blobs);
+    assert!(accepted(|| alias_registry::execute(unproven)).is_none());
+}
@@ -3,6 +3,9 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use sdk::{Blob, BlobData, BlobIndex, ContractAction, ContractName};
 use serde::{Deserialize, Serialize};
 
+use crate::realm::RealmPolicy;
+use crate::work::Work;
+
 extern crate alloc;
 
 /// Enum representing the actions that can be performed by the IdentityVerification contract.
@@ -10,16 +13,130 @@ extern crate alloc;
     Serialize, Deserialize, BorshSerialize, BorshDeserialize, Debug, Clone, Encode, Decode,
 )]
 pub enum IdentityAction {
+    /// `signature` is over [`crate::recovery::registration_message`], binding
+    /// `recovery_codes`: hashes of one-time codes, possibly none.
     RegisterIdentity {
         signature: String,
+        recovery_codes: Vec<String>,
     },
     VerifyIdentity {
         nonce: u32,
         signature: Option<String>,
     },
+    /// Authorize a hot key within `policy`. `signature` is the primary key's
+    /// signature over [`HotKeyPolicy::signing_message`].
+    AuthorizeHotKey {
+        nonce: u32,
+        policy: HotKeyPolicy,
+        signature: String,
+    },
+    /// Same as `VerifyIdentity`, signed by an authorized hot key instead of the primary key.
+    VerifyWithHotKey {
+        nonce: u32,
+        hot_key: String,
+        signature: String,
+    },
+    /// Schedule a pause (or unpause) of the contract, sent by the pause admin.
+    /// `signature` is over [`crate::pause::pause_message`].
+    SchedulePause { paused: bool, signature: String },
+    /// Move `account` (the lost public key) to the sending identity's key with
+    /// one of its recovery codes, passed as private input. `signature` is the
+    /// new key's over [`crate::recovery::recovery_message`].
+    RecoverWithCode { account: String, signature: String },
+    /// Create `realm`, sent by the root state's pause admin. `signature` is
+    /// over [`crate::realm::creation_message`].
+    CreateRealm {
+        realm: String,
+        /// Hex-encoded SEC1 public key of the realm's admin
+        admin: String,
+        /// Delay of the realm's pause switch, in seconds
+        pause_delay: u64,
+        policy: RealmPolicy,
+        signature: String,
+    },
+    /// Replace the policy, sent by the admin of the root state or, wrapped in
+    /// `InRealm`, of a realm. `signature` is over [`crate::realm::policy_message`].
+    SetPolicy {
+        policy: RealmPolicy,
+        signature: String,
+    },
+    /// Apply `action` to the state of `realm` instead of the root state.
+    InRealm {
+        realm: String,
+        action: Box<IdentityAction>,
+    },
+}
+
+/// Bounds within which a hot key may act on behalf of the primary key.
+#[derive(
+    Serialize, Deserialize, BorshSerialize, BorshDeserialize, Debug, Clone, Encode, Decode, PartialEq, Eq,
+)]
+pub struct HotKeyPolicy {
+    /// Hex-encoded SEC1 public key of the hot key
+    pub hot_key: String,
+    /// The hot key may only use nonces in `min_nonce..=max_nonce`
+    pub min_nonce: u32,
+    pub max_nonce: u32,
+    /// Contracts the hot key may send blobs to; empty means any
+    pub allowed_contracts: Vec<String>,
+    /// Unix timestamp (seconds) after which the policy is void
+    pub expires_at: Option<u64>,
+}
+
+impl HotKeyPolicy {
+    /// Message the primary key signs. `nonce` is the primary account's current
+    /// nonce, so a signed policy can't be replayed once consumed, and
+    /// `contract_name` the identity contract it is meant for.
+    pub fn signing_message(&self, contract_name: &str, nonce: u32) -> String {
+        format!(
+            "authorize hot key {} on {} nonces {}..={} contracts [{}] expires {} nonce {}",
+            self.hot_key,
+            contract_name,
+            self.min_nonce,
+            self.max_nonce,
+            self.allowed_contracts.join(","),
+            self.expires_at
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "never".to_string()),
+            nonce
+        )
+    }
 }
 
 impl IdentityAction {
+    /// The signature the action carries, if any.
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            IdentityAction::RegisterIdentity { signature, .. }
+            | IdentityAction::AuthorizeHotKey { signature, .. }
+            | IdentityAction::VerifyWithHotKey { signature, .. }
+            | IdentityAction::SchedulePause { signature, .. }
+            | IdentityAction::RecoverWithCode { signature, .. }
+            | IdentityAction::CreateRealm { signature, .. }
+            | IdentityAction::SetPolicy { signature, .. } => Some(signature),
+            IdentityAction::VerifyIdentity { signature, .. } => signature.as_deref(),
+            IdentityAction::InRealm { action, .. } => action.signature(),
+        }
+    }
+
+    /// Signatures and hashes the action costs when it succeeds. Failures stop
+    /// early and don't report any work.
+    pub fn work(&self) -> Work {
+        match self {
+            IdentityAction::RegisterIdentity { .. } | IdentityAction::VerifyIdentity { .. } => {
+                Work::new(1, 1)
+            }
+            // The code hash, then the new account hash
+            IdentityAction::RecoverWithCode { .. } => Work::new(1, 2),
+            IdentityAction::AuthorizeHotKey { .. }
+            | IdentityAction::VerifyWithHotKey { .. }
+            | IdentityAction::SchedulePause { .. }
+            | IdentityAction::CreateRealm { .. }
+            | IdentityAction::SetPolicy { .. } => Work::new(1, 0),
+            IdentityAction::InRealm { action, .. } => action.work(),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn as_blob(&self, contract_name: ContractName) -> Blob {
         <Self as ContractAction>::as_blob(self, contract_name, None, None)
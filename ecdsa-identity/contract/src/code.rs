@@ -0,0 +1,174 @@
+use core::fmt;
+
+use crate::work::Work;
+
+/// Stable result code of an identity action, leading its program output.
+///
+/// Clients match on the number, never on the message: messages are for humans
+/// and may be reworded. Codes are never renumbered or reused, new ones are
+/// appended. Codes shared with the OIDC identity contract keep its numbers;
+/// the ones specific to signatures and hot keys start at `x50`. The hundreds
+/// give the kind: `0` is success, `1xx` invalid input, `2xx` malformed state
+/// and `3xx` rejected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResultCode {
+    Ok,
+    InvalidInput,
+    MissingTxContext,
+    MissingRecoveryCode,
+    InvalidRealmName,
+    MissingSignature,
+    InvalidAccountName,
+    MalformedState,
+    IdentityNotFound,
+    IdentityExists,
+    InvalidNonce,
+    KeyAccountMismatch,
+    GovernanceDisabled,
+    ContractPaused,
+    InvalidRecoveryCode,
+    RealmNotFound,
+    RealmExists,
+    NestedRealm,
+    InvalidSignature,
+    EmptyHotKeyRange,
+    HotKeyNotAuthorized,
+    NonceOutsideHotKeyPolicy,
+    HotKeyPolicyExpired,
+    ContractNotAllowed,
+}
+
+impl ResultCode {
+    const ALL: [ResultCode; 24] = [
+        ResultCode::Ok,
+        ResultCode::InvalidInput,
+        ResultCode::MissingTxContext,
+        ResultCode::MissingRecoveryCode,
+        ResultCode::InvalidRealmName,
+        ResultCode::MissingSignature,
+        ResultCode::InvalidAccountName,
+        ResultCode::MalformedState,
+        ResultCode::IdentityNotFound,
+        ResultCode::IdentityExists,
+        ResultCode::InvalidNonce,
+        ResultCode::KeyAccountMismatch,
+        ResultCode::GovernanceDisabled,
+        ResultCode::ContractPaused,
+        ResultCode::InvalidRecoveryCode,
+        ResultCode::RealmNotFound,
+        ResultCode::RealmExists,
+        ResultCode::NestedRealm,
+        ResultCode::InvalidSignature,
+        ResultCode::EmptyHotKeyRange,
+        ResultCode::HotKeyNotAuthorized,
+        ResultCode::NonceOutsideHotKeyPolicy,
+        ResultCode::HotKeyPolicyExpired,
+        ResultCode::ContractNotAllowed,
+    ];
+
+    pub fn code(self) -> u16 {
+        match self {
+            ResultCode::Ok => 0,
+            ResultCode::InvalidInput => 100,
+            ResultCode::MissingTxContext => 103,
+            ResultCode::MissingRecoveryCode => 104,
+            ResultCode::InvalidRealmName => 105,
+            ResultCode::MissingSignature => 150,
+            ResultCode::InvalidAccountName => 151,
+            ResultCode::MalformedState => 200,
+            ResultCode::IdentityNotFound => 300,
+            ResultCode::IdentityExists => 301,
+            ResultCode::InvalidNonce => 302,
+            ResultCode::KeyAccountMismatch => 304,
+            ResultCode::GovernanceDisabled => 313,
+            ResultCode::ContractPaused => 320,
+            ResultCode::InvalidRecoveryCode => 321,
+            ResultCode::RealmNotFound => 322,
+            ResultCode::RealmExists => 323,
+            ResultCode::NestedRealm => 324,
+            ResultCode::InvalidSignature => 350,
+            ResultCode::EmptyHotKeyRange => 351,
+            ResultCode::HotKeyNotAuthorized => 352,
+            ResultCode::NonceOutsideHotKeyPolicy => 353,
+            ResultCode::HotKeyPolicyExpired => 354,
+            ResultCode::ContractNotAllowed => 355,
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|result| result.code() == code)
+    }
+
+    /// Human-readable description; not part of the stable interface.
+    pub fn message(self) -> &'static str {
+        match self {
+            ResultCode::Ok => "Ok",
+            ResultCode::InvalidInput => "Invalid input",
+            ResultCode::MissingTxContext => "This action requires the tx context",
+            ResultCode::MissingRecoveryCode => "This action requires a recovery code",
+            ResultCode::InvalidRealmName => "Invalid realm name",
+            ResultCode::MissingSignature => "Missing signature",
+            ResultCode::InvalidAccountName => "Invalid account extension",
+            ResultCode::MalformedState => "Could not decode identity state",
+            ResultCode::IdentityNotFound => "Identity not found",
+            ResultCode::IdentityExists => "Identity already exists",
+            ResultCode::InvalidNonce => "Invalid nonce",
+            ResultCode::KeyAccountMismatch => "Public key does not match the account",
+            ResultCode::GovernanceDisabled => "No pause admin configured",
+            ResultCode::ContractPaused => "Contract is paused",
+            ResultCode::InvalidRecoveryCode => "Recovery code unknown or already used",
+            ResultCode::RealmNotFound => "Realm not found",
+            ResultCode::RealmExists => "Realm already exists",
+            ResultCode::NestedRealm => "Realms can't be nested",
+            ResultCode::InvalidSignature => "Invalid signature",
+            ResultCode::EmptyHotKeyRange => "Empty hot key nonce range",
+            ResultCode::HotKeyNotAuthorized => "Hot key not authorized",
+            ResultCode::NonceOutsideHotKeyPolicy => "Nonce outside of the hot key policy",
+            ResultCode::HotKeyPolicyExpired => "Hot key policy expired",
+            ResultCode::ContractNotAllowed => "Blob contract not allowed by the hot key policy",
+        }
+    }
+
+    /// Program output of a failure with this code and the given detail.
+    pub fn fail(self, detail: impl fmt::Display) -> String {
+        output(self, &detail.to_string())
+    }
+}
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<ResultCode> for String {
+    fn from(code: ResultCode) -> Self {
+        code.fail(code.message())
+    }
+}
+
+/// Formats a program output: the numeric code, then `:` and the detail.
+pub fn output(code: ResultCode, detail: &str) -> String {
+    format!("{}:{}", code.code(), detail)
+}
+
+/// Formats a successful program output, with the action's [`Work`] between
+/// the code and the detail: `<code>/<work>:<detail>`.
+pub fn output_with_work(code: ResultCode, work: Work, detail: &str) -> String {
+    format!("{}/{}:{}", code.code(), work, detail)
+}
+
+/// Splits a program output written by [`output`] or [`output_with_work`] into
+/// its code and detail. Outputs from older guests give `None`.
+pub fn parse_output(output: &str) -> Option<(ResultCode, &str)> {
+    let (header, detail) = output.split_once(':')?;
+    let code = header.split_once('/').map_or(header, |(code, _)| code);
+    Some((ResultCode::from_code(code.parse().ok()?)?, detail))
+}
+
+/// The work reported by an output written by [`output_with_work`]; `None` for
+/// failures and outputs from older guests.
+pub fn parse_work(output: &str) -> Option<Work> {
+    let (header, _) = output.split_once(':')?;
+    Work::parse(header.split_once('/')?.1)
+}
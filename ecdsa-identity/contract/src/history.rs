@@ -0,0 +1,52 @@
+//! Control history of an account: a rolling hash over the events that change
+//! which keys control it. Kept next to each account and only ever extended,
+//! so a relying party that recorded it can tell whether the account was
+//! recovered or given a new hot key since.
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Security-relevant events of an account.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SecurityEvent<'a> {
+    /// Registered with the primary key hashing to `hash`
+    Registered { hash: &'a str },
+    /// `hot_key` authorized, or its policy replaced
+    HotKeyAuthorized { hot_key: &'a str },
+    /// Moved to the primary key hashing to `hash` with a recovery code
+    Recovered { hash: &'a str },
+}
+
+impl SecurityEvent<'_> {
+    /// Canonical text of the event, as hashed into the history.
+    pub fn describe(&self) -> String {
+        match self {
+            SecurityEvent::Registered { hash } => format!("registered {hash}"),
+            SecurityEvent::HotKeyAuthorized { hot_key } => format!("hot key {hot_key}"),
+            SecurityEvent::Recovered { hash } => format!("recovered {hash}"),
+        }
+    }
+}
+
+/// Head of an account's history, and how many events it covers.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct ControlHistory {
+    /// Hex; empty before the first event
+    pub head: String,
+    pub events: u32,
+}
+
+impl ControlHistory {
+    pub fn record(&mut self, event: SecurityEvent) {
+        self.head = next_head(&self.head, event);
+        self.events += 1;
+    }
+}
+
+/// Head following `head` once `event` is recorded: `sha256("{head}:{event}")`.
+pub fn next_head(head: &str, event: SecurityEvent) -> String {
+    hex::encode(Sha256::digest(
+        format!("{head}:{}", event.describe()).as_bytes(),
+    ))
+}
@@ -3,7 +3,11 @@ use std::collections::BTreeMap;
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
-use actions::IdentityAction;
+use actions::{HotKeyPolicy, IdentityAction};
+use code::ResultCode;
+use history::{ControlHistory, SecurityEvent};
+use pause::PauseSwitch;
+use realm::RealmPolicy;
 
 use hex::decode;
 use p384::ecdsa::signature::Verifier;
@@ -12,28 +16,42 @@ use sdk::{Digestable, RunResult};
 use sha2::{Digest, Sha256};
 
 pub mod actions;
+pub mod code;
+pub mod history;
+pub mod limits;
+pub mod pause;
+pub mod realm;
+pub mod recovery;
+pub mod work;
 
 extern crate alloc;
 
-/// Entry point of the contract's logic
+/// Entry point of the contract's logic. Program outputs start with a stable
+/// [`ResultCode`], see [`code::output`]; successes also carry the action's
+/// [`work::Work`].
 pub fn execute(contract_input: sdk::ContractInput) -> RunResult<IdentityContractState> {
+    limits::check_input(&contract_input).map_err(|err| ResultCode::InvalidInput.fail(err))?;
+
     // Parse contract inputs
     let (input, action) = sdk::guest::init_raw::<IdentityAction>(contract_input);
 
-    let action = action.ok_or("Failed to parse action")?;
+    let action = action.ok_or_else(|| ResultCode::InvalidInput.fail("Failed to parse action"))?;
+    if let Some(signature) = action.signature() {
+        limits::check_signature(signature).map_err(|err| ResultCode::InvalidInput.fail(err))?;
+    }
 
     // Parse initial state
-    let state: IdentityContractState = input
-        .initial_state
-        .clone()
-        .try_into()
-        .expect("failed to parse state");
+    let state: IdentityContractState =
+        bincode::decode_from_slice(&input.initial_state.0, bincode::config::standard())
+            .map(|(state, _)| state)
+            .map_err(|_| String::from(ResultCode::MalformedState))?;
 
     let identity = input.identity;
+    let timestamp = input.tx_ctx.as_ref().map(|ctx| ctx.timestamp);
     let contract_name = &input
         .blobs
         .get(input.index.0)
-        .ok_or("No blob")?
+        .ok_or_else(|| ResultCode::InvalidInput.fail("No blob"))?
         .contract_name;
 
     if input.index.0 == 0 {
@@ -42,13 +60,29 @@ pub fn execute(contract_input: sdk::ContractInput) -> RunResult<IdentityContract
             .blobs
             .split_first()
             .map(|(_, rest)| rest)
-            .ok_or("No blobs")?;
-        execute_action(state, action, contract_name, identity, blobs)
+            .ok_or_else(|| ResultCode::InvalidInput.fail("No blobs"))?;
+        execute_action(
+            state,
+            action,
+            contract_name,
+            identity,
+            blobs,
+            &input.private_input,
+            timestamp,
+        )
     } else {
         // Otherwise, it's less efficient as need to clone blobs & the remove is O(n)
         let mut blobs = input.blobs.clone();
         blobs.remove(input.index.0);
-        execute_action(state, action, contract_name, identity, &blobs)
+        execute_action(
+            state,
+            action,
+            contract_name,
+            identity,
+            &blobs,
+            &input.private_input,
+            timestamp,
+        )
     }
 }
 
@@ -57,12 +91,22 @@ pub fn execute(contract_input: sdk::ContractInput) -> RunResult<IdentityContract
 pub struct AccountInfo {
     pub hash: String,
     pub nonce: u32,
+    /// Hot keys authorized by the primary key, by hex public key
+    pub hot_keys: BTreeMap<String, HotKeyPolicy>,
+    /// Hashes of the account's unused recovery codes
+    pub recovery_codes: Vec<String>,
+    /// Registration, hot key authorizations and recoveries so far
+    pub history: ControlHistory,
 }
 
 /// The state of the contract, that is totally serialized on-chain
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
 pub struct IdentityContractState {
     identities: BTreeMap<String, AccountInfo>,
+    pause: PauseSwitch,
+    policy: RealmPolicy,
+    /// Realms of the root state, by name; always empty in a realm's state
+    realms: BTreeMap<String, IdentityContractState>,
 }
 
 /// Some helper methods for the state
@@ -70,13 +114,64 @@ impl IdentityContractState {
     pub fn new() -> Self {
         IdentityContractState {
             identities: BTreeMap::new(),
+            pause: PauseSwitch::default(),
+            policy: RealmPolicy::default(),
+            realms: BTreeMap::new(),
         }
     }
 
-    pub fn get_nonce(&self, username: &str) -> Result<u32, &'static str> {
-        let info = self.identities.get(username).ok_or("Identity not found")?;
+    /// Lets `pub_key` (hex SEC1) pause the contract, `delay` seconds after
+    /// scheduling it.
+    pub fn with_pause_admin(mut self, pub_key: String, delay: u64) -> Self {
+        self.pause.admin = Some(pub_key);
+        self.pause.delay = delay;
+        self
+    }
+
+    pub fn pause_switch(&self) -> &PauseSwitch {
+        &self.pause
+    }
+
+    pub fn policy(&self) -> &RealmPolicy {
+        &self.policy
+    }
+
+    pub fn realm(&self, realm: &str) -> Option<&IdentityContractState> {
+        self.realms.get(realm)
+    }
+
+    pub fn get_nonce(&self, username: &str) -> Result<u32, ResultCode> {
+        let info = self
+            .identities
+            .get(username)
+            .ok_or(ResultCode::IdentityNotFound)?;
         Ok(info.nonce)
     }
+
+    pub fn get_account(&self, username: &str) -> Result<&AccountInfo, ResultCode> {
+        self.identities
+            .get(username)
+            .ok_or(ResultCode::IdentityNotFound)
+    }
+
+    /// The registered accounts, by public key in hex order.
+    pub fn accounts(&self) -> impl Iterator<Item = (&String, &AccountInfo)> {
+        self.identities.iter()
+    }
+
+    /// Strict decoding of a state digest, rejecting trailing bytes, for hosts
+    /// inspecting a state they can't vouch for.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let (state, read) = bincode::decode_from_slice(bytes, bincode::config::standard())
+            .map_err(|err| format!("Could not decode identity state: {}", err))?;
+        if read != bytes.len() {
+            return Err(format!(
+                "Could not decode identity state: {} trailing bytes",
+                bytes.len() - read
+            ));
+        }
+        Ok(state)
+    }
 }
 
 pub fn execute_action(
@@ -85,93 +180,209 @@ pub fn execute_action(
     contract_name: &sdk::ContractName,
     account: sdk::Identity,
     blobs: &[sdk::Blob],
+    private_input: &[u8],
+    timestamp: Option<u64>,
 ) -> RunResult<IdentityContractState> {
     if !account.0.ends_with(&contract_name.0) {
-        return Err(format!(
-            "Invalid account extension. '.{contract_name}' expected."
-        ));
+        return Err(
+            ResultCode::InvalidAccountName.fail(format_args!("'.{contract_name}' expected"))
+        );
     }
     let pub_key = account
         .0
         .trim_end_matches(&contract_name.0)
         .trim_end_matches(".");
 
-    let program_output = match action {
-        IdentityAction::RegisterIdentity { signature } => {
-            state.register_identity(pub_key, &signature)
-        }
-        IdentityAction::VerifyIdentity { nonce, signature } => match signature {
-            Some(sig) => match state.verify_identity(pub_key, nonce, blobs, &sig) {
-                Ok(true) => Ok(format!("Identity verified for account: {}", account)),
-                Ok(false) => Err(format!(
-                    "Identity verification failed for account: {}",
-                    account
-                )),
-                Err(err) => Err(format!("⚠️ Error verifying identity: {}", err)),
-            },
-            None => Err(format!(
-                "Identity verification failed for account {}, missing signature",
-                account
-            )),
-        },
-    };
-    program_output.map(|output| (output, state, alloc::vec![]))
+    let mut work = action.work();
+    let detail = apply_action(
+        &mut state,
+        action,
+        &contract_name.0,
+        pub_key,
+        &account.0,
+        blobs,
+        private_input,
+        timestamp,
+    )?;
+    work.state_bytes = state.as_digest().0.len() as u64;
+    Ok((
+        code::output_with_work(ResultCode::Ok, work, &detail),
+        state,
+        alloc::vec![],
+    ))
+}
+
+/// Applies `action` to `state`, the root state or a realm's. `scope` is the
+/// name messages are signed for, see [`realm::scope`]. The detail of a success
+/// is the account, or the authorized hot key.
+#[allow(clippy::too_many_arguments)]
+fn apply_action(
+    state: &mut IdentityContractState,
+    action: IdentityAction,
+    scope: &str,
+    pub_key: &str,
+    account: &str,
+    blobs: &[sdk::Blob],
+    private_input: &[u8],
+    timestamp: Option<u64>,
+) -> Result<String, ResultCode> {
+    // A paused root state stops its realms too
+    state.pause.settle(timestamp)?;
+    if state.pause.paused && !matches!(action, IdentityAction::SchedulePause { .. }) {
+        return Err(ResultCode::ContractPaused);
+    }
+
+    match action {
+        IdentityAction::RegisterIdentity {
+            signature,
+            recovery_codes,
+        } => state
+            .register_identity(scope, pub_key, &signature, recovery_codes)
+            .map(|()| account.to_string()),
+        IdentityAction::VerifyIdentity { nonce, signature } => {
+            let signature = signature.ok_or(ResultCode::MissingSignature)?;
+            match state.verify_identity(scope, pub_key, nonce, blobs, &signature)? {
+                true => Ok(account.to_string()),
+                false => Err(ResultCode::KeyAccountMismatch),
+            }
+        }
+        IdentityAction::AuthorizeHotKey {
+            nonce,
+            policy,
+            signature,
+        } => state.authorize_hot_key(scope, pub_key, nonce, policy, &signature),
+        IdentityAction::VerifyWithHotKey {
+            nonce,
+            hot_key,
+            signature,
+        } => state
+            .verify_with_hot_key(
+                scope, pub_key, nonce, &hot_key, blobs, &signature, timestamp,
+            )
+            .map(|()| account.to_string()),
+        IdentityAction::SchedulePause { paused, signature } => state
+            .schedule_pause(scope, pub_key, paused, &signature, timestamp)
+            .map(|()| account.to_string()),
+        IdentityAction::RecoverWithCode {
+            account: lost_key,
+            signature,
+        } => {
+            let code = core::str::from_utf8(private_input)
+                .ok()
+                .filter(|code| !code.is_empty())
+                .ok_or(ResultCode::MissingRecoveryCode)?;
+            state
+                .recover_with_code(scope, &lost_key, pub_key, code, &signature)
+                .map(|()| account.to_string())
+        }
+        IdentityAction::CreateRealm {
+            realm,
+            admin,
+            pause_delay,
+            policy,
+            signature,
+        } => state
+            .create_realm(
+                scope,
+                pub_key,
+                &realm,
+                admin,
+                pause_delay,
+                policy,
+                &signature,
+            )
+            .map(|()| realm),
+        IdentityAction::SetPolicy { policy, signature } => state
+            .set_policy(scope, pub_key, policy, &signature)
+            .map(|()| account.to_string()),
+        IdentityAction::InRealm { realm, action } => {
+            realm::check_name(&realm)?;
+            if matches!(
+                *action,
+                IdentityAction::InRealm { .. } | IdentityAction::CreateRealm { .. }
+            ) {
+                return Err(ResultCode::NestedRealm);
+            }
+            let realm_state = state
+                .realms
+                .get_mut(&realm)
+                .ok_or(ResultCode::RealmNotFound)?;
+            apply_action(
+                realm_state,
+                *action,
+                &realm::scope(scope, &realm),
+                pub_key,
+                account,
+                blobs,
+                private_input,
+                timestamp,
+            )
+        }
+    }
 }
 
 // The IdentityVerification trait is implemented for the IdentityContractState struct
 // This trait is given by the sdk, as a "standard" for identity verification contracts
 // but you could do the same logic without it.
 impl IdentityContractState {
-    fn register_identity(&mut self, pub_key: &str, signature: &str) -> Result<String, String> {
-        let valid = verify_signature(pub_key, signature, "Hyle Registration").unwrap();
-
-        if !valid {
-            return Err("Invalid signature".to_string());
+    fn register_identity(
+        &mut self,
+        contract_name: &str,
+        pub_key: &str,
+        signature: &str,
+        recovery_codes: Vec<String>,
+    ) -> Result<(), ResultCode> {
+        if recovery_codes.len() > recovery::MAX_RECOVERY_CODES {
+            return Err(ResultCode::InvalidInput);
+        }
+        let message = recovery::registration_message(contract_name, &recovery_codes);
+        if !verify_signature(pub_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
         }
 
         let mut hasher = Sha256::new();
         hasher.update(pub_key.as_bytes());
         let hash_bytes = hasher.finalize();
-        let account_info = AccountInfo {
+        let mut account_info = AccountInfo {
             hash: hex::encode(hash_bytes),
             nonce: 0,
+            hot_keys: BTreeMap::new(),
+            recovery_codes,
+            history: ControlHistory::default(),
         };
+        account_info.history.record(SecurityEvent::Registered {
+            hash: &account_info.hash,
+        });
 
         if self
             .identities
             .insert(pub_key.to_string(), account_info)
             .is_some()
         {
-            return Err("Identity already exists".to_string());
+            return Err(ResultCode::IdentityExists);
         }
-        Ok("Identity registered".to_string())
+        Ok(())
     }
 
     fn verify_identity(
         &mut self,
+        contract_name: &str,
         pub_key: &str,
         nonce: u32,
         blobs: &[sdk::Blob],
         signature: &str,
-    ) -> Result<bool, String> {
+    ) -> Result<bool, ResultCode> {
         match self.identities.get_mut(pub_key) {
             Some(stored_info) => {
                 if nonce != stored_info.nonce {
-                    return Err("Invalid nonce".to_string());
+                    return Err(ResultCode::InvalidNonce);
                 }
 
-                let message = blobs
-                    .iter()
-                    .map(|blob| format!("{} {:?}", blob.contract_name, blob.data.0))
-                    .collect::<Vec<String>>()
-                    .join(" ");
-
-                let message = format!("verify {} {}", nonce, message);
-
-                let valid = verify_signature(pub_key, signature, &message).unwrap();
+                check_contracts(&self.policy.allowed_contracts, blobs)?;
+                let message = verification_message(contract_name, nonce, blobs);
 
-                if !valid {
-                    return Err("Invalid signature".to_string());
+                if !verify_signature(pub_key, signature, &message)? {
+                    return Err(ResultCode::InvalidSignature);
                 }
 
                 let mut hasher = Sha256::new();
@@ -185,16 +396,221 @@ impl IdentityContractState {
                 stored_info.nonce += 1;
                 Ok(true)
             }
-            None => Err("Identity not found".to_string()),
+            None => Err(ResultCode::IdentityNotFound),
         }
     }
 
-    #[allow(dead_code)]
-    fn get_identity_info(&self, account: &str) -> Result<String, &'static str> {
-        match self.identities.get(account) {
-            Some(info) => Ok(serde_json::to_string(&info).map_err(|_| "Failed to serialize")?),
-            None => Err("Identity not found"),
+    fn authorize_hot_key(
+        &mut self,
+        contract_name: &str,
+        pub_key: &str,
+        nonce: u32,
+        policy: HotKeyPolicy,
+        signature: &str,
+    ) -> Result<String, ResultCode> {
+        let stored_info = self
+            .identities
+            .get_mut(pub_key)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        if nonce != stored_info.nonce {
+            return Err(ResultCode::InvalidNonce);
+        }
+        if policy.min_nonce > policy.max_nonce {
+            return Err(ResultCode::EmptyHotKeyRange);
+        }
+
+        if !verify_signature(
+            pub_key,
+            signature,
+            &policy.signing_message(contract_name, nonce),
+        )? {
+            return Err(ResultCode::InvalidSignature);
+        }
+
+        stored_info.nonce += 1;
+        stored_info.history.record(SecurityEvent::HotKeyAuthorized {
+            hot_key: &policy.hot_key,
+        });
+        let hot_key = policy.hot_key.clone();
+        stored_info.hot_keys.insert(hot_key.clone(), policy);
+        Ok(hot_key)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn verify_with_hot_key(
+        &mut self,
+        contract_name: &str,
+        pub_key: &str,
+        nonce: u32,
+        hot_key: &str,
+        blobs: &[sdk::Blob],
+        signature: &str,
+        timestamp: Option<u64>,
+    ) -> Result<(), ResultCode> {
+        let stored_info = self
+            .identities
+            .get_mut(pub_key)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        if nonce != stored_info.nonce {
+            return Err(ResultCode::InvalidNonce);
+        }
+
+        let policy = stored_info
+            .hot_keys
+            .get(hot_key)
+            .ok_or(ResultCode::HotKeyNotAuthorized)?;
+        if nonce < policy.min_nonce || nonce > policy.max_nonce {
+            return Err(ResultCode::NonceOutsideHotKeyPolicy);
+        }
+        if let Some(expires_at) = policy.expires_at {
+            match timestamp {
+                Some(now) if now <= expires_at => {}
+                Some(_) => return Err(ResultCode::HotKeyPolicyExpired),
+                None => return Err(ResultCode::MissingTxContext),
+            }
+        }
+        check_contracts(&policy.allowed_contracts, blobs)?;
+        check_contracts(&self.policy.allowed_contracts, blobs)?;
+
+        let message = verification_message(contract_name, nonce, blobs);
+        if !verify_signature(hot_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
+        }
+
+        stored_info.nonce += 1;
+        Ok(())
+    }
+
+    fn schedule_pause(
+        &mut self,
+        contract_name: &str,
+        pub_key: &str,
+        paused: bool,
+        signature: &str,
+        timestamp: Option<u64>,
+    ) -> Result<(), ResultCode> {
+        let now = timestamp.ok_or(ResultCode::MissingTxContext)?;
+        let message = pause::pause_message(contract_name, paused, self.pause.nonce);
+        if !verify_signature(pub_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
+        }
+        self.pause.schedule(pub_key, paused, now)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_realm(
+        &mut self,
+        contract_name: &str,
+        pub_key: &str,
+        realm: &str,
+        admin: String,
+        pause_delay: u64,
+        policy: RealmPolicy,
+        signature: &str,
+    ) -> Result<(), ResultCode> {
+        realm::check_name(realm)?;
+        self.authenticate_admin(pub_key)?;
+        let message = realm::creation_message(
+            contract_name,
+            realm,
+            &admin,
+            pause_delay,
+            &policy,
+            self.pause.nonce,
+        );
+        if !verify_signature(pub_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
+        }
+        if self.realms.contains_key(realm) {
+            return Err(ResultCode::RealmExists);
         }
+        // An admin key that isn't a SEC1 point could never act
+        decode(&admin)
+            .ok()
+            .filter(|bytes| VerifyingKey::from_sec1_bytes(bytes).is_ok())
+            .ok_or(ResultCode::InvalidInput)?;
+
+        let mut state = IdentityContractState::new().with_pause_admin(admin, pause_delay);
+        state.policy = policy;
+        self.realms.insert(realm.to_string(), state);
+        self.pause.nonce += 1;
+        Ok(())
+    }
+
+    fn set_policy(
+        &mut self,
+        scope: &str,
+        pub_key: &str,
+        policy: RealmPolicy,
+        signature: &str,
+    ) -> Result<(), ResultCode> {
+        self.authenticate_admin(pub_key)?;
+        let message = realm::policy_message(scope, &policy, self.pause.nonce);
+        if !verify_signature(pub_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
+        }
+        self.policy = policy;
+        self.pause.nonce += 1;
+        Ok(())
+    }
+
+    /// Checks that `pub_key` is the admin key of this state.
+    fn authenticate_admin(&self, pub_key: &str) -> Result<(), ResultCode> {
+        match &self.pause.admin {
+            None => Err(ResultCode::GovernanceDisabled),
+            Some(admin) if admin != pub_key => Err(ResultCode::KeyAccountMismatch),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Moves the account of `lost_key` to `new_key`, keeping its nonce so old
+    /// signatures stay consumed. Hot keys authorized by the lost key are dropped.
+    fn recover_with_code(
+        &mut self,
+        contract_name: &str,
+        lost_key: &str,
+        new_key: &str,
+        code: &str,
+        signature: &str,
+    ) -> Result<(), ResultCode> {
+        if self.identities.contains_key(new_key) {
+            return Err(ResultCode::IdentityExists);
+        }
+        let message = recovery::recovery_message(contract_name, lost_key);
+        if !verify_signature(new_key, signature, &message)? {
+            return Err(ResultCode::InvalidSignature);
+        }
+
+        let code_hash = recovery::recovery_code_hash(lost_key, code);
+        let info = self
+            .identities
+            .get(lost_key)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        if !info.recovery_codes.contains(&code_hash) {
+            return Err(ResultCode::InvalidRecoveryCode);
+        }
+        let mut info = self.identities.remove(lost_key).expect("checked above");
+        // The other codes are salted with the lost key and were issued with
+        // it: drop them along with the hot keys
+        info.recovery_codes.clear();
+
+        let mut hasher = Sha256::new();
+        hasher.update(new_key.as_bytes());
+        info.hash = hex::encode(hasher.finalize());
+        info.hot_keys.clear();
+        info.history
+            .record(SecurityEvent::Recovered { hash: &info.hash });
+        self.identities.insert(new_key.to_string(), info);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn get_identity_info(&self, account: &str) -> Result<String, ResultCode> {
+        let info = self
+            .identities
+            .get(account)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        Ok(serde_json::to_string(&info).expect("Failed to serialize"))
     }
 }
 
@@ -224,15 +640,44 @@ impl From<sdk::StateDigest> for IdentityContractState {
     }
 }
 
-fn verify_signature(pub_key: &str, signature_hex: &str, message: &str) -> Result<bool, String> {
+/// Message signed to register a key with the identity contract `contract_name`.
+pub fn registration_message(contract_name: &str) -> String {
+    format!("Hyle Registration {}", contract_name)
+}
+
+/// Message signed to verify an identity: the identity contract, the nonce and
+/// every other blob of the transaction.
+pub fn verification_message(contract_name: &str, nonce: u32, blobs: &[sdk::Blob]) -> String {
+    let message = blobs
+        .iter()
+        .map(|blob| format!("{} {:?}", blob.contract_name, blob.data.0))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    format!("verify {} {} {}", contract_name, nonce, message)
+}
+
+/// Rejects transactions with a blob to a contract missing from a non-empty
+/// `allowed_contracts`.
+fn check_contracts(allowed_contracts: &[String], blobs: &[sdk::Blob]) -> Result<(), ResultCode> {
+    if !allowed_contracts.is_empty()
+        && blobs
+            .iter()
+            .any(|blob| !allowed_contracts.contains(&blob.contract_name.0))
+    {
+        return Err(ResultCode::ContractNotAllowed);
+    }
+    Ok(())
+}
+
+fn verify_signature(pub_key: &str, signature_hex: &str, message: &str) -> Result<bool, ResultCode> {
     // decode pubkey
-    let pubkey_bytes = decode(pub_key).map_err(|_| "Failed to decode Pub key".to_string())?;
+    let pubkey_bytes = decode(pub_key).map_err(|_| ResultCode::InvalidAccountName)?;
     let verifying_key =
         VerifyingKey::from_sec1_bytes(&pubkey_bytes).expect("Failed to generate verifying key");
 
     // decode signature
-    let signature_bytes =
-        decode(signature_hex).map_err(|_| "Failed to decode Signature".to_string())?;
+    let signature_bytes = decode(signature_hex).map_err(|_| ResultCode::InvalidSignature)?;
     let signature = Signature::from_der(&signature_bytes).unwrap();
 
     let msg = message.as_bytes();
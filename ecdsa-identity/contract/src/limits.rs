@@ -0,0 +1,82 @@
+use core::fmt;
+
+use sdk::ContractInput;
+
+/// Largest hex-encoded DER P-384 signature (at most 104 bytes).
+pub const MAX_SIGNATURE_HEX_LEN: usize = 2 * 104;
+/// Largest number of blobs in the transaction.
+pub const MAX_BLOBS: usize = 16;
+/// Largest encoded blob.
+pub const MAX_BLOB_LEN: usize = 4 * 1024;
+/// The only private input is a recovery code, for `RecoverWithCode`.
+pub const MAX_PRIVATE_INPUT_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LimitError {
+    TooManyBlobs { count: usize },
+    BlobTooLarge { index: usize, len: usize },
+    PrivateInputTooLarge { len: usize },
+    SignatureTooLarge { len: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooManyBlobs { count } => {
+                write!(f, "Too many blobs: {count} (max {MAX_BLOBS})")
+            }
+            LimitError::BlobTooLarge { index, len } => {
+                write!(f, "Blob {index} too large: {len} bytes (max {MAX_BLOB_LEN})")
+            }
+            LimitError::PrivateInputTooLarge { len } => write!(
+                f,
+                "Private input too large: {len} bytes (max {MAX_PRIVATE_INPUT_LEN})"
+            ),
+            LimitError::SignatureTooLarge { len } => write!(
+                f,
+                "Signature too large: {len} chars (max {MAX_SIGNATURE_HEX_LEN})"
+            ),
+        }
+    }
+}
+
+impl From<LimitError> for String {
+    fn from(err: LimitError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Rejects oversized inputs before any of them is decoded.
+pub fn check_input(input: &ContractInput) -> Result<(), LimitError> {
+    if input.blobs.len() > MAX_BLOBS {
+        return Err(LimitError::TooManyBlobs {
+            count: input.blobs.len(),
+        });
+    }
+    if let Some((index, blob)) = input
+        .blobs
+        .iter()
+        .enumerate()
+        .find(|(_, blob)| blob.data.0.len() > MAX_BLOB_LEN)
+    {
+        return Err(LimitError::BlobTooLarge {
+            index,
+            len: blob.data.0.len(),
+        });
+    }
+    if input.private_input.len() > MAX_PRIVATE_INPUT_LEN {
+        return Err(LimitError::PrivateInputTooLarge {
+            len: input.private_input.len(),
+        });
+    }
+    Ok(())
+}
+
+pub fn check_signature(signature: &str) -> Result<(), LimitError> {
+    if signature.len() > MAX_SIGNATURE_HEX_LEN {
+        return Err(LimitError::SignatureTooLarge {
+            len: signature.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::code::ResultCode;
+
+/// Emergency stop. A pause admin key, set when the contract is registered,
+/// schedules a pause or unpause that takes effect `delay` seconds later; while
+/// paused, every action but `SchedulePause` fails with
+/// [`ResultCode::ContractPaused`].
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct PauseSwitch {
+    /// Hex-encoded SEC1 public key of the admin; `None` disables pausing
+    pub admin: Option<String>,
+    /// Seconds between scheduling a change and it taking effect
+    pub delay: u64,
+    pub paused: bool,
+    pub scheduled: Option<ScheduledPause>,
+    /// Signed into every scheduled change and other admin message (see
+    /// [`crate::realm`]), so admin signatures can't be replayed
+    pub nonce: u32,
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ScheduledPause {
+    pub paused: bool,
+    /// Unix timestamp (seconds) from which the change applies
+    pub effective_at: u64,
+}
+
+impl PauseSwitch {
+    /// Applies the scheduled change if it is due. Whether it is depends on the
+    /// time, so the tx context is required while a pause is pending: a prover
+    /// can't skip a due pause by leaving it out. A pending unpause needs no
+    /// time: without it the contract just stays paused, which is safe.
+    pub fn settle(&mut self, timestamp: Option<u64>) -> Result<(), ResultCode> {
+        let Some(scheduled) = &self.scheduled else {
+            return Ok(());
+        };
+        let now = match timestamp {
+            Some(now) => now,
+            None if scheduled.paused && !self.paused => return Err(ResultCode::MissingTxContext),
+            None => return Ok(()),
+        };
+        if now >= scheduled.effective_at {
+            self.paused = scheduled.paused;
+            self.scheduled = None;
+        }
+        Ok(())
+    }
+
+    /// Schedules `paused` to apply after the delay, replacing any pending
+    /// change: scheduling the current value cancels it.
+    pub fn schedule(&mut self, pub_key: &str, paused: bool, now: u64) -> Result<(), ResultCode> {
+        match &self.admin {
+            None => return Err(ResultCode::GovernanceDisabled),
+            Some(admin) if admin != pub_key => return Err(ResultCode::KeyAccountMismatch),
+            Some(_) => {}
+        }
+        self.scheduled = if paused == self.paused {
+            None
+        } else {
+            Some(ScheduledPause {
+                paused,
+                effective_at: now.saturating_add(self.delay),
+            })
+        };
+        self.nonce += 1;
+        Ok(())
+    }
+}
+
+/// Message the pause admin signs to schedule a change.
+pub fn pause_message(contract_name: &str, paused: bool, nonce: u32) -> String {
+    let change = if paused { "pause" } else { "unpause" };
+    format!("{} {} nonce {}", change, contract_name, nonce)
+}
@@ -0,0 +1,93 @@
+//! Identity realms: namespaces that let one deployment serve many
+//! applications. A realm has its own accounts, policy and admin key, which
+//! holds the realm's pause switch and sets its policy. Actions apply to a realm
+//! when wrapped in `IdentityAction::InRealm`, and every message signed for
+//! them names the realm's [`scope`], so signatures don't carry over between
+//! realms. The pause admin of the root state creates realms.
+
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::code::ResultCode;
+
+/// Longest realm name.
+pub const MAX_REALM_NAME: usize = 64;
+
+/// What a realm, or the root state, accepts.
+#[derive(
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Default,
+    Encode,
+    Decode,
+    PartialEq,
+    Eq,
+)]
+pub struct RealmPolicy {
+    /// Web origins of the applications served. The contract can't see
+    /// origins: they are published for hosts and relayers to enforce.
+    pub origins: Vec<String>,
+    /// Contracts verified transactions may send blobs to; empty means any
+    pub allowed_contracts: Vec<String>,
+}
+
+impl RealmPolicy {
+    fn describe(&self) -> String {
+        format!(
+            "origins [{}] contracts [{}]",
+            self.origins.join(","),
+            self.allowed_contracts.join(",")
+        )
+    }
+}
+
+/// Name messages are signed for in `realm` of the identity contract
+/// `contract_name`, in place of the contract name.
+pub fn scope(contract_name: &str, realm: &str) -> String {
+    format!("{contract_name}/{realm}")
+}
+
+/// Realm names are short identifiers: ASCII letters, digits, `-` and `_`.
+pub fn check_name(realm: &str) -> Result<(), ResultCode> {
+    let valid = !realm.is_empty()
+        && realm.len() <= MAX_REALM_NAME
+        && realm
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ResultCode::InvalidRealmName)
+    }
+}
+
+/// Message the root admin signs to create `realm` with `admin` as its admin
+/// key. `nonce` is the admin's, see [`crate::pause::PauseSwitch`].
+pub fn creation_message(
+    contract_name: &str,
+    realm: &str,
+    admin: &str,
+    pause_delay: u64,
+    policy: &RealmPolicy,
+    nonce: u32,
+) -> String {
+    format!(
+        "create realm {} on {} admin {} delay {} {} nonce {}",
+        realm,
+        contract_name,
+        admin,
+        pause_delay,
+        policy.describe(),
+        nonce
+    )
+}
+
+/// Message an admin signs to replace the policy of `scope`.
+pub fn policy_message(scope: &str, policy: &RealmPolicy, nonce: u32) -> String {
+    format!("policy {} {} nonce {}", scope, policy.describe(), nonce)
+}
@@ -0,0 +1,37 @@
+//! One-time recovery codes, committed as hashes at registration. Each moves
+//! the account to a new key once, for users who lost their private key.
+
+use sha2::{Digest, Sha256};
+
+/// Most recovery codes an account can hold.
+pub const MAX_RECOVERY_CODES: usize = 10;
+
+/// Hash committed for `code`, salted with the account's public key. Dashes and
+/// case are ignored so codes can be shown grouped.
+pub fn recovery_code_hash(pub_key: &str, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(
+        format!("recovery:{pub_key}:{code}").as_bytes(),
+    ))
+}
+
+/// Message signed at registration. Registering with recovery codes binds
+/// their hashes, so a copied registration signature can't be sent with
+/// someone else's codes.
+pub fn registration_message(contract_name: &str, recovery_codes: &[String]) -> String {
+    let message = crate::registration_message(contract_name);
+    if recovery_codes.is_empty() {
+        message
+    } else {
+        format!("{} recovery {}", message, recovery_codes.join(","))
+    }
+}
+
+/// Message the new key signs to take over `account` (the old public key).
+pub fn recovery_message(contract_name: &str, account: &str) -> String {
+    format!("recover {} on {}", account, contract_name)
+}
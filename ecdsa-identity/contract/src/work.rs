@@ -0,0 +1,48 @@
+//! Approximate work metric of an action, reported in successful program
+//! outputs in the same format as the OIDC contract's, so that indexers and fee
+//! estimators can learn per-action costs from settled history.
+
+use core::fmt;
+
+/// What an action cost, in units that don't depend on the prover: counts of
+/// the expensive operations rather than cycles.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Work {
+    /// ECDSA signatures verified
+    pub signatures: u32,
+    /// Account and recovery code hashes computed
+    pub hashes: u32,
+    /// Size of the encoded state, which every action decodes and re-encodes
+    pub state_bytes: u64,
+}
+
+impl Work {
+    pub fn new(signatures: u32, hashes: u32) -> Self {
+        Work {
+            signatures,
+            hashes,
+            state_bytes: 0,
+        }
+    }
+
+    /// Reads back a metric written by [`fmt::Display`].
+    pub fn parse(work: &str) -> Option<Self> {
+        let mut parts = work.split(',');
+        let work = Work {
+            signatures: parts.next()?.parse().ok()?,
+            hashes: parts.next()?.parse().ok()?,
+            state_bytes: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(work)
+    }
+}
+
+impl fmt::Display for Work {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{}",
+            self.signatures, self.hashes, self.state_bytes
+        )
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{bail, Context, Result};
+use contract_identity::IdentityContractState;
+use hex::{decode, encode};
+use p384::ecdsa::signature::{Signer, Verifier};
+use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::encode_public_key;
+
+/// Registration evidence for one account, as found on-chain at export time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEvidence {
+    pub contract_name: String,
+    pub identity: String,
+    pub pub_key: String,
+    /// SHA-256 of the SEC1 public key bytes
+    pub key_fingerprint: String,
+    /// Account hash stored in the contract state
+    pub account_hash: String,
+    pub nonce: u32,
+    pub exported_at: u64,
+    /// Known transaction hashes for this account. Filled in by the caller
+    /// when it has them: the node API does not index transactions per account.
+    pub tx_hashes: Vec<String>,
+}
+
+/// An [`AuditEvidence`] signed by the account key, verifiable without a node.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditBundle {
+    pub evidence: AuditEvidence,
+    pub signature: String,
+}
+
+fn fingerprint(pub_key: &str) -> Result<String> {
+    let bytes = decode(pub_key).context("Public key is not valid hex")?;
+    Ok(encode(Sha256::digest(bytes)))
+}
+
+fn signing_payload(evidence: &AuditEvidence) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(evidence)?)
+}
+
+pub fn export(
+    state: &IdentityContractState,
+    contract_name: &str,
+    private_key: &SigningKey,
+    tx_hashes: Vec<String>,
+) -> Result<AuditBundle> {
+    let pub_key = encode_public_key(private_key);
+    let account = state
+        .get_account(&pub_key)
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    let evidence = AuditEvidence {
+        contract_name: contract_name.to_string(),
+        identity: format!("{}.{}", pub_key, contract_name),
+        key_fingerprint: fingerprint(&pub_key)?,
+        pub_key,
+        account_hash: account.hash.clone(),
+        nonce: account.nonce,
+        exported_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        tx_hashes,
+    };
+
+    let signature: Signature = private_key.sign(&signing_payload(&evidence)?);
+    Ok(AuditBundle {
+        evidence,
+        signature: encode(signature.to_der().as_bytes()),
+    })
+}
+
+/// Checks a bundle offline: signature, fingerprint and the account hash
+/// commitment the contract computes at registration.
+pub fn verify(bundle: &AuditBundle) -> Result<()> {
+    let evidence = &bundle.evidence;
+    let pubkey_bytes = decode(&evidence.pub_key).context("Public key is not valid hex")?;
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(&pubkey_bytes).context("Invalid public key")?;
+
+    let signature = Signature::from_der(&decode(&bundle.signature)?).context("Invalid signature")?;
+    verifying_key
+        .verify(&signing_payload(evidence)?, &signature)
+        .context("Bundle signature does not match the account key")?;
+
+    if evidence.key_fingerprint != fingerprint(&evidence.pub_key)? {
+        bail!("Key fingerprint does not match the public key");
+    }
+    if evidence.identity != format!("{}.{}", evidence.pub_key, evidence.contract_name) {
+        bail!("Identity does not match the public key and contract name");
+    }
+    if evidence.account_hash != encode(Sha256::digest(evidence.pub_key.as_bytes())) {
+        bail!("Account hash is not the commitment of this public key");
+    }
+    Ok(())
+}
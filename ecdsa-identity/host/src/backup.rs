@@ -0,0 +1,209 @@
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Subcommand;
+use hex::{decode, encode};
+use host_output::say;
+use p384::elliptic_curve::rand_core::RngCore;
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::utils::{create_private, keys_dir};
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// Bundle every stored account key into one encrypted archive
+    Create {
+        passphrase: String,
+        output: PathBuf,
+        /// Also PUT the archive to this URL (e.g. a presigned S3 URL)
+        #[arg(long)]
+        upload: Option<String>,
+    },
+    /// Restore account keys from a local archive or an HTTP(S) URL
+    Restore {
+        passphrase: String,
+        source: String,
+        /// Replace keys that are already stored locally
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+const BACKUP_VERSION: u32 = 1;
+const PBKDF2_ROUNDS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+/// AES-GCM's nonce size
+const NONCE_LEN: usize = 12;
+
+/// Everything needed to recreate the host's identities on another machine.
+/// Key files are copied as-is, so they stay encrypted with their own passwords.
+#[derive(Serialize, Deserialize, Default)]
+struct BackupContents {
+    /// Account name to hex-encoded key file
+    ecdsa_keys: BTreeMap<String, String>,
+}
+
+/// The archive written to disk: `BackupContents` encrypted with AES-256-GCM
+/// under a key derived from the backup passphrase.
+#[derive(Serialize, Deserialize)]
+struct BackupArchive {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Bundles every stored account key into an encrypted archive.
+pub fn create(passphrase: &str) -> Result<Vec<u8>> {
+    let mut contents = BackupContents::default();
+    for entry in fs::read_dir(keys_dir()).context("Failed to list key files")? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let account = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| anyhow!("Key file name {:?} is not UTF-8", name))?;
+        contents
+            .ecdsa_keys
+            .insert(account, encode(fs::read(entry.path())?));
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), serde_json::to_vec(&contents)?.as_ref())
+        .map_err(|_| anyhow!("Failed to encrypt backup"))?;
+
+    say!("Backed up {} account key(s)", contents.ecdsa_keys.len());
+    Ok(serde_json::to_vec_pretty(&BackupArchive {
+        version: BACKUP_VERSION,
+        salt: encode(salt),
+        nonce: encode(nonce),
+        ciphertext: encode(ciphertext),
+    })?)
+}
+
+/// Decrypts an archive and writes its key files back. Existing key files are
+/// left untouched unless `overwrite` is set.
+pub fn restore(archive: &[u8], passphrase: &str, overwrite: bool) -> Result<()> {
+    let archive: BackupArchive =
+        serde_json::from_slice(archive).context("Not a backup archive")?;
+    if archive.version != BACKUP_VERSION {
+        bail!("Unsupported backup version {}", archive.version);
+    }
+
+    // `Nonce::from_slice` panics on any other length
+    let salt = decode(&archive.salt).context("Corrupted backup salt")?;
+    let nonce = decode(&archive.nonce).context("Corrupted backup nonce")?;
+    if salt.len() != SALT_LEN || nonce.len() != NONCE_LEN {
+        bail!("Corrupted backup: wrong salt or nonce length");
+    }
+
+    let key = derive_key(passphrase, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            decode(&archive.ciphertext)?.as_ref(),
+        )
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted backup"))?;
+    let contents: BackupContents = serde_json::from_slice(&plaintext)?;
+
+    let dir = keys_dir();
+    for (account, key_file) in contents.ecdsa_keys {
+        // Account names come from the archive: refuse anything that would escape the keys dir
+        if Path::new(&account).file_name().and_then(|n| n.to_str()) != Some(account.as_str()) {
+            bail!("Invalid account name {:?} in backup", account);
+        }
+        let path = dir.join(&account);
+        if path.exists() && !overwrite {
+            say!("⚠️ Skipping {}: a key is already stored", account);
+            continue;
+        }
+        create_private(&path)?.write_all(&decode(key_file)?)?;
+        host_output::report(
+            "key_restored",
+            serde_json::json!({ "account": account }),
+            format_args!("✅ Restored {}", account),
+        );
+    }
+    Ok(())
+}
+
+/// Uploads an archive with a PUT, e.g. to a presigned S3-compatible URL.
+pub async fn upload(url: &str, archive: Vec<u8>) -> Result<()> {
+    reqwest::Client::new()
+        .put(url)
+        .body(archive)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Backup upload failed")?;
+    Ok(())
+}
+
+/// Fetches an archive from an HTTP(S) URL, or reads it from a local path.
+pub async fn fetch(source: &str) -> Result<Vec<u8>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let response = reqwest::get(source)
+            .await?
+            .error_for_status()
+            .context("Backup download failed")?;
+        Ok(response.bytes().await?.to_vec())
+    } else {
+        fs::read(source).with_context(|| format!("Failed to read {}", source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(salt: &[u8], nonce: &[u8]) -> Vec<u8> {
+        serde_json::to_vec(&BackupArchive {
+            version: BACKUP_VERSION,
+            salt: encode(salt),
+            nonce: encode(nonce),
+            ciphertext: encode([0u8; 32]),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn corrupted_archives_fail_without_panicking() {
+        for corrupted in [
+            archive(&[0; SALT_LEN], &[0; 8]),
+            archive(&[0; SALT_LEN], &[0; 16]),
+            archive(&[0; SALT_LEN], &[]),
+            archive(&[0; 4], &[0; NONCE_LEN]),
+        ] {
+            let err = restore(&corrupted, "passphrase", false).unwrap_err();
+            assert!(err.to_string().starts_with("Corrupted backup"), "{err}");
+        }
+
+        let mut not_hex: serde_json::Value =
+            serde_json::from_slice(&archive(&[0; SALT_LEN], &[0; NONCE_LEN])).unwrap();
+        not_hex["nonce"] = "zz".into();
+        let err = restore(&serde_json::to_vec(&not_hex).unwrap(), "passphrase", false);
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! W3C DID documents for registered accounts, under a `did:hyle` method whose
+//! identifier is the account's Hyle identity: `did:hyle:<public key>.<contract>`.
+//! The document is rendered from the contract state, so it is only as current
+//! as the node that served it.
+
+use anyhow::{Context, Result};
+use contract_identity::AccountInfo;
+use p384::PublicKey;
+use serde_json::{json, Value};
+
+/// DID of `pub_key`'s account on `contract_name`. Hex keys and contract names
+/// only hold characters DIDs allow, so nothing needs escaping.
+pub fn did(contract_name: &str, pub_key: &str) -> String {
+    format!("did:hyle:{}.{}", pub_key, contract_name)
+}
+
+fn public_key_jwk(pub_key: &str) -> Result<Value> {
+    let bytes = hex::decode(pub_key).context("Public key is not valid hex")?;
+    let key = PublicKey::from_sec1_bytes(&bytes).context("Invalid public key")?;
+    Ok(serde_json::to_value(key.to_jwk())?)
+}
+
+/// The account key is its only verification relationship. Hot keys are listed
+/// as verification methods, each with the policy bounding it, but in no
+/// relationship: a verifier unaware of the policies must not accept them.
+pub fn document(contract_name: &str, pub_key: &str, info: &AccountInfo) -> Result<Value> {
+    let id = did(contract_name, pub_key);
+    let primary = format!("{id}#key-0");
+
+    let mut methods = vec![json!({
+        "id": primary,
+        "type": "JsonWebKey2020",
+        "controller": id,
+        "publicKeyJwk": public_key_jwk(pub_key)?,
+    })];
+    for (index, (hot_key, policy)) in info.hot_keys.iter().enumerate() {
+        methods.push(json!({
+            "id": format!("{id}#hot-key-{index}"),
+            "type": "JsonWebKey2020",
+            "controller": id,
+            "publicKeyJwk": public_key_jwk(hot_key)?,
+            "hyleHotKeyPolicy": policy,
+        }));
+    }
+
+    Ok(json!({
+        "@context": [
+            "https://www.w3.org/ns/did/v1",
+            "https://w3id.org/security/suites/jws-2020/v1",
+        ],
+        "id": id,
+        "verificationMethod": methods,
+        "authentication": [primary],
+        "assertionMethod": [primary],
+        "capabilityInvocation": [primary],
+        "capabilityDelegation": [primary],
+        "hyleAccount": {
+            "accountHash": info.hash,
+            "nonce": info.nonce,
+            "recoveryCodes": info.recovery_codes.len(),
+            "controlHistory": info.history,
+        },
+    }))
+}
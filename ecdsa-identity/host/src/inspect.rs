@@ -0,0 +1,33 @@
+//! What `get-state` shows of the accounts of a decoded state.
+
+use contract_identity::AccountInfo;
+use serde_json::{json, Value};
+
+/// An account's nonce and the hashes it holds.
+pub fn account_json(pub_key: &str, info: &AccountInfo) -> Value {
+    json!({
+        "pub_key": pub_key,
+        "nonce": info.nonce,
+        "hash": info.hash,
+        "history": { "head": info.history.head, "events": info.history.events },
+        "hot_keys": info.hot_keys.keys().collect::<Vec<_>>(),
+        "recovery_codes": info.recovery_codes.len(),
+    })
+}
+
+pub fn render_account(pub_key: &str, info: &AccountInfo) -> String {
+    let mut text = pub_key.to_string();
+    text += &format!("\n  nonce:          {}", info.nonce);
+    text += &format!("\n  hash:           {}", info.hash);
+    let head = if info.history.head.is_empty() {
+        "-"
+    } else {
+        &info.history.head
+    };
+    text += &format!("\n  history:        {} ({} events)", head, info.history.events);
+    for hot_key in info.hot_keys.keys() {
+        text += &format!("\n  hot key:        {}", hot_key);
+    }
+    text += &format!("\n  recovery codes: {} unused", info.recovery_codes.len());
+    text
+}
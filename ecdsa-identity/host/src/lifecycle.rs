@@ -0,0 +1,129 @@
+use clap::Subcommand;
+use host_output::{say, OrFail};
+use sdk::{ProgramId, StateDigest, TxHash};
+use serde_json::json;
+
+use crate::prover::Zkvm;
+
+/// Contract lifecycle commands, shared in shape by every identity host.
+#[derive(Subcommand)]
+pub enum ContractCommand {
+    /// Register the contract with the program id of the local guest, for the
+    /// verifier of `--zkvm`
+    Register {
+        /// Hex public key allowed to schedule pauses; pausing is disabled without one
+        #[arg(long)]
+        pause_admin: Option<String>,
+        /// Seconds between scheduling a pause or unpause and it taking effect
+        #[arg(long, default_value_t = 86_400, requires = "pause_admin")]
+        pause_delay: u64,
+    },
+    /// Show the on-chain program id and state digest
+    Info {},
+    /// Compare the local guest id against the on-chain program id
+    Check {},
+    /// Schedule a pause, taking effect after the pause delay, signed with the
+    /// pause admin's locally stored key
+    Pause {
+        account: String,
+        password: String,
+    },
+    /// Schedule the end of a pause, or cancel a pending pause, signed with the
+    /// pause admin's locally stored key
+    Unpause {
+        account: String,
+        password: String,
+    },
+}
+
+/// Program id computed from the locally built guest for `zkvm`.
+pub fn local_program_id(zkvm: Zkvm) -> ProgramId {
+    zkvm.program_id().or_fail("usage")
+}
+
+pub fn print_info(contract_name: &str, program_id: &ProgramId, state: &StateDigest, zkvm: Zkvm) {
+    let program_id = hex::encode(&program_id.0);
+    let local_id = hex::encode(local_program_id(zkvm).0);
+    host_output::report(
+        "contract_info",
+        json!({
+            "contract": contract_name,
+            "program_id": program_id,
+            "local_id": local_id,
+            "state_size": state.0.len(),
+        }),
+        format_args!(
+            "contract:   {}\nprogram id: {}\nlocal id:   {}\nstate:      {} bytes",
+            contract_name,
+            program_id,
+            local_id,
+            state.0.len()
+        ),
+    );
+}
+
+pub fn report_registered(contract_name: &str, tx_hash: &TxHash) {
+    host_output::report(
+        "contract_registered",
+        json!({ "contract": contract_name, "tx_hash": tx_hash }),
+        format_args!("✅ Register contract tx sent. Tx hash: {}", tx_hash),
+    );
+}
+
+/// Reports whether the on-chain program id matches the local guest, and exits
+/// with a failure status when it doesn't.
+pub fn check(program_id: &ProgramId, zkvm: Zkvm) {
+    let local = local_program_id(zkvm);
+    if *program_id == local {
+        host_output::report(
+            "program_id_checked",
+            json!({ "program_id": hex::encode(&program_id.0) }),
+            "✅ On-chain program id matches the local guest",
+        );
+    } else {
+        host_output::fail(
+            "program_id",
+            format_args!(
+                "On-chain program id {} differs from the local guest {}",
+                hex::encode(&program_id.0),
+                hex::encode(local.0)
+            ),
+        );
+    }
+}
+
+/// Refuses to go on unless the local guest, the on-chain contract and, when
+/// given, `expected` (hex) all share one program id. Used with `--reproducible`
+/// before submitting proofs.
+pub fn ensure_program_id(on_chain: &ProgramId, expected: Option<&str>, zkvm: Zkvm) {
+    let local = local_program_id(zkvm);
+    let expected = match expected {
+        Some(expected) => ProgramId(
+            hex::decode(expected)
+                .unwrap_or_else(|_| host_output::fail("usage", "--expected-program-id is not hex")),
+        ),
+        None => local.clone(),
+    };
+
+    if local != expected {
+        host_output::fail(
+            "program_id",
+            format_args!(
+                "Local guest {} differs from the expected program id {}; rebuild with RISC0_USE_DOCKER=1",
+                hex::encode(&local.0),
+                hex::encode(&expected.0)
+            ),
+        );
+    }
+    if *on_chain != expected {
+        host_output::fail(
+            "program_id",
+            format_args!(
+                "On-chain program id {} differs from the expected {}, refusing to submit proofs",
+                hex::encode(&on_chain.0),
+                hex::encode(&expected.0)
+            ),
+        );
+    }
+    say!("✅ Program id {} pinned", hex::encode(&expected.0));
+}
@@ -1,16 +1,33 @@
+use app_dirs::AppDirs;
 use clap::{Parser, Subcommand};
-use client_sdk::helpers::risc0::Risc0Prover;
-use contract_identity::{actions::IdentityAction, IdentityContractState};
+use contract_identity::{
+    actions::{HotKeyPolicy, IdentityAction},
+    pause::pause_message,
+    recovery::{recovery_message, registration_message},
+    verification_message, IdentityContractState,
+};
+use host_output::{say, OrFail, Output};
 use sdk::api::APIRegisterContract;
 use sdk::BlobTransaction;
 use sdk::ProofTransaction;
 use sdk::{ContractInput, Digestable};
-
-// These constants represent the RISC-V ELF and the image ID generated by risc0-build.
-// The ELF is used for proving and the ID is used for verification.
-use methods_identity::{GUEST_ELF, GUEST_ID};
-
-use crate::utils::handle_secp384r1_identity;
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::backup::BackupCommand;
+use crate::lifecycle::ContractCommand;
+use crate::node::Node;
+use crate::prover::{Polling, Prover, ProverChoice, Zkvm};
+use crate::utils::{handle_secp384r1_identity, load_secp384r1_key};
+mod audit;
+mod backup;
+mod did;
+mod inspect;
+mod lifecycle;
+mod node;
+mod prover;
+mod recovery;
+mod selfcheck;
 mod utils;
 
 #[derive(Parser)]
@@ -20,28 +37,273 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
+    /// Before submitting proofs, check that the local guest and the on-chain
+    /// contract share the expected program id
     #[clap(long, short)]
     reproducible: bool,
 
+    /// Hex program id to pin against instead of the local guest's
+    #[arg(long, requires = "reproducible")]
+    expected_program_id: Option<String>,
+
     #[arg(long, default_value = "http://localhost:4321")]
     pub host: String,
 
+    /// `json` prints results and errors as JSON objects, one per line, and
+    /// messages on stderr
+    #[arg(long, value_enum, default_value_t = Output::Text)]
+    pub output: Output,
+
     #[arg(long, default_value = "ecdsa_identity")]
     pub contract_name: String,
+
+    /// Where proofs are generated: `local`, or `remote=<url>` to delegate them
+    /// to a proving service
+    #[arg(long, default_value = "local")]
+    pub prover: ProverChoice,
+
+    /// Seconds after which a remote proving job still pending is given up
+    #[arg(long)]
+    pub prover_timeout: Option<u64>,
+
+    /// zkVM the contract is registered with and proofs are generated in;
+    /// `sp1` needs a host built with `--features sp1`
+    #[arg(long, value_enum, default_value_t = Zkvm::Risc0)]
+    pub zkvm: Zkvm,
+
+    /// Keep keys under this directory instead of the platform's (also set by
+    /// `HYLE_IDENTITY_HOME`)
+    #[arg(long)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Run despite unsafe settings found by the startup self-check, such as
+    /// passwords given on the command line
+    #[arg(long)]
+    pub allow_unsafe: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    RegisterContract {},
+    /// Register and inspect the identity contract
+    Contract {
+        #[command(subcommand)]
+        command: ContractCommand,
+    },
     RegisterIdentity {
         account: String,
         password: String,
+        /// Also commit this many one-time recovery codes, printed once
+        #[arg(long, default_value_t = 0)]
+        recovery_codes: usize,
     },
     VerifyIdentity {
         account: String,
         password: String,
         nonce: u32,
     },
+    /// Export signed registration evidence for an account
+    AuditExport {
+        account: String,
+        password: String,
+        output: PathBuf,
+        /// Transaction hashes to include in the bundle
+        #[arg(long = "tx-hash")]
+        tx_hashes: Vec<String>,
+    },
+    /// Verify an exported audit bundle offline
+    AuditVerify { bundle: PathBuf },
+    /// Back up or restore the locally stored account keys
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Authorize a locally stored hot key to act for the account within bounds
+    AuthorizeHotKey {
+        account: String,
+        password: String,
+        hot_account: String,
+        hot_password: String,
+        /// Last nonce the hot key may use
+        #[arg(long)]
+        max_nonce: u32,
+        /// Contract the hot key may send blobs to (repeatable, default any)
+        #[arg(long = "contract")]
+        allowed_contracts: Vec<String>,
+        /// Unix timestamp after which the authorization expires
+        #[arg(long)]
+        expires_at: Option<u64>,
+    },
+    /// Move the account of a lost key to a locally stored new key with one of
+    /// its recovery codes, read from stdin
+    RecoverWithCode {
+        /// Hex public key of the lost account
+        lost_key: String,
+        account: String,
+        password: String,
+    },
+    /// Fetch the contract state and pretty-print its accounts
+    StateShow {},
+    /// Decode the contract state and print each account's nonce and hashes
+    GetState {
+        /// Hex public key of the only account to print
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// Render a registered account as a W3C DID document (`did:hyle:...`)
+    Did {
+        /// Hex public key of the account
+        pub_key: String,
+    },
+    /// Verify the primary account's identity by signing with a hot key
+    VerifyWithHotKey {
+        /// Hex public key of the primary account
+        pub_key: String,
+        hot_account: String,
+        hot_password: String,
+        nonce: u32,
+    },
+}
+
+impl Commands {
+    fn submits_proofs(&self) -> bool {
+        matches!(
+            self,
+            Commands::RegisterIdentity { .. }
+                | Commands::VerifyIdentity { .. }
+                | Commands::AuthorizeHotKey { .. }
+                | Commands::VerifyWithHotKey { .. }
+                | Commands::RecoverWithCode { .. }
+                | Commands::Contract {
+                    command: ContractCommand::Pause { .. } | ContractCommand::Unpause { .. },
+                }
+        )
+    }
+
+    /// Passwords and passphrases of the command, by name. `-` stands for one
+    /// to be read from stdin.
+    fn secrets(&mut self) -> Vec<(&'static str, &mut String)> {
+        match self {
+            Commands::RegisterIdentity { password, .. }
+            | Commands::VerifyIdentity { password, .. }
+            | Commands::AuditExport { password, .. }
+            | Commands::RecoverWithCode { password, .. }
+            | Commands::Contract {
+                command:
+                    ContractCommand::Pause { password, .. }
+                    | ContractCommand::Unpause { password, .. },
+            } => vec![("password", password)],
+            Commands::AuthorizeHotKey {
+                password,
+                hot_password,
+                ..
+            } => vec![("password", password), ("hot password", hot_password)],
+            Commands::VerifyWithHotKey { hot_password, .. } => {
+                vec![("hot password", hot_password)]
+            }
+            Commands::Backup {
+                command:
+                    BackupCommand::Create { passphrase, .. } | BackupCommand::Restore { passphrase, .. },
+            } => vec![("backup passphrase", passphrase)],
+            _ => vec![],
+        }
+    }
+}
+
+/// Sends `blobs` from `identity`, proves the first one and sends the proof.
+async fn send_and_prove(
+    client: &Node,
+    prover: &Prover<'_>,
+    contract_name: &str,
+    identity: String,
+    blobs: Vec<sdk::Blob>,
+    private_input: Vec<u8>,
+) {
+    let initial_state: IdentityContractState = client
+        .get_contract(&contract_name.into())
+        .await
+        .or_fail("node")
+        .state
+        .into();
+
+    let blob_tx = BlobTransaction {
+        identity: identity.into(),
+        blobs: blobs.clone(),
+    };
+
+    // Send the blob transaction
+    let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+    node::report_blob_sent(&blob_tx_hash);
+
+    // Build the contract input, with the context of the blob's block for the
+    // contract to settle scheduled pauses
+    let inputs = ContractInput {
+        initial_state: initial_state.as_digest(),
+        identity: blob_tx.identity,
+        tx_hash: blob_tx_hash.clone(),
+        private_input,
+        tx_ctx: Some(client.sequenced_context(&blob_tx_hash).await.or_fail("node")),
+        blobs,
+        index: sdk::BlobIndex(0),
+    };
+
+    // Generate the zk proof
+    let proof = prover.prove(inputs).await.or_fail("proof");
+
+    let proof_tx = ProofTransaction {
+        proof,
+        contract_name: contract_name.into(),
+    };
+
+    // Send the proof transaction
+    node::report_proof_sent(
+        &blob_tx_hash,
+        client.send_proof(&proof_tx, &blob_tx_hash).await,
+    );
+}
+
+/// Schedules the contract to be `paused` after its pause delay, with a
+/// `SchedulePause` signed by the pause admin's locally stored key.
+async fn schedule_pause(
+    client: &Node,
+    prover: &Prover<'_>,
+    contract_name: &str,
+    account: &str,
+    password: &str,
+    paused: bool,
+) {
+    let state: IdentityContractState = client
+        .get_contract(&contract_name.into())
+        .await
+        .or_fail("node")
+        .state
+        .into();
+
+    let admin_key = load_secp384r1_key(account, password).expect("Failed to load account key");
+    let pub_key = utils::encode_public_key(&admin_key);
+    let message = pause_message(contract_name, paused, state.pause_switch().nonce);
+    let (_, signature) =
+        handle_secp384r1_identity(account, password, message.as_bytes()).or_fail("key");
+
+    let action = IdentityAction::SchedulePause { paused, signature };
+    send_and_prove(
+        client,
+        prover,
+        contract_name,
+        format!("{}.{}", pub_key, contract_name),
+        vec![encode_blob(contract_name, action)],
+        vec![],
+    )
+    .await;
+}
+
+fn encode_blob(contract_name: &str, action: IdentityAction) -> sdk::Blob {
+    sdk::Blob {
+        contract_name: contract_name.into(),
+        data: sdk::BlobData(
+            bincode::encode_to_vec(action, bincode::config::standard())
+                .expect("failed to encode BlobData"),
+        ),
+    }
 }
 
 #[tokio::main]
@@ -51,49 +313,151 @@ async fn main() {
         .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
         .init();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    host_output::init(cli.output);
+
+    app_dirs::init(
+        AppDirs::resolve("ecdsa", cli.data_dir.as_deref())
+            .expect("Failed to locate the data directory"),
+    );
+    let migration = utils::migrate_legacy_keys().expect("Failed to move the existing keys");
+    if !migration.moved.is_empty() {
+        say!(
+            "📦 Moved {} key(s) to {}",
+            migration.moved.len(),
+            utils::keys_dir().display()
+        );
+    }
+    for kept in &migration.kept {
+        eprintln!(
+            "⚠️  {} left in place: a key with that name already exists",
+            kept.display()
+        );
+    }
 
-    let client = client_sdk::rest_client::NodeApiHttpClient::new(cli.host).unwrap();
+    let client = Node::new(cli.host.clone()).or_fail("usage");
 
     let contract_name = &cli.contract_name;
 
-    let prover = Risc0Prover::new(GUEST_ELF);
+    selfcheck::run(
+        &mut cli.command,
+        &client,
+        &cli.host,
+        contract_name,
+        cli.zkvm,
+    )
+    .await
+    .finish(cli.allow_unsafe);
+    for (name, secret) in cli.command.secrets() {
+        if secret == "-" {
+            *secret = utils::prompt_secret(name).expect("Failed to read the password");
+        }
+    }
+
+    let mut polling = Polling::default();
+    if let Some(timeout) = cli.prover_timeout {
+        polling.timeout = std::time::Duration::from_secs(timeout);
+    }
+    let prover = Prover::new(&cli.prover, polling, cli.zkvm).or_fail("usage");
+
+    if cli.reproducible && cli.command.submits_proofs() {
+        let contract = client
+            .get_contract(&contract_name.clone().into())
+            .await
+            .or_fail("node");
+        lifecycle::ensure_program_id(
+            &contract.program_id,
+            cli.expected_program_id.as_deref(),
+            cli.zkvm,
+        );
+    }
 
     match cli.command {
-        Commands::RegisterContract {} => {
+        Commands::Contract {
+            command:
+                ContractCommand::Register {
+                    pause_admin,
+                    pause_delay,
+                },
+        } => {
             // Build initial state of contract
-            let initial_state = IdentityContractState::new();
-            println!("Initial state: {:?}", initial_state);
+            let mut initial_state = IdentityContractState::new();
+            if let Some(pause_admin) = pause_admin {
+                initial_state = initial_state.with_pause_admin(pause_admin, pause_delay);
+            }
+            say!("Initial state: {:?}", initial_state);
 
             // Send the transaction to register the contract
             let res = client
                 .register_contract(&APIRegisterContract {
-                    verifier: "risc0".into(),
-                    program_id: sdk::ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec()),
+                    verifier: cli.zkvm.verifier().into(),
+                    program_id: lifecycle::local_program_id(cli.zkvm),
                     state_digest: initial_state.as_digest(),
                     contract_name: contract_name.clone().into(),
                 })
                 .await
-                .unwrap();
+                .or_fail("node");
 
-            println!("✅ Register contract tx sent. Tx hash: {}", res);
+            lifecycle::report_registered(contract_name, &res);
+        }
+        Commands::Contract {
+            command: ContractCommand::Info {},
+        } => {
+            let contract = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node");
+            lifecycle::print_info(
+                contract_name,
+                &contract.program_id,
+                &contract.state,
+                cli.zkvm,
+            );
+        }
+        Commands::Contract {
+            command: ContractCommand::Check {},
+        } => {
+            let contract = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node");
+            lifecycle::check(&contract.program_id, cli.zkvm);
         }
-        Commands::RegisterIdentity { account, password } => {
+        Commands::Contract {
+            command: ContractCommand::Pause { account, password },
+        } => schedule_pause(&client, &prover, contract_name, &account, &password, true).await,
+        Commands::Contract {
+            command: ContractCommand::Unpause { account, password },
+        } => schedule_pause(&client, &prover, contract_name, &account, &password, false).await,
+        Commands::RegisterIdentity {
+            account,
+            password,
+            recovery_codes,
+        } => {
             // Fetch the initial state from the node
             let initial_state: IdentityContractState = client
                 .get_contract(&contract_name.clone().into())
                 .await
-                .unwrap()
+                .or_fail("node")
                 .state
                 .into();
 
-            println!("Initial state {:?}", initial_state.clone());
-            println!("User {:?}", account.clone());
+            say!("Initial state {:?}", initial_state.clone());
+            say!("User {:?}", account.clone());
+
+            // Handle secp384r1 signed identity, creating the key on first use
+            let (pub_key, _) =
+                handle_secp384r1_identity(&account, &password, b"").or_fail("key");
 
-            // Handle secp384r1 signed identity
+            let (codes, recovery_codes) = recovery::generate(&pub_key, recovery_codes)
+                .expect("Failed to generate recovery codes");
+            if !codes.is_empty() {
+                recovery::print(&codes);
+            }
 
-            let (pub_key, signature) =
-                handle_secp384r1_identity(&account, &password, b"Hyle Registration").unwrap();
+            let message = registration_message(contract_name, &recovery_codes);
+            let (_, signature) = handle_secp384r1_identity(&account, &password, message.as_bytes())
+                .or_fail("key");
 
             let identity = format!("{}.{}", pub_key, contract_name);
 
@@ -101,7 +465,10 @@ async fn main() {
             // Build the blob transaction
             // ----
 
-            let action = IdentityAction::RegisterIdentity { signature };
+            let action = IdentityAction::RegisterIdentity {
+                signature,
+                recovery_codes,
+            };
 
             let blobs = vec![sdk::Blob {
                 contract_name: contract_name.clone().into(),
@@ -116,8 +483,8 @@ async fn main() {
             };
 
             // Send the blob transaction
-            let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-            println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
+            let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+            node::report_blob_sent(&blob_tx_hash);
 
             // ----
             // Prove the state transition
@@ -127,15 +494,15 @@ async fn main() {
             let inputs = ContractInput {
                 initial_state: initial_state.as_digest(),
                 identity: blob_tx.identity,
-                tx_hash: blob_tx_hash,
+                tx_hash: blob_tx_hash.clone(),
                 private_input: vec![],
-                tx_ctx: None,
+                tx_ctx: Some(client.sequenced_context(&blob_tx_hash).await.or_fail("node")),
                 blobs: blobs.clone(),
                 index: sdk::BlobIndex(0),
             };
 
             // Generate the zk proof
-            let proof = prover.prove(inputs).await.unwrap();
+            let proof = prover.prove(inputs).await.or_fail("proof");
 
             let proof_tx = ProofTransaction {
                 proof,
@@ -143,8 +510,10 @@ async fn main() {
             };
 
             // Send the proof transaction
-            let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-            println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+            node::report_proof_sent(
+                &blob_tx_hash,
+                client.send_proof(&proof_tx, &blob_tx_hash).await,
+            );
         }
         Commands::VerifyIdentity {
             account,
@@ -156,7 +525,7 @@ async fn main() {
                 let initial_state: IdentityContractState = client
                     .get_contract(&contract_name.clone().into())
                     .await
-                    .unwrap()
+                    .or_fail("node")
                     .state
                     .into();
 
@@ -174,12 +543,12 @@ async fn main() {
                     ),
                 };
 
-                let message = format!("{} {:?}", &message_blob.contract_name, &message_blob.data.0);
-
-                let message = format!("verify {} {}", nonce, message);
+                let message =
+                    verification_message(contract_name, nonce, std::slice::from_ref(&message_blob));
 
                 let (pub_key, signature) =
-                    handle_secp384r1_identity(&account, &password, &message.as_bytes()).unwrap();
+                    handle_secp384r1_identity(&account, &password, &message.as_bytes())
+                        .or_fail("key");
 
                 let identity = format!("{}.{}", pub_key, contract_name);
 
@@ -208,8 +577,8 @@ async fn main() {
                 };
 
                 // Send the blob transaction
-                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-                println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
+                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+                node::report_blob_sent(&blob_tx_hash);
 
                 // ----
                 // Prove the state transition
@@ -221,13 +590,13 @@ async fn main() {
                     identity: blob_tx.identity,
                     tx_hash: blob_tx_hash.clone(),
                     private_input: vec![],
-                    tx_ctx: None,
+                    tx_ctx: Some(client.sequenced_context(&blob_tx_hash).await.or_fail("node")),
                     blobs: blobs.clone(),
                     index: sdk::BlobIndex(0),
                 };
 
                 // Generate the zk proof
-                let proof = prover.prove(inputs).await.unwrap();
+                let proof = prover.prove(inputs).await.or_fail("proof");
 
                 let proof_tx = ProofTransaction {
                     proof,
@@ -235,9 +604,286 @@ async fn main() {
                 };
 
                 // Send the proof transaction
-                let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-                println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+                node::report_proof_sent(
+                    &blob_tx_hash,
+                    client.send_proof(&proof_tx, &blob_tx_hash).await,
+                );
+            }
+        }
+        Commands::AuditExport {
+            account,
+            password,
+            output,
+            tx_hashes,
+        } => {
+            let state: IdentityContractState = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state
+                .into();
+
+            let private_key =
+                load_secp384r1_key(&account, &password).expect("Failed to load account key");
+
+            let bundle = audit::export(&state, contract_name, &private_key, tx_hashes)
+                .expect("Failed to build audit bundle");
+
+            std::fs::write(&output, serde_json::to_vec_pretty(&bundle).unwrap())
+                .expect("Failed to write audit bundle");
+            host_output::report(
+                "audit_exported",
+                json!({ "path": output }),
+                format_args!("✅ Audit bundle written to {}", output.display()),
+            );
+        }
+        Commands::AuditVerify { bundle } => {
+            let data = std::fs::read(&bundle).expect("Failed to read audit bundle");
+            let bundle: audit::AuditBundle =
+                serde_json::from_slice(&data).expect("Invalid audit bundle");
+
+            match audit::verify(&bundle) {
+                Ok(()) => host_output::report(
+                    "audit_verified",
+                    json!({
+                        "valid": true,
+                        "identity": bundle.evidence.identity,
+                        "nonce": bundle.evidence.nonce,
+                    }),
+                    format_args!(
+                        "✅ Audit bundle valid for {} (nonce {})",
+                        bundle.evidence.identity, bundle.evidence.nonce
+                    ),
+                ),
+                Err(e) => host_output::report(
+                    "audit_verified",
+                    json!({ "valid": false, "problem": format!("{:#}", e) }),
+                    format_args!("❌ Audit bundle invalid: {:#}", e),
+                ),
+            }
+        }
+        Commands::Backup {
+            command:
+                BackupCommand::Create {
+                    passphrase,
+                    output,
+                    upload,
+                },
+        } => {
+            let archive = backup::create(&passphrase).expect("Failed to create backup");
+            std::fs::write(&output, &archive).expect("Failed to write backup");
+            host_output::report(
+                "backup_written",
+                json!({ "path": output }),
+                format_args!("✅ Backup written to {}", output.display()),
+            );
+
+            if let Some(url) = upload {
+                backup::upload(&url, archive)
+                    .await
+                    .expect("Failed to upload backup");
+                host_output::report("backup_uploaded", json!({ "url": url }), "✅ Backup uploaded");
+            }
+        }
+        Commands::Backup {
+            command:
+                BackupCommand::Restore {
+                    passphrase,
+                    source,
+                    overwrite,
+                },
+        } => {
+            let archive = backup::fetch(&source).await.expect("Failed to fetch backup");
+            backup::restore(&archive, &passphrase, overwrite).expect("Failed to restore backup");
+        }
+        Commands::AuthorizeHotKey {
+            account,
+            password,
+            hot_account,
+            hot_password,
+            max_nonce,
+            allowed_contracts,
+            expires_at,
+        } => {
+            let state: IdentityContractState = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state
+                .into();
+
+            // Creates the hot key on first use
+            let (hot_key, _) =
+                handle_secp384r1_identity(&hot_account, &hot_password, b"").or_fail("key");
+
+            let primary_key =
+                load_secp384r1_key(&account, &password).expect("Failed to load account key");
+            let pub_key = utils::encode_public_key(&primary_key);
+            let nonce = state
+                .get_nonce(&pub_key)
+                .unwrap_or_else(|_| host_output::fail("not_registered", "Account not registered"));
+
+            let policy = HotKeyPolicy {
+                hot_key,
+                min_nonce: nonce + 1,
+                max_nonce,
+                allowed_contracts,
+                expires_at,
+            };
+            let (_, signature) = handle_secp384r1_identity(
+                &account,
+                &password,
+                policy.signing_message(contract_name, nonce).as_bytes(),
+            )
+            .or_fail("key");
+
+            let action = IdentityAction::AuthorizeHotKey {
+                nonce,
+                policy,
+                signature,
+            };
+            send_and_prove(
+                &client,
+                &prover,
+                contract_name,
+                format!("{}.{}", pub_key, contract_name),
+                vec![encode_blob(contract_name, action)],
+                vec![],
+            )
+            .await;
+        }
+        Commands::VerifyWithHotKey {
+            pub_key,
+            hot_account,
+            hot_password,
+            nonce,
+        } => {
+            let message_blob = encode_blob(
+                contract_name,
+                IdentityAction::VerifyIdentity {
+                    signature: None,
+                    nonce,
+                },
+            );
+            let message = verification_message(
+                contract_name,
+                nonce,
+                std::slice::from_ref(&message_blob),
+            );
+
+            let (hot_key, signature) =
+                handle_secp384r1_identity(&hot_account, &hot_password, message.as_bytes())
+                    .or_fail("key");
+
+            let action = IdentityAction::VerifyWithHotKey {
+                nonce,
+                hot_key,
+                signature,
+            };
+            send_and_prove(
+                &client,
+                &prover,
+                contract_name,
+                format!("{}.{}", pub_key, contract_name),
+                vec![encode_blob(contract_name, action), message_blob],
+                vec![],
+            )
+            .await;
+        }
+        Commands::RecoverWithCode {
+            lost_key,
+            account,
+            password,
+        } => {
+            let code = recovery::prompt().expect("Failed to read the recovery code");
+
+            // Creates the new key on first use
+            let message = recovery_message(contract_name, &lost_key);
+            let (new_key, signature) =
+                handle_secp384r1_identity(&account, &password, message.as_bytes())
+                    .or_fail("key");
+
+            let action = IdentityAction::RecoverWithCode {
+                account: lost_key,
+                signature,
+            };
+            send_and_prove(
+                &client,
+                &prover,
+                contract_name,
+                format!("{}.{}", new_key, contract_name),
+                vec![encode_blob(contract_name, action)],
+                code.into_bytes(),
+            )
+            .await;
+        }
+        Commands::StateShow {} => {
+            let state: IdentityContractState = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state
+                .into();
+
+            host_output::report(
+                "state",
+                json!({ "contract": contract_name, "state": state }),
+                format_args!(
+                    "Contract: {}\n{}",
+                    contract_name,
+                    serde_json::to_string_pretty(&state).unwrap()
+                ),
+            );
+        }
+        Commands::GetState { account } => {
+            let digest = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state;
+            let state = IdentityContractState::from_bytes(&digest.0).or_fail("unknown_state");
+
+            let accounts: Vec<_> = match &account {
+                Some(account) => {
+                    let info = state.get_account(account).unwrap_or_else(|_| {
+                        host_output::fail("not_registered", "No account for this public key")
+                    });
+                    vec![(account, info)]
+                }
+                None => state.accounts().collect(),
+            };
+            say!(
+                "Contract: {} ({} bytes, {} account(s))",
+                contract_name,
+                digest.0.len(),
+                state.accounts().count()
+            );
+            for (pub_key, info) in accounts {
+                host_output::report(
+                    "account_state",
+                    inspect::account_json(pub_key, info),
+                    inspect::render_account(pub_key, info),
+                );
             }
         }
+        Commands::Did { pub_key } => {
+            let state: IdentityContractState = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state
+                .into();
+            let info = state.get_account(&pub_key).unwrap_or_else(|_| {
+                host_output::fail("not_registered", "No account for this public key")
+            });
+
+            let document =
+                did::document(contract_name, &pub_key, info).expect("Failed to render DID");
+            host_output::report(
+                "did",
+                json!({ "document": document }),
+                serde_json::to_string_pretty(&document).unwrap(),
+            );
+        }
     }
 }
@@ -0,0 +1,57 @@
+//! Recovery codes: generated here at registration, printed once and never
+//! written anywhere; only their hashes are sent to the contract.
+
+use std::io::{self, BufRead, Write};
+
+use aes_gcm::aead::OsRng;
+use anyhow::{bail, Result};
+use contract_identity::recovery::{recovery_code_hash, MAX_RECOVERY_CODES};
+use p384::elliptic_curve::rand_core::RngCore;
+
+/// Random bytes per code: 80 bits, shown as four groups of five hex digits.
+const CODE_BYTES: usize = 10;
+
+/// Generates `count` codes for the account of `pub_key`, returning them with
+/// their hashes.
+pub fn generate(pub_key: &str, count: usize) -> Result<(Vec<String>, Vec<String>)> {
+    if count > MAX_RECOVERY_CODES {
+        bail!("At most {} recovery codes per account", MAX_RECOVERY_CODES);
+    }
+    let codes: Vec<String> = (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; CODE_BYTES];
+            OsRng.fill_bytes(&mut bytes);
+            let digits = hex::encode(bytes);
+            digits
+                .as_bytes()
+                .chunks(5)
+                .map(|group| std::str::from_utf8(group).unwrap())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| recovery_code_hash(pub_key, code))
+        .collect();
+    Ok((codes, hashes))
+}
+
+pub fn print(codes: &[String]) {
+    let mut text =
+        "🔑 Recovery codes, each usable once. They aren't stored: write them down now.".to_string();
+    for code in codes {
+        text += &format!("\n   {}", code);
+    }
+    host_output::report("recovery_codes", serde_json::json!({ "codes": codes }), text);
+}
+
+/// Reads a code from stdin rather than the command line, keeping it out of
+/// the shell history. The prompt goes to stderr, stdout being for results.
+pub fn prompt() -> Result<String> {
+    eprint!("Recovery code: ");
+    io::stderr().flush()?;
+    let mut code = String::new();
+    io::stdin().lock().read_line(&mut code)?;
+    Ok(code.trim().to_string())
+}
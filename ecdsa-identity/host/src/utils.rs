@@ -1,16 +1,18 @@
 use aes_gcm::aead::{Aead, KeyInit, OsRng};
 use aes_gcm::{Aes256Gcm, Key, Nonce}; // AES-GCM for encryption
+use app_dirs::Migration;
 use dirs::data_dir;
 use hex::encode;
+use host_output::say;
 use p384::ecdsa::signature::SignerMut;
 use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
 use p384::elliptic_curve::pkcs8::{DecodePrivateKey, EncodePrivateKey};
 use p384::elliptic_curve::rand_core::RngCore;
 use pbkdf2::pbkdf2_hmac;
 use sha2::Sha256;
-use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::{Error, ErrorKind, Read, Write};
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
 
 // Derive a strong 256-bit encryption key from the password
 fn derive_key(password: &str) -> [u8; 32] {
@@ -21,39 +23,91 @@ fn derive_key(password: &str) -> [u8; 32] {
     key_bytes
 }
 
+/// Directory holding one encrypted key file per account.
+pub fn keys_dir() -> PathBuf {
+    app_dirs::get()
+        .keys()
+        .expect("Failed to create ECDSA keys directory")
+}
+
+/// Moves the key files of earlier versions, kept in `ecdsa_keys` right under
+/// the platform's data directory, to [`keys_dir`]. Does nothing once done.
+pub fn migrate_legacy_keys() -> Result<Migration, Error> {
+    match data_dir() {
+        Some(data_dir) => app_dirs::migrate_dir(&data_dir.join("ecdsa_keys"), &keys_dir()),
+        None => Ok(Migration::default()),
+    }
+}
+
+/// Smallest intact key file: the nonce, the AES-GCM tag and at least the
+/// 48-byte P-384 scalar.
+pub const MIN_KEY_FILE_LEN: u64 = 12 + 16 + 48;
+
+/// Creates `path` readable by its owner only, as key files should be.
+pub fn create_private(path: &Path) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Reads a password from stdin rather than the command line, keeping it out
+/// of the shell history and the process list. The prompt goes to stderr,
+/// stdout being for results.
+pub fn prompt_secret(name: &str) -> Result<String, Error> {
+    eprint!("{}: ", name);
+    io::stderr().flush()?;
+    let mut secret = String::new();
+    io::stdin().lock().read_line(&mut secret)?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn account_key_path(account: &str) -> PathBuf {
+    keys_dir().join(account)
+}
+
+/// Loads an existing account key, without generating one when it is missing.
+pub fn load_secp384r1_key(account: &str, password: &str) -> Result<SigningKey, Error> {
+    let account_path = account_key_path(account);
+    if !account_path.exists() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("No key stored for account {account}"),
+        ));
+    }
+    decrypt_key(password, &account_path)
+}
+
+/// Hex-encoded uncompressed SEC1 public key, as used in account identities.
+pub fn encode_public_key(private_key: &SigningKey) -> String {
+    let public_key = VerifyingKey::from(private_key);
+    encode(public_key.to_encoded_point(false).as_bytes())
+}
+
 pub fn handle_secp384r1_identity(
     account: &str,
     password: &str,
     message: &[u8],
 ) -> Result<(String, String), Error> {
-    let config_path = data_dir()
-        .expect("Failed to get data directory")
-        .join("ecdsa_keys");
-
-    // Ensure directory exists
-    if !config_path.exists() {
-        create_dir_all(&config_path).expect("Failed to create ECDSA keys directory");
-    }
-
-    let account_path = config_path.join(account);
+    let account_path = account_key_path(account);
     let mut private_key: SigningKey;
 
     if account_path.exists() {
-        println!("Retrieving private key from config");
+        say!("Retrieving private key from config");
         private_key = decrypt_key(password, &account_path).expect("Failed to decrypt key");
     } else {
-        println!("Generating new user private key");
+        say!("Generating new user private key");
         private_key = SigningKey::random(&mut OsRng);
         encrypt_key(password, &private_key, &account_path).expect("Failed to encrypt key");
     }
 
     let signature: Signature = private_key.sign(message);
-    let public_key = VerifyingKey::from(&private_key);
-
-    let binding = public_key.to_encoded_point(false);
-    let pubkey_bytes = binding.as_bytes();
 
-    Ok((encode(pubkey_bytes), encode(signature.to_der().as_bytes())))
+    Ok((
+        encode_public_key(&private_key),
+        encode(signature.to_der().as_bytes()),
+    ))
 }
 
 // Encrypt & Save Data to File
@@ -74,7 +128,7 @@ fn encrypt_key(password: &str, private_key: &SigningKey, filepath: &Path) -> Res
         .expect("Encryption failed");
 
     // Save (Nonce + Ciphertext) to file
-    let mut file = File::create(filepath)?;
+    let mut file = create_private(filepath)?;
     file.write_all(&nonce_bytes)?;
     file.write_all(&ciphertext)?;
     Ok(())
@@ -1,3 +1,8 @@
 fn main() {
     risc0_build::embed_methods();
+
+    // `--features sp1` also builds `sp1-guest`, whose ELF path the build
+    // exports as `SP1_ELF_sp1-guest`
+    #[cfg(feature = "sp1")]
+    sp1_build::build_program("sp1-guest");
 }
@@ -0,0 +1,17 @@
+#![no_main]
+
+use sdk::guest::commit;
+use sdk::guest::GuestEnv;
+use sdk::guest::SP1Env;
+
+use contract_identity::execute;
+use sdk::ContractInput;
+
+sp1_zkvm::entrypoint!(main);
+
+// Same program as `guest`, for the SP1 zkVM.
+fn main() {
+    let env = SP1Env {};
+    let input: ContractInput = env.read();
+    commit(env, input.clone(), execute(input));
+}
@@ -1 +1,5 @@
 include!(concat!(env!("OUT_DIR"), "/methods.rs"));
+
+/// ELF of the identity guest built for SP1.
+#[cfg(feature = "sp1")]
+pub const SP1_GUEST_ELF: &[u8] = include_bytes!(env!("SP1_ELF_sp1-guest"));
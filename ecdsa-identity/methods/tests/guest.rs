@@ -0,0 +1,147 @@
+//! Runs the contract inside the risc0 executor rather than natively.
+//!
+//! Each case executes the guest ELF without proving, checks its journal
+//! against the native `execute`, and fails when the cycle count exceeds the
+//! case's budget, so guest-only regressions show up before proving.
+
+use contract_identity::actions::IdentityAction;
+use contract_identity::code;
+use contract_identity::{registration_message, verification_message, IdentityContractState};
+use methods_identity::GUEST_ELF;
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
+use risc0_zkvm::{default_executor, ExecutorEnv};
+use sdk::{BlobIndex, ContractInput, Digestable, HyleOutput, StateDigest};
+
+const CONTRACT: &str = "ecdsa_identity";
+
+/// Cycle budget, with headroom over what the guest takes today.
+const BUDGET: u64 = 20_000_000;
+
+/// Executes the guest on `input` and returns its journal with the user cycles spent.
+fn run_guest(input: &ContractInput) -> (HyleOutput, u64) {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .expect("Failed to write guest input")
+        .build()
+        .expect("Failed to build executor env");
+    let session = default_executor()
+        .execute(env, GUEST_ELF)
+        .expect("Guest execution failed");
+    let cycles = session.segments.iter().map(|s| s.cycles as u64).sum();
+    let output = session
+        .journal
+        .decode()
+        .expect("Failed to decode guest journal");
+    (output, cycles)
+}
+
+/// Runs `input` in the guest, checks the journal agrees with the native
+/// execution and fits in the budget, and returns the next state.
+fn assert_guest_matches(input: ContractInput) -> StateDigest {
+    let (output, cycles) = run_guest(&input);
+
+    match contract_identity::execute(input) {
+        Ok((program_output, state, _)) => {
+            assert!(
+                output.success,
+                "guest failed: {}",
+                String::from_utf8_lossy(&output.program_outputs)
+            );
+            assert_eq!(output.program_outputs, program_output.into_bytes());
+            assert_eq!(output.next_state, state.as_digest());
+        }
+        Err(err) => {
+            assert!(
+                !output.success,
+                "guest succeeded where native execution failed"
+            );
+            assert_eq!(output.program_outputs, err.into_bytes());
+        }
+    }
+    assert!(
+        cycles <= BUDGET,
+        "{cycles} cycles exceed the budget of {BUDGET}"
+    );
+    output.next_state
+}
+
+fn input(state: StateDigest, key: &SigningKey, action: IdentityAction) -> ContractInput {
+    let pub_key = hex::encode(VerifyingKey::from(key).to_encoded_point(false).as_bytes());
+    ContractInput {
+        initial_state: state,
+        identity: format!("{pub_key}.{CONTRACT}").into(),
+        tx_hash: sdk::TxHash("guest-test".to_string()),
+        private_input: vec![],
+        tx_ctx: None,
+        blobs: vec![action.as_blob(CONTRACT.into())],
+        index: BlobIndex(0),
+    }
+}
+
+fn sign(key: &SigningKey, message: &str) -> String {
+    let signature: Signature = key.sign(message.as_bytes());
+    hex::encode(signature.to_der().as_bytes())
+}
+
+#[test]
+fn register_and_verify() {
+    let key = SigningKey::from_slice(&[7u8; 48]).expect("invalid test key");
+
+    let register = IdentityAction::RegisterIdentity {
+        signature: sign(&key, &registration_message(CONTRACT)),
+        recovery_codes: vec![],
+    };
+    let state = assert_guest_matches(input(
+        IdentityContractState::new().as_digest(),
+        &key,
+        register,
+    ));
+
+    let verify = IdentityAction::VerifyIdentity {
+        nonce: 0,
+        signature: Some(sign(&key, &verification_message(CONTRACT, 0, &[]))),
+    };
+    assert_guest_matches(input(state, &key, verify));
+}
+
+#[test]
+fn verify_with_wrong_nonce_fails() {
+    let key = SigningKey::from_slice(&[7u8; 48]).expect("invalid test key");
+
+    let register = IdentityAction::RegisterIdentity {
+        signature: sign(&key, &registration_message(CONTRACT)),
+        recovery_codes: vec![],
+    };
+    let state = assert_guest_matches(input(
+        IdentityContractState::new().as_digest(),
+        &key,
+        register,
+    ));
+
+    let verify = IdentityAction::VerifyIdentity {
+        nonce: 3,
+        signature: Some(sign(&key, &verification_message(CONTRACT, 3, &[]))),
+    };
+    assert_guest_matches(input(state, &key, verify));
+}
+
+#[test]
+fn register_reports_work() {
+    let key = SigningKey::from_slice(&[7u8; 48]).expect("invalid test key");
+
+    let register = IdentityAction::RegisterIdentity {
+        signature: sign(&key, &registration_message(CONTRACT)),
+        recovery_codes: vec![],
+    };
+    let (output, _) = run_guest(&input(
+        IdentityContractState::new().as_digest(),
+        &key,
+        register,
+    ));
+
+    let work = code::parse_work(&String::from_utf8_lossy(&output.program_outputs))
+        .expect("No work in the program output");
+    assert_eq!((work.signatures, work.hashes), (1, 1));
+    assert_eq!(work.state_bytes, output.next_state.0.len() as u64);
+}
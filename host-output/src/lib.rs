@@ -0,0 +1,157 @@
+//! `--output json` of the host CLIs, see the README.
+//!
+//! A host selects its [`Output`] once at startup with [`init`]. Messages for
+//! people are printed with [`say!`], results with [`report`] and errors with
+//! [`fail`]: in JSON mode, stdout then only carries the objects of results
+//! and errors, one per line.
+
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use serde_json::{json, Map, Value};
+
+/// What the host prints on stdout.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum Output {
+    /// Messages for people
+    #[default]
+    Text,
+    /// One JSON object per result or error, messages going to stderr
+    Json,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+/// Sets the output of this process. Only the first call has an effect. In
+/// JSON mode, panics are reported as `panic` errors too.
+pub fn init(output: Output) {
+    if OUTPUT.set(output).is_err() || output == Output::Text {
+        return;
+    }
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panicked".to_string());
+        println!("{}", event_line("error", json!({ "code": "panic", "message": message })));
+    }));
+}
+
+/// The output set by [`init`], text until then.
+pub fn get() -> Output {
+    OUTPUT.get().copied().unwrap_or_default()
+}
+
+pub fn is_json() -> bool {
+    get() == Output::Json
+}
+
+/// `println!` for messages meant for people, which go to stderr in JSON mode.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a result of the command: `text`, or in JSON mode an `event` object
+/// with `fields`.
+pub fn report(event: &str, fields: Value, text: impl Display) {
+    match get() {
+        Output::Text => println!("{}", text),
+        Output::Json => println!("{}", event_line(event, fields)),
+    }
+}
+
+/// Ends the process on an error: `❌ message` on stderr, and in JSON mode an
+/// `error` object with `code` on stdout.
+pub fn fail(code: &str, message: impl Display) -> ! {
+    fail_with(code, message, Value::Null)
+}
+
+/// [`fail`], adding `fields` to the `error` object.
+pub fn fail_with(code: &str, message: impl Display, fields: Value) -> ! {
+    eprintln!("❌ {}", message);
+    error(code, message, fields);
+    std::process::exit(1)
+}
+
+/// In JSON mode, prints an `error` object with `code`, `message` and
+/// `fields`, for hosts printing the message their own way.
+pub fn error(code: &str, message: impl Display, fields: Value) {
+    if !is_json() {
+        return;
+    }
+    let mut error = json!({ "code": code, "message": message.to_string() });
+    if let Value::Object(fields) = fields {
+        error.as_object_mut().unwrap().extend(fields);
+    }
+    println!("{}", event_line("error", error));
+}
+
+/// `unwrap` for results the host can't go on without, failing with `code`.
+pub trait OrFail<T> {
+    fn or_fail(self, code: &str) -> T;
+}
+
+impl<T, E: Display> OrFail<T> for Result<T, E> {
+    fn or_fail(self, code: &str) -> T {
+        self.unwrap_or_else(|err| fail(code, format_args!("{:#}", err)))
+    }
+}
+
+/// `fields` with their `event`, as one line. Fields other than an object are
+/// given as `value`.
+pub fn event_line(event: &str, fields: Value) -> String {
+    let mut object = Map::new();
+    object.insert("event".to_string(), event.into());
+    match fields {
+        Value::Object(fields) => object.extend(fields),
+        Value::Null => {}
+        value => {
+            object.insert("value".to_string(), value);
+        }
+    }
+    Value::Object(object).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_line_merges_fields() {
+        let line = event_line("blob_tx_sent", json!({ "tx_hash": "9f3c" }));
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({ "event": "blob_tx_sent", "tx_hash": "9f3c" })
+        );
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn test_event_line_wraps_other_values() {
+        assert_eq!(
+            serde_json::from_str::<Value>(&event_line("accounts", json!(["alice"]))).unwrap(),
+            json!({ "event": "accounts", "value": ["alice"] })
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&event_line("done", Value::Null)).unwrap(),
+            json!({ "event": "done" })
+        );
+    }
+
+    #[test]
+    fn test_text_until_init() {
+        assert_eq!(get(), Output::Text);
+        assert!(!is_json());
+    }
+}
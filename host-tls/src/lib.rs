@@ -0,0 +1,162 @@
+//! TLS, optionally mutual, for the HTTP services of the identity hosts, see
+//! the README.
+//!
+//! The services register and verify identities, so whoever can reach them
+//! controls on-chain accounts. Given a client CA, an [`Acceptor`] only
+//! completes handshakes with clients presenting a certificate the CA signed,
+//! and reports its [`ClientCert`] so services can bind what they issue to it.
+
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+
+/// PEM files of a TLS service.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TlsSettings {
+    /// Server certificate chain
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// CA that must have signed the clients' certificates; `None` serves TLS
+    /// without client authentication
+    pub client_ca: Option<PathBuf>,
+}
+
+/// The certificate a client authenticated with.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ClientCert {
+    /// See [`thumbprint`]
+    pub thumbprint: String,
+}
+
+/// Base64url SHA-256 of a DER certificate: the `x5t#S256` confirmation that
+/// RFC 8705 puts in certificate-bound tokens.
+pub fn thumbprint(der: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(der))
+}
+
+/// Accepts TLS connections with the configured certificate.
+#[derive(Clone)]
+pub struct Acceptor {
+    inner: tokio_rustls::TlsAcceptor,
+}
+
+impl Acceptor {
+    pub fn new(settings: &TlsSettings) -> io::Result<Self> {
+        let certs = read_certs(&settings.cert)?;
+        let key = read_key(&settings.key)?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &settings.client_ca {
+            Some(client_ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(client_ca)? {
+                    roots.add(cert).map_err(invalid)?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(invalid)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder.with_single_cert(certs, key).map_err(invalid)?;
+
+        Ok(Acceptor {
+            inner: tokio_rustls::TlsAcceptor::from(Arc::new(config)),
+        })
+    }
+
+    /// Completes the handshake on `stream`. The client certificate is `None`
+    /// unless the settings name a client CA, in which case clients without a
+    /// valid one fail the handshake.
+    pub async fn accept<S>(&self, stream: S) -> io::Result<(TlsStream<S>, Option<ClientCert>)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let stream = self.inner.accept(stream).await?;
+        let client = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| ClientCert {
+                thumbprint: thumbprint(cert.as_ref()),
+            });
+        Ok((stream, client))
+    }
+}
+
+fn read_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))
+        .collect::<io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("No certificate in {}", path.display()),
+        ));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(path)?))?.ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("No private key in {}", path.display()),
+        )
+    })
+}
+
+fn invalid(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("host-tls-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn thumbprint_is_base64url_sha256() {
+        assert_eq!(
+            thumbprint(b""),
+            "47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let dir = scratch("missing");
+        let settings = TlsSettings {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+            client_ca: None,
+        };
+        let err = Acceptor::new(&settings).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn pem_without_certificate_is_invalid() {
+        let dir = scratch("empty");
+        std::fs::write(dir.join("cert.pem"), "not a certificate\n").unwrap();
+        let err = read_certs(&dir.join("cert.pem")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -39,7 +39,7 @@ async function getAccount() {
 
 // Sign message using personal_sign
 async function signMessage() {
-  const message = 'hyle registration';
+  const message = 'hyle registration metamask_identity';
   const hexMessage = toHexMessage(message); // Convert message to hex
   console.log(hexMessage);
   const ethAddr = await ethereum.request({
@@ -0,0 +1,68 @@
+use bincode::{Decode, Encode};
+use sdk::{Blob, BlobData, ContractName};
+use serde::{Deserialize, Serialize};
+
+use crate::realm::RealmPolicy;
+
+/// Actions of the contract. The first three are those of the sdk's
+/// `identity_provider::IdentityAction`, in the same order: blobs encoded with
+/// either type decode the same. The private blob carries the signature each
+/// action needs.
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
+pub enum IdentityAction {
+    /// Signed over [`crate::registration_message`]
+    RegisterIdentity {
+        account: String,
+    },
+    VerifyIdentity {
+        account: String,
+        nonce: u32,
+    },
+    GetIdentityInfo {
+        account: String,
+    },
+    /// Claims `realm` for `account`, which becomes its admin. Signed over
+    /// [`crate::realm::creation_message`].
+    CreateRealm {
+        realm: String,
+        account: String,
+        policy: RealmPolicy,
+    },
+    /// Replaces the policy of a realm, sent wrapped in `InRealm` by its admin.
+    /// Signed over [`crate::realm::policy_message`].
+    SetPolicy {
+        account: String,
+        policy: RealmPolicy,
+    },
+    /// Applies `action` to the state of `realm` instead of the root state.
+    InRealm {
+        realm: String,
+        action: Box<IdentityAction>,
+    },
+    /// `RegisterIdentity`, committing `recovery_codes`: hashes of one-time
+    /// codes, see [`crate::recovery::recovery_code_hash`]. Signed over
+    /// [`crate::recovery::registration_message`].
+    RegisterWithRecoveryCodes {
+        account: String,
+        recovery_codes: Vec<String>,
+    },
+    /// Moves `lost_account` to `account`, named after a new address, with one
+    /// of its recovery codes. The private blob is the new address's signature
+    /// over [`crate::recovery::recovery_message`], a space, then the code.
+    RecoverWithCode {
+        account: String,
+        lost_account: String,
+    },
+}
+
+impl IdentityAction {
+    pub fn as_blob(&self, contract_name: ContractName) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData(
+                bincode::encode_to_vec(self, bincode::config::standard())
+                    .expect("failed to encode program inputs"),
+            ),
+        }
+    }
+}
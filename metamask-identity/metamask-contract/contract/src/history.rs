@@ -0,0 +1,51 @@
+//! Control history of an account: a rolling hash over its security events,
+//! its registration and the recoveries moving it to a new address. The head
+//! lets relying parties check accounts the same way across the identity
+//! contracts.
+
+use bincode::{Decode, Encode};
+use hex::encode;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use sha3::Keccak256;
+
+/// Security-relevant events of an account.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SecurityEvent<'a> {
+    /// Registered, with the account name hashing to `hash`
+    Registered { hash: &'a str },
+    /// Moved with a recovery code to the account hashing to `hash`
+    Recovered { hash: &'a str },
+}
+
+impl SecurityEvent<'_> {
+    /// Canonical text of the event, as hashed into the history.
+    pub fn describe(&self) -> String {
+        match self {
+            SecurityEvent::Registered { hash } => format!("registered {hash}"),
+            SecurityEvent::Recovered { hash } => format!("recovered {hash}"),
+        }
+    }
+}
+
+/// Head of an account's history, and how many events it covers.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct ControlHistory {
+    /// Hex; empty before the first event
+    pub head: String,
+    pub events: u32,
+}
+
+impl ControlHistory {
+    pub fn record(&mut self, event: SecurityEvent) {
+        self.head = next_head(&self.head, event);
+        self.events += 1;
+    }
+}
+
+/// Head following `head` once `event` is recorded: `keccak256("{head}:{event}")`.
+pub fn next_head(head: &str, event: SecurityEvent) -> String {
+    encode(Keccak256::digest(
+        format!("{head}:{}", event.describe()).as_bytes(),
+    ))
+}
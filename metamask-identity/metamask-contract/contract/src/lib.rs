@@ -1,17 +1,39 @@
+use actions::IdentityAction;
 use bincode::{Decode, Encode};
+use code::ResultCode;
 use hex::{decode, encode};
+use history::{ControlHistory, SecurityEvent};
 use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
-use sdk::{identity_provider::IdentityVerification, Digestable, HyleOutput};
+use realm::RealmPolicy;
+use sdk::{Digestable, HyleOutput};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use sha3::Keccak256;
 use std::collections::BTreeMap;
 
-/// Entry point of the contract's logic
+pub mod actions;
+pub mod code;
+pub mod history;
+pub mod limits;
+pub mod realm;
+pub mod recovery;
+pub mod work;
+
+/// Entry point of the contract's logic. Program outputs start with a stable
+/// [`ResultCode`], see [`code::output`]; successes also carry the action's
+/// [`work::Work`].
 pub fn execute(contract_input: sdk::ContractInput) -> HyleOutput {
+    if let Err(err) = limits::check_input(&contract_input) {
+        let state: IdentityContractState = contract_input.initial_state.clone().into();
+        return sdk::utils::as_hyle_output(
+            contract_input,
+            state,
+            Err(ResultCode::InvalidInput.fail(err)),
+        );
+    }
+
     // Parse contract inputs
-    let (input, action) =
-        sdk::guest::init_raw::<sdk::identity_provider::IdentityAction>(contract_input);
+    let (input, action) = sdk::guest::init_raw::<IdentityAction>(contract_input);
 
     // Parse initial state
     let mut state: IdentityContractState = input.initial_state.clone().into();
@@ -19,23 +41,132 @@ pub fn execute(contract_input: sdk::ContractInput) -> HyleOutput {
     // Extract private information
     let signature = core::str::from_utf8(&input.private_blob.0).unwrap();
 
+    // Signatures are bound to the name this contract is deployed under
+    let contract_name = input.blobs[input.index.0].contract_name.0.clone();
+
     // Execute the given action
-    let res = sdk::identity_provider::execute_action(&mut state, action, signature);
+    let res = execute_action(&mut state, action, &contract_name, signature);
 
     sdk::utils::as_hyle_output(input, state, res)
 }
 
+/// Message signed with `personal_sign` to register an identity. It names the
+/// contract, so a signature can't be replayed on another deployment.
+pub fn registration_message(contract_name: &str) -> String {
+    format!("hyle registration {contract_name}")
+}
+
+/// Message signed with `personal_sign` to verify an identity at `nonce`, for
+/// the contract or realm `scope`. The nonce makes each signature good once.
+pub fn verification_message(scope: &str, nonce: u32) -> String {
+    format!("hyle verification {scope} nonce {nonce}")
+}
+
+/// Same dispatch as `sdk::identity_provider::execute_action`, with result
+/// codes instead of English program outputs. The detail of a success is the
+/// account, or its info for `GetIdentityInfo`, or the realm for `CreateRealm`.
+pub fn execute_action(
+    state: &mut IdentityContractState,
+    action: IdentityAction,
+    contract_name: &str,
+    private_input: &str,
+) -> Result<String, String> {
+    let mut work = work::action_work(&action);
+    let detail = apply_action(state, action, contract_name, contract_name, private_input)?;
+    work.state_bytes = state.as_digest().0.len() as u64;
+    Ok(code::output_with_work(ResultCode::Ok, work, &detail))
+}
+
+/// Applies `action` to `state`, the root state or a realm's. Messages are
+/// signed for `scope`, see [`realm::scope`].
+fn apply_action(
+    state: &mut IdentityContractState,
+    action: IdentityAction,
+    contract_name: &str,
+    scope: &str,
+    private_input: &str,
+) -> Result<String, ResultCode> {
+    match action {
+        IdentityAction::RegisterIdentity { account } => state
+            .register_identity(contract_name, scope, &account, private_input, vec![])
+            .map(|()| account),
+        IdentityAction::RegisterWithRecoveryCodes {
+            account,
+            recovery_codes,
+        } => state
+            .register_identity(contract_name, scope, &account, private_input, recovery_codes)
+            .map(|()| account),
+        IdentityAction::VerifyIdentity { account, nonce } => {
+            match state.verify_identity(contract_name, scope, &account, nonce, private_input)? {
+                true => Ok(account),
+                false => Err(ResultCode::KeyAccountMismatch),
+            }
+        }
+        IdentityAction::GetIdentityInfo { account } => state.get_identity_info(&account),
+        IdentityAction::CreateRealm {
+            realm,
+            account,
+            policy,
+        } => state
+            .create_realm(contract_name, &realm, &account, policy, private_input)
+            .map(|()| realm),
+        IdentityAction::SetPolicy { account, policy } => state
+            .set_policy(contract_name, scope, &account, policy, private_input)
+            .map(|()| account),
+        IdentityAction::RecoverWithCode {
+            account,
+            lost_account,
+        } => {
+            let (signature, code) = recovery::split_private_input(private_input)?;
+            state
+                .recover_with_code(contract_name, scope, &lost_account, &account, code, signature)
+                .map(|()| account)
+        }
+        IdentityAction::InRealm { realm, action } => {
+            realm::check_name(&realm)?;
+            if matches!(
+                *action,
+                IdentityAction::InRealm { .. } | IdentityAction::CreateRealm { .. }
+            ) {
+                return Err(ResultCode::NestedRealm);
+            }
+            let realm_state = state
+                .realms
+                .get_mut(&realm)
+                .ok_or(ResultCode::RealmNotFound)?;
+            apply_action(
+                realm_state,
+                *action,
+                contract_name,
+                &realm::scope(scope, &realm),
+                private_input,
+            )
+        }
+    }
+}
+
 /// Struct to hold account's information
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct AccountInfo {
     pub pub_key_hash: String,
     pub nonce: u32,
+    /// Hashes of the account's unused recovery codes
+    pub recovery_codes: Vec<String>,
+    /// Its registration and recoveries so far
+    pub history: ControlHistory,
 }
 
 /// The state of the contract, that is totally serialized on-chain
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
 pub struct IdentityContractState {
     identities: BTreeMap<String, AccountInfo>,
+    /// Address of the realm's admin; the root state has none
+    admin: Option<String>,
+    /// Signed into every policy change, so admin signatures can't be replayed
+    admin_nonce: u32,
+    policy: RealmPolicy,
+    /// Realms of the root state, by name; always empty in a realm's state
+    realms: BTreeMap<String, IdentityContractState>,
 }
 
 /// Some helper methods for the state
@@ -43,62 +174,109 @@ impl IdentityContractState {
     pub fn new() -> Self {
         IdentityContractState {
             identities: BTreeMap::new(),
+            admin: None,
+            admin_nonce: 0,
+            policy: RealmPolicy::default(),
+            realms: BTreeMap::new(),
         }
     }
 
-    pub fn get_nonce(&self, account: &str) -> Result<u32, &'static str> {
-        let info = self.identities.get(account).ok_or("Identity not found")?;
+    pub fn admin(&self) -> Option<&str> {
+        self.admin.as_deref()
+    }
+
+    pub fn policy(&self) -> &RealmPolicy {
+        &self.policy
+    }
+
+    pub fn realm(&self, realm: &str) -> Option<&IdentityContractState> {
+        self.realms.get(realm)
+    }
+
+    pub fn get_nonce(&self, account: &str) -> Result<u32, ResultCode> {
+        let info = self
+            .identities
+            .get(account)
+            .ok_or(ResultCode::IdentityNotFound)?;
         Ok(info.nonce)
     }
 }
 
-// The IdentityVerification trait is implemented for the IdentityContractState struct
-// This trait is given by the sdk, as a "standard" for identity verification contracts
-// but you could do the same logic without it.
-impl IdentityVerification for IdentityContractState {
-    fn register_identity(
+// Same methods as the sdk's `IdentityVerification` trait, whose `&'static str`
+// errors can't carry a result code.
+impl IdentityContractState {
+    /// Registers `account` of the contract `contract_name`, with a signature
+    /// over the registration message of `scope`: the contract name, or a
+    /// realm's scope. With recovery codes, the message also binds their hashes.
+    pub fn register_identity(
         &mut self,
+        contract_name: &str,
+        scope: &str,
         account: &str,
         private_input: &str,
-    ) -> Result<(), &'static str> {
+        recovery_codes: Vec<String>,
+    ) -> Result<(), ResultCode> {
+        if recovery_codes.len() > recovery::MAX_RECOVERY_CODES {
+            return Err(ResultCode::InvalidInput);
+        }
         // Parse the signature
-        let pub_key = account.trim_end_matches(".metamask_identity");
+        let pub_key = account.trim_end_matches(&format!(".{contract_name}"));
 
-        let valid = k256_verifier(pub_key, private_input, "hyle registration");
+        let message = if recovery_codes.is_empty() {
+            registration_message(scope)
+        } else {
+            recovery::registration_message(scope, &recovery_codes)
+        };
+        let valid = k256_verifier(pub_key, private_input, &message);
 
         if !valid {
-            return Err("Invalid signature");
+            return Err(ResultCode::InvalidSignature);
         }
 
         let pub_key_hash = Keccak256::digest(account.as_bytes());
         let pub_key_hash_hex = encode(pub_key_hash);
 
-        let account_info = AccountInfo {
+        let mut account_info = AccountInfo {
             pub_key_hash: pub_key_hash_hex,
             nonce: 0,
+            recovery_codes,
+            history: ControlHistory::default(),
         };
+        account_info.history.record(SecurityEvent::Registered {
+            hash: &account_info.pub_key_hash,
+        });
 
         if self
             .identities
             .insert(account.to_string(), account_info)
             .is_some()
         {
-            return Err("Identity already exists");
+            return Err(ResultCode::IdentityExists);
         }
 
         Ok(())
     }
 
-    fn verify_identity(
+    /// Verifies `account` at `nonce`, with a signature over the verification
+    /// message of `scope`.
+    pub fn verify_identity(
         &mut self,
+        contract_name: &str,
+        scope: &str,
         account: &str,
         nonce: u32,
-        _private_input: &str,
-    ) -> Result<bool, &'static str> {
+        private_input: &str,
+    ) -> Result<bool, ResultCode> {
         match self.identities.get_mut(account) {
             Some(stored_info) => {
                 if nonce != stored_info.nonce {
-                    return Err("Invalid nonce");
+                    return Err(ResultCode::InvalidNonce);
+                }
+
+                let pub_key = account.trim_end_matches(&format!(".{contract_name}"));
+                let message = verification_message(scope, nonce);
+                if !k256_verifier(pub_key, private_input, &message) {
+                    return Err(ResultCode::InvalidSignature);
                 }
 
                 // ✅ Step 2: Compute Keccak256 hash of the account (to match register_identity)
@@ -111,15 +289,108 @@ impl IdentityVerification for IdentityContractState {
                 stored_info.nonce += 1;
                 Ok(true)
             }
-            None => Err("Identity not found"),
+            None => Err(ResultCode::IdentityNotFound),
+        }
+    }
+
+    /// Moves `lost_account` to `account`, named after the address that signed
+    /// the recovery message of `scope`, keeping its nonce so old signatures
+    /// stay consumed. The account's other codes are dropped.
+    pub fn recover_with_code(
+        &mut self,
+        contract_name: &str,
+        scope: &str,
+        lost_account: &str,
+        account: &str,
+        code: &str,
+        signature: &str,
+    ) -> Result<(), ResultCode> {
+        if self.identities.contains_key(account) {
+            return Err(ResultCode::IdentityExists);
+        }
+        let pub_key = account.trim_end_matches(&format!(".{contract_name}"));
+        let message = recovery::recovery_message(scope, lost_account);
+        if !k256_verifier(pub_key, signature, &message) {
+            return Err(ResultCode::InvalidSignature);
+        }
+
+        let code_hash = recovery::recovery_code_hash(lost_account, code);
+        let info = self
+            .identities
+            .get(lost_account)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        if !info.recovery_codes.contains(&code_hash) {
+            return Err(ResultCode::InvalidRecoveryCode);
+        }
+        let mut info = self.identities.remove(lost_account).expect("checked above");
+        // The other codes are salted with the lost account and were issued
+        // with it
+        info.recovery_codes.clear();
+        info.pub_key_hash = encode(Keccak256::digest(account.as_bytes()));
+        info.history.record(SecurityEvent::Recovered {
+            hash: &info.pub_key_hash,
+        });
+        self.identities.insert(account.to_string(), info);
+        Ok(())
+    }
+
+    /// Claims `realm` for `account`, first come, first served.
+    pub fn create_realm(
+        &mut self,
+        contract_name: &str,
+        realm: &str,
+        account: &str,
+        policy: RealmPolicy,
+        private_input: &str,
+    ) -> Result<(), ResultCode> {
+        realm::check_name(realm)?;
+        if self.realms.contains_key(realm) {
+            return Err(ResultCode::RealmExists);
+        }
+        let pub_key = account.trim_end_matches(&format!(".{contract_name}"));
+        let message = realm::creation_message(contract_name, realm, &policy);
+        if !k256_verifier(pub_key, private_input, &message) {
+            return Err(ResultCode::InvalidSignature);
         }
+
+        let mut state = IdentityContractState::new();
+        state.admin = Some(sanitize_hex(pub_key).to_string());
+        state.policy = policy;
+        self.realms.insert(realm.to_string(), state);
+        Ok(())
     }
 
-    fn get_identity_info(&self, account: &str) -> Result<String, &'static str> {
-        match self.identities.get(account) {
-            Some(info) => Ok(serde_json::to_string(&info).map_err(|_| "Failed to serialize")?),
-            None => Err("Identity not found"),
+    pub fn set_policy(
+        &mut self,
+        contract_name: &str,
+        scope: &str,
+        account: &str,
+        policy: RealmPolicy,
+        private_input: &str,
+    ) -> Result<(), ResultCode> {
+        let admin = self
+            .admin
+            .as_deref()
+            .ok_or(ResultCode::GovernanceDisabled)?;
+        let pub_key = account.trim_end_matches(&format!(".{contract_name}"));
+        if sanitize_hex(pub_key) != admin {
+            return Err(ResultCode::KeyAccountMismatch);
         }
+        let message = realm::policy_message(scope, &policy, self.admin_nonce);
+        if !k256_verifier(pub_key, private_input, &message) {
+            return Err(ResultCode::InvalidSignature);
+        }
+        self.policy = policy;
+        self.admin_nonce += 1;
+        Ok(())
+    }
+
+    pub fn get_identity_info(&self, account: &str) -> Result<String, ResultCode> {
+        let info = self
+            .identities
+            .get(account)
+            .ok_or(ResultCode::IdentityNotFound)?;
+        Ok(serde_json::to_string(&info).expect("Failed to serialize"))
     }
 }
 
@@ -0,0 +1,66 @@
+use core::fmt;
+
+use sdk::ContractInput;
+
+use crate::recovery::MAX_RECOVERY_CODE_LEN;
+
+/// Largest hex-encoded recoverable signature: 65 bytes, optionally `0x`-prefixed.
+pub const MAX_SIGNATURE_HEX_LEN: usize = 2 + 2 * 65;
+/// Largest private input: a signature, then a space and a recovery code.
+pub const MAX_PRIVATE_INPUT_LEN: usize = MAX_SIGNATURE_HEX_LEN + 1 + MAX_RECOVERY_CODE_LEN;
+/// Largest number of blobs in the transaction.
+pub const MAX_BLOBS: usize = 16;
+/// Largest encoded blob.
+pub const MAX_BLOB_LEN: usize = 4 * 1024;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LimitError {
+    TooManyBlobs { count: usize },
+    BlobTooLarge { index: usize, len: usize },
+    PrivateInputTooLarge { len: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooManyBlobs { count } => {
+                write!(f, "Too many blobs: {count} (max {MAX_BLOBS})")
+            }
+            LimitError::BlobTooLarge { index, len } => {
+                write!(f, "Blob {index} too large: {len} bytes (max {MAX_BLOB_LEN})")
+            }
+            LimitError::PrivateInputTooLarge { len } => write!(
+                f,
+                "Private input too large: {len} bytes (max {MAX_PRIVATE_INPUT_LEN})"
+            ),
+        }
+    }
+}
+
+/// Rejects oversized inputs before any of them is decoded.
+pub fn check_input(input: &ContractInput) -> Result<(), LimitError> {
+    if input.blobs.len() > MAX_BLOBS {
+        return Err(LimitError::TooManyBlobs {
+            count: input.blobs.len(),
+        });
+    }
+    if let Some((index, blob)) = input
+        .blobs
+        .iter()
+        .enumerate()
+        .find(|(_, blob)| blob.data.0.len() > MAX_BLOB_LEN)
+    {
+        return Err(LimitError::BlobTooLarge {
+            index,
+            len: blob.data.0.len(),
+        });
+    }
+    // The private blob carries the signature, followed by a space and a
+    // recovery code for `RecoverWithCode`
+    if input.private_blob.0.len() > MAX_PRIVATE_INPUT_LEN {
+        return Err(LimitError::PrivateInputTooLarge {
+            len: input.private_blob.0.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Identity realms: namespaces that let one deployment serve many
+//! applications. A realm has its own accounts and policy, and an admin: the
+//! account that claimed it with `CreateRealm`. Actions apply to a realm when
+//! wrapped in `IdentityAction::InRealm`, and every message signed for them
+//! names the realm's [`scope`], so signatures don't carry over between realms.
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::code::ResultCode;
+
+/// Longest realm name.
+pub const MAX_REALM_NAME: usize = 64;
+
+/// What a realm serves.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct RealmPolicy {
+    /// Web origins of the applications served. The contract can't see
+    /// origins: they are published for hosts and relayers to enforce.
+    pub origins: Vec<String>,
+}
+
+/// Name messages are signed for in `realm` of the identity contract
+/// `contract_name`, in place of the contract name.
+pub fn scope(contract_name: &str, realm: &str) -> String {
+    format!("{contract_name}/{realm}")
+}
+
+/// Realm names are short identifiers: ASCII letters, digits, `-` and `_`.
+pub fn check_name(realm: &str) -> Result<(), ResultCode> {
+    let valid = !realm.is_empty()
+        && realm.len() <= MAX_REALM_NAME
+        && realm
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ResultCode::InvalidRealmName)
+    }
+}
+
+/// Message signed with `personal_sign` to claim `realm`.
+pub fn creation_message(contract_name: &str, realm: &str, policy: &RealmPolicy) -> String {
+    format!(
+        "hyle realm {} on {} origins [{}]",
+        realm,
+        contract_name,
+        policy.origins.join(",")
+    )
+}
+
+/// Message the admin of `scope` signs to replace its policy. `nonce` is the
+/// admin's, bumped by every change.
+pub fn policy_message(scope: &str, policy: &RealmPolicy, nonce: u32) -> String {
+    format!(
+        "hyle policy {} origins [{}] nonce {}",
+        scope,
+        policy.origins.join(","),
+        nonce
+    )
+}
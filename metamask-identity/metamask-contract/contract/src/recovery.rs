@@ -0,0 +1,53 @@
+//! One-time recovery codes, committed as hashes at registration. Each moves
+//! the account to a new address once, for users who lost their wallet's seed
+//! phrase.
+
+use hex::encode;
+use sha2::Digest;
+use sha3::Keccak256;
+
+use crate::code::ResultCode;
+
+/// Most recovery codes an account can hold.
+pub const MAX_RECOVERY_CODES: usize = 10;
+/// Longest recovery code, dashes included.
+pub const MAX_RECOVERY_CODE_LEN: usize = 64;
+
+/// Hash committed for `code`, salted with `account`. Dashes and case are
+/// ignored so codes can be shown grouped.
+pub fn recovery_code_hash(account: &str, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    encode(Keccak256::digest(
+        format!("recovery:{account}:{code}").as_bytes(),
+    ))
+}
+
+/// Message signed with `personal_sign` to register with recovery codes, for
+/// the contract or realm `scope`. It binds their hashes, so a copied
+/// registration signature can't be sent with someone else's codes.
+pub fn registration_message(scope: &str, recovery_codes: &[String]) -> String {
+    format!(
+        "{} recovery {}",
+        crate::registration_message(scope),
+        recovery_codes.join(",")
+    )
+}
+
+/// Message the new address signs with `personal_sign` to take over
+/// `account`, the lost one, in `scope`.
+pub fn recovery_message(scope: &str, account: &str) -> String {
+    format!("hyle recover {account} on {scope}")
+}
+
+/// Splits the private input of `RecoverWithCode`: the new address's
+/// signature, a space, then the recovery code.
+pub fn split_private_input(private_input: &str) -> Result<(&str, &str), ResultCode> {
+    private_input
+        .split_once(' ')
+        .filter(|(_, code)| !code.is_empty())
+        .ok_or(ResultCode::MissingRecoveryCode)
+}
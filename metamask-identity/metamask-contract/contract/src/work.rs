@@ -0,0 +1,64 @@
+//! Approximate work metric of an action, reported in successful program
+//! outputs in the same format as the other identity contracts', so that
+//! indexers and fee estimators can learn per-action costs from settled history.
+
+use core::fmt;
+
+use crate::actions::IdentityAction;
+
+/// What an action cost, in units that don't depend on the prover: counts of
+/// the expensive operations rather than cycles.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Work {
+    /// `personal_sign` signatures recovered
+    pub signatures: u32,
+    /// Keccak account and recovery code hashes computed
+    pub hashes: u32,
+    /// Size of the encoded state, which every action decodes and re-encodes
+    pub state_bytes: u64,
+}
+
+impl Work {
+    pub fn new(signatures: u32, hashes: u32) -> Self {
+        Work {
+            signatures,
+            hashes,
+            state_bytes: 0,
+        }
+    }
+
+    /// Reads back a metric written by [`fmt::Display`].
+    pub fn parse(work: &str) -> Option<Self> {
+        let mut parts = work.split(',');
+        let work = Work {
+            signatures: parts.next()?.parse().ok()?,
+            hashes: parts.next()?.parse().ok()?,
+            state_bytes: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(work)
+    }
+}
+
+impl fmt::Display for Work {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{}",
+            self.signatures, self.hashes, self.state_bytes
+        )
+    }
+}
+
+/// Signatures and hashes `action` costs when it succeeds.
+pub fn action_work(action: &IdentityAction) -> Work {
+    match action {
+        IdentityAction::RegisterIdentity { .. }
+        | IdentityAction::RegisterWithRecoveryCodes { .. } => Work::new(1, 1),
+        IdentityAction::VerifyIdentity { .. } => Work::new(1, 1),
+        IdentityAction::GetIdentityInfo { .. } => Work::new(0, 0),
+        IdentityAction::CreateRealm { .. } | IdentityAction::SetPolicy { .. } => Work::new(1, 0),
+        IdentityAction::InRealm { action, .. } => action_work(action),
+        // The code's hash and the new account's
+        IdentityAction::RecoverWithCode { .. } => Work::new(1, 2),
+    }
+}
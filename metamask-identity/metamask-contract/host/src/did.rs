@@ -0,0 +1,46 @@
+//! W3C DID documents for registered accounts, under a `did:hyle` method whose
+//! identifier is the account's Hyle identity: `did:hyle:<address>.<contract>`.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+/// DID of `account` (`<address>.<contract>`). Addresses and contract names only
+/// hold characters DIDs allow, so nothing needs escaping.
+pub fn did(account: &str) -> String {
+    format!("did:hyle:{}", account)
+}
+
+/// The account is controlled by the Ethereum address it is named after, as a
+/// CAIP-10 account: `personal_sign` signatures are not bound to a chain, so
+/// the mainnet id stands for any.
+///
+/// `info` is the JSON returned by `get_identity_info`.
+pub fn document(account: &str, contract_name: &str, info: &str) -> Result<Value> {
+    let id = did(account);
+    let address = account
+        .strip_suffix(&format!(".{contract_name}"))
+        .context("The account doesn't belong to this contract")?;
+    let method = format!("{id}#controller");
+    let info: Value = serde_json::from_str(info).context("Invalid account info")?;
+
+    Ok(json!({
+        "@context": [
+            "https://www.w3.org/ns/did/v1",
+            "https://w3id.org/security/suites/secp256k1recovery-2020/v2",
+        ],
+        "id": id,
+        "verificationMethod": [{
+            "id": method,
+            "type": "EcdsaSecp256k1RecoveryMethod2020",
+            "controller": id,
+            "blockchainAccountId": format!("eip155:1:{address}"),
+        }],
+        "authentication": [method],
+        "assertionMethod": [method],
+        "hyleAccount": {
+            "accountHash": info["pub_key_hash"],
+            "nonce": info["nonce"],
+            "controlHistory": info["history"],
+        },
+    }))
+}
@@ -1,22 +1,38 @@
-use axum::{extract::Json, http::Method, routing::post, Router};
+use axum::{
+    extract::Json,
+    http::{Method, StatusCode},
+    routing::post,
+    Extension, Router,
+};
 use clap::{Parser, Subcommand};
-use client_sdk::helpers::risc0::Risc0Prover;
 use contract_identity::IdentityContractState;
 use hex::decode;
+use host_output::{say, OrFail, Output};
+use host_tls::{Acceptor, ClientCert, TlsSettings};
 use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
 use sdk::RegisterContractTransaction;
 use sdk::{BlobTransaction, TxHash};
 use sdk::{ContractInput, Digestable};
 use sdk::{Identity, ProofTransaction};
 use serde::Deserialize;
+use serde_json::json;
 use sha3::Digest;
 use sha3::Keccak256;
+use std::collections::HashMap;
 use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tower_http::cors::{Any, CorsLayer};
 
-// These constants represent the RISC-V ELF and the image ID generated by risc0-build.
-// The ELF is used for proving and the ID is used for verification.
-use methods_identity::{GUEST_ELF, GUEST_ID};
+use crate::lifecycle::ContractCommand;
+use crate::node::Node;
+use crate::prover::{Prover, Zkvm};
+mod did;
+mod lifecycle;
+mod node;
+mod prover;
+mod selfcheck;
+mod tls;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -25,26 +41,84 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
+    /// Before submitting proofs, check that the local guest and the on-chain
+    /// contract share the expected program id
     #[clap(long, short)]
     reproducible: bool,
 
+    /// Hex program id to pin against instead of the local guest's
+    #[arg(long, requires = "reproducible")]
+    expected_program_id: Option<String>,
+
     #[arg(long, default_value = "http://localhost:4321")]
     pub host: String,
 
+    /// `json` prints results and errors as JSON objects, one per line, and
+    /// messages on stderr
+    #[arg(long, value_enum, default_value_t = Output::Text)]
+    pub output: Output,
+
     #[arg(long, default_value = "metamask_identity")]
     pub contract_name: String,
+
+    /// zkVM the contract is registered with and proofs are generated in;
+    /// `sp1` needs a host built with `--features sp1`
+    #[arg(long, value_enum, default_value_t = Zkvm::Risc0)]
+    pub zkvm: Zkvm,
+
+    /// Run despite unsafe settings found by the startup self-check, such as
+    /// serving `/prove` beyond localhost without TLS
+    #[arg(long)]
+    pub allow_unsafe: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    RunServer,
-    RegisterContract {},
+    /// Serve `/prove`, over TLS with `--tls-cert`, requiring client
+    /// certificates signed by `--client-ca` if given
+    RunServer {
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        client_ca: Option<PathBuf>,
+    },
+    /// Register and inspect the identity contract
+    Contract {
+        #[command(subcommand)]
+        command: ContractCommand,
+    },
     RegisterIdentity { identity: String, password: String },
-    VerifyIdentity { public_key: String, nonce: u32 },
+    /// Verify an account with its signature over
+    /// `contract_identity::verification_message`
+    VerifyIdentity {
+        public_key: String,
+        nonce: u32,
+        signature: String,
+    },
     GetIdentity { public_key: String },
     ValidateSignature { account: String, signature: String },
+    /// Render a registered account as a W3C DID document (`did:hyle:...`)
+    Did { account: String },
 }
 
+impl Commands {
+    fn submits_proofs(&self) -> bool {
+        matches!(
+            self,
+            Commands::RunServer { .. }
+                | Commands::RegisterIdentity { .. }
+                | Commands::VerifyIdentity { .. }
+                | Commands::GetIdentity { .. }
+        )
+    }
+}
+
+/// Identities bound to the certificate of the first client that had them
+/// proved, by thumbprint.
+type Bindings = Arc<Mutex<HashMap<String, String>>>;
+
 #[derive(Deserialize)]
 struct ProveRequest {
     tx_hash: TxHash,
@@ -61,46 +135,94 @@ async fn main() {
         .init();
 
     let cli = Cli::parse();
+    host_output::init(cli.output);
 
-    let client = client_sdk::rest_client::NodeApiHttpClient::new(cli.host).unwrap();
+    let client = Node::new(cli.host.clone()).or_fail("usage");
 
     let contract_name = &cli.contract_name;
 
-    let prover = Risc0Prover::new(GUEST_ELF);
+    selfcheck::run(&cli.command, &client, &cli.host, contract_name, cli.zkvm)
+        .await
+        .finish(cli.allow_unsafe);
+
+    let prover = Prover::new(cli.zkvm).or_fail("usage");
+
+    if cli.reproducible && cli.command.submits_proofs() {
+        let contract = client
+            .get_contract(&contract_name.clone().into())
+            .await
+            .or_fail("node");
+        lifecycle::ensure_program_id(
+            &contract.program_id,
+            cli.expected_program_id.as_deref(),
+            cli.zkvm,
+        );
+    }
 
     match cli.command {
-        Commands::RegisterContract {} => {
+        Commands::Contract {
+            command: ContractCommand::Register {},
+        } => {
             // Build initial state of contract
             let initial_state = IdentityContractState::new();
-            println!("Initial state: {:?}", initial_state);
+            say!("Initial state: {:?}", initial_state);
 
             // Send the transaction to register the contract
             let register_tx = RegisterContractTransaction {
                 owner: "examples".to_string(),
-                verifier: "risc0".into(),
-                program_id: sdk::ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec()),
+                verifier: cli.zkvm.verifier().into(),
+                program_id: lifecycle::local_program_id(cli.zkvm),
                 state_digest: initial_state.as_digest(),
                 contract_name: contract_name.clone().into(),
             };
             let res = client
                 .send_tx_register_contract(&register_tx)
                 .await
-                .unwrap();
+                .or_fail("node");
 
-            println!("✅ Register contract tx sent. Tx hash: {}", res);
+            lifecycle::report_registered(contract_name, &res);
         }
+        Commands::Contract {
+            command: ContractCommand::Info {},
+        } => {
+            let contract = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node");
+            lifecycle::print_info(
+                contract_name,
+                &contract.program_id,
+                &contract.state,
+                cli.zkvm,
+            );
+        }
+        Commands::Contract {
+            command: ContractCommand::Check {},
+        } => {
+            let contract = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node");
+            lifecycle::check(&contract.program_id, cli.zkvm);
+        }
+        Commands::Contract {
+            command: ContractCommand::Pause {},
+        } => lifecycle::unsupported("pause"),
+        Commands::Contract {
+            command: ContractCommand::Unpause {},
+        } => lifecycle::unsupported("unpause"),
         Commands::RegisterIdentity { identity, password } => {
-            println!("contract_name {:?}", contract_name.clone());
+            say!("contract_name {:?}", contract_name.clone());
             // Fetch the initial state from the node
             let initial_state: IdentityContractState = client
                 .get_contract(&contract_name.clone().into())
                 .await
-                .unwrap()
+                .or_fail("node")
                 .state
                 .into();
 
-            println!("Initial state {:?}", initial_state.clone());
-            println!("Public key {:?}", identity.clone());
+            say!("Initial state {:?}", initial_state.clone());
+            say!("Public key {:?}", identity.clone());
             // ----
             // Build the blob transaction
             // ----
@@ -125,32 +247,32 @@ async fn main() {
             };
 
             // Send the blob transaction
-            let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-            println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
-            println!("✅ Blob tx identity {}", blob_tx.identity);
+            let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+            node::report_blob_sent(&blob_tx_hash);
+            say!("✅ Blob tx identity {}", blob_tx.identity);
 
             // ----
             // Prove the state transition
             // ----
 
-            println!("contract_name: {}", contract_name);
+            say!("contract_name: {}", contract_name);
 
             // Build the contract input
             let inputs = ContractInput {
                 initial_state: initial_state.as_digest(),
                 identity: identity.clone().into(),
-                tx_hash: blob_tx_hash,
+                tx_hash: blob_tx_hash.clone(),
                 private_blob: sdk::BlobData(password.into_bytes().to_vec()),
                 blobs: blobs.clone(),
                 index: sdk::BlobIndex(0),
             };
 
-            println!("inputs: {:?}", inputs.clone());
+            say!("inputs: {:?}", inputs.clone());
 
             // Generate the zk proof
-            let proof = prover.prove(inputs).await.unwrap();
+            let proof = prover.prove(inputs).await.or_fail("proof");
 
-            println!("proof generated");
+            say!("proof generated");
 
             let proof_tx = ProofTransaction {
                 proof,
@@ -158,16 +280,22 @@ async fn main() {
             };
 
             // Send the proof transaction
-            let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-            println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+            node::report_proof_sent(
+                &blob_tx_hash,
+                client.send_proof(&proof_tx, &blob_tx_hash).await,
+            );
         }
-        Commands::VerifyIdentity { public_key, nonce } => {
+        Commands::VerifyIdentity {
+            public_key,
+            nonce,
+            signature,
+        } => {
             {
                 // Fetch the initial state from the node
                 let initial_state: IdentityContractState = client
                     .get_contract(&contract_name.clone().into())
                     .await
-                    .unwrap()
+                    .or_fail("node")
                     .state
                     .into();
                 // ----
@@ -191,8 +319,8 @@ async fn main() {
                 };
 
                 // Send the blob transaction
-                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-                println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
+                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+                node::report_blob_sent(&blob_tx_hash);
 
                 // ----
                 // Prove the state transition
@@ -203,13 +331,13 @@ async fn main() {
                     initial_state: initial_state.as_digest(),
                     identity: blob_tx.identity,
                     tx_hash: blob_tx_hash.clone(),
-                    private_blob: sdk::BlobData(vec![]),
+                    private_blob: sdk::BlobData(signature.into_bytes()),
                     blobs: blobs.clone(),
                     index: sdk::BlobIndex(0),
                 };
 
                 // Generate the zk proof
-                let proof = prover.prove(inputs).await.unwrap();
+                let proof = prover.prove(inputs).await.or_fail("proof");
 
                 let proof_tx = ProofTransaction {
                     proof,
@@ -217,8 +345,10 @@ async fn main() {
                 };
 
                 // Send the proof transaction
-                let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-                println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+                node::report_proof_sent(
+                    &blob_tx_hash,
+                    client.send_proof(&proof_tx, &blob_tx_hash).await,
+                );
             }
         }
         Commands::GetIdentity { public_key } => {
@@ -227,7 +357,7 @@ async fn main() {
                 let initial_state: IdentityContractState = client
                     .get_contract(&contract_name.clone().into())
                     .await
-                    .unwrap()
+                    .or_fail("node")
                     .state
                     .into();
                 // ----
@@ -250,8 +380,8 @@ async fn main() {
                 };
 
                 // Send the blob transaction
-                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-                println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
+                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+                node::report_blob_sent(&blob_tx_hash);
 
                 // ----
                 // Prove the state transition
@@ -268,7 +398,7 @@ async fn main() {
                 };
 
                 // Generate the zk proof
-                let proof = prover.prove(inputs).await.unwrap();
+                let proof = prover.prove(inputs).await.or_fail("proof");
 
                 let proof_tx = ProofTransaction {
                     proof,
@@ -276,76 +406,143 @@ async fn main() {
                 };
 
                 // Send the proof transaction
-                let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-                println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+                node::report_proof_sent(
+                    &blob_tx_hash,
+                    client.send_proof(&proof_tx, &blob_tx_hash).await,
+                );
             }
         }
 
+        Commands::Did { account } => {
+            let state: IdentityContractState = client
+                .get_contract(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .state
+                .into();
+            let info = state
+                .get_identity_info(&account)
+                .unwrap_or_else(|_| host_output::fail("not_registered", "No such account"));
+
+            let document =
+                did::document(&account, contract_name, &info).expect("Failed to render DID");
+            host_output::report(
+                "did",
+                json!({ "document": document }),
+                serde_json::to_string_pretty(&document).unwrap(),
+            );
+        }
+
         Commands::ValidateSignature { signature, account } => {
             //Example \`personal_sign\` message
             //0xc4b1989d045e1f9aacc448032a7e278780de9a1c1735984c8d4e95cc1840715b3255b0cb791df3c5c137fa22773f9f6976b96418581e44f3fdf1e6ec395f6b661b
             //0x437aa724e898f0ba345852bbbc2e416d9346e1c9
 
-            //hyle registration
-            //0x3279f925d976ddfc012a95157f87054717610b4fa08028913ab3139f552e76342d609c11acbca164fb9c64fb5553db3fe34c826c0084fd6bc934dcd154993b0a1b
-            //0x437aa724e898f0ba345852bbbc2e416d9346e1c9
+            //Registration message, e.g. `hyle registration metamask_identity`
+            let message = contract_identity::registration_message(contract_name);
 
-            let res = k256_verifier(account, signature);
+            let res = k256_verifier(account.clone(), signature, &message);
 
-            if res {
-                println!("✅ Signature successfully validated.");
-            } else {
-                println!("❌ Signature invalid");
-            }
+            host_output::report(
+                "signature_validated",
+                json!({ "account": account, "valid": res }),
+                if res {
+                    "✅ Signature successfully validated."
+                } else {
+                    "❌ Signature invalid"
+                },
+            );
         }
 
-        Commands::RunServer => {
-            run_server().await;
+        Commands::RunServer {
+            tls_cert,
+            tls_key,
+            client_ca,
+        } => {
+            let tls = tls_cert.zip(tls_key).map(|(cert, key)| TlsSettings {
+                cert,
+                key,
+                client_ca,
+            });
+            run_server(tls).await;
         }
     }
 }
 
+/// Address `run-server` listens on.
+fn server_address() -> String {
+    env::var("HYLEOOF_HOST").unwrap_or_else(|_| "127.0.0.1:3000".to_string())
+}
+
 // Function to start the REST server
-async fn run_server() {
+async fn run_server(tls: Option<TlsSettings>) {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(vec![Method::POST])
         .allow_headers(Any);
 
-    let app = Router::new().route("/prove", post(prove)).layer(cors);
+    let app = Router::new()
+        .route("/prove", post(prove))
+        .layer(cors)
+        .layer(Extension(Bindings::default()));
 
-    let addr = env::var("HYLEOOF_HOST").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let addr = server_address();
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    println!("Server running on {}", addr);
 
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    match tls {
+        Some(settings) => {
+            let acceptor = Acceptor::new(&settings).expect("Invalid TLS settings");
+            say!("Server running on https://{}", addr);
+            tls::serve(listener, acceptor, app).await;
+        }
+        None => {
+            say!("Server running on {}", addr);
+            let app = app.layer(Extension(None::<ClientCert>));
+            axum::serve(listener, app.into_make_service())
+                .await
+                .unwrap();
+        }
+    }
 }
 
-// Handler for /prove endpoint
-async fn prove(Json(request): Json<ProveRequest>) -> Json<TxHash> {
+// Handler for /prove endpoint. Under mutual TLS, an identity is bound to the
+// certificate of the first client asking for it, other clients get a 403.
+async fn prove(
+    Extension(bindings): Extension<Bindings>,
+    Extension(client): Extension<Option<ClientCert>>,
+    Json(request): Json<ProveRequest>,
+) -> Result<Json<Option<TxHash>>, StatusCode> {
+    if let Some(client) = &client {
+        let mut bindings = bindings.lock().unwrap();
+        let bound = bindings
+            .entry(request.identity.to_string())
+            .or_insert_with(|| client.thumbprint.clone());
+        if *bound != client.thumbprint {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
     let cli = Cli::parse();
-    let client = client_sdk::rest_client::NodeApiHttpClient::new(cli.host).unwrap();
-    let prover = Risc0Prover::new(GUEST_ELF);
+    let client = Node::new(cli.host).unwrap();
+    let prover = Prover::new(cli.zkvm).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let initial_state: IdentityContractState = client
         .get_contract(&request.contract_name.clone().into())
         .await
-        .unwrap()
+        .map_err(|_| StatusCode::BAD_GATEWAY)?
         .state
         .into();
 
-    println!("Initial state {:?}", initial_state.clone());
-    println!("identity {:?}", request.identity.clone());
-    println!("signature {:?}", request.signature.clone());
-    println!("contract_name {:?}", request.contract_name.clone());
-    println!("tx_hash {:?}", request.tx_hash.clone());
+    say!("Initial state {:?}", initial_state.clone());
+    say!("identity {:?}", request.identity.clone());
+    say!("signature {:?}", request.signature.clone());
+    say!("contract_name {:?}", request.contract_name.clone());
+    say!("tx_hash {:?}", request.tx_hash.clone());
 
     let action = sdk::identity_provider::IdentityAction::RegisterIdentity {
         account: request.identity.to_string(),
     };
-    println!("action {:?}", action.clone());
+    say!("action {:?}", action.clone());
     let blobs = vec![sdk::Blob {
         contract_name: request.contract_name.clone().into(),
         data: sdk::BlobData(
@@ -363,7 +560,7 @@ async fn prove(Json(request): Json<ProveRequest>) -> Json<TxHash> {
         index: sdk::BlobIndex(0),
     };
 
-    println!("inputs {:?}", inputs.clone());
+    say!("inputs {:?}", inputs.clone());
 
     let proof = prover.prove(inputs).await.unwrap();
     let proof_tx = ProofTransaction {
@@ -371,19 +568,23 @@ async fn prove(Json(request): Json<ProveRequest>) -> Json<TxHash> {
         contract_name: request.contract_name.clone().into(),
     };
 
-    let proof_tx_hash: TxHash = client.send_tx_proof(&proof_tx).await.unwrap();
+    // `null` when the proof settled but the node's answer was lost
+    let proof_tx_hash = client
+        .send_proof(&proof_tx, &request.tx_hash.clone().into())
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
     //println!("Proof transaction sent: {:?}", proof.clone());
-    Json(proof_tx_hash)
+    Ok(Json(proof_tx_hash))
 }
 
-pub fn k256_verifier(pub_key: String, signature_hex: String) -> bool {
+pub fn k256_verifier(pub_key: String, signature_hex: String, message: &str) -> bool {
     let sig = &String::from(signature_hex);
     let pb = &String::from(pub_key);
 
     let signature_hex_str = sanitize_hex(sig);
     let pub_key_str = sanitize_hex(pb);
 
-    let msg = "hyle registration".as_bytes();
+    let msg = message.as_bytes();
 
     // Apply Ethereum Signed Message Prefix (EIP-191)
     let eth_message = format!(
@@ -0,0 +1,118 @@
+//! The node's REST client, retrying failed calls with exponential backoff (see
+//! `node-retry/README.md` at the root of the repository).
+
+use anyhow::Result;
+use client_sdk::rest_client::{IndexerApiHttpClient, NodeApiHttpClient};
+use node_retry::Backoff;
+use sdk::api::TransactionStatus;
+use sdk::{
+    BlobTransaction, Contract, ContractName, ProofTransaction, RegisterContractTransaction, TxHash,
+};
+use serde_json::json;
+
+pub struct Node {
+    client: NodeApiHttpClient,
+    /// The indexer is served alongside the node's REST API.
+    indexer: IndexerApiHttpClient,
+}
+
+impl Node {
+    pub fn new(url: String) -> Result<Self> {
+        Ok(Node {
+            client: NodeApiHttpClient::new(url.clone())?,
+            indexer: IndexerApiHttpClient::new(url)?,
+        })
+    }
+
+    pub async fn get_contract(&self, contract_name: &ContractName) -> Result<Contract> {
+        node_retry::retry("Reading the contract", &Backoff::default(), || {
+            self.client.get_contract(contract_name)
+        })
+        .await
+    }
+
+    /// Not retried: registering is rare enough to be run again by hand.
+    pub async fn send_tx_register_contract(
+        &self,
+        tx: &RegisterContractTransaction,
+    ) -> Result<TxHash> {
+        self.client.send_tx_register_contract(tx).await
+    }
+
+    /// A blob transaction's hash is that of its content, so a resent one the
+    /// node already got is no second transaction.
+    pub async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        node_retry::retry("Sending the blob tx", &Backoff::default(), || {
+            self.client.send_tx_blob(tx)
+        })
+        .await
+    }
+
+    /// Status of the transaction `tx_hash`, as the node's indexer reports it.
+    pub async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        node_retry::retry("Reading the tx status", &Backoff::default(), || async move {
+            Ok(self
+                .indexer
+                .get_transaction_with_hash(tx_hash)
+                .await?
+                .transaction_status)
+        })
+        .await
+    }
+
+    /// Sends `tx`, proving the blob transaction `blob_tx_hash`, retrying as
+    /// long as that transaction didn't settle. `None` when it did: an attempt
+    /// whose answer was lost landed.
+    pub async fn send_proof(
+        &self,
+        tx: &ProofTransaction,
+        blob_tx_hash: &TxHash,
+    ) -> Result<Option<TxHash>> {
+        node_retry::retry_send(
+            "Sending the proof tx",
+            &Backoff::default(),
+            || self.client.send_tx_proof(tx),
+            || async move { Ok(is_settled(&self.tx_status(blob_tx_hash).await?)) },
+        )
+        .await
+    }
+}
+
+/// Whether a blob transaction of `status` settled, a proof of it having
+/// landed, whatever the outcome.
+fn is_settled(status: &TransactionStatus) -> bool {
+    matches!(status, TransactionStatus::Success | TransactionStatus::Failure)
+}
+
+pub fn report_blob_sent(blob_tx_hash: &TxHash) {
+    host_output::report(
+        "blob_tx_sent",
+        json!({ "tx_hash": blob_tx_hash }),
+        format_args!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash),
+    );
+}
+
+/// Reports the outcome of [`Node::send_proof`] for `blob_tx_hash`, exiting
+/// when it failed.
+pub fn report_proof_sent(blob_tx_hash: &TxHash, sent: Result<Option<TxHash>>) {
+    match sent {
+        Ok(Some(proof_tx_hash)) => host_output::report(
+            "proof_tx_sent",
+            json!({ "blob_tx_hash": blob_tx_hash, "tx_hash": proof_tx_hash }),
+            format_args!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash),
+        ),
+        Ok(None) => host_output::report(
+            "proof_settled",
+            json!({ "blob_tx_hash": blob_tx_hash }),
+            "✅ Proof tx already settled",
+        ),
+        Err(err) => host_output::fail_with(
+            "proof",
+            format_args!(
+                "Proof tx failed: {:#}\n{} stays unproven until it times out",
+                err, blob_tx_hash
+            ),
+            json!({ "blob_tx_hash": blob_tx_hash }),
+        ),
+    }
+}
@@ -0,0 +1,87 @@
+//! Proving backends: the guest is proven with Risc0 or, in hosts built with
+//! `--features sp1`, with SP1 (`--zkvm sp1`). The contract must have been
+//! registered with the same `--zkvm`, as the node picks its verifier from the
+//! registration.
+
+#[cfg(feature = "sp1")]
+use std::sync::OnceLock;
+
+#[cfg(not(feature = "sp1"))]
+use anyhow::bail;
+use anyhow::Result;
+use client_sdk::helpers::risc0::Risc0Prover;
+#[cfg(feature = "sp1")]
+use client_sdk::helpers::sp1::SP1Prover;
+use methods_identity::{GUEST_ELF, GUEST_ID};
+#[cfg(feature = "sp1")]
+use methods_identity::SP1_GUEST_ELF;
+use sdk::{ContractInput, ProgramId, ProofData};
+
+#[cfg(not(feature = "sp1"))]
+const NO_SP1: &str = "This host was built without SP1: rebuild it with `--features sp1`";
+
+/// Value of `--zkvm`: which zkVM the guest is registered and proven with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Zkvm {
+    #[default]
+    Risc0,
+    /// Needs a host built with `--features sp1`
+    Sp1,
+}
+
+impl Zkvm {
+    /// Verifier the node checks the contract's proofs with.
+    pub fn verifier(self) -> &'static str {
+        match self {
+            Zkvm::Risc0 => "risc0",
+            Zkvm::Sp1 => "sp1",
+        }
+    }
+
+    /// Program id of the local guest: the image id for Risc0, the verifying
+    /// key for SP1.
+    pub fn program_id(self) -> Result<ProgramId> {
+        match self {
+            Zkvm::Risc0 => Ok(ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec())),
+            #[cfg(feature = "sp1")]
+            Zkvm::Sp1 => {
+                // Computing the verifying key sets the whole program up
+                static PROGRAM_ID: OnceLock<ProgramId> = OnceLock::new();
+                if let Some(program_id) = PROGRAM_ID.get() {
+                    return Ok(program_id.clone());
+                }
+                let program_id = SP1Prover::new(SP1_GUEST_ELF).program_id()?;
+                Ok(PROGRAM_ID.get_or_init(|| program_id).clone())
+            }
+            #[cfg(not(feature = "sp1"))]
+            Zkvm::Sp1 => bail!(NO_SP1),
+        }
+    }
+}
+
+/// Proves the executions of the guest.
+pub enum Prover {
+    Risc0(Risc0Prover<'static>),
+    #[cfg(feature = "sp1")]
+    Sp1(SP1Prover),
+}
+
+impl Prover {
+    pub fn new(zkvm: Zkvm) -> Result<Self> {
+        Ok(match zkvm {
+            Zkvm::Risc0 => Prover::Risc0(Risc0Prover::new(GUEST_ELF)),
+            #[cfg(feature = "sp1")]
+            Zkvm::Sp1 => Prover::Sp1(SP1Prover::new(SP1_GUEST_ELF)),
+            #[cfg(not(feature = "sp1"))]
+            Zkvm::Sp1 => bail!(NO_SP1),
+        })
+    }
+
+    pub async fn prove(&self, input: ContractInput) -> Result<ProofData> {
+        match self {
+            Prover::Risc0(prover) => prover.prove(input).await,
+            #[cfg(feature = "sp1")]
+            Prover::Sp1(prover) => prover.prove(input).await,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Startup self-check, see the `host-check` crate.
+
+use std::time::SystemTime;
+
+use host_check::{KeyFile, SelfCheck};
+
+use crate::lifecycle::local_program_id;
+use crate::node::Node;
+use crate::prover::Zkvm;
+use crate::{server_address, Commands};
+
+/// Checks the settings `command` runs with on `zkvm`. The node is only asked
+/// when the command submits proofs.
+pub async fn run(
+    command: &Commands,
+    client: &Node,
+    host: &str,
+    contract_name: &str,
+    zkvm: Zkvm,
+) -> SelfCheck {
+    let mut check = SelfCheck::new();
+
+    if let Commands::RunServer {
+        tls_key, client_ca, ..
+    } = command
+    {
+        let address = server_address();
+        check.plaintext_listener("`/prove`", &address, tls_key.is_some());
+        if let Some(tls_key) = tls_key {
+            check.key_file(tls_key, KeyFile::Pem);
+            let host = address.rsplit_once(':').map_or(&*address, |(host, _)| host);
+            if client_ca.is_none() && !host_check::is_loopback(host) {
+                check.warn(
+                    "Listener",
+                    format_args!("`/prove` served on {} to any client", address),
+                    "require client certificates with --client-ca",
+                );
+            }
+        }
+    }
+
+    if command.submits_proofs() {
+        let local = local_program_id(zkvm);
+        match client.get_contract(&contract_name.into()).await {
+            Ok(contract) if contract.program_id == local => check.pass(),
+            Ok(contract) => check.fail(
+                "Program id",
+                format_args!(
+                    "on-chain {} differs from the local guest {}, proofs would be rejected",
+                    hex::encode(&contract.program_id.0),
+                    hex::encode(local.0)
+                ),
+                "build the guest at the deployed revision (RISC0_USE_DOCKER=1), pass the \
+                 --zkvm it was registered with, or pick the contract built from this one \
+                 with --contract-name",
+            ),
+            Err(err) => check.fail(
+                "Node",
+                format_args!("can't fetch {} from {}: {:#}", contract_name, host, err),
+                "check --host and --contract-name, or register the contract with \
+                 `contract register`",
+            ),
+        }
+        match host_check::node_time(host).await {
+            Ok(node_time) => check.clock(node_time, SystemTime::now()),
+            Err(err) => check.warn(
+                "Clock",
+                format_args!("can't read the node's time: {}", err),
+                "make sure the local clock is synchronized",
+            ),
+        }
+    }
+
+    check
+}
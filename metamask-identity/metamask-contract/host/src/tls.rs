@@ -0,0 +1,35 @@
+//! Serves the REST API over TLS, see the `host-tls` crate.
+
+use axum::{Extension, Router};
+use host_tls::Acceptor;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use tokio::net::TcpListener;
+
+/// Like `axum::serve`, handing every request of a connection the client's
+/// certificate as an `Extension<Option<ClientCert>>`.
+pub async fn serve(listener: TcpListener, acceptor: Acceptor, app: Router) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let (acceptor, app) = (acceptor.clone(), app.clone());
+        tokio::spawn(async move {
+            let (stream, client) = match acceptor.accept(stream).await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    eprintln!("TLS handshake failed: {}", err);
+                    return;
+                }
+            };
+            let service = TowerToHyperService::new(app.layer(Extension(client)));
+            if let Err(err) = Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("Connection failed: {}", err);
+            }
+        });
+    }
+}
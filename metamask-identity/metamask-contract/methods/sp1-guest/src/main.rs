@@ -0,0 +1,14 @@
+#![no_main]
+
+use sdk::guest::GuestEnv;
+use sdk::guest::SP1Env;
+
+use contract_identity::execute;
+
+sp1_zkvm::entrypoint!(main);
+
+// Same program as `guest`, for the SP1 zkVM.
+fn main() {
+    let env = SP1Env {};
+    env.commit(&execute(env.read()));
+}
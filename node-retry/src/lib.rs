@@ -0,0 +1,189 @@
+//! Retries of node calls with exponential backoff, see the README.
+//!
+//! [`retry`] is for calls that can be repeated as they are: reads, and writes
+//! the node deduplicates. [`retry_send`] is for writes that must not be
+//! applied twice: before each new attempt, it asks whether the earlier ones
+//! took effect after all.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// How failed calls are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Calls made in all, the first one included
+    pub attempts: u32,
+    /// Wait before the first retry, doubled before each next one
+    pub initial_delay: Duration,
+    /// Longest wait between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Backoff {
+    /// Wait before retry number `retry`, counted from 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+}
+
+/// Calls `call` until it succeeds, at most `backoff.attempts` times, and
+/// returns its last error if it never does. `what` names the call in the
+/// retry reports, e.g. `Sending the blob tx`.
+pub async fn retry<T, E, F, Fut>(what: &str, backoff: &Backoff, mut call: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retries = 0;
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(err) if retries + 1 < backoff.attempts => {
+                wait(what, backoff, retries, &err).await;
+                retries += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Like [`retry`], but before each new attempt asks `landed` whether an
+/// earlier one took effect although it failed, e.g. because the response was
+/// lost. Returns `None` when one did, without sending again.
+pub async fn retry_send<T, E, F, Fut, L, LFut>(
+    what: &str,
+    backoff: &Backoff,
+    mut send: F,
+    mut landed: L,
+) -> Result<Option<T>, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    L: FnMut() -> LFut,
+    LFut: Future<Output = Result<bool, E>>,
+{
+    let mut retries = 0;
+    loop {
+        let err = match send().await {
+            Ok(value) => return Ok(Some(value)),
+            Err(err) => err,
+        };
+        if retries + 1 >= backoff.attempts {
+            return Err(err);
+        }
+        wait(what, backoff, retries, &err).await;
+        retries += 1;
+        // A node that can't be asked can't take the transaction either:
+        // sending again is then as safe as the check
+        if let Ok(true) = landed().await {
+            return Ok(None);
+        }
+    }
+}
+
+async fn wait(what: &str, backoff: &Backoff, retry: u32, err: &impl Display) {
+    let delay = backoff.delay(retry);
+    eprintln!("⚠️  {} failed ({}), retrying in {:?}", what, err, delay);
+    tokio::time::sleep(delay).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_delay_doubles_up_to_the_max() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(0), Duration::from_millis(500));
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+        assert_eq!(backoff.delay(5), Duration::from_secs(10));
+        assert_eq!(backoff.delay(64), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_until_success() {
+        let calls = Cell::new(0);
+        let result: Result<u32, String> = retry("Reading", &Backoff::default(), || {
+            calls.set(calls.get() + 1);
+            let calls = calls.get();
+            async move {
+                if calls < 3 {
+                    Err("unreachable".to_string())
+                } else {
+                    Ok(calls)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_gives_up_after_the_attempts() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry("Reading", &Backoff::default(), || {
+            calls.set(calls.get() + 1);
+            async { Err("unreachable".to_string()) }
+        })
+        .await;
+        assert_eq!(result, Err("unreachable".to_string()));
+        assert_eq!(calls.get(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_send_stops_once_landed() {
+        let sends = Cell::new(0);
+        let result: Result<Option<()>, String> = retry_send(
+            "Sending",
+            &Backoff::default(),
+            || {
+                sends.set(sends.get() + 1);
+                async { Err("connection reset".to_string()) }
+            },
+            || async { Ok(true) },
+        )
+        .await;
+        assert_eq!(result, Ok(None));
+        assert_eq!(sends.get(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_send_resends_when_not_landed() {
+        let sends = Cell::new(0);
+        let result: Result<Option<u32>, String> = retry_send(
+            "Sending",
+            &Backoff::default(),
+            || {
+                sends.set(sends.get() + 1);
+                let sends = sends.get();
+                async move {
+                    if sends < 2 {
+                        Err("connection reset".to_string())
+                    } else {
+                        Ok(sends)
+                    }
+                }
+            },
+            || async { Ok(false) },
+        )
+        .await;
+        assert_eq!(result, Ok(Some(2)));
+    }
+}
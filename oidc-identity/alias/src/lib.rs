@@ -0,0 +1,248 @@
+//! Alias registry: maps one user handle to that user's accounts in several
+//! identity contracts (OIDC, ECDSA, ...), so applications can accept any of them.
+//!
+//! Like the attestation registry, it never verifies anything itself. An action
+//! is only accepted when the transaction's identity belongs to a trusted
+//! identity contract that also has a blob in the same transaction registering
+//! or verifying that account, so it settles only if that contract proved it.
+//!
+//! A handle is created by the first account linking it. Further accounts join
+//! only when invited by an account already linked to the handle, so a handle
+//! can't be hijacked by whoever links second.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bincode::{Decode, Encode};
+use ecdsa_identity::actions::IdentityAction as EcdsaAction;
+use sdk::{Blob, BlobData, BlobIndex, ContractAction, ContractInput, ContractName, Digestable, RunResult};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
+pub enum AliasAction {
+    /// Link the transaction's identity to `handle`, creating it if unclaimed.
+    Link { handle: String },
+    /// Allow `account` to link itself to `handle`. The transaction's identity
+    /// must already be linked to it.
+    Invite { handle: String, account: String },
+    /// Remove the transaction's identity from `handle`.
+    Unlink { handle: String },
+}
+
+impl AliasAction {
+    pub fn as_blob(&self, contract_name: ContractName) -> Blob {
+        <Self as ContractAction>::as_blob(self, contract_name, None, None)
+    }
+}
+
+impl ContractAction for AliasAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        _caller: Option<BlobIndex>,
+        _callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData(
+                bincode::encode_to_vec(self, bincode::config::standard())
+                    .expect("failed to encode program inputs"),
+            ),
+        }
+    }
+}
+
+/// How a trusted identity contract proves accounts, which tells its blobs
+/// authenticating the transaction's identity apart.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IdentityScheme {
+    /// The OIDC contract: registrations and verifications name the account,
+    /// which must be the transaction's identity
+    Oidc,
+    /// The ECDSA contract: the transaction's identity is the signing key
+    Ecdsa,
+}
+
+impl IdentityScheme {
+    /// Whether `blob`, among the transaction's `blobs`, registers or verifies
+    /// `account`.
+    fn authenticates(self, blobs: &[Blob], index: usize, account: &str) -> bool {
+        match self {
+            IdentityScheme::Oidc => oidc_provider::parse_composed_blob(blobs, &BlobIndex(index))
+                .is_some_and(|action| action.parameters.authenticates() == Some(account)),
+            IdentityScheme::Ecdsa => {
+                let action = bincode::decode_from_slice::<EcdsaAction, _>(
+                    &blobs[index].data.0,
+                    bincode::config::standard(),
+                );
+                matches!(
+                    action,
+                    Ok((
+                        EcdsaAction::RegisterIdentity { .. }
+                            | EcdsaAction::VerifyIdentity { .. }
+                            | EcdsaAction::VerifyWithHotKey { .. },
+                        _
+                    ))
+                )
+            }
+        }
+    }
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct Alias {
+    /// Identity contract name to the account linked in it
+    pub accounts: BTreeMap<String, String>,
+    /// Accounts allowed to link themselves next
+    pub invited: BTreeSet<String>,
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
+pub struct AliasRegistry {
+    trusted_contracts: BTreeMap<String, IdentityScheme>,
+    aliases: BTreeMap<String, Alias>,
+    /// Reverse index: account to the handle it is linked to
+    handles: BTreeMap<String, String>,
+}
+
+impl AliasRegistry {
+    pub fn new(trusted_contracts: impl IntoIterator<Item = (String, IdentityScheme)>) -> Self {
+        AliasRegistry {
+            trusted_contracts: trusted_contracts.into_iter().collect(),
+            aliases: BTreeMap::new(),
+            handles: BTreeMap::new(),
+        }
+    }
+
+    pub fn alias(&self, handle: &str) -> Option<&Alias> {
+        self.aliases.get(handle)
+    }
+
+    pub fn handle_of(&self, account: &str) -> Option<&str> {
+        self.handles.get(account).map(String::as_str)
+    }
+
+    /// Whether `account` is one of the identities of `handle`.
+    pub fn accepts(&self, handle: &str, account: &str) -> bool {
+        self.handle_of(account) == Some(handle)
+    }
+
+    /// Returns the trusted identity contract managing `account`, checking it
+    /// has a blob in the transaction registering or verifying the account.
+    fn identity_contract(&self, account: &str, blobs: &[Blob]) -> Result<String, String> {
+        let (identity_contract, scheme) = self
+            .trusted_contracts
+            .iter()
+            .find(|(contract, _)| account.ends_with(&format!(".{contract}")))
+            .ok_or_else(|| format!("Account {account} is not managed by a trusted contract"))?;
+
+        let authenticated = blobs.iter().enumerate().any(|(index, blob)| {
+            blob.contract_name.0 == *identity_contract
+                && scheme.authenticates(blobs, index, account)
+        });
+        if !authenticated {
+            return Err(format!(
+                "Transaction has no blob of {identity_contract} verifying {account}"
+            ));
+        }
+        Ok(identity_contract.clone())
+    }
+
+    fn link(&mut self, account: &str, handle: &str, blobs: &[Blob]) -> Result<(), String> {
+        let identity_contract = self.identity_contract(account, blobs)?;
+        if let Some(existing) = self.handles.get(account) {
+            return Err(format!("Account {account} is already linked to {existing}"));
+        }
+
+        let alias = self.aliases.entry(handle.to_string()).or_default();
+        if !alias.accounts.is_empty() && !alias.invited.remove(account) {
+            return Err(format!("Account {account} was not invited to {handle}"));
+        }
+        if alias.accounts.contains_key(&identity_contract) {
+            return Err(format!(
+                "Handle {handle} already has an account in {identity_contract}"
+            ));
+        }
+        alias
+            .accounts
+            .insert(identity_contract, account.to_string());
+        self.handles.insert(account.to_string(), handle.to_string());
+        Ok(())
+    }
+
+    fn invite(
+        &mut self,
+        inviter: &str,
+        handle: &str,
+        account: String,
+        blobs: &[Blob],
+    ) -> Result<(), String> {
+        self.identity_contract(inviter, blobs)?;
+        if !self.accepts(handle, inviter) {
+            return Err(format!("Account {inviter} is not linked to {handle}"));
+        }
+        self.aliases
+            .get_mut(handle)
+            .ok_or("Unknown handle")?
+            .invited
+            .insert(account);
+        Ok(())
+    }
+
+    fn unlink(&mut self, account: &str, handle: &str, blobs: &[Blob]) -> Result<(), String> {
+        let identity_contract = self.identity_contract(account, blobs)?;
+        if !self.accepts(handle, account) {
+            return Err(format!("Account {account} is not linked to {handle}"));
+        }
+        self.handles.remove(account);
+        let alias = self.aliases.get_mut(handle).ok_or("Unknown handle")?;
+        alias.accounts.remove(&identity_contract);
+        if alias.accounts.is_empty() {
+            self.aliases.remove(handle);
+        }
+        Ok(())
+    }
+}
+
+impl Digestable for AliasRegistry {
+    fn as_digest(&self) -> sdk::StateDigest {
+        sdk::StateDigest(
+            bincode::encode_to_vec(self, bincode::config::standard())
+                .expect("Failed to encode AliasRegistry"),
+        )
+    }
+}
+
+impl TryFrom<sdk::StateDigest> for AliasRegistry {
+    type Error = String;
+
+    fn try_from(state: sdk::StateDigest) -> Result<Self, Self::Error> {
+        match bincode::decode_from_slice(&state.0, bincode::config::standard()) {
+            Ok((registry, read)) if read == state.0.len() => Ok(registry),
+            _ => Err("Could not decode alias registry state".to_string()),
+        }
+    }
+}
+
+pub fn execute(input: ContractInput) -> RunResult<AliasRegistry> {
+    let (input, action) = sdk::guest::init_raw::<AliasAction>(input);
+    let action = action.ok_or("Failed to parse input blob")?;
+
+    let mut state: AliasRegistry = input.initial_state.clone().try_into()?;
+    let account = &input.identity.0;
+
+    let output = match action {
+        AliasAction::Link { handle } => {
+            state.link(account, &handle, &input.blobs)?;
+            format!("Linked account: {} to {}", account, handle)
+        }
+        AliasAction::Invite { handle, account: invitee } => {
+            state.invite(account, &handle, invitee.clone(), &input.blobs)?;
+            format!("Invited account: {} to {}", invitee, handle)
+        }
+        AliasAction::Unlink { handle } => {
+            state.unlink(account, &handle, &input.blobs)?;
+            format!("Unlinked account: {} from {}", account, handle)
+        }
+    };
+    Ok((output, state, vec![]))
+}
@@ -0,0 +1,166 @@
+//! Attestation registry: records claims such as "account X proved a Google
+//! login" so that other applications can rely on them without re-running the
+//! identity proof.
+//!
+//! The registry never verifies anything itself. An `Attest` blob is only
+//! accepted when the transaction's identity belongs to one of the trusted
+//! identity contracts and that contract also has a blob in the same
+//! transaction registering or verifying that very account: the node only
+//! settles the transaction once the identity contract has proven its blob,
+//! which is what backs the attestation.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bincode::{Decode, Encode};
+use sdk::{Blob, BlobData, BlobIndex, ContractAction, ContractInput, ContractName, Digestable, RunResult};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
+pub enum AttestationAction {
+    /// Attest `claim` for the transaction's identity.
+    Attest { claim: String },
+}
+
+impl AttestationAction {
+    pub fn as_blob(&self, contract_name: ContractName) -> Blob {
+        <Self as ContractAction>::as_blob(self, contract_name, None, None)
+    }
+}
+
+impl ContractAction for AttestationAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        _caller: Option<BlobIndex>,
+        _callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData(
+                bincode::encode_to_vec(self, bincode::config::standard())
+                    .expect("failed to encode program inputs"),
+            ),
+        }
+    }
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Attestation {
+    pub claim: String,
+    /// Identity contract that authenticated the account
+    pub identity_contract: String,
+    pub tx_hash: String,
+    /// Block timestamp, when the prover supplied the transaction context
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
+pub struct AttestationRegistry {
+    trusted_contracts: BTreeSet<String>,
+    attestations: BTreeMap<String, Vec<Attestation>>,
+}
+
+impl AttestationRegistry {
+    pub fn new(trusted_contracts: impl IntoIterator<Item = String>) -> Self {
+        AttestationRegistry {
+            trusted_contracts: trusted_contracts.into_iter().collect(),
+            attestations: BTreeMap::new(),
+        }
+    }
+
+    pub fn attestations(&self, account: &str) -> &[Attestation] {
+        self.attestations
+            .get(account)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn has_claim(&self, account: &str, claim: &str) -> bool {
+        self.attestations(account).iter().any(|a| a.claim == claim)
+    }
+
+    fn attest(
+        &mut self,
+        account: &str,
+        claim: String,
+        blobs: &[Blob],
+        tx_hash: String,
+        timestamp: Option<u64>,
+    ) -> Result<(), String> {
+        let identity_contract = self
+            .trusted_contracts
+            .iter()
+            .find(|contract| account.ends_with(&format!(".{contract}")))
+            .ok_or_else(|| format!("Account {account} is not managed by a trusted contract"))?
+            .clone();
+
+        // Any other action, or one for another account, proves nothing of it
+        let authenticated = blobs.iter().enumerate().any(|(index, blob)| {
+            blob.contract_name.0 == identity_contract
+                && oidc_provider::parse_composed_blob(blobs, &BlobIndex(index))
+                    .is_some_and(|action| action.parameters.authenticates() == Some(account))
+        });
+        if !authenticated {
+            return Err(format!(
+                "Transaction has no blob of {identity_contract} verifying {account}"
+            ));
+        }
+
+        self.attestations
+            .entry(account.to_string())
+            .or_default()
+            .push(Attestation {
+                claim,
+                identity_contract,
+                tx_hash,
+                timestamp,
+            });
+        Ok(())
+    }
+}
+
+impl Digestable for AttestationRegistry {
+    fn as_digest(&self) -> sdk::StateDigest {
+        sdk::StateDigest(
+            bincode::encode_to_vec(self, bincode::config::standard())
+                .expect("Failed to encode AttestationRegistry"),
+        )
+    }
+}
+
+impl TryFrom<sdk::StateDigest> for AttestationRegistry {
+    type Error = String;
+
+    fn try_from(state: sdk::StateDigest) -> Result<Self, Self::Error> {
+        match bincode::decode_from_slice(&state.0, bincode::config::standard()) {
+            Ok((registry, read)) if read == state.0.len() => Ok(registry),
+            _ => Err("Could not decode attestation registry state".to_string()),
+        }
+    }
+}
+
+pub fn execute(input: ContractInput) -> RunResult<AttestationRegistry> {
+    let (input, action) = sdk::guest::init_raw::<AttestationAction>(input);
+    let action = action.ok_or("Failed to parse input blob")?;
+
+    let mut state: AttestationRegistry = input.initial_state.clone().try_into()?;
+
+    let timestamp = input.tx_ctx.as_ref().map(|ctx| ctx.timestamp);
+
+    match action {
+        AttestationAction::Attest { claim } => {
+            state.attest(
+                &input.identity.0,
+                claim.clone(),
+                &input.blobs,
+                input.tx_hash.0.clone(),
+                timestamp,
+            )?;
+            Ok((
+                format!("Attested `{}` for account: {}", claim, input.identity),
+                state,
+                vec![],
+            ))
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Native timing of the claims scanner against `serde_json`.
+//!
+//! Native time is only a proxy for guest cycles; to measure cycles, run the
+//! `claims_parse_cycles` test of `methods-identity` with and without its
+//! `serde-claims` feature.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use oidc_identity::claims::{self, Claims};
+
+const PAYLOAD: &str = r#"{"iss":"https://accounts.google.com","azp":"1234987819200.apps.googleusercontent.com","aud":"1234987819200.apps.googleusercontent.com","sub":"10769150350006150715113082367","at_hash":"HK6E_P6Dh8Y93mRNtsDB1Q","hd":"example.com","email":"jsmith@example.com","email_verified":true,"iat":1353601026,"exp":1353604926,"nonce":"0394852-3190485-2490358","name":"Jane Smith","picture":"https://lh3.googleusercontent.com/a/photo.jpg","given_name":"Jane","family_name":"Smith","locale":"en"}"#;
+
+const ITERATIONS: u32 = 100_000;
+
+fn time(name: &str, parse: impl Fn(&[u8]) -> Claims) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(parse(black_box(PAYLOAD.as_bytes())));
+    }
+    println!("{name:<10} {:?}/parse", start.elapsed() / ITERATIONS);
+}
+
+fn main() {
+    time("scanner", |payload| claims::parse(payload).unwrap());
+    time("serde_json", |payload| {
+        serde_json::from_slice(payload).unwrap()
+    });
+}
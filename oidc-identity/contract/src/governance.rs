@@ -0,0 +1,68 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use oidc_provider::code::ResultCode;
+use oidc_provider::AdminOperation;
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PendingOperation {
+    pub operation: AdminOperation,
+    pub proposed_at: u64,
+    pub executable_at: u64,
+}
+
+/// Admin configuration and the queue of time-locked operations.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Governance {
+    /// Account allowed to propose operations. Without one, governance is disabled.
+    pub admin: Option<String>,
+    /// Minimum number of blocks between proposal and execution
+    pub min_delay: u64,
+    pub next_id: u32,
+    pub pending: BTreeMap<u32, PendingOperation>,
+    /// Set by an executed `Pause`, cleared by `Unpause`
+    pub paused: bool,
+}
+
+impl Governance {
+    pub fn propose(&mut self, operation: AdminOperation, block_height: u64) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(
+            id,
+            PendingOperation {
+                operation,
+                proposed_at: block_height,
+                executable_at: block_height.saturating_add(self.min_delay),
+            },
+        );
+        id
+    }
+
+    /// Removes and returns the operation once its delay has elapsed.
+    pub fn take_executable(
+        &mut self,
+        id: u32,
+        block_height: u64,
+    ) -> Result<AdminOperation, ResultCode> {
+        match self.pending.get(&id) {
+            None => Err(ResultCode::UnknownAdminOperation),
+            Some(pending) if block_height < pending.executable_at => {
+                Err(ResultCode::AdminOperationTimeLocked)
+            }
+            Some(_) => self
+                .pending
+                .remove(&id)
+                .map(|pending| pending.operation)
+                .ok_or(ResultCode::UnknownAdminOperation),
+        }
+    }
+
+    pub fn cancel(&mut self, id: u32) -> Result<(), ResultCode> {
+        self.pending
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(ResultCode::UnknownAdminOperation)
+    }
+}
@@ -1,81 +1,115 @@
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+//! The contract's [`SignatureVerifier`], with the RustCrypto crates. Parsing
+//! and checking tokens is [`oidc_provider::jwt`]'s, for other guests to reuse.
+
 use jsonwebkey::JsonWebKey;
-use oidc_provider::{JwkPublicKey, OpenIdContext};
+use oidc_provider::jwt::{decode_b64, SignatureVerifier};
+use oidc_provider::{JwkPublicKey, JwkSet, OpenIdContext};
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    EncodedPoint, FieldBytes,
+};
 use rsa::{
     pkcs8::DecodePublicKey,
     sha2::{Digest, Sha256},
     Pkcs1v15Sign, RsaPublicKey,
 };
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub email: String,
-    pub exp: u64,
-    pub aud: String,
-    pub iss: String,
-}
+pub use oidc_provider::jwt::{check_token_time, DEFAULT_MAX_CLOCK_SKEW};
 
-fn split_jwt(token: &str) -> Result<(&str, &str, &str), String> {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 3 {
-        return Err("Invalid JWT structure".to_string());
-    }
-    Ok((parts[0], parts[1], parts[2]))
-}
+use crate::claims::Claims;
 
-fn decode_b64(input: &str) -> Result<Vec<u8>, String> {
-    URL_SAFE_NO_PAD
-        .decode(input)
-        .map_err(|_| "Failed to decode Base64".to_string())
-}
+/// Verifies RS256, ES256 and EdDSA signatures.
+pub struct RustCrypto;
 
+/// [`oidc_provider::jwt::verify_jwt_signature`] with [`RustCrypto`].
 pub fn verify_jwt_signature(
     token: &str,
-    jwk_pub_key: &JwkPublicKey,
+    keys: &JwkSet,
     context: &OpenIdContext,
 ) -> Result<Claims, String> {
-    let jwt_str = format!(
-        "{{\"kty\":\"RSA\",\"e\":\"{}\",\"n\":\"{}\"}}",
-        jwk_pub_key.e, jwk_pub_key.n
-    );
+    oidc_provider::jwt::verify_jwt_signature(token, keys, context, &RustCrypto)
+}
 
-    let jwk: JsonWebKey = jwt_str.parse().unwrap();
+impl SignatureVerifier for RustCrypto {
+    fn verify_rs256(
+        &self,
+        jwk_pub_key: &JwkPublicKey,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        let jwt_str = format!(
+            "{{\"kty\":\"RSA\",\"e\":\"{}\",\"n\":\"{}\"}}",
+            jwk_pub_key.e, jwk_pub_key.n
+        );
 
-    let pub_key = RsaPublicKey::from_public_key_der(jwk.key.to_der().as_slice()).unwrap();
+        // `n` and `e` come from the action: a malformed key fails the token
+        let jwk: JsonWebKey = jwt_str
+            .parse()
+            .map_err(|_| "Invalid RSA public key".to_string())?;
 
-    let (header_b64, payload_b64, signature_b64) = split_jwt(token)?;
+        let pub_key = RsaPublicKey::from_public_key_der(jwk.key.to_der().as_slice())
+            .map_err(|_| "Invalid RSA public key".to_string())?;
 
-    let signing_input = format!("{}.{}", header_b64, payload_b64);
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(signing_input);
+        let hashed = &hasher.finalize();
 
-    let mut hasher = sha2::Sha256::new();
-    hasher.update(signing_input);
-    let hashed = &hasher.finalize();
+        pub_key
+            .verify(Pkcs1v15Sign::new::<Sha256>(), hashed, signature)
+            .map_err(|e| format!("JWT signature verification failed: {}", e))
+    }
 
-    let signature = decode_b64(signature_b64)?;
+    fn verify_es256(
+        &self,
+        jwk_pub_key: &JwkPublicKey,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        if jwk_pub_key.crv != "P-256" {
+            return Err(format!("Unsupported EC curve `{}`", jwk_pub_key.crv));
+        }
+        let x = decode_b64(&jwk_pub_key.x)?;
+        let y = decode_b64(&jwk_pub_key.y)?;
+        if x.len() != 32 || y.len() != 32 {
+            return Err("Invalid P-256 coordinates".to_string());
+        }
+        let point = EncodedPoint::from_affine_coordinates(
+            FieldBytes::from_slice(&x),
+            FieldBytes::from_slice(&y),
+            false,
+        );
+        let pub_key = VerifyingKey::from_encoded_point(&point)
+            .map_err(|_| "Invalid P-256 public key".to_string())?;
 
-    pub_key
-        .verify(Pkcs1v15Sign::new::<Sha256>(), hashed, &signature)
-        .map_err(|e| format!("JWT signature verification failed: {}", e))?;
+        // JWS signatures are `r || s`, not DER
+        let signature =
+            Signature::from_slice(signature).map_err(|_| "Invalid ES256 signature".to_string())?;
 
-    let payload_bytes = decode_b64(payload_b64)?;
+        pub_key
+            .verify(signing_input, &signature)
+            .map_err(|e| format!("JWT signature verification failed: {}", e))
+    }
 
-    let claims: Claims = serde_json::from_slice(&payload_bytes)
-        .map_err(|_| "Failed to parse JWT claims".to_string())?;
+    fn verify_eddsa(
+        &self,
+        jwk_pub_key: &JwkPublicKey,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        if jwk_pub_key.crv != "Ed25519" {
+            return Err(format!("Unsupported OKP curve `{}`", jwk_pub_key.crv));
+        }
+        let x: [u8; 32] = decode_b64(&jwk_pub_key.x)?
+            .try_into()
+            .map_err(|_| "Invalid Ed25519 public key".to_string())?;
+        let pub_key = ed25519_dalek::VerifyingKey::from_bytes(&x)
+            .map_err(|_| "Invalid Ed25519 public key".to_string())?;
+        let signature = ed25519_dalek::Signature::from_slice(signature)
+            .map_err(|_| "Invalid EdDSA signature".to_string())?;
 
-    if !claims.aud.contains(&context.audience) {
-        return Err(format!(
-            "Invalid Audience: expected `{}`, got `{:?}`",
-            context.audience, claims.aud
-        ));
+        // Strict: refuses small-order keys and non-canonical signatures
+        pub_key
+            .verify_strict(signing_input, &signature)
+            .map_err(|e| format!("JWT signature verification failed: {}", e))
     }
-    if claims.iss != context.issuer {
-        return Err(format!(
-            "Invalid Issuer: expected `{}`, got `{}`",
-            context.issuer, claims.iss
-        ));
-    }
-
-    Ok(claims)
 }
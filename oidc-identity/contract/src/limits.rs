@@ -0,0 +1,125 @@
+use core::fmt;
+
+use sdk::ContractInput;
+use serde::{Deserialize, Serialize};
+
+/// Largest ID token accepted. Real-world tokens are 1-2 KB; anything bigger is
+/// only going to inflate RSA/JSON cycles.
+pub const MAX_TOKEN_LEN: usize = 8 * 1024;
+/// Largest encoded private input (token plus encoding overhead).
+pub const MAX_PRIVATE_INPUT_LEN: usize = MAX_TOKEN_LEN + 1024;
+/// Largest encoded private input with a state witness (see `merkle`): the
+/// state without its accounts, the accounts used and their proofs.
+pub const MAX_WITNESSED_INPUT_LEN: usize = MAX_PRIVATE_INPUT_LEN + 64 * 1024;
+/// Largest number of blobs in the transaction.
+pub const MAX_BLOBS: usize = 16;
+/// Largest encoded blob.
+pub const MAX_BLOB_LEN: usize = 4 * 1024;
+/// Most cycles one action may take in the guest. Keeps new features (larger
+/// states, more credentials) within what can be proven in reasonable time.
+pub const MAX_GUEST_CYCLES: u64 = 8_000_000;
+/// Most heap one action may allocate in the guest, well under the zkVM's.
+pub const MAX_GUEST_HEAP: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LimitError {
+    TooManyBlobs { count: usize },
+    BlobTooLarge { index: usize, len: usize },
+    PrivateInputTooLarge { len: usize, max: usize },
+    TokenTooLarge { len: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooManyBlobs { count } => {
+                write!(f, "Too many blobs: {count} (max {MAX_BLOBS})")
+            }
+            LimitError::BlobTooLarge { index, len } => {
+                write!(f, "Blob {index} too large: {len} bytes (max {MAX_BLOB_LEN})")
+            }
+            LimitError::PrivateInputTooLarge { len, max } => {
+                write!(f, "Private input too large: {len} bytes (max {max})")
+            }
+            LimitError::TokenTooLarge { len } => {
+                write!(f, "ID token too large: {len} bytes (max {MAX_TOKEN_LEN})")
+            }
+        }
+    }
+}
+
+impl From<LimitError> for String {
+    fn from(err: LimitError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Resources spent by one action, written to the host's stdout by guests
+/// built with the `instrument` feature; their journal stays the program
+/// output alone.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GuestStats {
+    pub cycles: u64,
+    /// Heap allocated by the action, in bytes; the guest allocator never frees
+    pub heap: u64,
+}
+
+impl GuestStats {
+    pub fn check_budget(&self) -> Result<(), String> {
+        if self.cycles > MAX_GUEST_CYCLES {
+            return Err(format!(
+                "{} cycles exceed the budget of {MAX_GUEST_CYCLES}",
+                self.cycles
+            ));
+        }
+        if self.heap > MAX_GUEST_HEAP {
+            return Err(format!(
+                "{} heap bytes exceed the budget of {MAX_GUEST_HEAP}",
+                self.heap
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Rejects oversized inputs before any of them is decoded.
+pub fn check_input(input: &ContractInput) -> Result<(), LimitError> {
+    check_input_within(input, MAX_PRIVATE_INPUT_LEN)
+}
+
+/// [`check_input`] with a private input of up to `max_private_input` bytes.
+pub fn check_input_within(
+    input: &ContractInput,
+    max_private_input: usize,
+) -> Result<(), LimitError> {
+    if input.blobs.len() > MAX_BLOBS {
+        return Err(LimitError::TooManyBlobs {
+            count: input.blobs.len(),
+        });
+    }
+    if let Some((index, blob)) = input
+        .blobs
+        .iter()
+        .enumerate()
+        .find(|(_, blob)| blob.data.0.len() > MAX_BLOB_LEN)
+    {
+        return Err(LimitError::BlobTooLarge {
+            index,
+            len: blob.data.0.len(),
+        });
+    }
+    if input.private_input.len() > max_private_input {
+        return Err(LimitError::PrivateInputTooLarge {
+            len: input.private_input.len(),
+            max: max_private_input,
+        });
+    }
+    Ok(())
+}
+
+pub fn check_token(token: &str) -> Result<(), LimitError> {
+    if token.len() > MAX_TOKEN_LEN {
+        return Err(LimitError::TokenTooLarge { len: token.len() });
+    }
+    Ok(())
+}
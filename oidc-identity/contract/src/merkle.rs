@@ -0,0 +1,500 @@
+//! Merkle commitment of the state.
+//!
+//! The accounts, email index and subject bindings of the root state and of
+//! each realm are kept in sparse Merkle trees ([`oidc_provider::smt`]): an
+//! account's leaf also holds the email hashes indexed to it, and a subject's
+//! leaf the accounts it is bound to. A state commits to `H(H(accounts root,
+//! emails root), H(subjects root, H(header)))`, where the header is
+//! everything else, realms included by their own commitment. Built with the
+//! `merkle-state` feature, the contract's digest is this 32-byte commitment
+//! instead of the whole encoded state, and proofs no longer carry every
+//! account: the private input holds a [`WitnessedInput`], the state with only
+//! the entries the action uses and their proofs, checked against the digest
+//! before the action is applied.
+//!
+//! Provers need the full state to build witnesses, with
+//! [`OidcIdentity::witness`]; the node only keeps the commitment.
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use oidc_provider::archive::{self, MerkleArchive, Node};
+use oidc_provider::claim_policy::ClaimPolicy;
+use oidc_provider::disclosure::DisclosurePolicy;
+use oidc_provider::failure::Failure;
+use oidc_provider::hasher::{AccountHasher, DefaultHasher};
+use oidc_provider::issuer::IssuerMatch;
+use oidc_provider::smt::{self, LeafUpdate, SmtProof};
+use oidc_provider::{JwkSet, PrivateInput};
+use sdk::{ContractInput, Digestable, RunResult};
+
+use crate::governance::Governance;
+use crate::{limits, AccountInfo, OidcIdentity};
+
+/// Everything a state commits to besides its trees.
+#[derive(Encode)]
+struct Header<'a> {
+    allowed_issuers: &'a BTreeMap<String, BTreeSet<String>>,
+    claim_policies: &'a BTreeMap<String, ClaimPolicy>,
+    governance: &'a Governance,
+    disclosure_policy: &'a DisclosurePolicy,
+    max_inactivity: &'a Option<u64>,
+    max_clock_skew: &'a u64,
+    require_token_nonce: &'a bool,
+    archive: &'a MerkleArchive,
+    origins: &'a BTreeSet<String>,
+    realms: Vec<(&'a str, Node)>,
+    issuer_matches: &'a BTreeMap<String, IssuerMatch>,
+    issuer_keys: &'a BTreeMap<String, JwkSet>,
+}
+
+/// Leaf of `account` in its state's accounts tree.
+fn account_leaf(account: &str, info: &AccountInfo, emails: &[&str]) -> Node {
+    let record = bincode::encode_to_vec((info, emails), bincode::config::standard())
+        .expect("Failed to encode AccountInfo");
+    archive::leaf(account, &record)
+}
+
+/// Leaf of the subject hashing to `hash` in its state's subjects tree.
+fn subject_leaf(hash: &str, accounts: &[&str]) -> Node {
+    let record = bincode::encode_to_vec(accounts, bincode::config::standard())
+        .expect("Failed to encode subject accounts");
+    archive::leaf(hash, &record)
+}
+
+/// Leaves of a state's trees, by account, email hash and subject hash. From a
+/// partial state, subjects only hold the accounts carried.
+struct Leaves {
+    accounts: BTreeMap<String, Node>,
+    emails: BTreeMap<String, Node>,
+    subjects: BTreeMap<String, Node>,
+}
+
+impl Leaves {
+    fn roots(&self) -> Roots {
+        Roots {
+            accounts: root(&self.accounts),
+            emails: root(&self.emails),
+            subjects: root(&self.subjects),
+        }
+    }
+}
+
+/// Roots of a state's trees.
+struct Roots {
+    accounts: Node,
+    emails: Node,
+    subjects: Node,
+}
+
+fn nodes(leaves: &BTreeMap<String, Node>) -> BTreeMap<Node, Node> {
+    leaves
+        .iter()
+        .map(|(key, leaf)| (smt::key(key), *leaf))
+        .collect()
+}
+
+fn root(leaves: &BTreeMap<String, Node>) -> Node {
+    smt::root(&nodes(leaves))
+}
+
+fn commit(state: &OidcIdentity, roots: Roots, realms: Vec<(&str, Node)>) -> Node {
+    let header = Header {
+        allowed_issuers: &state.allowed_issuers,
+        claim_policies: &state.claim_policies,
+        governance: &state.governance,
+        disclosure_policy: &state.disclosure_policy,
+        max_inactivity: &state.max_inactivity,
+        max_clock_skew: &state.max_clock_skew,
+        require_token_nonce: &state.require_token_nonce,
+        archive: &state.archive,
+        origins: &state.origins,
+        realms,
+        issuer_matches: &state.issuer_matches,
+        issuer_keys: &state.issuer_keys,
+    };
+    let header = bincode::encode_to_vec(&header, bincode::config::standard())
+        .expect("Failed to encode state header");
+    DefaultHasher::hash_pair(
+        &DefaultHasher::hash_pair(&roots.accounts, &roots.emails),
+        &DefaultHasher::hash_pair(&roots.subjects, &DefaultHasher::digest(&header)),
+    )
+}
+
+impl OidcIdentity {
+    fn leaves(&self) -> Leaves {
+        let mut indexed: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (email, account) in &self.email_index {
+            indexed.entry(account).or_default().push(email);
+        }
+        let mut bound: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (account, info) in &self.identities {
+            for hash in &info.hashes {
+                bound.entry(hash).or_default().push(account);
+            }
+        }
+        Leaves {
+            accounts: self
+                .identities
+                .iter()
+                .map(|(account, info)| {
+                    let emails = indexed
+                        .get(account.as_str())
+                        .map_or(&[][..], Vec::as_slice);
+                    (account.clone(), account_leaf(account, info, emails))
+                })
+                .collect(),
+            emails: self
+                .email_index
+                .iter()
+                .map(|(email, account)| (email.clone(), archive::leaf(email, account.as_bytes())))
+                .collect(),
+            subjects: bound
+                .iter()
+                .map(|(hash, accounts)| (hash.to_string(), subject_leaf(hash, accounts)))
+                .collect(),
+        }
+    }
+
+    /// Commitment of the whole state.
+    pub fn commitment(&self) -> Node {
+        let realms = self
+            .realms
+            .iter()
+            .map(|(name, realm)| (name.as_str(), realm.commitment()))
+            .collect();
+        commit(self, self.leaves().roots(), realms)
+    }
+
+    /// The state as carried in a [`WitnessedInput`] for an action using `keys`
+    /// of the root state and `realm_keys` of the realms.
+    pub fn witness(
+        &self,
+        keys: &WitnessKeys,
+        realm_keys: &BTreeMap<String, WitnessKeys>,
+    ) -> (OidcIdentity, StateWitness) {
+        let (mut partial, trees) = self.witness_trees(keys);
+        let mut realms = BTreeMap::new();
+        for (name, keys) in realm_keys {
+            if let Some(realm) = self.realms.get(name) {
+                let (realm, trees) = realm.witness_trees(keys);
+                partial.realms.insert(name.clone(), realm);
+                realms.insert(name.clone(), trees);
+            }
+        }
+        let witness = StateWitness {
+            trees: Some(trees),
+            realms,
+        };
+        (partial, witness)
+    }
+
+    /// This state with only the entries `keys` need, its realms untouched, and
+    /// their trees.
+    fn witness_trees(&self, keys: &WitnessKeys) -> (OidcIdentity, StateTrees) {
+        // The subjects of the accounts, which the action may unbind, and every
+        // account bound to them, which their leaves hold
+        let mut subjects = keys.subjects.clone();
+        subjects.extend(
+            keys.accounts
+                .iter()
+                .filter_map(|account| self.identities.get(account))
+                .flat_map(|info| info.hashes.iter().cloned()),
+        );
+        let mut accounts = keys.accounts.clone();
+        accounts.extend(
+            self.identities
+                .iter()
+                .filter(|(_, info)| info.hashes.iter().any(|hash| subjects.contains(hash)))
+                .map(|(account, _)| account.clone()),
+        );
+        // The emails indexed to the accounts, which their leaves hold
+        let mut emails = keys.emails.clone();
+        emails.extend(
+            self.email_index
+                .iter()
+                .filter(|(_, account)| accounts.contains(*account))
+                .map(|(email, _)| email.clone()),
+        );
+
+        let leaves = self.leaves();
+        let mut partial = self.clone();
+        partial
+            .identities
+            .retain(|account, _| accounts.contains(account));
+        partial
+            .email_index
+            .retain(|email, _| emails.contains(email));
+        let trees = StateTrees {
+            accounts: TreeWitness::new(&leaves.accounts, accounts),
+            emails: TreeWitness::new(&leaves.emails, emails),
+            subjects: TreeWitness::new(&leaves.subjects, subjects),
+        };
+        (partial, trees)
+    }
+}
+
+/// Keys of a state an action uses, present or not.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct WitnessKeys {
+    /// Accounts it reads or writes, such as the acting one, the admin for
+    /// governance, and the targets of freezes and prunes
+    pub accounts: BTreeSet<String>,
+    /// Email hashes it looks up or indexes, such as a registration's when the
+    /// disclosure policy hashes emails
+    pub emails: BTreeSet<String>,
+    /// Subject hashes it binds or needs unbound, such as the subject of a
+    /// registration or the new one of a migration, link or recovery. Those of
+    /// the accounts are added.
+    pub subjects: BTreeSet<String>,
+}
+
+/// Root of a tree and the proofs of the keys carried.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct TreeWitness {
+    pub root: Node,
+    /// By key, including keys proved absent
+    pub proofs: BTreeMap<String, SmtProof>,
+}
+
+impl TreeWitness {
+    fn new(leaves: &BTreeMap<String, Node>, keys: BTreeSet<String>) -> Self {
+        let nodes = nodes(leaves);
+        TreeWitness {
+            root: smt::root(&nodes),
+            proofs: keys
+                .into_iter()
+                .map(|key| {
+                    let proof = smt::proof(&nodes, &smt::key(&key));
+                    (key, proof)
+                })
+                .collect(),
+        }
+    }
+
+    /// Fails if a leaf is carried without a proof.
+    fn proves_all(&self, kind: &str, leaves: &BTreeMap<String, Node>) -> Result<(), String> {
+        match leaves.keys().find(|key| !self.proofs.contains_key(*key)) {
+            Some(key) => Err(format!("{kind} {key} is carried without a proof")),
+            None => Ok(()),
+        }
+    }
+
+    /// The root, once every proof is checked against `leaves`.
+    fn checked_root(&self, kind: &str, leaves: &BTreeMap<String, Node>) -> Result<Node, String> {
+        for (key, proof) in &self.proofs {
+            let leaf = leaves.get(key).copied().unwrap_or(smt::EMPTY_LEAF);
+            if smt::root_from(&smt::key(key), leaf, proof) != Some(self.root) {
+                return Err(format!("Invalid proof for {kind} {key}"));
+            }
+        }
+        Ok(self.root)
+    }
+
+    /// The root once the leaves carried went from `before` to `after`. Fails
+    /// if a leaf changed without a proof.
+    fn updated_root(
+        &self,
+        kind: &str,
+        before: &BTreeMap<String, Node>,
+        after: &BTreeMap<String, Node>,
+    ) -> Result<Node, String> {
+        let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        let mut updates = Vec::new();
+        for key in keys {
+            if before.get(key) == after.get(key) {
+                continue;
+            }
+            let proof = self
+                .proofs
+                .get(key)
+                .ok_or_else(|| format!("{kind} {key} is not witnessed"))?;
+            updates.push(LeafUpdate {
+                key: smt::key(key),
+                leaf: after.get(key).copied().unwrap_or(smt::EMPTY_LEAF),
+                proof,
+            });
+        }
+        smt::update(self.root, &updates).ok_or_else(|| "Malformed state witness".to_string())
+    }
+}
+
+/// Witness of a state carried with only some of its entries.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct StateTrees {
+    pub accounts: TreeWitness,
+    pub emails: TreeWitness,
+    /// Subjects proved carry every account bound to them; others may carry
+    /// some of them
+    pub subjects: TreeWitness,
+}
+
+impl StateTrees {
+    /// Roots of `state`'s trees, once the proofs are checked.
+    fn roots(&self, state: &OidcIdentity) -> Result<Roots, String> {
+        let leaves = state.leaves();
+        self.accounts.proves_all("Account", &leaves.accounts)?;
+        self.emails.proves_all("Email", &leaves.emails)?;
+        Ok(Roots {
+            accounts: self.accounts.checked_root("Account", &leaves.accounts)?,
+            emails: self.emails.checked_root("Email", &leaves.emails)?,
+            subjects: self.subjects.checked_root("Subject", &leaves.subjects)?,
+        })
+    }
+
+    /// Roots once `before`, checked against these trees, became `after`.
+    fn updated_roots(&self, before: &OidcIdentity, after: &OidcIdentity) -> Result<Roots, String> {
+        // A new recovery subject must be bound to no account, which only its
+        // proof shows
+        for (account, info) in &after.identities {
+            let Some(provider) = &info.recovery_provider else {
+                continue;
+            };
+            let kept = before
+                .identities
+                .get(account)
+                .and_then(|info| info.recovery_provider.as_ref())
+                .is_some_and(|old| old.hash == provider.hash);
+            if !kept && !self.subjects.proofs.contains_key(&provider.hash) {
+                return Err(format!("Recovery subject of {account} is not witnessed"));
+            }
+        }
+        let (old, new) = (before.leaves(), after.leaves());
+        Ok(Roots {
+            accounts: self
+                .accounts
+                .updated_root("Account", &old.accounts, &new.accounts)?,
+            emails: self.emails.updated_root("Email", &old.emails, &new.emails)?,
+            subjects: self
+                .subjects
+                .updated_root("Subject", &old.subjects, &new.subjects)?,
+        })
+    }
+}
+
+/// Proofs for the entries carried by a partial state. A state, root or realm,
+/// without trees is carried in full.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct StateWitness {
+    pub trees: Option<StateTrees>,
+    pub realms: BTreeMap<String, StateTrees>,
+}
+
+impl StateWitness {
+    /// Commitment of the full state `state` is part of, once every proof is
+    /// checked against its tree's root.
+    pub fn commitment(&self, state: &OidcIdentity) -> Result<Node, String> {
+        let realms = state
+            .realms
+            .iter()
+            .map(|(name, realm)| {
+                let roots = roots(realm, self.realms.get(name))?;
+                Ok((name.as_str(), commit(realm, roots, Vec::new())))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(commit(state, roots(state, self.trees.as_ref())?, realms))
+    }
+
+    /// Commitment once `before`, the state this witness was checked against,
+    /// became `after`. Fails if the action wrote an entry it didn't prove.
+    pub fn updated_commitment(
+        &self,
+        before: &OidcIdentity,
+        after: &OidcIdentity,
+    ) -> Result<Node, String> {
+        let realms = after
+            .realms
+            .iter()
+            .map(|(name, realm)| {
+                let roots = match (before.realms.get(name), self.realms.get(name)) {
+                    (Some(old), Some(trees)) => trees.updated_roots(old, realm)?,
+                    // Created by the action, or carried in full
+                    _ => realm.leaves().roots(),
+                };
+                Ok((name.as_str(), commit(realm, roots, Vec::new())))
+            })
+            .collect::<Result<_, String>>()?;
+        let roots = match &self.trees {
+            Some(trees) => trees.updated_roots(before, after)?,
+            None => after.leaves().roots(),
+        };
+        Ok(commit(after, roots, realms))
+    }
+}
+
+/// Roots of `state`'s trees: from their witness if any, or else from all the
+/// entries.
+fn roots(state: &OidcIdentity, trees: Option<&StateTrees>) -> Result<Roots, String> {
+    match trees {
+        Some(trees) => trees.roots(state),
+        None => Ok(state.leaves().roots()),
+    }
+}
+
+/// Private input of a contract built with `merkle-state`.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
+pub struct WitnessedInput {
+    pub private_input: PrivateInput,
+    /// The state, with only the entries proved by `witness`
+    pub state: OidcIdentity,
+    pub witness: StateWitness,
+}
+
+impl WitnessedInput {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::encode_to_vec(self, bincode::config::standard())
+            .expect("failed to encode witnessed input")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        match bincode::decode_from_slice(bytes, bincode::config::standard()) {
+            Ok((input, read)) if read == bytes.len() => Ok(input),
+            _ => Err("Could not decode witnessed input"),
+        }
+    }
+}
+
+/// A partial state after an action, and the commitment of the full state.
+#[derive(Debug, Clone)]
+pub struct WitnessedState {
+    pub state: OidcIdentity,
+    pub commitment: Node,
+}
+
+impl Digestable for WitnessedState {
+    fn as_digest(&self) -> sdk::StateDigest {
+        sdk::StateDigest(self.commitment.to_vec())
+    }
+}
+
+/// [`crate::execute`] for a contract whose digest is the state commitment.
+pub fn execute(input: ContractInput) -> RunResult<WitnessedState> {
+    limits::check_input_within(&input, limits::MAX_WITNESSED_INPUT_LEN)
+        .map_err(Failure::invalid_input)?;
+
+    let parsed_blob = crate::parse_action(&input)?;
+    crate::check_identity(&input, &parsed_blob)?;
+
+    let WitnessedInput {
+        private_input,
+        state,
+        witness,
+    } = WitnessedInput::from_bytes(&input.private_input).map_err(Failure::invalid_input)?;
+    if input.initial_state.0 != witness.commitment(&state).map_err(Failure::invalid_input)? {
+        return Err(Failure::invalid_input(
+            "State witness doesn't match the state digest",
+        ));
+    }
+    crate::check_private_input(&input, &private_input, &state)?;
+
+    let block = crate::block_context(&input);
+
+    let before = state.clone();
+    let (output, state, callees) =
+        oidc_provider::execute_action(state, parsed_blob, &private_input, block)?;
+    let commitment = witness
+        .updated_commitment(&before, &state)
+        .map_err(Failure::invalid_input)?;
+    Ok((output, WitnessedState { state, commitment }, callees))
+}
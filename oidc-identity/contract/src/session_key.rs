@@ -0,0 +1,20 @@
+//! Verification of session key signatures, see [`oidc_provider::session`].
+
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+/// The P-256 key of a session key's hex SEC1 encoding.
+pub fn decode(public_key: &str) -> Result<VerifyingKey, String> {
+    let bytes = hex::decode(public_key).map_err(|_| "Session key isn't hex".to_string())?;
+    VerifyingKey::from_sec1_bytes(&bytes).map_err(|_| "Invalid P-256 session key".to_string())
+}
+
+/// Checks `signature`, the hex of `r || s`, by `public_key` over `message`.
+pub fn verify(public_key: &str, signature: &str, message: &str) -> Result<(), String> {
+    let key = decode(public_key)?;
+    let signature = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or_else(|| "Invalid session key signature".to_string())?;
+    key.verify(message.as_bytes(), &signature)
+        .map_err(|e| format!("Session key signature verification failed: {}", e))
+}
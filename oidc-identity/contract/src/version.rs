@@ -0,0 +1,403 @@
+//! Versioned encoding of the state digest.
+//!
+//! A digest is [`VERSION_MARKER`], the layout version, then the bincode
+//! encoding of the state in that layout. v1 digests, written before states
+//! were versioned, are the bare encoding: they never start with the marker,
+//! which no bincode varint uses. Decoding upgrades any supported version to
+//! the current layout; encoding always writes [`STATE_VERSION`], so the next
+//! action on a deployed contract migrates its state.
+//!
+//! | Version | Layout change                                  |
+//! |---------|------------------------------------------------|
+//! | 1       | unversioned                                    |
+//! | 2       | same layout as v1, with the version prefix     |
+//! | 3       | [`AccountInfo::metadata`]                      |
+//! | 4       | [`AccountInfo::recovery_provider`]             |
+//! | 5       | [`AccountInfo::session_keys`]                  |
+//! | 6       | the email index of each state                  |
+//! | 7       | the issuer match rules of each state           |
+//! | 8       | [`AccountInfo::nonce_strategy`] and its window |
+//! | 9       | the keys of each allowed issuer                |
+//!
+//! States before v9 stored no keys: their allowed issuers are verified with
+//! the keys of each action until allowed again, and their pending
+//! `AllowIssuer` operations, executed, would leave the issuer without any.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bincode::Decode;
+use oidc_provider::archive::MerkleArchive;
+use oidc_provider::claim_policy::ClaimPolicy;
+use oidc_provider::disclosure::DisclosurePolicy;
+use oidc_provider::history::ControlHistory;
+use oidc_provider::issuer::IssuerMatch;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::realm::RealmConfig;
+use oidc_provider::recovery::RecoveryProvider;
+use oidc_provider::session::SessionKey;
+use oidc_provider::{AdminOperation, JwkSet, OpenIdContext};
+use sdk::StateDigest;
+
+use crate::governance::{Governance, PendingOperation};
+use crate::{AccountInfo, NonceRange, OidcIdentity};
+
+/// First byte of a versioned digest.
+pub const VERSION_MARKER: u8 = 0xff;
+
+/// Layout version written by [`encode`].
+pub const STATE_VERSION: u8 = 9;
+
+pub fn encode(state: &OidcIdentity) -> Vec<u8> {
+    let mut bytes = vec![VERSION_MARKER, STATE_VERSION];
+    bincode::encode_into_std_write(state, &mut bytes, bincode::config::standard())
+        .expect("Failed to encode identity state");
+    bytes
+}
+
+/// The layout version of `bytes`, without decoding the state.
+pub fn version(bytes: &[u8]) -> Option<u8> {
+    match bytes {
+        [VERSION_MARKER, version, ..] => Some(*version),
+        [VERSION_MARKER] | [] => None,
+        _ => Some(1),
+    }
+}
+
+/// Strict decoding of a digest of any supported version, rejecting trailing bytes.
+pub fn decode(bytes: &[u8]) -> Result<OidcIdentity, &'static str> {
+    match (version(bytes), bytes) {
+        (Some(1), _) => decode_legacy::<AccountInfoV1, NoEmailIndex, NoIssuerMatches>(bytes),
+        (Some(2), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV1, NoEmailIndex, NoIssuerMatches>(layout)
+        }
+        (Some(3), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV3, NoEmailIndex, NoIssuerMatches>(layout)
+        }
+        (Some(4), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV4, NoEmailIndex, NoIssuerMatches>(layout)
+        }
+        (Some(5), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV5, NoEmailIndex, NoIssuerMatches>(layout)
+        }
+        (Some(6), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV5, EmailIndex, NoIssuerMatches>(layout)
+        }
+        (Some(7), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfoV5, EmailIndex, IssuerMatches>(layout)
+        }
+        (Some(8), [_, _, layout @ ..]) => {
+            decode_legacy::<AccountInfo, EmailIndex, IssuerMatches>(layout)
+        }
+        (Some(STATE_VERSION), [_, _, layout @ ..]) => decode_layout(layout),
+        _ => Err("Unsupported identity state version"),
+    }
+}
+
+/// Re-encodes a digest of any supported version in the current one.
+pub fn migrate(digest: &StateDigest) -> Result<StateDigest, &'static str> {
+    decode(&digest.0).map(|state| StateDigest(encode(&state)))
+}
+
+/// Decodes an account record archived by `PruneInactive` in any layout.
+/// Records carry no version: the current layout is tried first.
+pub(crate) fn decode_account(record: &[u8]) -> Option<AccountInfo> {
+    strict_decode::<AccountInfo>(record)
+        .or_else(|| strict_decode::<AccountInfoV5>(record).map(AccountInfo::from))
+        .or_else(|| strict_decode::<AccountInfoV4>(record).map(AccountInfo::from))
+        .or_else(|| strict_decode::<AccountInfoV3>(record).map(AccountInfo::from))
+        .or_else(|| strict_decode::<AccountInfoV1>(record).map(AccountInfo::from))
+}
+
+/// A state of v1 to v8, whose accounts have the layout of `A`, whose email
+/// index, from v6, is `E` and whose issuer match rules, from v7, are `M`.
+fn decode_legacy<A, E, M>(bytes: &[u8]) -> Result<OidcIdentity, &'static str>
+where
+    A: Decode + Into<AccountInfo>,
+    E: Decode + Into<EmailIndex>,
+    M: Decode + Into<IssuerMatches>,
+{
+    strict_decode::<LegacyState<A, E, M>>(bytes)
+        .map(OidcIdentity::from)
+        .ok_or("Could not decode identity state")
+}
+
+fn decode_layout(bytes: &[u8]) -> Result<OidcIdentity, &'static str> {
+    strict_decode(bytes).ok_or("Could not decode identity state")
+}
+
+fn strict_decode<T: Decode>(bytes: &[u8]) -> Option<T> {
+    match bincode::decode_from_slice(bytes, bincode::config::standard()) {
+        Ok((value, read)) if read == bytes.len() => Some(value),
+        _ => None,
+    }
+}
+
+/// Accounts of v1 and v2.
+#[derive(Decode)]
+struct AccountInfoV1 {
+    hashes: BTreeSet<String>,
+    nonce: u32,
+    session_epoch: u32,
+    frozen: bool,
+    reserved: Option<NonceRange>,
+    last_active: u64,
+    recovery_codes: Vec<String>,
+    history: ControlHistory,
+}
+
+impl From<AccountInfoV1> for AccountInfo {
+    fn from(info: AccountInfoV1) -> Self {
+        AccountInfo {
+            hashes: info.hashes,
+            nonce: info.nonce,
+            session_epoch: info.session_epoch,
+            frozen: info.frozen,
+            reserved: info.reserved,
+            last_active: info.last_active,
+            recovery_codes: info.recovery_codes,
+            history: info.history,
+            metadata: AccountMetadata::default(),
+            recovery_provider: None,
+            session_keys: Vec::new(),
+            nonce_strategy: NonceStrategy::Strict,
+            nonce_window: 0,
+        }
+    }
+}
+
+/// Accounts of v3, with metadata.
+#[derive(Decode)]
+struct AccountInfoV3 {
+    info: AccountInfoV1,
+    metadata: AccountMetadata,
+}
+
+impl From<AccountInfoV3> for AccountInfo {
+    fn from(AccountInfoV3 { info, metadata }: AccountInfoV3) -> Self {
+        AccountInfo {
+            metadata,
+            ..info.into()
+        }
+    }
+}
+
+/// Accounts of v4, with a recovery provider.
+#[derive(Decode)]
+struct AccountInfoV4 {
+    info: AccountInfoV3,
+    recovery_provider: Option<RecoveryProvider>,
+}
+
+impl From<AccountInfoV4> for AccountInfo {
+    fn from(
+        AccountInfoV4 {
+            info,
+            recovery_provider,
+        }: AccountInfoV4,
+    ) -> Self {
+        AccountInfo {
+            recovery_provider,
+            ..info.into()
+        }
+    }
+}
+
+/// Accounts of v5 to v7, with session keys.
+#[derive(Decode)]
+struct AccountInfoV5 {
+    info: AccountInfoV4,
+    session_keys: Vec<SessionKey>,
+}
+
+impl From<AccountInfoV5> for AccountInfo {
+    fn from(AccountInfoV5 { info, session_keys }: AccountInfoV5) -> Self {
+        AccountInfo {
+            session_keys,
+            ..info.into()
+        }
+    }
+}
+
+type EmailIndex = BTreeMap<String, String>;
+
+/// The email index of states before v6, which had none.
+#[derive(Decode)]
+struct NoEmailIndex;
+
+impl From<NoEmailIndex> for EmailIndex {
+    fn from(_: NoEmailIndex) -> Self {
+        EmailIndex::new()
+    }
+}
+
+type IssuerMatches = BTreeMap<String, IssuerMatch>;
+
+/// The issuer match rules of states before v7, which matched exactly.
+#[derive(Decode)]
+struct NoIssuerMatches;
+
+impl From<NoIssuerMatches> for IssuerMatches {
+    fn from(_: NoIssuerMatches) -> Self {
+        IssuerMatches::new()
+    }
+}
+
+/// Governance of v1 to v8, whose operations allowed issuers without keys.
+#[derive(Decode)]
+struct GovernanceV1 {
+    admin: Option<String>,
+    min_delay: u64,
+    next_id: u32,
+    pending: BTreeMap<u32, PendingOperationV1>,
+    paused: bool,
+}
+
+impl From<GovernanceV1> for Governance {
+    fn from(governance: GovernanceV1) -> Self {
+        Governance {
+            admin: governance.admin,
+            min_delay: governance.min_delay,
+            next_id: governance.next_id,
+            pending: upgrade(governance.pending),
+            paused: governance.paused,
+        }
+    }
+}
+
+#[derive(Decode)]
+struct PendingOperationV1 {
+    operation: AdminOperationV1,
+    proposed_at: u64,
+    executable_at: u64,
+}
+
+impl From<PendingOperationV1> for PendingOperation {
+    fn from(pending: PendingOperationV1) -> Self {
+        PendingOperation {
+            operation: pending.operation.into(),
+            proposed_at: pending.proposed_at,
+            executable_at: pending.executable_at,
+        }
+    }
+}
+
+#[derive(Decode)]
+enum AdminOperationV1 {
+    AllowIssuer { context: OpenIdContext },
+    DisallowIssuer { issuer: String },
+    FreezeAccount { account: String },
+    UnfreezeAccount { account: String },
+    Pause,
+    Unpause,
+    CreateRealm { realm: String, config: RealmConfigV1 },
+    AllowOrigin { origin: String },
+    DisallowOrigin { origin: String },
+}
+
+impl From<AdminOperationV1> for AdminOperation {
+    fn from(operation: AdminOperationV1) -> Self {
+        match operation {
+            AdminOperationV1::AllowIssuer { context } => AdminOperation::AllowIssuer {
+                context,
+                keys: JwkSet::default(),
+            },
+            AdminOperationV1::DisallowIssuer { issuer } => {
+                AdminOperation::DisallowIssuer { issuer }
+            }
+            AdminOperationV1::FreezeAccount { account } => {
+                AdminOperation::FreezeAccount { account }
+            }
+            AdminOperationV1::UnfreezeAccount { account } => {
+                AdminOperation::UnfreezeAccount { account }
+            }
+            AdminOperationV1::Pause => AdminOperation::Pause,
+            AdminOperationV1::Unpause => AdminOperation::Unpause,
+            AdminOperationV1::CreateRealm { realm, config } => AdminOperation::CreateRealm {
+                realm,
+                config: config.into(),
+            },
+            AdminOperationV1::AllowOrigin { origin } => AdminOperation::AllowOrigin { origin },
+            AdminOperationV1::DisallowOrigin { origin } => {
+                AdminOperation::DisallowOrigin { origin }
+            }
+        }
+    }
+}
+
+/// Realm configs of v1 to v8, allowing issuers without keys.
+#[derive(Decode)]
+struct RealmConfigV1 {
+    admin: String,
+    min_delay: u64,
+    allowed_issuers: Vec<OpenIdContext>,
+    origins: Vec<String>,
+    disclosure_policy: DisclosurePolicy,
+    max_inactivity: Option<u64>,
+}
+
+impl From<RealmConfigV1> for RealmConfig {
+    fn from(config: RealmConfigV1) -> Self {
+        RealmConfig {
+            admin: config.admin,
+            min_delay: config.min_delay,
+            allowed_issuers: config
+                .allowed_issuers
+                .into_iter()
+                .map(|context| (context, JwkSet::default()))
+                .collect(),
+            origins: config.origins,
+            disclosure_policy: config.disclosure_policy,
+            max_inactivity: config.max_inactivity,
+        }
+    }
+}
+
+#[derive(Decode)]
+struct LegacyState<A, E, M> {
+    identities: BTreeMap<String, A>,
+    allowed_issuers: BTreeMap<String, BTreeSet<String>>,
+    claim_policies: BTreeMap<String, ClaimPolicy>,
+    governance: GovernanceV1,
+    disclosure_policy: DisclosurePolicy,
+    max_inactivity: Option<u64>,
+    max_clock_skew: u64,
+    require_token_nonce: bool,
+    archive: MerkleArchive,
+    origins: BTreeSet<String>,
+    realms: BTreeMap<String, LegacyState<A, E, M>>,
+    email_index: E,
+    issuer_matches: M,
+}
+
+impl<A, E, M> From<LegacyState<A, E, M>> for OidcIdentity
+where
+    A: Into<AccountInfo>,
+    E: Into<EmailIndex>,
+    M: Into<IssuerMatches>,
+{
+    fn from(state: LegacyState<A, E, M>) -> Self {
+        OidcIdentity {
+            identities: upgrade(state.identities),
+            allowed_issuers: state.allowed_issuers,
+            claim_policies: state.claim_policies,
+            governance: state.governance.into(),
+            disclosure_policy: state.disclosure_policy,
+            max_inactivity: state.max_inactivity,
+            max_clock_skew: state.max_clock_skew,
+            require_token_nonce: state.require_token_nonce,
+            archive: state.archive,
+            origins: state.origins,
+            realms: upgrade(state.realms),
+            email_index: state.email_index.into(),
+            issuer_matches: state.issuer_matches.into(),
+            issuer_keys: BTreeMap::new(),
+        }
+    }
+}
+
+fn upgrade<Old: Into<New>, New>(map: BTreeMap<String, Old>) -> BTreeMap<String, New> {
+    map.into_iter()
+        .map(|(key, old)| (key, old.into()))
+        .collect()
+}
@@ -0,0 +1,121 @@
+//! Table-driven conformance of the contract's claim handling with the quirks
+//! of real identity providers' ID tokens, see `fixtures`.
+
+mod fixtures;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use fixtures::{Outcome, TestKey};
+use oidc_identity::claims::Claims;
+use oidc_identity::jwt::verify_jwt_signature;
+use serde_json::Value;
+
+/// The outcome a verification result stands for; errors that no fixture
+/// should cause fail the test.
+fn outcome(result: &Result<Claims, String>) -> Outcome {
+    match result {
+        Ok(_) => Outcome::Accepted,
+        Err(err) if err.starts_with("Invalid Audience") => Outcome::WrongAudience,
+        Err(err) if err.starts_with("Invalid Issuer") => Outcome::WrongIssuer,
+        Err(err) if err == "Failed to parse JWT claims" => Outcome::MalformedClaims,
+        Err(err) => panic!("Unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn claims_match_provider_quirks() {
+    let key = TestKey::load();
+    let mut cases = 0;
+    let mut failures = Vec::new();
+
+    for provider in fixtures::providers() {
+        for case in &provider.cases {
+            cases += 1;
+            let token = provider.token(&key, case);
+            let result = verify_jwt_signature(&token, &key.jwk(), &provider.context);
+            let label = format!("{} / {} ({})", provider.provider, case.name, case.quirk);
+
+            if outcome(&result) != case.outcome {
+                failures.push(format!(
+                    "{}: expected {:?}, got {:?}",
+                    label, case.outcome, result
+                ));
+                continue;
+            }
+            if let Ok(claims) = result {
+                let expected = case.claims();
+                for (field, value) in [
+                    ("sub", &claims.sub),
+                    ("email", &claims.email),
+                    ("iss", &claims.iss),
+                ] {
+                    if expected[field].as_str() != Some(value.as_str()) {
+                        failures.push(format!("{}: `{}` read as {:?}", label, field, value));
+                    }
+                }
+                let aud: Vec<&str> = match &expected["aud"] {
+                    Value::Array(audiences) => audiences.iter().filter_map(Value::as_str).collect(),
+                    audience => audience.as_str().into_iter().collect(),
+                };
+                if claims.aud != aud {
+                    failures.push(format!("{}: `aud` read as {:?}", label, claims.aud));
+                }
+                if expected["exp"].as_u64() != Some(claims.exp) {
+                    failures.push(format!("{}: `exp` read as {}", label, claims.exp));
+                }
+                for (field, value) in [("iat", claims.iat), ("nbf", claims.nbf)] {
+                    if expected[field].as_u64() != value {
+                        failures.push(format!("{}: `{}` read as {:?}", label, field, value));
+                    }
+                }
+            }
+        }
+    }
+
+    assert!(cases > 0, "No fixtures found");
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn every_provider_has_an_accepted_token() {
+    let providers = fixtures::providers();
+    for name in ["Google", "Microsoft Entra ID", "Auth0", "Keycloak"] {
+        let provider = providers
+            .iter()
+            .find(|provider| provider.provider == name)
+            .unwrap_or_else(|| panic!("No fixtures for {}", name));
+        assert!(
+            provider
+                .cases
+                .iter()
+                .any(|case| case.outcome == Outcome::Accepted),
+            "No accepted token for {}",
+            name
+        );
+    }
+}
+
+#[test]
+fn tampered_fixture_is_rejected() {
+    let key = TestKey::load();
+    let providers = fixtures::providers();
+    let provider = &providers[0];
+    let case = &provider.cases[0];
+
+    // Swap in another subject, keeping the original signature
+    let token = provider.token(&key, case);
+    let parts: Vec<&str> = token.split('.').collect();
+    let payload = case.payload().replacen("\"sub\":\"", "\"sub\":\"0", 1);
+    let forged = format!(
+        "{}.{}.{}",
+        parts[0],
+        URL_SAFE_NO_PAD.encode(payload),
+        parts[2]
+    );
+
+    let err = verify_jwt_signature(&forged, &key.jwk(), &provider.context).unwrap_err();
+    assert!(
+        err.starts_with("JWT signature verification failed"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,104 @@
+//! Decoding and migration of the state digests of every layout version, see
+//! `fixtures/state`: each fixture holds the same state in its version.
+
+use oidc_identity::version::{self, STATE_VERSION, VERSION_MARKER};
+use oidc_identity::OidcIdentity;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use sdk::StateDigest;
+
+fn fixture(hex: &str) -> StateDigest {
+    StateDigest(hex::decode(hex.trim()).expect("Fixture isn't hex"))
+}
+
+fn v1() -> StateDigest {
+    fixture(include_str!("fixtures/state/v1.hex"))
+}
+
+fn v2() -> StateDigest {
+    fixture(include_str!("fixtures/state/v2.hex"))
+}
+
+fn v3() -> StateDigest {
+    fixture(include_str!("fixtures/state/v3.hex"))
+}
+
+fn v4() -> StateDigest {
+    fixture(include_str!("fixtures/state/v4.hex"))
+}
+
+fn v5() -> StateDigest {
+    fixture(include_str!("fixtures/state/v5.hex"))
+}
+
+fn v6() -> StateDigest {
+    fixture(include_str!("fixtures/state/v6.hex"))
+}
+
+fn v7() -> StateDigest {
+    fixture(include_str!("fixtures/state/v7.hex"))
+}
+
+fn v8() -> StateDigest {
+    fixture(include_str!("fixtures/state/v8.hex"))
+}
+
+fn v9() -> StateDigest {
+    fixture(include_str!("fixtures/state/v9.hex"))
+}
+
+/// The fixture in [`STATE_VERSION`].
+fn current() -> StateDigest {
+    v9()
+}
+
+#[test]
+fn v1_state_decodes_to_the_current_layout() {
+    let digest = v1();
+    assert_eq!(version::version(&digest.0), Some(1));
+
+    let state = OidcIdentity::from(digest);
+    let account = state
+        .account("alice.oidc_identity")
+        .expect("Fixture account is missing");
+    assert_eq!(account.nonce, 3);
+    assert_eq!(account.session_epoch, 1);
+    assert_eq!(account.last_active, 42);
+    assert_eq!(account.metadata, AccountMetadata::default());
+    assert_eq!(account.recovery_provider, None);
+    assert!(account.session_keys.is_empty());
+    assert_eq!(account.nonce_strategy, NonceStrategy::Strict);
+    assert!(account
+        .hashes
+        .contains("8fb8632570028e4577587d581bdeb8c3c9145b5938eda92c237182218767f5d5"));
+}
+
+#[test]
+fn migrate_upgrades_every_version_to_the_current_one() {
+    let older = [v1(), v2(), v3(), v4(), v5(), v6(), v7(), v8()];
+    for (version, digest) in (1..).zip(older) {
+        let migrated = version::migrate(&digest).expect("Supported versions migrate");
+        assert_eq!(migrated.0[..2], [VERSION_MARKER, STATE_VERSION]);
+        assert_eq!(migrated.0, current().0, "v{version}");
+    }
+    assert_eq!(OidcIdentity::from(current()).to_bytes(), current().0);
+}
+
+#[test]
+fn migrate_is_idempotent() {
+    let migrated = version::migrate(&current()).expect("The current version migrates");
+    assert_eq!(migrated.0, current().0);
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    let mut future = current().0;
+    future[1] = STATE_VERSION + 1;
+    assert!(OidcIdentity::from_bytes(&future).is_err());
+    assert!(version::migrate(&StateDigest(future)).is_err());
+    assert!(OidcIdentity::from_bytes(&[VERSION_MARKER]).is_err());
+
+    let mut trailing = v1().0;
+    trailing.push(0);
+    assert!(version::migrate(&StateDigest(trailing)).is_err());
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use risc0_zkvm::{default_prover, ExecutorEnv, ProverOpts, Receipt};
+use sdk::{ContractInput, ProofData};
+
+use methods_identity::{AGGREGATE_GUEST_ELF, GUEST_ELF, GUEST_ID};
+
+/// Proves one identity action and returns its succinct receipt.
+fn prove_one(input: &ContractInput) -> Result<Receipt> {
+    let env = ExecutorEnv::builder().write(input)?.build()?;
+    let info = default_prover()
+        .prove_with_opts(env, GUEST_ELF, &ProverOpts::succinct())
+        .context("Failed to prove identity action")?;
+    Ok(info.receipt)
+}
+
+/// Proves every input, then folds the receipts into a single aggregated proof
+/// whose journal is the list of the inner journals, in order.
+///
+/// Settling it requires a node that accepts multi-output risc0 proofs; on
+/// nodes that don't, submit the individual proofs instead.
+pub fn prove_batch(inputs: &[ContractInput]) -> Result<ProofData> {
+    let receipts = inputs.iter().map(prove_one).collect::<Result<Vec<_>>>()?;
+
+    let journals: Vec<Vec<u8>> = receipts.iter().map(|r| r.journal.bytes.clone()).collect();
+
+    let mut env = ExecutorEnv::builder();
+    for receipt in receipts {
+        env.add_assumption(receipt);
+    }
+    let env = env.write(&(GUEST_ID, journals))?.build()?;
+
+    let receipt = default_prover()
+        .prove_with_opts(env, AGGREGATE_GUEST_ELF, &ProverOpts::succinct())
+        .context("Failed to aggregate receipts")?
+        .receipt;
+
+    Ok(ProofData(
+        borsh::to_vec(&receipt).context("Failed to encode aggregated receipt")?,
+    ))
+}
@@ -0,0 +1,125 @@
+//! The archive of pruned accounts as this host saw it: what `PruneInactive`
+//! published, which `Reactivate` proofs are built from. The contract only
+//! keeps the archive's root, so the journal must not be lost.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use oidc_identity::{ArchivedAccount, OidcIdentity};
+use oidc_provider::archive::{self, ArchiveProof};
+use serde::{Deserialize, Serialize};
+
+/// One leaf of the archive.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+struct Entry {
+    account: String,
+    /// Hex of the encoded account, as in [`ArchivedAccount`]
+    record: String,
+    /// Set once the account was reactivated, which zeroes its leaf
+    cleared: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ArchiveJournal {
+    entries: Vec<Entry>,
+}
+
+/// Where the journal of `contract_name`'s archive is kept.
+pub fn path(contract_name: &str) -> PathBuf {
+    app_dirs::get()
+        .data()
+        .expect("Failed to create the data directory")
+        .join(format!("{}.archive.json", contract_name))
+}
+
+/// The entries pruning `accounts` from `state` publishes, in order. The
+/// records are the accounts as they are now, which pruning doesn't change.
+pub fn archived(state: &OidcIdentity, accounts: &[String]) -> Result<Vec<ArchivedAccount>> {
+    accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| {
+            let info = state
+                .account(account)
+                .with_context(|| format!("{} isn't a live account", account))?;
+            let record = bincode::encode_to_vec(info, bincode::config::standard())?;
+            Ok(ArchivedAccount {
+                index: u32::try_from(state.archive().len() + i as u64)?,
+                account: account.clone(),
+                record: hex::encode(record),
+            })
+        })
+        .collect()
+}
+
+impl ArchiveJournal {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(ArchiveJournal::default());
+        }
+        let data = std::fs::read(path).context("Failed to read the archive journal")?;
+        serde_json::from_slice(&data).context("Invalid archive journal")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .context("Failed to write the archive journal")
+    }
+
+    pub fn record(&mut self, archived: &[ArchivedAccount]) -> Result<()> {
+        for entry in archived {
+            if entry.index as usize != self.entries.len() {
+                bail!(
+                    "{} was archived at {}, but the journal has {} entries: \
+                     another pruner's accounts are missing",
+                    entry.account,
+                    entry.index,
+                    self.entries.len()
+                );
+            }
+            self.entries.push(Entry {
+                account: entry.account.clone(),
+                record: entry.record.clone(),
+                cleared: false,
+            });
+        }
+        Ok(())
+    }
+
+    /// The record of `account`'s latest archived state and its inclusion
+    /// proof against `state`'s archive.
+    pub fn proof(&self, state: &OidcIdentity, account: &str) -> Result<(Vec<u8>, ArchiveProof)> {
+        if self.entries.len() as u64 != state.archive().len() {
+            bail!(
+                "The archive has {} leaves but the journal {}: another pruner's \
+                 accounts are missing",
+                state.archive().len(),
+                self.entries.len()
+            );
+        }
+        let index = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.account == account && !entry.cleared)
+            .with_context(|| format!("{} isn't in the archive journal", account))?;
+        let leaves = self
+            .entries
+            .iter()
+            .map(|entry| {
+                if entry.cleared {
+                    return Ok([0; 32]);
+                }
+                Ok(archive::leaf(&entry.account, &hex::decode(&entry.record)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let record = hex::decode(&self.entries[index].record)?;
+        Ok((record, archive::proof(&leaves, index as u32)))
+    }
+
+    /// Zeroes the leaf `proof` is for, once its account was reactivated.
+    pub fn clear(&mut self, proof: &ArchiveProof) {
+        if let Some(entry) = self.entries.get_mut(proof.index as usize) {
+            entry.cleared = true;
+        }
+    }
+}
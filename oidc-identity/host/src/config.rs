@@ -1,7 +1,14 @@
 use config::{Config, Environment, File};
+use host_tls::TlsSettings;
+use oidc_provider::claim_policy::ClaimPolicy;
+use oidc_provider::disclosure::{Claim, Disclosure};
+use oidc_provider::issuer::IssuerMatch;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::prover::{self, Polling, ProverChoice};
 
 pub fn load_config(config_file_path: Option<&Path>) -> anyhow::Result<AppConfig> {
     let mut settings = Config::builder();
@@ -22,17 +29,186 @@ pub struct AppConfig {
     pub contract: ContractConfig,
     pub server: ServerConfig,
     pub identity_providers: HashMap<String, IdentityProvider>,
+    #[serde(default)]
+    pub proving: ProvingConfig,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    #[serde(default)]
+    pub credentials: CredentialsConfig,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ContractConfig {
     pub name: String,
+    #[serde(default = "default_attestation_name")]
+    pub attestation_name: String,
+    #[serde(default = "default_alias_name")]
+    pub alias_name: String,
+    /// Account allowed to propose time-locked admin operations
+    #[serde(default)]
+    pub admin: Option<String>,
+    /// Blocks between proposing an admin operation and executing it
+    #[serde(default)]
+    pub admin_delay: u64,
+    /// Claims `disclose-claim` may publish, and how (`hashed` or `plain`)
+    #[serde(default)]
+    pub disclosure: BTreeMap<Claim, Disclosure>,
+    /// Blocks without activity after which accounts can be pruned; never if unset
+    #[serde(default)]
+    pub max_inactivity: Option<u64>,
+    /// Seconds ID token times may be off from the block time; 60 if unset
+    #[serde(default)]
+    pub max_clock_skew: Option<u64>,
+    /// Refuse to verify with ID tokens carrying no nonce, such as refreshed ones
+    #[serde(default)]
+    pub require_token_nonce: bool,
+}
+
+fn default_attestation_name() -> String {
+    "attestation_registry".to_string()
+}
+
+fn default_alias_name() -> String {
+    "alias_registry".to_string()
+}
+
+/// Where proofs are generated, and figures used to estimate proving time and
+/// cost before submitting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingConfig {
+    /// Backend used when `--prover` is not given: `local`, or `remote=<url>`
+    #[serde(default)]
+    pub prover: Option<ProverChoice>,
+    /// Seconds between two polls of a remote proving job
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Seconds after which a remote proving job still pending is given up
+    #[serde(default = "default_job_timeout_secs")]
+    pub job_timeout_secs: u64,
+    /// Proving throughput of the local prover
+    #[serde(default = "default_cycles_per_second")]
+    pub cycles_per_second: u64,
+    /// Cost of proving a million cycles, in the node's settlement unit
+    #[serde(default = "default_cost_per_mcycle")]
+    pub cost_per_mcycle: u64,
+}
+
+impl Default for ProvingConfig {
+    fn default() -> Self {
+        ProvingConfig {
+            prover: None,
+            poll_interval_secs: default_poll_interval_secs(),
+            job_timeout_secs: default_job_timeout_secs(),
+            cycles_per_second: default_cycles_per_second(),
+            cost_per_mcycle: default_cost_per_mcycle(),
+        }
+    }
+}
+
+impl ProvingConfig {
+    /// How remote proving jobs are waited for.
+    pub fn polling(&self) -> Polling {
+        Polling {
+            interval: Duration::from_secs(self.poll_interval_secs.max(1)),
+            timeout: Duration::from_secs(self.job_timeout_secs),
+        }
+    }
+}
+
+fn default_poll_interval_secs() -> u64 {
+    prover::POLL_INTERVAL.as_secs()
+}
+
+fn default_job_timeout_secs() -> u64 {
+    prover::JOB_TIMEOUT.as_secs()
+}
+
+fn default_cycles_per_second() -> u64 {
+    100_000
+}
+
+fn default_cost_per_mcycle() -> u64 {
+    1
+}
+
+/// Accounts kept verified by `watch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// Providers whose account is watched, one account each
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Seconds between scheduled verifications
+    #[serde(default = "default_watch_interval")]
+    pub interval: u64,
+    /// Seconds to wait for a verification to settle before alerting
+    #[serde(default = "default_settle_timeout")]
+    pub settle_timeout: u64,
+    /// Webhook receiving failed verifications as JSON
+    #[serde(default)]
+    pub alert_url: Option<String>,
+    /// Serve the local API over TLS, mutual when a client CA is set
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// PEM files of a TLS service, see the `host-tls` crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Require client certificates signed by this CA
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    pub fn settings(&self) -> TlsSettings {
+        TlsSettings {
+            cert: self.cert.clone(),
+            key: self.key.clone(),
+            client_ca: self.client_ca.clone(),
+        }
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            providers: Vec::new(),
+            interval: default_watch_interval(),
+            settle_timeout: default_settle_timeout(),
+            alert_url: None,
+            tls: None,
+        }
+    }
+}
+
+fn default_watch_interval() -> u64 {
+    3600
+}
+
+fn default_settle_timeout() -> u64 {
+    120
+}
+
+/// Issuer of the verifiable credentials `verify-identity --credential` signs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialsConfig {
+    /// URI naming the issuer in credentials, e.g. a `did:web`
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// PEM-encoded P-256 private key signing them
+    #[serde(default)]
+    pub key: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub server_url: String,
+    /// Independent nodes that must report the same contract state as `host`
+    #[serde(default)]
+    pub witnesses: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -40,20 +216,55 @@ pub struct IdentityProvider {
     pub issuer_url: String,
     pub audience_url: String,
     pub jwk_public_key_url: String,
+    /// RFC 7009 token revocation endpoint, used on logout
+    #[serde(default)]
+    pub revocation_url: Option<String>,
+    /// Push the authorization request to the provider's PAR endpoint (RFC 9126)
+    /// instead of passing it in the browser URL
+    #[serde(default)]
+    pub par: bool,
+    /// Ask for a signed authorization response (JARM, `response_mode=jwt`)
+    /// and validate it before using the code
+    #[serde(default)]
+    pub jarm: bool,
+    /// Claims the provider's tokens must carry, e.g. a Google Workspace
+    /// domain; the contract enforces it once registered with it
+    #[serde(default)]
+    pub policy: ClaimPolicy,
+    /// JWS algorithms the contract may accept for this provider's tokens
+    #[serde(default)]
+    pub algorithms: Vec<String>,
+    /// Issuer the contract matches tokens against, when not `issuer_url`:
+    /// e.g. the `{tenantid}` template of a multi-tenant provider
+    #[serde(default)]
+    pub token_issuer: Option<String>,
+    /// How the contract matches tokens' `iss` against the issuer
+    #[serde(default)]
+    pub issuer_match: IssuerMatch,
+    /// Keep the provider's refresh token, encrypted, so that later commands
+    /// log in without the browser
+    #[serde(default)]
+    pub remember_login: bool,
 }
 
 impl IdentityProvider {
+    /// The issuer of the contexts sent to the contract.
+    pub fn token_issuer(&self) -> &str {
+        self.token_issuer.as_deref().unwrap_or(&self.issuer_url)
+    }
+
     /// Fetch client secret from environment variables.
     pub fn get_client_secret(&self, provider_name: &str) -> String {
-        std::env::var(format!(
-            "OIDC_{}_CLIENT_SECRET",
-            provider_name.to_uppercase()
-        ))
-        .unwrap_or_else(|_| {
+        std::env::var(client_secret_var(provider_name)).unwrap_or_else(|_| {
             panic!(
-                "Missing environment variable: OIDC_{}_CLIENT_SECRET",
-                provider_name.to_uppercase()
+                "Missing environment variable: {}",
+                client_secret_var(provider_name)
             )
         })
     }
 }
+
+/// Environment variable holding the client secret of `provider_name`.
+pub fn client_secret_var(provider_name: &str) -> String {
+    format!("OIDC_{}_CLIENT_SECRET", provider_name.to_uppercase())
+}
@@ -0,0 +1,89 @@
+//! Detached signature over the settings deciding where tokens and proofs go.
+//!
+//! Whoever can edit `config.toml` can point the host at another node or at a
+//! look-alike contract, which then receives the proofs built from the user's
+//! ID tokens. Operators sign the node URL, the contract name and the program
+//! id once with `config-sign`; hosts started with `--config-key` refuse to run
+//! unless the current settings carry a valid signature.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+use crate::lifecycle;
+use crate::prover::Zkvm;
+
+/// Where the signature is kept, next to `config.toml`.
+pub const CONFIG_SIGNATURE_PATH: &str = "./host/config.sig";
+
+/// The signed subset of the configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedSettings {
+    pub node: String,
+    pub contract: String,
+    /// Hex program id of the local guest, for the `--zkvm` it runs on
+    pub program_id: String,
+}
+
+impl SignedSettings {
+    pub fn of(config: &AppConfig, zkvm: Zkvm) -> Self {
+        SignedSettings {
+            node: config.server.host.clone(),
+            contract: config.contract.name.clone(),
+            program_id: hex::encode(lifecycle::local_program_id(zkvm).0),
+        }
+    }
+}
+
+/// Signs the current settings with a PEM-encoded P-256 private key, as an
+/// ES256 JWS written to `signature_path`.
+pub fn sign(
+    config: &AppConfig,
+    zkvm: Zkvm,
+    private_key: &Path,
+    signature_path: &Path,
+) -> Result<()> {
+    let pem = std::fs::read(private_key).context("Failed to read the signing key")?;
+    let key = EncodingKey::from_ec_pem(&pem).context("Signing key is not a P-256 PEM key")?;
+    let signature = jsonwebtoken::encode(
+        &Header::new(Algorithm::ES256),
+        &SignedSettings::of(config, zkvm),
+        &key,
+    )?;
+    std::fs::write(signature_path, signature).context("Failed to write the config signature")
+}
+
+/// Checks that `signature_path` holds a signature of the current settings by
+/// the PEM-encoded P-256 public key.
+pub fn verify(
+    config: &AppConfig,
+    zkvm: Zkvm,
+    public_key: &Path,
+    signature_path: &Path,
+) -> Result<()> {
+    let pem = std::fs::read(public_key).context("Failed to read the config key")?;
+    let key = DecodingKey::from_ec_pem(&pem).context("Config key is not a P-256 PEM key")?;
+    let signature = std::fs::read_to_string(signature_path)
+        .with_context(|| format!("No config signature at {}", signature_path.display()))?;
+
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    let signed = jsonwebtoken::decode::<SignedSettings>(signature.trim(), &key, &validation)
+        .context("Invalid config signature")?
+        .claims;
+
+    let current = SignedSettings::of(config, zkvm);
+    if signed != current {
+        bail!(
+            "config.toml differs from the signed settings: signed {:?}, found {:?}",
+            signed,
+            current
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! `config validate`: checks `config.toml` and the endpoints it points to
+//! before the host relies on them. `serve --watch-config` runs the offline
+//! part of it on reloaded settings before using them.
+
+use std::path::Path;
+
+use clap::Subcommand;
+use host_output::say;
+use serde_json::json;
+use url::Url;
+
+use crate::config::{client_secret_var, load_config, AppConfig, IdentityProvider};
+use crate::doctor::discover;
+use crate::oidc_client::OIDCClient;
+use crate::providers;
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Load config.toml, probe each provider's discovery document and keys,
+    /// and check that their client secrets are set
+    Validate {},
+}
+
+/// Issues found in `config` without reaching any provider: URLs that don't
+/// parse, client secrets missing from the environment, unknown providers.
+pub fn static_issues(config: &AppConfig) -> Vec<String> {
+    let mut issues = Vec::new();
+    if config.identity_providers.is_empty() {
+        issues.push("no `[identity_providers.<name>]` configured".to_string());
+    }
+    if let Err(err) = Url::parse(&config.server.server_url) {
+        issues.push(format!("`server.server_url` is not a URL: {}", err));
+    }
+    for name in providers::names(config) {
+        let provider = &config.identity_providers[name];
+        let urls = [
+            ("issuer_url", Some(&provider.issuer_url)),
+            ("jwk_public_key_url", Some(&provider.jwk_public_key_url)),
+            ("revocation_url", provider.revocation_url.as_ref()),
+        ];
+        for (field, url) in urls {
+            if let Some(Err(err)) = url.map(|url| Url::parse(url)) {
+                issues.push(format!("{}: `{}` is not a URL: {}", name, field, err));
+            }
+        }
+        let secret = client_secret_var(name);
+        if std::env::var(&secret).unwrap_or_default().is_empty() {
+            issues.push(format!(
+                "{}: no client secret, set {} in the environment or .env",
+                name, secret
+            ));
+        }
+    }
+    for name in &config.watch.providers {
+        if !config.identity_providers.contains_key(name) {
+            issues.push(format!("`watch.providers` names {}, which isn't configured", name));
+        }
+    }
+    issues
+}
+
+/// Issues with the endpoints of provider `name`: its discovery document and
+/// its keys must be served.
+async fn endpoint_issues(name: &str, provider: &IdentityProvider) -> Vec<String> {
+    let mut issues = Vec::new();
+    match discover(&provider.issuer_url).await {
+        Ok(metadata) => {
+            say!("✅ {}: discovery, issuer {}", name, metadata.issuer().as_str());
+            let jwks_uri = metadata.jwks_uri().url().as_str();
+            if jwks_uri != provider.jwk_public_key_url {
+                say!(
+                    "⚠️  {}: discovery advertises the keys at {}, not {}",
+                    name,
+                    jwks_uri,
+                    provider.jwk_public_key_url
+                );
+            }
+        }
+        Err(err) => issues.push(format!(
+            "{}: discovery at {} failed: {:#}",
+            name, provider.issuer_url, err
+        )),
+    }
+    match OIDCClient::fetch_jwks(&provider.jwk_public_key_url).await {
+        Ok(keys) if keys.is_empty() => issues.push(format!(
+            "{}: no keys served at {}",
+            name, provider.jwk_public_key_url
+        )),
+        Ok(keys) => say!("✅ {}: {} key(s) served", name, keys.len()),
+        Err(err) => issues.push(format!(
+            "{}: keys at {} unreachable: {}",
+            name, provider.jwk_public_key_url, err
+        )),
+    }
+    issues
+}
+
+/// Loads the settings of `path` and reports every issue found with them,
+/// reaching each provider. Returns whether there was none.
+pub async fn validate(path: &Path) -> bool {
+    let issues = match load_config(Some(path)) {
+        Ok(config) => {
+            let mut issues = static_issues(&config);
+            for name in providers::names(&config) {
+                issues.extend(endpoint_issues(name, &config.identity_providers[name]).await);
+            }
+            issues
+        }
+        Err(err) => vec![format!("{} can't be loaded: {:#}", path.display(), err)],
+    };
+
+    if issues.is_empty() {
+        host_output::report(
+            "config_valid",
+            json!({ "path": path }),
+            format_args!("✅ {} is valid", path.display()),
+        );
+        return true;
+    }
+    for issue in &issues {
+        say!("❌ {}", issue);
+    }
+    let message = format!("{} issue(s) in {}", issues.len(), path.display());
+    say!("{}", message);
+    host_output::error("config", message, json!({ "issues": issues }));
+    false
+}
@@ -0,0 +1,122 @@
+//! W3C Verifiable Credentials vouching for settled verifications.
+//!
+//! Off-chain verifiers rarely follow the chain. Once a verification settles,
+//! the host can sign a credential stating that the account verified with the
+//! OIDC scheme at a given block height, referencing the blob transaction so
+//! the claim can still be checked on-chain. Credentials use the JWT encoding
+//! of the VC data model (VC-JWT), signed ES256 with a P-256 key as in
+//! `config-sign`: verifiers only need the issuer's public key.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+/// Scheme the accounts of this contract verify with.
+pub const SCHEME: &str = "oidc";
+
+const CREDENTIAL_TYPE: &str = "HyleIdentityVerification";
+
+/// What the credential attests: its `credentialSubject`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    /// DID of the verified account, see [`crate::did::did`]
+    pub id: String,
+    pub contract: String,
+    pub scheme: String,
+    /// Issuer of the ID token the verification was proved with
+    pub provider: String,
+    /// Blob transaction of the verification
+    pub tx_hash: String,
+    /// Block height at which the host saw the verification settled
+    pub block_height: u64,
+}
+
+/// A credential whose signature checked out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedCredential {
+    pub issuer: String,
+    /// Unix time of issuance
+    pub issued_at: u64,
+    pub verification: Verification,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    sub: String,
+    nbf: u64,
+    jti: String,
+    vc: Credential,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Credential {
+    #[serde(rename = "@context")]
+    context: Vec<String>,
+    #[serde(rename = "type")]
+    types: Vec<String>,
+    credential_subject: Verification,
+}
+
+/// Signs a credential for `verification` on behalf of `issuer` with a
+/// PEM-encoded P-256 private key, returning the VC-JWT.
+pub fn issue(issuer: &str, private_key: &Path, verification: Verification) -> Result<String> {
+    let pem = std::fs::read(private_key).context("Failed to read the credential key")?;
+    let key = EncodingKey::from_ec_pem(&pem).context("Credential key is not a P-256 PEM key")?;
+    let claims = Claims {
+        iss: issuer.to_string(),
+        sub: verification.id.clone(),
+        nbf: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        jti: format!("urn:hyle:tx:{}", verification.tx_hash),
+        vc: Credential {
+            context: vec!["https://www.w3.org/2018/credentials/v1".to_string()],
+            types: vec![
+                "VerifiableCredential".to_string(),
+                CREDENTIAL_TYPE.to_string(),
+            ],
+            credential_subject: verification,
+        },
+    };
+    Ok(jsonwebtoken::encode(
+        &Header::new(Algorithm::ES256),
+        &claims,
+        &key,
+    )?)
+}
+
+/// Checks `credential` against the issuer's PEM-encoded P-256 public key and
+/// returns what it attests.
+pub fn verify(credential: &str, public_key: &Path) -> Result<VerifiedCredential> {
+    let pem = std::fs::read(public_key).context("Failed to read the issuer key")?;
+    let key = DecodingKey::from_ec_pem(&pem).context("Issuer key is not a P-256 PEM key")?;
+
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    validation.validate_nbf = true;
+    validation.validate_aud = false;
+    let claims = jsonwebtoken::decode::<Claims>(credential.trim(), &key, &validation)
+        .context("Invalid credential signature")?
+        .claims;
+
+    if !claims.vc.types.iter().any(|t| t == CREDENTIAL_TYPE) {
+        bail!("Not a {} credential", CREDENTIAL_TYPE);
+    }
+    if claims.sub != claims.vc.credential_subject.id {
+        bail!(
+            "Credential subject {} differs from the JWT subject {}",
+            claims.vc.credential_subject.id,
+            claims.sub
+        );
+    }
+    Ok(VerifiedCredential {
+        issuer: claims.iss,
+        issued_at: claims.nbf,
+        verification: claims.vc.credential_subject,
+    })
+}
@@ -0,0 +1,95 @@
+//! W3C DID documents for registered accounts, under a `did:hyle` method whose
+//! identifier is the account's Hyle identity: `did:hyle:<subject>.<contract>`.
+//!
+//! OIDC accounts have no key of their own: they are bound to issuers'
+//! subjects, proved with ID tokens signed by the issuers' rotating keys. The
+//! document therefore has no verification method; it carries the bindings, the
+//! attestations issued to the account and the identities linked to it by an
+//! alias, so that external verifiers can consume them.
+
+use alias_registry::Alias;
+use attestation_registry::Attestation;
+use oidc_identity::AccountInfo;
+use serde_json::{json, Value};
+
+/// DID of `identity` (`<name>.<contract>`), escaping the characters DIDs don't
+/// allow in an identifier, such as the `@` of e-mail subjects.
+pub fn did(identity: &str) -> String {
+    let mut did = String::from("did:hyle:");
+    for byte in identity.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => did.push(byte as char),
+            _ => did.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    did
+}
+
+/// Attestations recorded for the account by one attestation registry.
+pub struct Attestations<'a> {
+    pub registry: &'a str,
+    pub attestations: &'a [Attestation],
+}
+
+/// The alias linking the account to identities on other contracts.
+pub struct Linked<'a> {
+    pub registry: &'a str,
+    pub handle: &'a str,
+    pub alias: &'a Alias,
+}
+
+pub fn document(
+    account: &str,
+    info: &AccountInfo,
+    attestations: Option<Attestations>,
+    linked: Option<Linked>,
+) -> Value {
+    let mut document = json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": did(account),
+        "hyleAccount": {
+            "accountHashes": info.hashes,
+            "nonce": info.nonce,
+            "sessionEpoch": info.session_epoch,
+            "frozen": info.frozen,
+            "lastActive": info.last_active,
+            "controlHistory": info.history,
+        },
+    });
+
+    if let Some(linked) = linked {
+        let also_known_as: Vec<String> = linked
+            .alias
+            .accounts
+            .values()
+            .filter(|identity| *identity != account)
+            .map(|identity| did(identity))
+            .collect();
+        document["alsoKnownAs"] = json!(also_known_as);
+        document["hyleAlias"] = json!({
+            "registry": linked.registry,
+            "handle": linked.handle,
+        });
+    }
+
+    if let Some(Attestations {
+        registry,
+        attestations,
+    }) = attestations
+    {
+        document["hyleAttestations"] = attestations
+            .iter()
+            .map(|attestation| {
+                json!({
+                    "registry": registry,
+                    "claim": attestation.claim,
+                    "identityContract": attestation.identity_contract,
+                    "txHash": attestation.tx_hash,
+                    "timestamp": attestation.timestamp,
+                })
+            })
+            .collect();
+    }
+
+    document
+}
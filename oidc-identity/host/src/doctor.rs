@@ -0,0 +1,256 @@
+//! `doctor`: checks a provider's configuration against what the contract
+//! accepts, before a user spends a full prove/submit cycle finding out.
+//!
+//! Each step prints one line; a failing step doesn't stop the later ones,
+//! unless they depend on it.
+
+use std::fmt::Display;
+
+use anyhow::{anyhow, Context, Result};
+use host_output::say;
+use jsonwebtoken::{decode_header, Algorithm};
+use oidc_provider::{JwkSet, OpenIdContext};
+use openidconnect::core::{CoreJwsSigningAlgorithm, CoreProviderMetadata};
+use openidconnect::IssuerUrl;
+use serde_json::json;
+use tokio::net::TcpListener;
+use url::Url;
+
+use crate::config::{AppConfig, IdentityProvider};
+use crate::login;
+use crate::oidc_client::{build_http_client, Flow, Jwk, OIDCClient};
+
+/// Tally of the steps run so far.
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn check<T>(
+        &mut self,
+        step: &str,
+        result: Result<T>,
+        detail: impl Fn(&T) -> String,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => {
+                let detail = detail(&value);
+                host_output::report(
+                    "doctor_step",
+                    json!({ "step": step, "passed": true, "detail": detail }),
+                    format_args!("✅ {}: {}", step, detail),
+                );
+                Some(value)
+            }
+            Err(err) => {
+                host_output::report(
+                    "doctor_step",
+                    json!({ "step": step, "passed": false, "detail": format!("{:#}", err) }),
+                    format_args!("❌ {}: {:#}", step, err),
+                );
+                self.failures += 1;
+                None
+            }
+        }
+    }
+
+    fn warn(&self, step: &str, message: impl Display) {
+        host_output::report(
+            "doctor_step",
+            json!({ "step": step, "warning": message.to_string() }),
+            format_args!("⚠️ {}: {}", step, message),
+        );
+    }
+}
+
+/// Runs every check for `provider_name`, logging in through the browser for
+/// the token step unless `skip_login`. Returns whether all checks passed.
+pub async fn run(
+    config: &AppConfig,
+    provider_name: &str,
+    identity_provider: &IdentityProvider,
+    strict_keys: bool,
+    flow: Flow,
+    skip_login: bool,
+) -> bool {
+    let mut report = Report::default();
+
+    // Discovery, and the algorithms the provider may sign ID tokens with:
+    // the contract verifies RS256, ES256 and EdDSA
+    let discovery = report.check(
+        "Discovery",
+        discover(&identity_provider.issuer_url).await,
+        |metadata| format!("issuer {}", metadata.issuer().as_str()),
+    );
+    if let Some(metadata) = &discovery {
+        let algs = metadata.id_token_signing_alg_values_supported();
+        report.check(
+            "Signing algorithms",
+            if algs.contains(&CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256)
+                || algs.contains(&CoreJwsSigningAlgorithm::EcdsaP256Sha256)
+                || algs.contains(&CoreJwsSigningAlgorithm::EdDsa)
+            {
+                Ok(algs)
+            } else {
+                Err(anyhow!(
+                    "none of RS256, ES256 or EdDSA offered, the contract can't verify {:?}",
+                    algs
+                ))
+            },
+            |algs| format!("RS256, ES256 or EdDSA offered among {:?}", algs),
+        );
+        let jwks_uri = metadata.jwks_uri().url().as_str();
+        if jwks_uri != identity_provider.jwk_public_key_url {
+            report.warn(
+                "JWKS URL",
+                format_args!(
+                    "configured {} but discovery advertises {}",
+                    identity_provider.jwk_public_key_url, jwks_uri
+                ),
+            );
+        }
+    }
+
+    // PAR and JARM are opt-in per provider: check the provider offers them
+    if identity_provider.par || identity_provider.jarm {
+        if let Some(document) = report.check(
+            "Discovery document",
+            OIDCClient::fetch_discovery(&identity_provider.issuer_url).await,
+            |_| "fetched".to_string(),
+        ) {
+            if identity_provider.par {
+                report.check(
+                    "PAR",
+                    document["pushed_authorization_request_endpoint"]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("no pushed_authorization_request_endpoint")),
+                    |endpoint| format!("requests pushed to {}", endpoint),
+                );
+            }
+            if identity_provider.jarm {
+                let modes = document["response_modes_supported"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let offered = modes
+                    .iter()
+                    .any(|mode| mode == "jwt" || mode == "query.jwt");
+                report.check(
+                    "JARM",
+                    if offered {
+                        Ok(modes)
+                    } else {
+                        Err(anyhow!("response mode jwt not offered among {:?}", modes))
+                    },
+                    |_| "signed responses offered".to_string(),
+                );
+            }
+        }
+    }
+
+    let keys = report.check(
+        "JWKS",
+        OIDCClient::fetch_jwks(&identity_provider.jwk_public_key_url)
+            .await
+            .map_err(|err| anyhow!(err))
+            .and_then(|keys| {
+                if keys.is_empty() {
+                    Err(anyhow!("no RSA key with a key id"))
+                } else {
+                    Ok(keys)
+                }
+            }),
+        |keys| format!("{} RSA key(s)", keys.len()),
+    );
+
+    let redirect_url = format!("{}/callback", config.server.server_url);
+    report.check(
+        "Redirect URI",
+        redirect_listener(&config.server.server_url).await,
+        |address| format!("{} can be served on {}", redirect_url, address),
+    );
+    say!(
+        "   Make sure {} is registered as a redirect URI at {}",
+        redirect_url, provider_name
+    );
+
+    if skip_login {
+        report.warn("ID token", "skipped, run without --skip-login to test one");
+    } else if discovery.is_some() && keys.is_some() {
+        let login = login(config, provider_name, identity_provider, strict_keys, flow).await;
+        let token = login.id_token.to_string();
+        report.check(
+            "ID token",
+            check_token(&token, &login.jwk, identity_provider),
+            |subject| format!("verified as the contract would, subject {}", subject),
+        );
+    }
+
+    if report.failures == 0 {
+        host_output::report(
+            "doctor",
+            json!({ "provider": provider_name }),
+            format_args!("✅ {} looks ready", provider_name),
+        );
+    } else {
+        let message = format!("{} check(s) failed for {}", report.failures, provider_name);
+        say!("❌ {}", message);
+        host_output::error(
+            "doctor",
+            message,
+            json!({ "provider": provider_name, "failures": report.failures }),
+        );
+    }
+    report.failures == 0
+}
+
+pub async fn discover(issuer_url: &str) -> Result<CoreProviderMetadata> {
+    let issuer = IssuerUrl::new(issuer_url.trim_end_matches('/').to_string())
+        .context("Invalid issuer URL")?;
+    CoreProviderMetadata::discover_async(issuer, &build_http_client())
+        .await
+        .context("Failed to fetch OpenID Provider metadata")
+}
+
+/// Binds the address the login callback is captured on, as `login` will.
+async fn redirect_listener(server_url: &str) -> Result<String> {
+    let url = Url::parse(server_url).context("Invalid server URL")?;
+    let address = format!(
+        "{}:{}",
+        url.host_str().context("Server URL has no host")?,
+        url.port_or_known_default()
+            .context("Server URL has no port")?
+    );
+    TcpListener::bind(&address)
+        .await
+        .with_context(|| format!("Can't listen on {}", address))?;
+    Ok(address)
+}
+
+/// Decodes a fresh ID token and verifies it with the contract's own code.
+fn check_token(token: &str, jwk: &Jwk, identity_provider: &IdentityProvider) -> Result<String> {
+    let header = decode_header(token).context("Invalid JWT header")?;
+    if !matches!(
+        header.alg,
+        Algorithm::RS256 | Algorithm::ES256 | Algorithm::EdDSA
+    ) {
+        return Err(anyhow!(
+            "token signed with {:?}, not RS256, ES256 or EdDSA",
+            header.alg
+        ));
+    }
+    let claims = oidc_identity::jwt::verify_jwt_signature(
+        token,
+        &JwkSet::from(jwk.public_key()),
+        &OpenIdContext {
+            issuer: identity_provider.token_issuer().to_string(),
+            audience: vec![identity_provider.audience_url.clone()],
+            policy: identity_provider.policy.clone(),
+            algorithms: identity_provider.algorithms.clone(),
+            issuer_match: identity_provider.issuer_match,
+        },
+    )
+    .map_err(|err| anyhow!(err))?;
+    Ok(claims.sub)
+}
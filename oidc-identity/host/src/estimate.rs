@@ -0,0 +1,145 @@
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use oidc_provider::{code, failure::Failure, work::Work};
+use risc0_zkvm::{default_executor, ExecutorEnv};
+use sdk::{ContractInput, HyleOutput};
+use serde_json::{json, Value};
+
+use crate::config::ProvingConfig;
+use crate::node::NodeTransport;
+
+/// Approximate size of a succinct risc0 receipt, independent of the cycle count.
+const SUCCINCT_RECEIPT_SIZE: usize = 224 * 1024;
+
+/// What proving and settling one contract input is expected to take.
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    /// Cycles executed by the guest
+    pub user_cycles: u64,
+    /// Cycles actually proven, with each segment padded to a power of two
+    pub total_cycles: u64,
+    pub segments: usize,
+    pub proving_time: Duration,
+    pub receipt_size: usize,
+    /// Proving cost, in `cost_per_mcycle` units
+    pub proving_cost: u64,
+    /// Settlement cost, when the node exposes one
+    pub settlement_cost: Option<u64>,
+    /// Work reported in the program output, when the action succeeds
+    pub work: Option<Work>,
+    /// Why the action fails, when it does
+    pub failure: Option<Failure>,
+}
+
+impl Estimate {
+    pub fn cost(&self) -> u64 {
+        self.proving_cost
+            .saturating_add(self.settlement_cost.unwrap_or(0))
+    }
+
+    /// The fields of `--output json`, sizes in bytes and times in seconds.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "user_cycles": self.user_cycles,
+            "total_cycles": self.total_cycles,
+            "segments": self.segments,
+            "proving_time_secs": self.proving_time.as_secs(),
+            "receipt_size": self.receipt_size,
+            "proving_cost": self.proving_cost,
+            "settlement_cost": self.settlement_cost,
+            "cost": self.cost(),
+            "work": self.work.map(|work| json!({
+                "signatures": work.signatures,
+                "hashes": work.hashes,
+                "state_bytes": work.state_bytes,
+            })),
+            "failure": self.failure.as_ref().map(|failure| json!({
+                "code": failure.code.code(),
+                "detail": failure.detail,
+            })),
+        })
+    }
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "cycles: {} ({} proven in {} segments)",
+            self.user_cycles, self.total_cycles, self.segments
+        )?;
+        writeln!(f, "proving time: ~{}s", self.proving_time.as_secs())?;
+        writeln!(f, "receipt size: ~{} KiB", self.receipt_size / 1024)?;
+        if let Some(failure) = &self.failure {
+            writeln!(f, "fails with {}: {}", failure.code.code(), failure.detail)?;
+        }
+        if let Some(work) = self.work {
+            writeln!(
+                f,
+                "work: {} signatures, {} hashes, {} state bytes",
+                work.signatures, work.hashes, work.state_bytes
+            )?;
+        }
+        match self.settlement_cost {
+            Some(cost) => writeln!(f, "settlement cost: {}", cost)?,
+            None => writeln!(f, "settlement cost: not exposed by the node")?,
+        }
+        write!(f, "total cost: {}", self.cost())
+    }
+}
+
+/// Executes the guest without proving to measure the cycles it takes, and
+/// derives the expected proving time, receipt size and cost from it.
+///
+/// The input's tx hash doesn't need to be the final one: it doesn't change the
+/// executed code path.
+pub async fn estimate(
+    node: &impl NodeTransport,
+    elf: &[u8],
+    input: &ContractInput,
+    pricing: &ProvingConfig,
+) -> Result<Estimate> {
+    let env = ExecutorEnv::builder().write(input)?.build()?;
+    let session = default_executor()
+        .execute(env, elf)
+        .context("Failed to execute the guest")?;
+
+    let user_cycles: u64 = session.segments.iter().map(|s| s.cycles as u64).sum();
+    let total_cycles: u64 = session.segments.iter().map(|s| 1u64 << s.po2).sum();
+    let proving_time =
+        Duration::from_secs(total_cycles.div_ceil(pricing.cycles_per_second.max(1)));
+    let proving_cost = total_cycles.div_ceil(1_000_000) * pricing.cost_per_mcycle;
+    let output: HyleOutput = session
+        .journal
+        .decode()
+        .context("Failed to decode the guest journal")?;
+    let program_output = String::from_utf8_lossy(&output.program_outputs);
+
+    Ok(Estimate {
+        user_cycles,
+        total_cycles,
+        segments: session.segments.len(),
+        proving_time,
+        receipt_size: SUCCINCT_RECEIPT_SIZE,
+        proving_cost,
+        settlement_cost: node.settlement_cost(SUCCINCT_RECEIPT_SIZE).await?,
+        work: code::parse_work(&program_output),
+        failure: Failure::parse(&program_output),
+    })
+}
+
+/// Fails when the estimate exceeds `max_cost`, before anything is submitted.
+pub fn check_budget(estimate: &Estimate, max_cost: Option<u64>) -> Result<()> {
+    if let Some(max_cost) = max_cost {
+        if estimate.cost() > max_cost {
+            bail!(
+                "Estimated cost {} exceeds the budget of {}",
+                estimate.cost(),
+                max_cost
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use alias_registry::AliasRegistry;
+use anyhow::{bail, Result};
+use attestation_registry::AttestationRegistry;
+use oidc_identity::{AccountInfo, OidcIdentity};
+use sdk::StateDigest;
+use serde_json::{json, Value};
+
+/// A contract state decoded without knowing beforehand which contract it belongs to.
+pub enum DecodedState {
+    OidcIdentity(OidcIdentity),
+    AttestationRegistry(AttestationRegistry),
+    AliasRegistry(AliasRegistry),
+}
+
+/// Tries every schema this host knows. States carry no type tag, so a schema
+/// matches when it decodes the whole digest without leftover bytes.
+pub fn decode_state(digest: &StateDigest) -> Result<DecodedState> {
+    if let Ok(state) = OidcIdentity::from_bytes(&digest.0) {
+        return Ok(DecodedState::OidcIdentity(state));
+    }
+    if let Ok(state) = AttestationRegistry::try_from(digest.clone()) {
+        return Ok(DecodedState::AttestationRegistry(state));
+    }
+    if let Ok(state) = AliasRegistry::try_from(digest.clone()) {
+        return Ok(DecodedState::AliasRegistry(state));
+    }
+    bail!("Unknown state format ({} bytes)", digest.0.len())
+}
+
+pub fn schema(state: &DecodedState) -> &'static str {
+    match state {
+        DecodedState::OidcIdentity(_) => "oidc identity",
+        DecodedState::AttestationRegistry(_) => "attestation registry",
+        DecodedState::AliasRegistry(_) => "alias registry",
+    }
+}
+
+pub fn to_json(state: &DecodedState) -> Result<Value> {
+    Ok(match state {
+        DecodedState::OidcIdentity(state) => serde_json::to_value(state)?,
+        DecodedState::AttestationRegistry(state) => serde_json::to_value(state)?,
+        DecodedState::AliasRegistry(state) => serde_json::to_value(state)?,
+    })
+}
+
+pub fn render(state: &DecodedState) -> Result<String> {
+    let json = match state {
+        DecodedState::OidcIdentity(state) => serde_json::to_string_pretty(state)?,
+        DecodedState::AttestationRegistry(state) => serde_json::to_string_pretty(state)?,
+        DecodedState::AliasRegistry(state) => serde_json::to_string_pretty(state)?,
+    };
+    Ok(format!("Schema: {}\n{json}", schema(state)))
+}
+
+/// What `get-state` shows of an account: its nonce and the hashes it holds.
+pub fn account_json(account: &str, info: &AccountInfo) -> Value {
+    json!({
+        "account": account,
+        "nonce": info.nonce,
+        "hashes": info.hashes,
+        "frozen": info.frozen,
+        "history": { "head": info.history.head, "events": info.history.events },
+        "recovery_codes": info.recovery_codes.len(),
+        "session_keys": info.session_keys.len(),
+        "provider": info.metadata.provider,
+    })
+}
+
+pub fn render_account(account: &str, info: &AccountInfo) -> String {
+    let mut text = format!("{}{}", account, if info.frozen { " (frozen)" } else { "" });
+    text += &format!("\n  nonce:          {}", info.nonce);
+    for hash in &info.hashes {
+        text += &format!("\n  subject hash:   {}", hash);
+    }
+    let head = if info.history.head.is_empty() {
+        "-"
+    } else {
+        &info.history.head
+    };
+    text += &format!("\n  history:        {} ({} events)", head, info.history.events);
+    text += &format!("\n  recovery codes: {} unused", info.recovery_codes.len());
+    text += &format!("\n  session keys:   {}", info.session_keys.len());
+    if let Some(provider) = &info.metadata.provider {
+        text += &format!("\n  provider:       {}", provider);
+    }
+    text
+}
@@ -0,0 +1,133 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use host_output::say;
+use serde::{Deserialize, Serialize};
+
+use crate::oidc_client::Jwk;
+
+/// Key material pinned for one issuer, by key id.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+struct PinnedKeys {
+    keys: BTreeMap<String, (String, String)>,
+}
+
+impl PinnedKeys {
+    fn from_jwks(keys: &HashMap<String, Jwk>) -> Self {
+        PinnedKeys {
+            keys: keys
+                .iter()
+                .map(|(kid, jwk)| (kid.clone(), jwk.material()))
+                .collect(),
+        }
+    }
+}
+
+/// Trust-on-first-use pins of every issuer's JWKS, persisted between runs.
+#[derive(Serialize, Deserialize, Default)]
+pub struct PinStore {
+    issuers: BTreeMap<String, PinnedKeys>,
+}
+
+/// How a freshly fetched key set relates to the pinned one.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinCheck {
+    FirstSeen,
+    Unchanged,
+    /// Keys were added or retired while at least one pinned key stayed as is,
+    /// which is how providers rotate.
+    Rotated {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// A pinned key id now maps to different material, or no pinned key
+    /// survived: the endpoint may be compromised or intercepted.
+    Unexpected { reason: String },
+}
+
+impl PinStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(PinStore::default());
+        }
+        let data = std::fs::read(path).context("Failed to read JWKS pins")?;
+        serde_json::from_slice(&data).context("Invalid JWKS pins file")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?).context("Failed to write JWKS pins")
+    }
+
+    pub fn check(&self, issuer: &str, keys: &HashMap<String, Jwk>) -> PinCheck {
+        let Some(pinned) = self.issuers.get(issuer) else {
+            return PinCheck::FirstSeen;
+        };
+        let fetched = PinnedKeys::from_jwks(keys);
+        if *pinned == fetched {
+            return PinCheck::Unchanged;
+        }
+
+        if let Some(kid) = pinned
+            .keys
+            .iter()
+            .find(|(kid, material)| fetched.keys.get(*kid).is_some_and(|m| m != *material))
+            .map(|(kid, _)| kid)
+        {
+            return PinCheck::Unexpected {
+                reason: format!("key `{}` changed its material", kid),
+            };
+        }
+
+        let pinned_ids: BTreeSet<_> = pinned.keys.keys().collect();
+        let fetched_ids: BTreeSet<_> = fetched.keys.keys().collect();
+        if pinned_ids.is_disjoint(&fetched_ids) {
+            return PinCheck::Unexpected {
+                reason: "every pinned key was replaced at once".to_string(),
+            };
+        }
+
+        PinCheck::Rotated {
+            added: fetched_ids
+                .difference(&pinned_ids)
+                .map(|kid| kid.to_string())
+                .collect(),
+            removed: pinned_ids
+                .difference(&fetched_ids)
+                .map(|kid| kid.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn pin(&mut self, issuer: &str, keys: &HashMap<String, Jwk>) {
+        self.issuers
+            .insert(issuer.to_string(), PinnedKeys::from_jwks(keys));
+    }
+}
+
+/// Checks the fetched JWKS against the pins at `path` and updates them.
+///
+/// Unexpected changes are reported and, with `strict`, refused; otherwise the
+/// new set is pinned so the warning fires only once.
+pub fn verify(path: &Path, issuer: &str, keys: &HashMap<String, Jwk>, strict: bool) -> Result<()> {
+    let mut store = PinStore::load(path)?;
+    match store.check(issuer, keys) {
+        PinCheck::Unchanged => return Ok(()),
+        PinCheck::FirstSeen => say!("🔑 Pinning {} key(s) for {}", keys.len(), issuer),
+        PinCheck::Rotated { added, removed } => say!(
+            "🔑 {} rotated its keys (added {:?}, retired {:?})",
+            issuer, added, removed
+        ),
+        PinCheck::Unexpected { reason } => {
+            if strict {
+                bail!("JWKS of {} changed unexpectedly: {}", issuer, reason);
+            }
+            say!(
+                "⚠️ JWKS of {} changed unexpectedly: {}. Re-pinning; use --strict-keys to refuse instead",
+                issuer, reason
+            );
+        }
+    }
+    store.pin(issuer, keys);
+    store.save(path)
+}
@@ -0,0 +1,122 @@
+//! Cross-checks what the node reports before the host trusts it.
+//!
+//! Nonces are derived from the contract state the node returns, so a lying
+//! endpoint can make the host sign for a stale nonce or prove against a forged
+//! state. Verifying the state against block headers and settlement proofs
+//! would be the real fix, but the node API exposes neither: the best a host
+//! can do is ask independent nodes, the `server.witnesses`, and only trust a
+//! state digest or program id they all agree on.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use sdk::api::{APIRegisterContract, TransactionStatus};
+use sdk::{
+    BlobTransaction, ContractName, ProgramId, ProofTransaction, StateDigest, TxContext, TxHash,
+};
+
+use crate::node::{Node, NodeTransport};
+
+/// Attempts before disagreeing witnesses are reported: nodes a block apart
+/// briefly disagree while a transaction settles.
+const ATTEMPTS: u32 = 3;
+
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// A node whose contract reads are confirmed by witness nodes. Writes only go
+/// to the node itself.
+pub struct LightClient {
+    node: Node,
+    witnesses: Vec<Node>,
+}
+
+impl LightClient {
+    /// `witnesses` are node URLs, as accepted by [`Node::from_url`].
+    pub fn new(node: Node, witnesses: &[String]) -> Result<Self> {
+        let witnesses = witnesses
+            .iter()
+            .map(|url| Node::from_url(url))
+            .collect::<Result<_>>()?;
+        Ok(LightClient { node, witnesses })
+    }
+
+    /// Runs `read` against the node then every witness, until they all agree.
+    async fn confirmed<'a, T, F, Fut>(&'a self, what: &str, read: F) -> Result<T>
+    where
+        T: PartialEq,
+        F: Fn(&'a Node) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let reported = read(&self.node).await?;
+            let mut disagreeing = None;
+            for (index, witness) in self.witnesses.iter().enumerate() {
+                if read(witness).await? != reported {
+                    disagreeing = Some(index);
+                    break;
+                }
+            }
+            match disagreeing {
+                None => return Ok(reported),
+                Some(_) if attempt < ATTEMPTS => {
+                    attempt += 1;
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Some(index) => bail!(
+                    "Witness #{} disagrees with the node on the {}, refusing to trust it",
+                    index,
+                    what
+                ),
+            }
+        }
+    }
+}
+
+impl NodeTransport for LightClient {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest> {
+        self.confirmed(&format!("state of {}", contract_name), |node| {
+            node.get_contract_state(contract_name)
+        })
+        .await
+    }
+
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId> {
+        self.confirmed(&format!("program id of {}", contract_name), |node| {
+            node.get_program_id(contract_name)
+        })
+        .await
+    }
+
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash> {
+        self.node.register_contract(tx).await
+    }
+
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        self.node.send_tx_blob(tx).await
+    }
+
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash> {
+        self.node.send_tx_proof(tx).await
+    }
+
+    /// Witnesses may be a few blocks apart, so only the node is asked.
+    async fn block_height(&self) -> Result<u64> {
+        self.node.block_height().await
+    }
+
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        self.node.tx_status(tx_hash).await
+    }
+
+    /// A proof committing to another context than the block's doesn't
+    /// settle, so only the node is asked.
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>> {
+        self.node.tx_context(tx_hash).await
+    }
+
+    async fn settlement_cost(&self, proof_size: usize) -> Result<Option<u64>> {
+        self.node.settlement_cost(proof_size).await
+    }
+}
@@ -1,25 +1,80 @@
+use alias_registry::{AliasAction, AliasRegistry, IdentityScheme};
+use anyhow::Context;
+use app_dirs::AppDirs;
+use attestation_registry::{AttestationAction, AttestationRegistry};
 use clap::{Parser, Subcommand};
 use client_sdk::helpers::risc0::Risc0Prover;
 use dotenv::dotenv;
+use host_output::{say, OrFail, Output};
 use oidc_identity::OidcIdentity;
+use oidc_provider::account::{derive_account, email_hash, AccountScheme};
+use oidc_provider::code::ResultCode;
+use oidc_provider::disclosure::{Claim, Disclosure, DisclosurePolicy};
+use oidc_provider::hasher::{AccountHasher, DefaultHasher};
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::session::MAX_SESSION_KEY_LIFETIME;
+use oidc_provider::AdminOperation;
 use oidc_provider::IdentityAction;
-use oidc_provider::JwkPublicKey;
+use oidc_provider::IdentityVerification;
+use oidc_provider::JwkSet;
 use oidc_provider::OpenIdContext;
+use oidc_provider::PrivateInput;
+use openidconnect::core::{CoreIdToken, CoreIdTokenClaims};
+use openidconnect::{AccessToken, RefreshToken};
+use rand::rngs::OsRng;
+use rand::RngCore;
 use sdk::api::APIRegisterContract;
 use sdk::BlobTransaction;
 use sdk::ProofTransaction;
 use sdk::{ContractInput, Digestable};
+use serde_json::{json, Value};
 
+mod aggregate;
+mod archive_journal;
 mod config;
+mod config_signature;
+mod config_validate;
+mod credential;
+mod did;
+mod doctor;
+mod estimate;
+mod inspect;
+mod jwks_pin;
+mod lifecycle;
+mod light_client;
+mod node;
 mod oidc_client;
-use std::path::Path;
+mod prover;
+mod providers;
+mod recovery;
+mod selfcheck;
+mod serve;
+mod session_key;
+mod session_store;
+mod template;
+mod token_store;
+mod watch;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use config::load_config;
-use oidc_client::OIDCClient;
+use archive_journal::ArchiveJournal;
+use config::{load_config, AppConfig, IdentityProvider};
+use config_validate::ConfigCommand;
+use lifecycle::ContractCommand;
+use light_client::LightClient;
+use node::{Node, NodeTransport};
+use oidc_client::{AuthClient, Flow, Jwk, OIDCClient};
+use prover::{Prover, ProverChoice, Zkvm};
+use providers::ProvidersCommand;
+use session_store::SessionStore;
+use template::Template;
 
 // These constants represent the RISC-V ELF and the image ID generated by risc0-build.
 // The ELF is used for proving and the ID is used for verification.
-use methods_identity::{GUEST_ELF, GUEST_ID};
+use methods_identity::{
+    ALIAS_GUEST_ELF, ALIAS_GUEST_ID, ATTESTATION_GUEST_ELF, ATTESTATION_GUEST_ID, GUEST_ELF,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -30,13 +85,978 @@ struct Cli {
 
     #[arg(long, default_value = "google")]
     pub provider: String,
+
+    /// Print the proving estimate and exit without submitting anything
+    #[arg(long)]
+    pub estimate: bool,
+
+    /// Abort before submitting when the estimated cost exceeds this budget
+    #[arg(long)]
+    pub max_cost: Option<u64>,
+
+    /// Refuse to log in when an issuer's key set changed outside a normal rotation
+    #[arg(long)]
+    pub strict_keys: bool,
+
+    /// Before submitting proofs, check that the local guest and the on-chain
+    /// contract share the expected program id
+    #[arg(long, short)]
+    pub reproducible: bool,
+
+    /// Hex program id to pin against instead of the local guest's
+    #[arg(long, requires = "reproducible")]
+    pub expected_program_id: Option<String>,
+
+    /// PEM public key that must have signed the node URL, contract name and
+    /// program id (see `config-sign`)
+    #[arg(long)]
+    pub config_key: Option<PathBuf>,
+
+    /// Act on this account (`<name>.<contract>`) instead of the one named
+    /// after the `--provider` login, e.g. one the provider is linked to
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Salt the account was registered with (see `register-identity --salted`)
+    #[arg(long)]
+    pub salt: Option<String>,
+
+    /// Where identity proofs are generated: `local`, or `remote=<url>` to
+    /// delegate them to a proving service (default: `prover` of the
+    /// `[proving]` section of config.toml, else `local`)
+    #[arg(long)]
+    pub prover: Option<ProverChoice>,
+
+    /// zkVM the contract is registered with and identity proofs are generated
+    /// in; `sp1` needs a host built with `--features sp1` (default: `risc0`)
+    #[arg(long, value_enum)]
+    pub zkvm: Option<Zkvm>,
+
+    /// Keep host files under this directory instead of the platform's (also
+    /// set by `HYLE_IDENTITY_HOME`)
+    #[arg(long)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Run despite unsafe settings found by the startup self-check, such as
+    /// secrets written in config.toml
+    #[arg(long)]
+    pub allow_unsafe: bool,
+
+    /// Register the contract first if the node doesn't know it yet
+    #[arg(long)]
+    pub auto_register: bool,
+
+    /// How to log in: `browser` through the `server_url` redirect, or
+    /// `device` to approve a code from any other device (default: `browser`)
+    #[arg(long, value_enum)]
+    pub flow: Option<Flow>,
+
+    /// `json` to print results and errors as JSON objects, one per line, and
+    /// everything else on stderr (default: `text`)
+    #[arg(long, value_enum)]
+    pub output: Option<Output>,
+}
+
+/// Options left unset, on the command line and in the template run, take
+/// their default.
+impl Cli {
+    fn zkvm(&self) -> Zkvm {
+        self.zkvm.unwrap_or_default()
+    }
+
+    fn flow(&self) -> Flow {
+        self.flow.unwrap_or_default()
+    }
+}
+
+const CONFIG_PATH: &str = "./host/config.toml";
+
+/// Where earlier versions pinned first-seen JWKS, next to `config.toml`.
+const LEGACY_JWKS_PINS_PATH: &str = "./host/jwks_pins.json";
+
+/// Where first-seen JWKS are pinned.
+fn jwks_pins_path() -> PathBuf {
+    app_dirs::get()
+        .state()
+        .expect("Failed to create the state directory")
+        .join("jwks_pins.json")
+}
+
+/// Estimation settings applied before any transaction is sent.
+struct Budget<'a> {
+    pricing: &'a config::ProvingConfig,
+    max_cost: Option<u64>,
+    estimate_only: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    RegisterContract {},
-    RegisterIdentity {},
-    VerifyIdentity { nonce: u32 },
+    /// Register and inspect the OIDC identity contract
+    Contract {
+        #[command(subcommand)]
+        command: ContractCommand,
+    },
+    /// Register the attestation registry, trusting this OIDC contract
+    RegisterAttestationContract {},
+    /// Register the alias registry, trusting this OIDC contract and the `--trust` ones
+    RegisterAliasContract {
+        /// ECDSA identity contracts whose accounts may be linked, e.g. ecdsa_identity
+        #[arg(long)]
+        trust: Vec<String>,
+    },
+    /// Verify the identity and update its handle in the alias registry in the same transaction
+    Alias {
+        nonce: u32,
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+    RegisterIdentity {
+        /// Also commit this many one-time recovery codes, printed once
+        #[arg(long, default_value_t = 0)]
+        recovery_codes: usize,
+        /// Hash the subject with a random salt, printed once, instead of
+        /// storing its guessable `sub:iss` hash. Every later command for the
+        /// account then needs `--salt`
+        #[arg(long)]
+        salted: bool,
+        /// Name for wallets to show; only its hash is stored, as the
+        /// account's display hash
+        #[arg(long)]
+        display_name: Option<String>,
+        /// Accept any unused nonce of this many from the next, so that
+        /// verifications can settle in any order
+        #[arg(long, conflicts_with = "timestamp_nonces")]
+        nonce_window: Option<u32>,
+        /// Accept any nonce above the last used, such as the current time
+        #[arg(long)]
+        timestamp_nonces: bool,
+    },
+    VerifyIdentity {
+        nonce: u32,
+        /// Record this claim in the attestation registry in the same transaction
+        #[arg(long)]
+        attest: Option<String>,
+        /// Once the verification settles, write a verifiable credential for it
+        /// to this file, signed with the `[credentials]` key
+        #[arg(long)]
+        credential: Option<PathBuf>,
+    },
+    /// Invalidate all sessions of the account and revoke the provider token
+    Logout {},
+    /// Delete the account from the contract
+    UnregisterIdentity {},
+    /// Keep the accounts of the `[watch]` providers verified, re-proving on a
+    /// schedule or on demand through a local API
+    Watch {
+        /// Address of the local API
+        #[arg(long, default_value = "127.0.0.1:8700")]
+        listen: String,
+    },
+    /// Serve registration and verification over a REST API, logging users in
+    /// through the `server_url` redirect
+    Serve {
+        /// Address of the REST API
+        #[arg(long, default_value = "127.0.0.1:3000")]
+        listen: String,
+        /// Reload the identity providers and `server_url` of config.toml when
+        /// it changes, without a restart
+        #[arg(long)]
+        watch_config: bool,
+    },
+    /// Reserve the next `count` nonces so verifications can be proved in
+    /// parallel and settled in any order
+    ReserveNonces {
+        count: u32,
+    },
+    /// Publish one claim of the ID token (email, subject or issuer), hashed
+    /// unless `--plain` is given, as far as the contract's policy allows
+    DiscloseClaim {
+        claim: String,
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Publish custom claims of the ID token, such as `groups` or `org_id`,
+    /// for downstream contracts to consume
+    ExtractClaims {
+        #[arg(required = true)]
+        claims: Vec<String>,
+    },
+    /// Rebind an account to the subject returned by `--provider`, logging in
+    /// first with the provider the account is currently bound to
+    MigrateSubject {
+        account: String,
+        #[arg(long)]
+        from_provider: String,
+    },
+    /// Also bind an account to the subject returned by `--provider`, logging
+    /// in first with a provider the account is already bound to
+    LinkProvider {
+        account: String,
+        #[arg(long)]
+        from_provider: String,
+    },
+    /// Unbind the subject hashing to `hash` from an account, logging in with
+    /// `--provider`, another of its subjects
+    UnlinkProvider { account: String, hash: String },
+    /// Rebind an account to the subject returned by `--provider` with one of
+    /// its recovery codes, read from stdin
+    RecoverWithCode { account: String },
+    /// Let the subject returned by `--provider` take an account over, `delay`
+    /// blocks after asking to with `recover-identity`, logging in first with a
+    /// provider the account is bound to
+    AddRecoveryProvider {
+        account: String,
+        #[arg(long)]
+        from_provider: String,
+        #[arg(long)]
+        delay: u64,
+    },
+    /// Take an account over as its recovery provider, the subject returned by
+    /// `--provider`: a first run starts the recovery, one once its delay has
+    /// passed completes it
+    RecoverIdentity { account: String },
+    /// Verify the account at `nonce` with an ID token and authorize a new
+    /// session key, printed once, for `--blocks`
+    AuthorizeSessionKey {
+        nonce: u32,
+        #[arg(long, default_value_t = MAX_SESSION_KEY_LIFETIME)]
+        blocks: u64,
+    },
+    /// Verify `--account` at `nonce` with one of its session keys, read from
+    /// stdin, instead of an ID token
+    VerifyWithSessionKey { nonce: u32 },
+    /// Archive accounts inactive for `contract.max_inactivity` blocks,
+    /// keeping their records in the archive journal for `reactivate`
+    Prune {
+        #[arg(required = true)]
+        accounts: Vec<String>,
+    },
+    /// Restore an archived account, proving its record with the archive journal
+    Reactivate { account: String },
+    /// Prove several already-sent actions in one aggregated proof transaction.
+    /// Each file holds a JSON-encoded `ContractInput`.
+    ProveBatch { inputs: Vec<PathBuf> },
+    /// Render a registered account as a W3C DID document (`did:hyle:...`),
+    /// with its attestations and linked identities when those registries exist
+    Did { account: String },
+    /// Check a credential written by `verify-identity --credential` against
+    /// the issuer's PEM P-256 public key
+    VerifyCredential {
+        credential: PathBuf,
+        #[arg(long)]
+        key: PathBuf,
+    },
+    /// Fetch a contract's state and pretty-print it, detecting its schema
+    StateShow {
+        /// Defaults to the configured OIDC contract
+        contract: Option<String>,
+    },
+    /// Decode the OIDC contract's state and print each account's nonce and
+    /// hashes
+    GetState {
+        /// Only print this account
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// List the registered accounts in name order, a page at a time
+    Accounts {
+        /// Start after this account, the last one of the previous page
+        #[arg(long)]
+        after: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Find the account registered with an email, if the contract indexes emails
+    AccountByEmail { email: String },
+    /// Sign the node URL, contract name and local program id with a PEM
+    /// P-256 private key, for hosts run with `--config-key`
+    ConfigSign { key: PathBuf },
+    /// Check config.toml and the endpoints of its providers
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Check a provider's discovery document, keys and redirect URI against
+    /// what the contract accepts, then test a fresh ID token
+    Doctor {
+        /// Defaults to `--provider`
+        provider: Option<String>,
+        /// Don't log in, skipping the ID token check
+        #[arg(long)]
+        skip_login: bool,
+    },
+    /// Run the flow described by a YAML, JSON or TOML transaction template
+    Run { template: PathBuf },
+    /// Inspect the identity providers `--provider` picks from
+    Providers {
+        #[command(subcommand)]
+        command: ProvidersCommand,
+    },
+    /// Prove and send the proofs of blob transactions sent by runs that
+    /// stopped before their proof, and drop expired logins
+    Resume {
+        /// Forget the proofs that fail again, e.g. once their blob tx timed out
+        #[arg(long)]
+        drop_failed: bool,
+    },
+}
+
+impl Commands {
+    fn submits_proofs(&self) -> bool {
+        matches!(
+            self,
+            Commands::RegisterIdentity { .. }
+                | Commands::VerifyIdentity { .. }
+                | Commands::Logout { .. }
+                | Commands::UnregisterIdentity { .. }
+                | Commands::ReserveNonces { .. }
+                | Commands::Watch { .. }
+                | Commands::Serve { .. }
+                | Commands::DiscloseClaim { .. }
+                | Commands::ExtractClaims { .. }
+                | Commands::MigrateSubject { .. }
+                | Commands::LinkProvider { .. }
+                | Commands::UnlinkProvider { .. }
+                | Commands::RecoverWithCode { .. }
+                | Commands::AddRecoveryProvider { .. }
+                | Commands::RecoverIdentity { .. }
+                | Commands::Prune { .. }
+                | Commands::Reactivate { .. }
+                | Commands::AuthorizeSessionKey { .. }
+                | Commands::VerifyWithSessionKey { .. }
+                | Commands::Alias { .. }
+                | Commands::ProveBatch { .. }
+                | Commands::Resume { .. }
+                | Commands::Contract {
+                    command: ContractCommand::Pause { .. }
+                        | ContractCommand::Unpause { .. }
+                        | ContractCommand::AllowIssuer { .. }
+                }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+enum AliasCommand {
+    /// Link the account to a handle, creating it if unclaimed
+    Link { handle: String },
+    /// Let another account (e.g. `<pubkey>.ecdsa_identity`) link itself to the handle
+    Invite { handle: String, account: String },
+    /// Remove the account from the handle
+    Unlink { handle: String },
+}
+
+/// Result of a completed interactive OIDC login.
+struct Login {
+    id_token: CoreIdToken,
+    access_token: AccessToken,
+    claims: CoreIdTokenClaims,
+    jwk: Jwk,
+    /// Only requested by `watch` and providers with `remember_login`, and
+    /// only if the provider issues one
+    refresh_token: Option<RefreshToken>,
+}
+
+/// Runs the authorization code flow in the user's browser and checks the returned tokens.
+async fn login(
+    config: &AppConfig,
+    provider_name: &str,
+    identity_provider: &IdentityProvider,
+    strict_keys: bool,
+    flow: Flow,
+) -> Login {
+    authorize(
+        config,
+        provider_name,
+        identity_provider,
+        strict_keys,
+        flow,
+        false,
+        None,
+    )
+    .await
+}
+
+/// The account acted on: `--account` if given, else the one named after the
+/// login's subject, or after its hash with `salt`.
+fn acting_account(
+    account: Option<&str>,
+    identity_provider: &IdentityProvider,
+    login: &Login,
+    contract_name: &str,
+    salt: Option<&str>,
+) -> String {
+    match account {
+        Some(account) => account.to_string(),
+        None => {
+            derive_account(AccountScheme::Oidc {
+                issuer: &identity_provider.issuer_url,
+                subject: login.claims.subject(),
+                salt,
+                contract_name,
+            })
+            .account
+        }
+    }
+}
+
+/// `login` bound to the nonce the account acted on is at, as actions proving
+/// control of it with an ID token check. Without `--account`, the account is
+/// named after the subject, which takes a first login to learn.
+async fn login_for_account(
+    config: &AppConfig,
+    cli: &Cli,
+    identity_provider: &IdentityProvider,
+    client: &impl NodeTransport,
+    contract_name: &str,
+) -> (Login, String) {
+    let account = match &cli.account {
+        Some(account) => account.clone(),
+        None => {
+            let login = login(
+                config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
+            acting_account(
+                None,
+                identity_provider,
+                &login,
+                contract_name,
+                cli.salt.as_deref(),
+            )
+        }
+    };
+    let nonce = account_nonce(client, contract_name, &account).await;
+    let login = login_for_nonce(
+        config,
+        &cli.provider,
+        identity_provider,
+        cli.strict_keys,
+        cli.flow(),
+        nonce,
+    )
+    .await;
+    (login, account)
+}
+
+/// The nonce `account` is at. An unknown account is refused by the contract
+/// later, with its result code.
+async fn account_nonce(client: &impl NodeTransport, contract_name: &str, account: &str) -> u32 {
+    let state: OidcIdentity = client
+        .get_contract_state(&contract_name.into())
+        .await
+        .or_fail("node")
+        .into();
+    state.get_nonce(account).unwrap_or(0)
+}
+
+/// `login` for a `VerifyIdentity` at `nonce`, with an ID token only valid for it.
+async fn login_for_nonce(
+    config: &AppConfig,
+    provider_name: &str,
+    identity_provider: &IdentityProvider,
+    strict_keys: bool,
+    flow: Flow,
+    nonce: u32,
+) -> Login {
+    authorize(
+        config,
+        provider_name,
+        identity_provider,
+        strict_keys,
+        flow,
+        false,
+        Some(nonce),
+    )
+    .await
+}
+
+/// `login`, optionally asking for a refresh token, or binding the ID token to
+/// an account nonce. The device flow can't bind one: its tokens carry no nonce.
+async fn authorize(
+    config: &AppConfig,
+    provider_name: &str,
+    identity_provider: &IdentityProvider,
+    strict_keys: bool,
+    flow: Flow,
+    offline: bool,
+    bind_nonce: Option<u32>,
+) -> Login {
+    let client_secret = &identity_provider.get_client_secret(provider_name);
+    let oidc_client = OIDCClient::build(
+        identity_provider.issuer_url.to_string(),
+        identity_provider.audience_url.to_string(),
+        Some(client_secret.to_string()),
+        &format!("{}/callback", config.server.server_url),
+    )
+    .await
+    .expect("Failed to build provider");
+
+    // Saved logins need a refresh token to be saved in the first place
+    let password = identity_provider.remember_login.then(|| {
+        token_store::password(provider_name).expect("Failed to read the saved login password")
+    });
+    let offline = offline || password.is_some();
+    let remembered = match &password {
+        Some(password) => remembered_tokens(provider_name, &oidc_client, password).await,
+        None => None,
+    };
+    let fresh = remembered.is_none();
+
+    let (id_token, access_token, refresh_token, claims) = match (remembered, flow) {
+        (Some(tokens), _) => {
+            if bind_nonce.is_some() {
+                eprintln!("⚠️  Refreshed ID tokens can't be bound to the account nonce");
+            }
+            tokens
+        }
+        (None, Flow::Browser) => {
+            browser_login(
+                config,
+                identity_provider,
+                client_secret,
+                &oidc_client,
+                offline,
+                bind_nonce,
+            )
+            .await
+        }
+        (None, Flow::Device) => {
+            if bind_nonce.is_some() {
+                eprintln!("⚠️  Device flow ID tokens can't be bound to the account nonce");
+            }
+            let (id_token, access_token, refresh_token) =
+                OIDCClient::device_flow(&oidc_client, &identity_provider.issuer_url, offline)
+                    .await
+                    .expect("Device authorization failed");
+            let claims = OIDCClient::verify_refreshed_id_token(&oidc_client, &id_token)
+                .expect("Failed to verify id token");
+            (id_token, access_token, refresh_token, claims)
+        }
+    };
+
+    // Refreshed ID tokens needn't carry the access token's hash
+    if fresh {
+        let _ = OIDCClient::verify_access_token(&oidc_client, &id_token, &access_token, &claims)
+            .expect("Failed to verify access token");
+    }
+
+    let keys = OIDCClient::fetch_jwks(&identity_provider.jwk_public_key_url)
+        .await
+        .expect("Failed to fetch JWKS");
+    jwks_pin::verify(
+        &jwks_pins_path(),
+        &identity_provider.issuer_url,
+        &keys,
+        strict_keys,
+    )
+    .expect("JWKS pinning check failed");
+
+    let jwk = OIDCClient::find_jwk(&keys, &id_token.to_string()).expect("Failed to match jwks");
+
+    if let Some(password) = &password {
+        match &refresh_token {
+            Some(refresh_token) => token_store::save(provider_name, refresh_token, password)
+                .expect("Failed to save the login"),
+            None => eprintln!(
+                "⚠️  {} issued no refresh token, the login can't be saved",
+                provider_name
+            ),
+        }
+    }
+
+    Login {
+        id_token,
+        access_token,
+        claims,
+        jwk,
+        refresh_token,
+    }
+}
+
+/// Tokens renewed with the refresh token saved for `provider_name`, with the
+/// rotated refresh token, or the saved one if the provider didn't rotate it.
+/// `None` when there is none or the provider refuses it, for a new login.
+async fn remembered_tokens(
+    provider_name: &str,
+    oidc_client: &AuthClient,
+    password: &str,
+) -> Option<(
+    CoreIdToken,
+    AccessToken,
+    Option<RefreshToken>,
+    CoreIdTokenClaims,
+)> {
+    let refresh_token =
+        token_store::load(provider_name, password).expect("Failed to load the saved login")?;
+    let refreshed = async move {
+        let (id_token, access_token, rotated) =
+            OIDCClient::refresh_id_token(oidc_client, &refresh_token).await?;
+        let claims = OIDCClient::verify_refreshed_id_token(oidc_client, &id_token)?;
+        anyhow::Ok((
+            id_token,
+            access_token,
+            Some(rotated.unwrap_or(refresh_token)),
+            claims,
+        ))
+    }
+    .await;
+    match refreshed {
+        Ok(tokens) => {
+            say!("🔄 Logged in with the saved {} login", provider_name);
+            Some(tokens)
+        }
+        Err(err) => {
+            eprintln!(
+                "⚠️  The saved {} login was refused, log in again: {:#}",
+                provider_name, err
+            );
+            None
+        }
+    }
+}
+
+/// The authorization code flow of `authorize`, through the user's browser and
+/// the `server_url` redirect.
+async fn browser_login(
+    config: &AppConfig,
+    identity_provider: &IdentityProvider,
+    client_secret: &str,
+    oidc_client: &AuthClient,
+    offline: bool,
+    bind_nonce: Option<u32>,
+) -> (
+    CoreIdToken,
+    AccessToken,
+    Option<RefreshToken>,
+    CoreIdTokenClaims,
+) {
+    let (auth_url, csrf_token, nonce, pkce_verifier) =
+        OIDCClient::generate_auth_url(oidc_client, offline, identity_provider.jarm, bind_nonce);
+    let auth_url = if identity_provider.par {
+        OIDCClient::push_authorization_request(
+            &identity_provider.issuer_url,
+            &identity_provider.audience_url,
+            client_secret,
+            &auth_url,
+        )
+        .await
+        .expect("Failed to push the authorization request")
+    } else {
+        auth_url
+    };
+
+    say!("Open the following URL in your browser to authenticate:");
+    say!("{}", auth_url);
+
+    let auth_code = if identity_provider.jarm {
+        let response = OIDCClient::capture_callback_param(&config.server.server_url, "response")
+            .await
+            .expect("Failed to capture the authorization response");
+        let keys = OIDCClient::fetch_jwks(&identity_provider.jwk_public_key_url)
+            .await
+            .expect("Failed to fetch JWKS");
+        OIDCClient::verify_jarm_response(
+            &response,
+            &keys,
+            &identity_provider.issuer_url,
+            &identity_provider.audience_url,
+            &csrf_token,
+        )
+        .expect("Invalid authorization response")
+    } else {
+        OIDCClient::capture_access_code(&config.server.server_url, &csrf_token)
+            .await
+            .expect("Authorization failed")
+    };
+
+    let (id_token, access_token, refresh_token) =
+        OIDCClient::exchange_code_for_tokens(oidc_client, auth_code, pkce_verifier)
+            .await
+            .expect("Failed to exchange code");
+
+    let claims = OIDCClient::verify_id_token(oidc_client, &id_token, &nonce)
+        .expect("Failed to verify id token");
+
+    (id_token, access_token, refresh_token, claims)
+}
+
+/// The keys `provider` publishes now, as the contract stores them.
+async fn provider_keys(provider: &IdentityProvider) -> JwkSet {
+    let keys = OIDCClient::fetch_jwks(&provider.jwk_public_key_url)
+        .await
+        .expect("Failed to fetch JWKS");
+    let mut keys: Vec<_> = keys.values().map(Jwk::public_key).collect();
+    // In the JWKS order, the same keys would make another state
+    keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+    JwkSet { keys }
+}
+
+/// The state the contract is registered with, only accepting the configured
+/// providers and the keys they publish now.
+async fn initial_state(config: &AppConfig) -> OidcIdentity {
+    let mut issuers = Vec::new();
+    for provider in config.identity_providers.values() {
+        let context = OpenIdContext {
+            issuer: provider.token_issuer().to_string(),
+            audience: vec![provider.audience_url.clone()],
+            policy: provider.policy.clone(),
+            algorithms: provider.algorithms.clone(),
+            issuer_match: provider.issuer_match,
+        };
+        issuers.push((context, provider_keys(provider).await));
+    }
+    let initial_state = OidcIdentity::with_allowed_issuers(issuers);
+    let initial_state = match config.contract.admin.clone() {
+        Some(admin) => initial_state.with_admin(admin, config.contract.admin_delay),
+        None => initial_state,
+    }
+    .with_disclosure_policy(DisclosurePolicy {
+        claims: config.contract.disclosure.clone(),
+    });
+    let initial_state = match config.contract.max_inactivity {
+        Some(max_inactivity) => initial_state.with_pruning(max_inactivity),
+        None => initial_state,
+    };
+    let initial_state = match config.contract.max_clock_skew {
+        Some(max_clock_skew) => initial_state.with_max_clock_skew(max_clock_skew),
+        None => initial_state,
+    };
+    if config.contract.require_token_nonce {
+        initial_state.with_token_nonce_required()
+    } else {
+        initial_state
+    }
+}
+
+/// What to do about the refusals a user can fix.
+fn refusal_hint(code: ResultCode) -> Option<&'static str> {
+    match code {
+        ResultCode::IdentityNotFound => Some("Register the account first"),
+        ResultCode::InvalidNonce => Some("The account's nonce moved on: fetch it again and retry"),
+        ResultCode::InvalidToken | ResultCode::TokenExpired | ResultCode::TokenNotYetValid => {
+            Some("The ID token was refused: log in again, or check the provider with `doctor`")
+        }
+        ResultCode::TokenAccountMismatch => {
+            Some("The login belongs to another account: pick it with `--account`")
+        }
+        ResultCode::AccountFrozen => Some("The account is frozen until governance unfreezes it"),
+        _ => None,
+    }
+}
+
+/// Sends `action` as a blob transaction from `identity_id`, followed by
+/// `extra_blobs`, then proves the identity blob and sends the proof.
+async fn send_and_prove(
+    client: &impl NodeTransport,
+    prover: &Prover<'_>,
+    budget: &Budget<'_>,
+    contract_name: &str,
+    identity_id: String,
+    action: IdentityAction,
+    extra_blobs: Vec<sdk::Blob>,
+    private_input: PrivateInput,
+) -> (sdk::TxHash, BlobTransaction) {
+    // Fetch the initial state from the node
+    let initial_state: OidcIdentity = client
+        .get_contract_state(&contract_name.into())
+        .await
+        .or_fail("node")
+        .into();
+
+    // ----
+    // Build the blob transaction
+    // ----
+
+    let mut blobs = vec![sdk::Blob {
+        contract_name: contract_name.into(),
+        data: sdk::BlobData(
+            bincode::encode_to_vec(action, bincode::config::standard())
+                .expect("failed to encode BlobData"),
+        ),
+    }];
+    blobs.extend(extra_blobs);
+    let blob_tx = BlobTransaction {
+        identity: identity_id.into(),
+        blobs: blobs.clone(),
+    };
+
+    // Build the contract input, with the tx hash and the context of its block
+    // filled in once the blob tx is sent: until then, the estimate assumes the
+    // next block
+    let mut inputs = ContractInput {
+        initial_state: initial_state.as_digest(),
+        identity: blob_tx.identity.clone(),
+        tx_hash: sdk::TxHash(String::new()),
+        private_input: private_input.to_bytes(),
+        tx_ctx: Some(node::next_context(client).await.or_fail("node")),
+        blobs: blobs.clone(),
+        index: sdk::BlobIndex(0),
+    };
+
+    // Estimate before submitting anything, so going over budget costs nothing
+    let estimate = estimate::estimate(client, GUEST_ELF, &inputs, budget.pricing)
+        .await
+        .expect("Failed to estimate proving cost");
+    host_output::report("estimate", estimate.to_json(), &estimate);
+    if let Err(err) = estimate::check_budget(&estimate, budget.max_cost) {
+        host_output::fail("over_budget", err);
+    }
+    if budget.estimate_only {
+        std::process::exit(0);
+    }
+    // A refused action would still settle, as a failure
+    if let Some(failure) = &estimate.failure {
+        let hint = refusal_hint(failure.code);
+        host_output::fail_with(
+            "refused",
+            format_args!(
+                "The contract refuses the action: {}{}",
+                failure.detail,
+                hint.map(|hint| format!("\n{}", hint)).unwrap_or_default()
+            ),
+            json!({ "result_code": failure.code.code(), "hint": hint }),
+        );
+    }
+
+    let store = SessionStore::open().expect("Failed to open the session store");
+
+    // Send the blob transaction
+    let blob_tx_hash = client.send_tx_blob(&blob_tx).await.or_fail("node");
+    report_blob_sent(&blob_tx_hash);
+
+    // ----
+    // Prove the state transition
+    // ----
+
+    inputs.tx_hash = blob_tx_hash.clone();
+    // Should proving be interrupted, `resume` finishes it
+    store
+        .add_pending(contract_name, &inputs)
+        .expect("Failed to record the pending proof");
+    inputs.tx_ctx = Some(
+        node::sequenced_context(client, &blob_tx_hash)
+            .await
+            .or_fail("node"),
+    );
+
+    // Generate the zk proof
+    let proof = prover.prove(inputs).await.or_fail("proof");
+
+    let proof_tx = ProofTransaction {
+        proof,
+        contract_name: contract_name.into(),
+    };
+
+    // Send the proof transaction
+    report_proof_sent(
+        "Proof",
+        contract_name,
+        node::send_proof(client, &proof_tx, &blob_tx_hash).await,
+    );
+    store
+        .remove_pending(contract_name, &blob_tx_hash)
+        .expect("Failed to forget the pending proof");
+
+    (blob_tx_hash, blob_tx)
+}
+
+/// Proves the blob at `index` of an already sent transaction for a companion
+/// registry (attestations, aliases) running `elf`.
+async fn prove_companion(
+    client: &impl NodeTransport,
+    elf: &[u8],
+    registry_name: &str,
+    blob_tx_hash: sdk::TxHash,
+    blob_tx: BlobTransaction,
+    index: usize,
+) {
+    let initial_state = client
+        .get_contract_state(&registry_name.into())
+        .await
+        .or_fail("node");
+
+    let tx_ctx = node::sequenced_context(client, &blob_tx_hash)
+        .await
+        .or_fail("node");
+
+    let inputs = ContractInput {
+        initial_state,
+        identity: blob_tx.identity,
+        tx_hash: blob_tx_hash.clone(),
+        private_input: vec![],
+        tx_ctx: Some(tx_ctx),
+        blobs: blob_tx.blobs,
+        index: sdk::BlobIndex(index),
+    };
+    let store = SessionStore::open().expect("Failed to open the session store");
+    store
+        .add_pending(registry_name, &inputs)
+        .expect("Failed to record the pending proof");
+
+    let proof = Risc0Prover::new(elf).prove(inputs).await.or_fail("proof");
+
+    let proof_tx = ProofTransaction {
+        proof,
+        contract_name: registry_name.into(),
+    };
+    report_proof_sent(
+        &format!("{} proof", registry_name),
+        registry_name,
+        node::send_proof(client, &proof_tx, &blob_tx_hash).await,
+    );
+    store
+        .remove_pending(registry_name, &blob_tx_hash)
+        .expect("Failed to forget the pending proof");
+}
+
+fn report_blob_sent(blob_tx_hash: &sdk::TxHash) {
+    host_output::report(
+        "blob_tx_sent",
+        json!({ "tx_hash": blob_tx_hash }),
+        format_args!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash),
+    );
+}
+
+/// Reports the outcome of [`node::send_proof`] for the `what` tx, proving a
+/// blob of `contract_name`, exiting when it failed: the proof stays pending
+/// for `resume`.
+fn report_proof_sent(
+    what: &str,
+    contract_name: &str,
+    sent: anyhow::Result<Option<sdk::TxHash>>,
+) {
+    match sent {
+        Ok(Some(proof_tx_hash)) => host_output::report(
+            "proof_tx_sent",
+            json!({ "contract": contract_name, "tx_hash": proof_tx_hash }),
+            format_args!("✅ {} tx sent. Tx hash: {}", what, proof_tx_hash),
+        ),
+        Ok(None) => host_output::report(
+            "proof_settled",
+            json!({ "contract": contract_name }),
+            format_args!("✅ {} tx already settled", what),
+        ),
+        Err(err) => host_output::fail(
+            "proof",
+            format_args!(
+                "{} tx failed: {:#}\nThe blob tx is pending: run `resume` to prove it again",
+                what, err
+            ),
+        ),
+    }
+}
+
+/// Proving backend of `--prover`, else of config.toml, else the local one.
+fn prover_choice(cli: &Cli, config: &AppConfig) -> ProverChoice {
+    cli.prover
+        .clone()
+        .or_else(|| config.proving.prover.clone())
+        .unwrap_or(ProverChoice::Local)
 }
 
 #[tokio::main]
@@ -48,263 +1068,1465 @@ async fn main() {
         .with_env_filter(tracing_subscriber::filter::EnvFilter::from_default_env())
         .init();
 
-    let config = load_config(Some(Path::new("./host/config.toml"))).unwrap();
-
     let cli = Cli::parse();
+    host_output::init(cli.output.unwrap_or_default());
+
+    // Before loading the settings, whose errors it reports instead of failing
+    if let Commands::Config {
+        command: ConfigCommand::Validate {},
+    } = &cli.command
+    {
+        if !config_validate::validate(Path::new(CONFIG_PATH)).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = load_config(Some(Path::new(CONFIG_PATH)))
+        .with_context(|| format!("Failed to load {}", CONFIG_PATH))
+        .or_fail("config");
+
+    // `run <template>` stands for the command line the template describes
+    let template_path = match &cli.command {
+        Commands::Run { template } => Some(template.clone()),
+        _ => None,
+    };
+    let (cli, template_run) = match template_path {
+        Some(path) => {
+            let template = Template::load(&path).or_fail("usage");
+            let prover = prover_choice(&cli, &config).to_string();
+            let args = template.args("host", &cli.provider, &prover);
+            let mut expanded = Cli::parse_from(&args);
+            expanded.estimate |= cli.estimate;
+            expanded.strict_keys |= cli.strict_keys;
+            expanded.reproducible |= cli.reproducible;
+            expanded.max_cost = expanded.max_cost.or(cli.max_cost);
+            expanded.expected_program_id = expanded.expected_program_id.or(cli.expected_program_id);
+            expanded.config_key = expanded.config_key.or(cli.config_key);
+            expanded.data_dir = expanded.data_dir.or(cli.data_dir);
+            expanded.allow_unsafe |= cli.allow_unsafe;
+            expanded.flow = expanded.flow.or(cli.flow);
+            expanded.zkvm = expanded.zkvm.or(cli.zkvm);
+            expanded.auto_register |= cli.auto_register;
+            expanded.salt = expanded.salt.or(cli.salt);
+            expanded.output = expanded.output.or(cli.output);
+            (expanded, Some((path, template, args)))
+        }
+        None => (cli, None),
+    };
+
+    app_dirs::init(
+        AppDirs::resolve("oidc", cli.data_dir.as_deref())
+            .expect("Failed to locate the data directory"),
+    );
+    let migration = app_dirs::migrate_file(Path::new(LEGACY_JWKS_PINS_PATH), &jwks_pins_path())
+        .expect("Failed to move the JWKS pins");
+    for moved in &migration.moved {
+        say!("📦 Moved the JWKS pins to {}", moved.display());
+    }
+    for kept in &migration.kept {
+        eprintln!(
+            "⚠️  {} left in place: pins already exist at {}",
+            kept.display(),
+            jwks_pins_path().display()
+        );
+    }
+
+    if let Some(config_key) = &cli.config_key {
+        if let Err(err) = config_signature::verify(
+            &config,
+            cli.zkvm(),
+            config_key,
+            Path::new(config_signature::CONFIG_SIGNATURE_PATH),
+        ) {
+            host_output::fail(
+                "config_signature",
+                format_args!("Refusing to run with unsigned settings: {:#}", err),
+            );
+        }
+    }
 
-    let client = client_sdk::rest_client::NodeApiHttpClient::new(config.server.host).unwrap();
+    let client =
+        LightClient::new(Node::from_url(&config.server.host).unwrap(), &config.server.witnesses)
+            .unwrap();
+
+    // Before the self-check, which refuses to submit to a missing contract
+    if cli.auto_register && cli.command.submits_proofs() {
+        lifecycle::ensure_contract(
+            &client,
+            &config.contract.name,
+            initial_state(&config).await.as_digest(),
+            cli.zkvm(),
+            Duration::from_secs(config.watch.settle_timeout),
+        )
+        .await
+        .unwrap_or_else(|err| {
+            host_output::fail(
+                "node",
+                format_args!("Failed to register {}: {:#}", config.contract.name, err),
+            )
+        });
+    }
+
+    selfcheck::run(
+        &cli.command,
+        &cli.provider,
+        cli.zkvm(),
+        &config,
+        Path::new(CONFIG_PATH),
+        &client,
+    )
+    .await
+    .finish(cli.allow_unsafe);
 
     let contract_name = &config.contract.name;
 
-    let prover = Risc0Prover::new(GUEST_ELF);
+    let prover = Prover::new(
+        &prover_choice(&cli, &config),
+        config.proving.polling(),
+        cli.zkvm(),
+    )
+    .or_fail("usage");
+    let budget = Budget {
+        pricing: &config.proving,
+        max_cost: cli.max_cost,
+        estimate_only: cli.estimate,
+    };
+
+    // Listing providers must work whichever `--provider` is picked
+    if let Commands::Providers {
+        command: ProvidersCommand::List {},
+    } = &cli.command
+    {
+        if !providers::list(&config, &cli.provider).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Neither does resuming need a provider: the ID tokens are in the store
+    if let Commands::Resume { drop_failed } = cli.command {
+        match session_store::resume(&config, &client, &prover, drop_failed).await {
+            Ok(true) => return,
+            Ok(false) => {
+                host_output::error("proof", "Some pending proofs failed", Value::Null);
+                std::process::exit(1);
+            }
+            Err(err) => host_output::fail("session_store", format_args!("{:#}", err)),
+        }
+    }
 
     let identity_provider = config
         .identity_providers
         .get(&cli.provider)
-        .unwrap_or_else(|| panic!("{} not set in config.toml", cli.provider));
+        .unwrap_or_else(|| {
+            host_output::fail(
+                "usage",
+                format_args!(
+                    "{} not set in config.toml, pick one of {} with --provider",
+                    cli.provider,
+                    providers::names(&config).join(", ")
+                ),
+            )
+        });
+
+    let context = OpenIdContext {
+        issuer: identity_provider.token_issuer().to_string(),
+        audience: vec![identity_provider.audience_url.to_string()],
+        policy: identity_provider.policy.clone(),
+        algorithms: identity_provider.algorithms.clone(),
+        issuer_match: identity_provider.issuer_match,
+    };
+
+    if cli.reproducible && cli.command.submits_proofs() {
+        let program_id = client
+            .get_program_id(&contract_name.clone().into())
+            .await
+            .or_fail("node");
+        lifecycle::ensure_program_id(&program_id, cli.expected_program_id.as_deref(), cli.zkvm());
+    }
 
     match cli.command {
-        Commands::RegisterContract {} => {
-            // Build initial state of contract
-            let initial_state = OidcIdentity::new();
-            println!("Initial state: {:?}", initial_state);
+        Commands::Contract {
+            command: ContractCommand::Register {},
+        } => {
+            let initial_state = initial_state(&config).await;
+            say!("Initial state: {:?}", initial_state);
 
             // Send the transaction to register the contract
             let res = client
                 .register_contract(&APIRegisterContract {
-                    verifier: "risc0".into(),
-                    program_id: sdk::ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec()),
+                    verifier: cli.zkvm().verifier().into(),
+                    program_id: lifecycle::local_program_id(cli.zkvm()),
                     state_digest: initial_state.as_digest(),
                     contract_name: contract_name.clone().into(),
                 })
                 .await
-                .unwrap();
+                .or_fail("node");
 
-            println!("✅ Register contract tx sent. Tx hash: {}", res);
+            lifecycle::report_registered(contract_name, &res);
+        }
+        Commands::Contract {
+            command: ContractCommand::Info {},
+        } => {
+            let name = contract_name.clone().into();
+            let program_id = client.get_program_id(&name).await.or_fail("node");
+            let state = client.get_contract_state(&name).await.or_fail("node");
+            lifecycle::print_info(contract_name, &program_id, &state, cli.zkvm());
         }
-        Commands::RegisterIdentity {} => {
-            // Fetch the initial state from the node
-            let initial_state: OidcIdentity = client
-                .get_contract(&contract_name.clone().into())
+        Commands::Contract {
+            command: ContractCommand::Check {},
+        } => {
+            let program_id = client
+                .get_program_id(&contract_name.clone().into())
                 .await
-                .unwrap()
-                .state
+                .or_fail("node");
+            lifecycle::check(&program_id, cli.zkvm());
+        }
+        Commands::Contract {
+            command:
+                command @ (ContractCommand::Pause { .. }
+                | ContractCommand::Unpause { .. }
+                | ContractCommand::AllowIssuer { .. }),
+        } => {
+            let (operation, execute) = match command {
+                ContractCommand::Pause { execute } => (AdminOperation::Pause, execute),
+                ContractCommand::Unpause { execute } => (AdminOperation::Unpause, execute),
+                ContractCommand::AllowIssuer { execute } => {
+                    let operation = AdminOperation::AllowIssuer {
+                        context: context.clone(),
+                        keys: provider_keys(identity_provider).await,
+                    };
+                    (operation, execute)
+                }
+                _ => unreachable!(),
+            };
+            let admin = config.contract.admin.clone().unwrap_or_else(|| {
+                host_output::fail("usage", "contract.admin is not set, so there is no governance")
+            });
+            let login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
+
+            // Proposals are numbered in order, so the next id is this one's
+            // unless another proposal settles first
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node")
                 .into();
+            let id = execute.unwrap_or(state.governance().next_id);
+            let action = match execute {
+                Some(id) => IdentityAction::ExecuteAdminOperation {
+                    admin_context: context,
+                    admin_jwk_pub_key: login.jwk.public_key().into(),
+                    id,
+                },
+                None => IdentityAction::ProposeAdminOperation {
+                    admin_context: context,
+                    admin_jwk_pub_key: login.jwk.public_key().into(),
+                    operation: operation.clone(),
+                },
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                admin,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+
+            if execute.is_some() {
+                host_output::report(
+                    "admin_operation_executed",
+                    json!({ "id": id, "operation": format!("{:?}", operation) }),
+                    format_args!("✅ Executed operation {}", id),
+                );
+            } else {
+                host_output::report(
+                    "admin_operation_proposed",
+                    json!({
+                        "id": id,
+                        "operation": format!("{:?}", operation),
+                        "delay": state.governance().min_delay,
+                    }),
+                    format_args!(
+                        "✅ Proposed {:?} as operation {}: run again with `--execute {}` once \
+                         {} blocks have passed",
+                        operation,
+                        id,
+                        id,
+                        state.governance().min_delay
+                    ),
+                );
+            }
+        }
+        Commands::RegisterAttestationContract {} => {
+            let initial_state = AttestationRegistry::new([contract_name.clone()]);
+            say!("Initial state: {:?}", initial_state);
+
+            let res = client
+                .register_contract(&APIRegisterContract {
+                    verifier: "risc0".into(),
+                    program_id: sdk::ProgramId(sdk::to_u8_array(&ATTESTATION_GUEST_ID).to_vec()),
+                    state_digest: initial_state.as_digest(),
+                    contract_name: config.contract.attestation_name.clone().into(),
+                })
+                .await
+                .or_fail("node");
+
+            lifecycle::report_registered(&config.contract.attestation_name, &res);
+        }
+        Commands::RegisterAliasContract { trust } => {
+            let initial_state = AliasRegistry::new(
+                std::iter::once((contract_name.clone(), IdentityScheme::Oidc))
+                    .chain(trust.into_iter().map(|name| (name, IdentityScheme::Ecdsa))),
+            );
+            say!("Initial state: {:?}", initial_state);
+
+            let res = client
+                .register_contract(&APIRegisterContract {
+                    verifier: "risc0".into(),
+                    program_id: sdk::ProgramId(sdk::to_u8_array(&ALIAS_GUEST_ID).to_vec()),
+                    state_digest: initial_state.as_digest(),
+                    contract_name: config.contract.alias_name.clone().into(),
+                })
+                .await
+                .or_fail("node");
+
+            lifecycle::report_registered(&config.contract.alias_name, &res);
+        }
+        Commands::Alias { nonce, command } => {
+            let login = login_for_nonce(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+
+            let identity_id = acting_account(
+                cli.account.as_deref(),
+                identity_provider,
+                &login,
+                contract_name,
+                cli.salt.as_deref(),
+            );
 
-            println!("Initial state {:?}", initial_state.clone());
+            let action = IdentityAction::VerifyIdentity {
+                account: identity_id.clone(),
+                nonce,
+                jwk_pub_key: login.jwk.public_key().into(),
+                context,
+            };
+
+            let alias_action = match command {
+                AliasCommand::Link { handle } => AliasAction::Link { handle },
+                AliasCommand::Invite { handle, account } => {
+                    AliasAction::Invite { handle, account }
+                }
+                AliasCommand::Unlink { handle } => AliasAction::Unlink { handle },
+            };
+            let registry_name = &config.contract.alias_name;
 
-            let client_secret = &identity_provider.get_client_secret(&cli.provider);
-            let oidc_client = OIDCClient::build(
-                identity_provider.issuer_url.to_string(),
-                identity_provider.audience_url.to_string(),
-                Some(client_secret.to_string()),
-                &format!("{}/callback", config.server.server_url),
+            let (blob_tx_hash, blob_tx) = send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![alias_action.as_blob(registry_name.clone().into())],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
             )
-            .await
-            .expect("Failed to build provider");
+            .await;
+
+            prove_companion(
+                &client,
+                ALIAS_GUEST_ELF,
+                registry_name,
+                blob_tx_hash,
+                blob_tx,
+                1,
+            )
+            .await;
+        }
+        Commands::RegisterIdentity {
+            recovery_codes,
+            salted,
+            display_name,
+            nonce_window,
+            timestamp_nonces,
+        } => {
+            let login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
+
+            say!("{:?}", login.jwk);
+
+            let salt = match cli.salt.clone() {
+                None if salted => {
+                    let mut bytes = [0u8; 16];
+                    OsRng.fill_bytes(&mut bytes);
+                    let salt = hex::encode(bytes);
+                    host_output::report(
+                        "salt",
+                        json!({ "salt": salt }),
+                        format_args!(
+                            "🧂 Salt, not stored anywhere: {}\n   Pass `--salt {}` to every later command.",
+                            salt, salt
+                        ),
+                    );
+                    Some(salt)
+                }
+                salt => salt,
+            };
+            let identity_id = acting_account(
+                None,
+                identity_provider,
+                &login,
+                contract_name,
+                salt.as_deref(),
+            );
+
+            let (codes, code_hashes) = recovery::generate(&identity_id, recovery_codes)
+                .expect("Failed to generate recovery codes");
+            if !codes.is_empty() {
+                recovery::print(&codes);
+            }
+
+            let action = IdentityAction::RegisterIdentity {
+                account: identity_id.clone(),
+                jwk_pub_key: login.jwk.public_key().into(),
+                context,
+                recovery_codes: code_hashes,
+                metadata: AccountMetadata::new(
+                    Some(cli.provider.clone()),
+                    display_name.map(|name| DefaultHasher::digest_hex(name.as_bytes())),
+                ),
+                nonce_strategy: match (nonce_window, timestamp_nonces) {
+                    (Some(size), _) => NonceStrategy::Window { size },
+                    (None, true) => NonceStrategy::Timestamp,
+                    (None, false) => NonceStrategy::Strict,
+                },
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(salt),
+            )
+            .await;
+        }
+        Commands::VerifyIdentity {
+            nonce,
+            attest,
+            credential,
+        } => {
+            let issuer = credential.as_ref().map(|_| {
+                let credentials = &config.credentials;
+                match (&credentials.issuer, &credentials.key) {
+                    (Some(issuer), Some(key)) => (issuer.clone(), key.clone()),
+                    _ => host_output::fail(
+                        "usage",
+                        "--credential needs `issuer` and `key` under [credentials]",
+                    ),
+                }
+            });
+            let login = login_for_nonce(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+
+            let identity_id = acting_account(
+                cli.account.as_deref(),
+                identity_provider,
+                &login,
+                contract_name,
+                cli.salt.as_deref(),
+            );
+
+            let action = IdentityAction::VerifyIdentity {
+                account: identity_id.clone(),
+                nonce,
+                jwk_pub_key: login.jwk.public_key().into(),
+                context,
+            };
 
-            let (auth_url, _, nonce, pkce_verifier) = OIDCClient::generate_auth_url(&oidc_client);
+            let registry_name = &config.contract.attestation_name;
+            let extra_blobs = match &attest {
+                Some(claim) => vec![AttestationAction::Attest {
+                    claim: claim.clone(),
+                }
+                .as_blob(registry_name.clone().into())],
+                None => vec![],
+            };
 
-            println!("Open the following URL in your browser to authenticate:");
-            println!("{}", auth_url);
+            let (blob_tx_hash, blob_tx) = send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id.clone(),
+                action,
+                extra_blobs,
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
 
-            let auth_code = OIDCClient::capture_access_code(&config.server.server_url).await;
+            if attest.is_some() {
+                prove_companion(
+                    &client,
+                    ATTESTATION_GUEST_ELF,
+                    registry_name,
+                    blob_tx_hash.clone(),
+                    blob_tx,
+                    1,
+                )
+                .await;
+            }
 
-            let (id_token, access_token) =
-                OIDCClient::exchange_code_for_tokens(&oidc_client, auth_code, pkce_verifier)
+            if let (Some(path), Some((issuer, key))) = (credential, issuer) {
+                watch::wait_for_nonce(&client, &config, &identity_id, nonce + 1)
                     .await
-                    .expect("Failed to exchange code");
+                    .unwrap_or_else(|err| {
+                        host_output::fail(
+                            "not_settled",
+                            format_args!(
+                                "Verification tx {} did not settle: {:#}",
+                                blob_tx_hash, err
+                            ),
+                        )
+                    });
+                let verification = credential::Verification {
+                    id: did::did(&identity_id),
+                    contract: contract_name.clone(),
+                    scheme: credential::SCHEME.to_string(),
+                    provider: identity_provider.issuer_url.clone(),
+                    tx_hash: blob_tx_hash.0.clone(),
+                    block_height: client.block_height().await.or_fail("node"),
+                };
+                let jwt = credential::issue(&issuer, &key, verification)
+                    .expect("Failed to issue the credential");
+                std::fs::write(&path, jwt).expect("Failed to write the credential");
+                host_output::report(
+                    "credential_written",
+                    json!({ "path": path }),
+                    format_args!("🎫 Credential written to {}", path.display()),
+                );
+            }
+        }
+        Commands::Logout {} => {
+            let (login, identity_id) =
+                login_for_account(&config, &cli, identity_provider, &client, contract_name).await;
 
-            let claims = OIDCClient::verify_id_token(&oidc_client, &id_token, &nonce)
-                .expect("Failed to verify id token");
+            let action = IdentityAction::Logout {
+                account: identity_id.clone(),
+                jwk_pub_key: login.jwk.public_key().into(),
+                context,
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+
+            match &identity_provider.revocation_url {
+                Some(revocation_url) => {
+                    OIDCClient::revoke_token(
+                        revocation_url,
+                        &identity_provider.audience_url,
+                        &identity_provider.get_client_secret(&cli.provider),
+                        login.access_token.secret(),
+                    )
+                    .await
+                    .expect("Failed to revoke provider token");
+                    host_output::report(
+                        "token_revoked",
+                        json!({ "provider": cli.provider }),
+                        "✅ Provider token revoked",
+                    );
+                }
+                None => say!("⚠️ No revocation_url configured, provider token left untouched"),
+            }
+            if token_store::forget(&cli.provider).expect("Failed to forget the saved login") {
+                host_output::report(
+                    "login_forgotten",
+                    json!({ "provider": cli.provider }),
+                    "✅ Saved login forgotten",
+                );
+            }
+        }
+        Commands::UnregisterIdentity {} => {
+            let (login, identity_id) =
+                login_for_account(&config, &cli, identity_provider, &client, contract_name).await;
 
-            let _ =
-                OIDCClient::verify_access_token(&oidc_client, &id_token, &access_token, &claims)
-                    .expect("Failed to verify access token");
+            let action = IdentityAction::UnregisterIdentity {
+                account: identity_id.clone(),
+                jwk_pub_key: login.jwk.public_key().into(),
+                context,
+            };
 
-            let jwk_res = OIDCClient::match_jwks(
-                &id_token.to_string(),
-                &identity_provider.jwk_public_key_url,
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::Watch { listen } => {
+            watch::run(
+                &config,
+                &client,
+                &prover,
+                cli.strict_keys,
+                cli.flow(),
+                &listen,
+            )
+            .await
+            .expect("Watch mode failed");
+        }
+        Commands::Serve {
+            listen,
+            watch_config,
+        } => {
+            serve::run(
+                &config,
+                &client,
+                &prover,
+                cli.strict_keys,
+                cli.salt.clone(),
+                cli.max_cost,
+                &listen,
+                watch_config.then_some(Path::new(CONFIG_PATH)),
             )
             .await
-            .expect("Failed to match google jwks");
+            .expect("Serve mode failed");
+        }
+        Commands::ReserveNonces { count } => {
+            let (login, identity_id) =
+                login_for_account(&config, &cli, identity_provider, &client, contract_name).await;
 
-            println!("{:?}", jwk_res);
+            let action = IdentityAction::ReserveNonces {
+                account: identity_id.clone(),
+                count,
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+            };
 
-            let identity_id = format!("{}.{}", claims.subject().to_string(), config.contract.name);
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        // By reference: `login_for_account` reads the rest of `cli`
+        Commands::DiscloseClaim { ref claim, plain } => {
+            let claim: Claim = serde_json::from_value(serde_json::Value::String(claim.clone()))
+                .expect("Unknown claim, expected email, subject or issuer");
+            let disclosure = if plain {
+                Disclosure::Plain
+            } else {
+                Disclosure::Hashed
+            };
 
-            // ----
-            // Build the blob transaction
-            // ----
+            let (login, identity_id) =
+                login_for_account(&config, &cli, identity_provider, &client, contract_name).await;
 
-            let action = IdentityAction::RegisterIdentity {
+            let action = IdentityAction::DiscloseClaim {
+                account: identity_id.clone(),
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+                claim,
+                disclosure,
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::ExtractClaims { ref claims } => {
+            let (login, identity_id) =
+                login_for_account(&config, &cli, identity_provider, &client, contract_name).await;
+
+            let action = IdentityAction::ExtractClaims {
                 account: identity_id.clone(),
-                jwk_pub_key: JwkPublicKey {
-                    n: jwk_res.n,
-                    e: jwk_res.e,
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+                claims: claims.clone(),
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::MigrateSubject {
+            account,
+            from_provider,
+        } => {
+            let old_provider = config
+                .identity_providers
+                .get(&from_provider)
+                .unwrap_or_else(|| panic!("{} not set in config.toml", from_provider));
+
+            say!("Log in with the account's current identity ({})", from_provider);
+            let nonce = account_nonce(&client, contract_name, &account).await;
+            let old_login = login_for_nonce(
+                &config,
+                &from_provider,
+                old_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+            say!("Log in with the new identity ({})", cli.provider);
+            let new_login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
+
+            let action = IdentityAction::MigrateSubject {
+                account: account.clone(),
+                old_context: OpenIdContext {
+                    issuer: old_provider.token_issuer().to_string(),
+                    audience: vec![old_provider.audience_url.to_string()],
+                    policy: old_provider.policy.clone(),
+                    algorithms: old_provider.algorithms.clone(),
+                    issuer_match: old_provider.issuer_match,
                 },
+                old_jwk_pub_key: old_login.jwk.public_key().into(),
+                new_context: context,
+                new_jwk_pub_key: new_login.jwk.public_key().into(),
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account,
+                action,
+                vec![],
+                PrivateInput::TokenPair {
+                    old_token: old_login.id_token.to_string(),
+                    new_token: new_login.id_token.to_string(),
+                }
+                .salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::LinkProvider {
+            account,
+            from_provider,
+        } => {
+            let current_provider = config
+                .identity_providers
+                .get(&from_provider)
+                .unwrap_or_else(|| panic!("{} not set in config.toml", from_provider));
+
+            say!("Log in with a linked identity ({})", from_provider);
+            let nonce = account_nonce(&client, contract_name, &account).await;
+            let current_login = login_for_nonce(
+                &config,
+                &from_provider,
+                current_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+            say!("Log in with the identity to link ({})", cli.provider);
+            let new_login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
+
+            let action = IdentityAction::LinkProvider {
+                account: account.clone(),
                 context: OpenIdContext {
-                    issuer: identity_provider.issuer_url.to_string(),
-                    audience: identity_provider.audience_url.to_string(),
+                    issuer: current_provider.token_issuer().to_string(),
+                    audience: vec![current_provider.audience_url.to_string()],
+                    policy: current_provider.policy.clone(),
+                    algorithms: current_provider.algorithms.clone(),
+                    issuer_match: current_provider.issuer_match,
                 },
-            };
-            let blobs = vec![sdk::Blob {
-                contract_name: contract_name.clone().into(),
-                data: sdk::BlobData(
-                    bincode::encode_to_vec(action, bincode::config::standard())
-                        .expect("failed to encode BlobData"),
-                ),
-            }];
-            let blob_tx = BlobTransaction {
-                identity: identity_id.into(),
-                blobs: blobs.clone(),
+                jwk_pub_key: current_login.jwk.public_key().into(),
+                new_context: context,
+                new_jwk_pub_key: new_login.jwk.public_key().into(),
             };
 
-            // Send the blob transaction
-            let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-            println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
-
-            // ----
-            // Prove the state transition
-            // ----
-
-            // Build the contract input
-            let inputs = ContractInput {
-                initial_state: initial_state.as_digest(),
-                identity: blob_tx.identity,
-                tx_hash: blob_tx_hash,
-                private_input: id_token.to_string().clone().into_bytes().to_vec(),
-                tx_ctx: None,
-                blobs: blobs.clone(),
-                index: sdk::BlobIndex(0),
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account,
+                action,
+                vec![],
+                PrivateInput::TokenPair {
+                    old_token: current_login.id_token.to_string(),
+                    new_token: new_login.id_token.to_string(),
+                }
+                .salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::UnlinkProvider { account, hash } => {
+            let nonce = account_nonce(&client, contract_name, &account).await;
+            let login = login_for_nonce(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+
+            let action = IdentityAction::UnlinkProvider {
+                account: account.clone(),
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+                hash,
             };
 
-            // Generate the zk proof
-            let proof = prover.prove(inputs).await.unwrap();
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::RecoverWithCode { account } => {
+            let code = recovery::prompt().expect("Failed to read the recovery code");
+            say!("Log in with the new identity ({})", cli.provider);
+            let login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
 
-            let proof_tx = ProofTransaction {
-                proof,
-                contract_name: contract_name.clone().into(),
+            let action = IdentityAction::RecoverWithCode {
+                account: account.clone(),
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
             };
 
-            // Send the proof transaction
-            let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-            println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account,
+                action,
+                vec![],
+                PrivateInput::TokenWithRecoveryCode {
+                    token: login.id_token.to_string(),
+                    code,
+                }
+                .salted(cli.salt.clone()),
+            )
+            .await;
         }
-        Commands::VerifyIdentity { nonce } => {
-            {
-                // Fetch the initial state from the node
-                let initial_state: OidcIdentity = client
-                    .get_contract(&contract_name.clone().into())
-                    .await
-                    .unwrap()
-                    .state
-                    .into();
+        Commands::AddRecoveryProvider {
+            account,
+            from_provider,
+            delay,
+        } => {
+            let current_provider = config
+                .identity_providers
+                .get(&from_provider)
+                .unwrap_or_else(|| panic!("{} not set in config.toml", from_provider));
 
-                let client_secret = &identity_provider.get_client_secret(&cli.provider);
+            say!("Log in with a linked identity ({})", from_provider);
+            let nonce = account_nonce(&client, contract_name, &account).await;
+            let current_login = login_for_nonce(
+                &config,
+                &from_provider,
+                current_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
+            say!("Log in with the recovery identity ({})", cli.provider);
+            let recovery_login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
 
-                let oidc_client = OIDCClient::build(
-                    identity_provider.issuer_url.to_string(),
-                    identity_provider.audience_url.to_string(),
-                    Some(client_secret.to_string()),
-                    &format!("{}/callback", config.server.server_url),
-                )
+            let action = IdentityAction::AddRecoveryProvider {
+                account: account.clone(),
+                context: OpenIdContext {
+                    issuer: current_provider.token_issuer().to_string(),
+                    audience: vec![current_provider.audience_url.to_string()],
+                    policy: current_provider.policy.clone(),
+                    algorithms: current_provider.algorithms.clone(),
+                    issuer_match: current_provider.issuer_match,
+                },
+                jwk_pub_key: current_login.jwk.public_key().into(),
+                recovery_context: context,
+                recovery_jwk_pub_key: recovery_login.jwk.public_key().into(),
+                delay,
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account,
+                action,
+                vec![],
+                PrivateInput::TokenPair {
+                    old_token: current_login.id_token.to_string(),
+                    new_token: recovery_login.id_token.to_string(),
+                }
+                .salted(cli.salt.clone()),
+            )
+            .await;
+        }
+        Commands::RecoverIdentity { account } => {
+            // Whether this run completes a pending recovery, and the delay of
+            // one it starts
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
                 .await
-                .expect("Failed to build provider");
+                .or_fail("node")
+                .into();
+            let recovery = state.account(&account).and_then(|info| {
+                let provider = info.recovery_provider.as_ref()?;
+                Some((provider.pending_at(info.nonce).is_some(), provider.delay))
+            });
 
-                let (auth_url, _, auth_nonce, pkce_verifier) =
-                    OIDCClient::generate_auth_url(&oidc_client);
+            say!("Log in with the recovery identity ({})", cli.provider);
+            let login = login(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+            )
+            .await;
 
-                println!("Open the following URL in your browser to authenticate:");
-                println!("{}", auth_url);
+            let action = IdentityAction::RecoverIdentity {
+                account: account.clone(),
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+            };
 
-                let auth_code = OIDCClient::capture_access_code(&config.server.server_url).await;
+            let (blob_tx_hash, _) = send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account.clone(),
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
 
-                let (id_token, access_token) =
-                    OIDCClient::exchange_code_for_tokens(&oidc_client, auth_code, pkce_verifier)
+            match recovery {
+                Some((false, delay)) => {
+                    let tx_ctx = node::sequenced_context(&client, &blob_tx_hash)
                         .await
-                        .expect("Failed to exchange code");
+                        .or_fail("node");
+                    let ready_at = tx_ctx.block_height.0.saturating_add(delay);
+                    host_output::report(
+                        "recovery_started",
+                        json!({ "account": account, "ready_at": ready_at }),
+                        format_args!(
+                            "⏳ Recovery of {} started: run recover-identity again from block {}",
+                            account, ready_at
+                        ),
+                    );
+                }
+                _ => host_output::report(
+                    "identity_recovered",
+                    json!({ "account": account }),
+                    format_args!("✅ {} is now bound to the recovery identity", account),
+                ),
+            }
+        }
+        Commands::AuthorizeSessionKey { nonce, blocks } => {
+            let expiry = session_key::expiry(&client, blocks)
+                .await
+                .unwrap_or_else(|err| host_output::fail("usage", format_args!("{:#}", err)));
+            let login = login_for_nonce(
+                &config,
+                &cli.provider,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+                nonce,
+            )
+            .await;
 
-                let claims = OIDCClient::verify_id_token(&oidc_client, &id_token, &auth_nonce)
-                    .expect("Failed to verify id token");
+            let identity_id = acting_account(
+                cli.account.as_deref(),
+                identity_provider,
+                &login,
+                contract_name,
+                cli.salt.as_deref(),
+            );
 
-                let _ = OIDCClient::verify_access_token(
-                    &oidc_client,
-                    &id_token,
-                    &access_token,
-                    &claims,
-                )
-                .expect("Failed to verify access token");
+            let key = session_key::generate();
+            let action = IdentityAction::AuthorizeSessionKey {
+                account: identity_id.clone(),
+                nonce,
+                context,
+                jwk_pub_key: login.jwk.public_key().into(),
+                pubkey: session_key::public_key(&key),
+                expiry,
+            };
 
-                let jwk_res = OIDCClient::match_jwks(
-                    &id_token.to_string(),
-                    &identity_provider.jwk_public_key_url,
-                )
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                action,
+                vec![],
+                PrivateInput::Token(login.id_token.to_string()).salted(cli.salt.clone()),
+            )
+            .await;
+            session_key::print(&key, expiry);
+        }
+        Commands::VerifyWithSessionKey { nonce } => {
+            let Some(identity_id) = cli.account.clone() else {
+                host_output::fail("usage", "verify-with-session-key needs --account")
+            };
+            let key = session_key::prompt()
+                .unwrap_or_else(|err| host_output::fail("usage", format_args!("{:#}", err)));
+            let action = IdentityAction::VerifyWithSessionKey {
+                account: identity_id.clone(),
+                nonce,
+            };
+
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id.clone(),
+                action,
+                vec![],
+                session_key::sign(&key, contract_name, &identity_id, nonce, &[]),
+            )
+            .await;
+        }
+        Commands::Prune { accounts } => {
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
                 .await
-                .expect("Failed to match google jwks");
-
-                // ----
-                // Build the blob transaction
-                // ----
-
-                let identity_id =
-                    format!("{}.{}", claims.subject().to_string(), config.contract.name);
-
-                let action = IdentityAction::VerifyIdentity {
-                    account: identity_id.clone(),
-                    nonce,
-                    jwk_pub_key: JwkPublicKey {
-                        n: jwk_res.n,
-                        e: jwk_res.e,
-                    },
-                    context: OpenIdContext {
-                        issuer: identity_provider.issuer_url.to_string(),
-                        audience: identity_provider.audience_url.to_string(),
-                    },
-                };
-                let blobs = vec![sdk::Blob {
-                    contract_name: contract_name.clone().into(),
-                    data: sdk::BlobData(
-                        bincode::encode_to_vec(action, bincode::config::standard())
-                            .expect("failed to encode BlobData"),
-                    ),
-                }];
-                let blob_tx = BlobTransaction {
-                    identity: identity_id.into(),
-                    blobs: blobs.clone(),
-                };
+                .or_fail("node")
+                .into();
+            let archived = archive_journal::archived(&state, &accounts)
+                .unwrap_or_else(|err| host_output::fail("usage", format_args!("{:#}", err)));
+            let journal_path = archive_journal::path(contract_name);
+            let mut journal = ArchiveJournal::load(&journal_path).or_fail("archive_journal");
 
-                // Send the blob transaction
-                let blob_tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
-                println!("✅ Blob tx sent. Tx hash: {}", blob_tx_hash);
-
-                // ----
-                // Prove the state transition
-                // ----
-
-                // Build the contract input
-                let inputs = ContractInput {
-                    initial_state: initial_state.as_digest(),
-                    identity: blob_tx.identity,
-                    tx_hash: blob_tx_hash.clone(),
-                    private_input: id_token.to_string().clone().into_bytes().to_vec(),
-                    tx_ctx: None,
-                    blobs: blobs.clone(),
-                    index: sdk::BlobIndex(0),
-                };
+            // Anyone can prune: the first account stands in for the sender
+            let identity_id = cli.account.clone().unwrap_or_else(|| accounts[0].clone());
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                identity_id,
+                IdentityAction::PruneInactive { accounts },
+                vec![],
+                // Pruning has no private data
+                PrivateInput::Batch(vec![]),
+            )
+            .await;
+
+            journal.record(&archived).or_fail("archive_journal");
+            journal.save(&journal_path).or_fail("archive_journal");
+            host_output::report(
+                "accounts_pruned",
+                json!({ "archived": archived }),
+                format_args!(
+                    "✅ Archived {} accounts, journaled in {}",
+                    archived.len(),
+                    journal_path.display()
+                ),
+            );
+        }
+        Commands::Reactivate { account } => {
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .into();
+            let journal_path = archive_journal::path(contract_name);
+            let mut journal = ArchiveJournal::load(&journal_path).or_fail("archive_journal");
+            let (record, proof) = journal
+                .proof(&state, &account)
+                .unwrap_or_else(|err| host_output::fail("usage", format_args!("{:#}", err)));
 
-                // Generate the zk proof
-                let proof = prover.prove(inputs).await.unwrap();
+            send_and_prove(
+                &client,
+                &prover,
+                &budget,
+                contract_name,
+                account.clone(),
+                IdentityAction::Reactivate {
+                    account: account.clone(),
+                    record,
+                    proof: proof.clone(),
+                },
+                vec![],
+                // Reactivating has no private data either: the record is public
+                PrivateInput::Batch(vec![]),
+            )
+            .await;
 
-                let proof_tx = ProofTransaction {
+            journal.clear(&proof);
+            journal.save(&journal_path).or_fail("archive_journal");
+            host_output::report(
+                "account_reactivated",
+                json!({ "account": account }),
+                format_args!("✅ {} restored from the archive", account),
+            );
+        }
+        Commands::ProveBatch { inputs } => {
+            let inputs: Vec<ContractInput> = inputs
+                .iter()
+                .map(|path| {
+                    let data = std::fs::read(path).expect("Failed to read contract input");
+                    serde_json::from_slice(&data).expect("Invalid contract input")
+                })
+                .collect();
+
+            let proof = tokio::task::spawn_blocking(move || aggregate::prove_batch(&inputs))
+                .await
+                .unwrap()
+                .expect("Failed to prove batch");
+
+            let proof_tx_hash = client
+                .send_tx_proof(&ProofTransaction {
                     proof,
                     contract_name: contract_name.clone().into(),
-                };
+                })
+                .await
+                .or_fail("node");
+            host_output::report(
+                "proof_tx_sent",
+                json!({ "contract": contract_name, "tx_hash": proof_tx_hash }),
+                format_args!("✅ Aggregated proof tx sent. Tx hash: {}", proof_tx_hash),
+            );
+        }
+        Commands::VerifyCredential { credential, key } => {
+            let jwt = std::fs::read_to_string(&credential).expect("Failed to read the credential");
+            match credential::verify(&jwt, &key) {
+                Ok(verified) => {
+                    let verification = &verified.verification;
+                    host_output::report(
+                        "credential",
+                        json!({
+                            "issuer": verified.issuer,
+                            "id": verification.id,
+                            "scheme": verification.scheme,
+                            "provider": verification.provider,
+                            "contract": verification.contract,
+                            "block_height": verification.block_height,
+                            "tx_hash": verification.tx_hash,
+                        }),
+                        format_args!(
+                            "✅ Credential issued by {}\n{} verified via {} ({}) on {} at height {}, tx {}",
+                            verified.issuer,
+                            verification.id,
+                            verification.scheme,
+                            verification.provider,
+                            verification.contract,
+                            verification.block_height,
+                            verification.tx_hash
+                        ),
+                    );
+                }
+                Err(err) => host_output::fail("credential", format_args!("{:#}", err)),
+            }
+        }
+        Commands::StateShow { contract } => {
+            let contract = contract.unwrap_or_else(|| contract_name.clone());
+            let digest = client
+                .get_contract_state(&contract.clone().into())
+                .await
+                .or_fail("node");
 
-                // Send the proof transaction
-                let proof_tx_hash = client.send_tx_proof(&proof_tx).await.unwrap();
-                println!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash);
+            let state = inspect::decode_state(&digest).or_fail("unknown_state");
+            host_output::report(
+                "state",
+                json!({
+                    "contract": contract,
+                    "schema": inspect::schema(&state),
+                    "state": inspect::to_json(&state).unwrap(),
+                }),
+                format_args!("Contract: {}\n{}", contract, inspect::render(&state).unwrap()),
+            );
+        }
+        Commands::GetState { account } => {
+            let digest = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node");
+            let state = OidcIdentity::from_bytes(&digest.0).or_fail("unknown_state");
+
+            let accounts: Vec<_> = match &account {
+                Some(account) => match state.account(account) {
+                    Some(info) => vec![(account, info)],
+                    None => host_output::fail(
+                        "not_registered",
+                        format_args!("{} is not registered on {}", account, contract_name),
+                    ),
+                },
+                None => state.list_accounts(None, usize::MAX).collect(),
+            };
+            say!(
+                "Contract: {} ({} bytes, {} account(s))",
+                contract_name,
+                digest.0.len(),
+                state.list_accounts(None, usize::MAX).count()
+            );
+            for (account, info) in accounts {
+                host_output::report(
+                    "account_state",
+                    inspect::account_json(account, info),
+                    inspect::render_account(account, info),
+                );
             }
         }
+        Commands::Did { account } => {
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .into();
+            let Some(info) = state.account(&account) else {
+                host_output::fail(
+                    "not_registered",
+                    format_args!("{} is not registered on {}", account, contract_name),
+                );
+            };
+
+            // The companion registries are optional: skip the ones not deployed
+            let attestation_name = &config.contract.attestation_name;
+            let attestations = client
+                .get_contract_state(&attestation_name.clone().into())
+                .await
+                .ok()
+                .and_then(|digest| AttestationRegistry::try_from(digest).ok());
+            let alias_name = &config.contract.alias_name;
+            let aliases = client
+                .get_contract_state(&alias_name.clone().into())
+                .await
+                .ok()
+                .and_then(|digest| AliasRegistry::try_from(digest).ok());
+            let linked = aliases.as_ref().and_then(|aliases| {
+                let handle = aliases.handle_of(&account)?;
+                Some(did::Linked {
+                    registry: alias_name,
+                    handle,
+                    alias: aliases.alias(handle)?,
+                })
+            });
+
+            let document = did::document(
+                &account,
+                info,
+                attestations.as_ref().map(|registry| did::Attestations {
+                    registry: attestation_name,
+                    attestations: registry.attestations(&account),
+                }),
+                linked,
+            );
+            host_output::report(
+                "did",
+                json!({ "document": document }),
+                serde_json::to_string_pretty(&document).unwrap(),
+            );
+        }
+        Commands::Accounts { after, limit } => {
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .into();
+            let page: Vec<_> = state.list_accounts(after.as_deref(), limit).collect();
+            for (account, info) in &page {
+                host_output::report(
+                    "account",
+                    json!({
+                        "account": account,
+                        "nonce": info.nonce,
+                        "subjects": info.hashes.len(),
+                        "provider": info.metadata.provider,
+                        "frozen": info.frozen,
+                    }),
+                    format_args!(
+                        "{}\tnonce {}\t{} subject(s)\t{}{}",
+                        account,
+                        info.nonce,
+                        info.hashes.len(),
+                        info.metadata.provider.as_deref().unwrap_or("-"),
+                        if info.frozen { "\tfrozen" } else { "" }
+                    ),
+                );
+            }
+            if let (true, Some((last, _))) = (page.len() == limit, page.last()) {
+                eprintln!("Next page: --after {}", last);
+            }
+        }
+        Commands::AccountByEmail { email } => {
+            let state: OidcIdentity = client
+                .get_contract_state(&contract_name.clone().into())
+                .await
+                .or_fail("node")
+                .into();
+            match state.account_by_email_hash(&email_hash(&email)) {
+                Ok(account) => host_output::report(
+                    "account",
+                    json!({ "account": account }),
+                    account,
+                ),
+                Err(code) => host_output::fail_with(
+                    "not_found",
+                    code,
+                    json!({ "result_code": code.code() }),
+                ),
+            }
+        }
+        Commands::ConfigSign { key } => {
+            let signature_path = Path::new(config_signature::CONFIG_SIGNATURE_PATH);
+            config_signature::sign(&config, cli.zkvm(), &key, signature_path)
+                .expect("Failed to sign the config");
+            host_output::report(
+                "config_signed",
+                json!({ "path": signature_path }),
+                format_args!(
+                    "✅ Signed {:?} into {}",
+                    config_signature::SignedSettings::of(&config, cli.zkvm()),
+                    signature_path.display()
+                ),
+            );
+        }
+        Commands::Doctor {
+            provider,
+            skip_login,
+        } => {
+            let provider_name = provider.unwrap_or_else(|| cli.provider.clone());
+            let Some(identity_provider) = config.identity_providers.get(&provider_name) else {
+                host_output::fail(
+                    "usage",
+                    format_args!("{} not set in config.toml", provider_name),
+                );
+            };
+            let healthy = doctor::run(
+                &config,
+                &provider_name,
+                identity_provider,
+                cli.strict_keys,
+                cli.flow(),
+                skip_login,
+            )
+            .await;
+            if !healthy {
+                std::process::exit(1);
+            }
+        }
+        Commands::Run { .. } => unreachable!("templates are expanded before dispatch"),
+        Commands::Providers { .. } => unreachable!("providers are listed before dispatch"),
+        Commands::Resume { .. } => unreachable!("flows are resumed before dispatch"),
+        Commands::Config { .. } => unreachable!("the config is validated before loading it"),
+    }
+
+    if let Some((path, template, args)) = template_run {
+        template
+            .record(&path, &args)
+            .expect("Failed to record the template run");
     }
 }
@@ -0,0 +1,792 @@
+#[cfg(test)]
+use std::collections::HashMap;
+use std::path::PathBuf;
+#[cfg(test)]
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use client_sdk::rest_client::{IndexerApiHttpClient, NodeApiHttpClient};
+use node_retry::Backoff;
+use sdk::api::{APIBlock, APIRegisterContract, APITransaction, TransactionStatus};
+use sdk::{
+    BlobTransaction, BlockHeight, ContractName, ProgramId, ProofTransaction, StateDigest,
+    TxContext, TxHash,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+/// The subset of the node API the host relies on.
+///
+/// Keeping it behind a trait lets the host talk to a node over HTTP, over a
+/// Unix domain socket when co-located, or to an in-process fake in tests.
+#[allow(async_fn_in_trait)]
+pub trait NodeTransport {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest>;
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId>;
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash>;
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash>;
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash>;
+    async fn block_height(&self) -> Result<u64>;
+
+    /// Status of the transaction `tx_hash`, as the node's indexer reports it.
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus>;
+
+    /// Context of the block the transaction `tx_hash` was sequenced in, which
+    /// proofs of its blobs commit to. `None` until it is sequenced.
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>>;
+
+    /// Cost the node charges to settle a proof of `proof_size` bytes, for nodes
+    /// that publish one. The current node API doesn't, hence the default.
+    async fn settlement_cost(&self, _proof_size: usize) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// Any node transport selected from the configured `server.host`.
+pub enum Node {
+    Http(NodeApiHttpClient),
+    Unix(UnixSocketNode),
+}
+
+impl Node {
+    /// `unix:///path/to/node.sock` selects the Unix socket transport, anything
+    /// else is treated as an HTTP(S) base URL.
+    pub fn from_url(url: &str) -> Result<Self> {
+        match url.strip_prefix("unix://") {
+            Some(path) => Ok(Node::Unix(UnixSocketNode::new(path))),
+            None => Ok(Node::Http(NodeApiHttpClient::new(url.to_string())?)),
+        }
+    }
+}
+
+/// Reads and blob transactions are retried on failure, see the node-retry
+/// crate: a blob transaction's hash is that of its content, so a resent one
+/// the node already got is no second transaction. Proofs are retried by
+/// [`send_proof`] only, which checks they didn't settle first.
+impl NodeTransport for Node {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest> {
+        node_retry::retry("Reading the contract state", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => node.get_contract_state(contract_name).await,
+                Node::Unix(node) => node.get_contract_state(contract_name).await,
+            }
+        })
+        .await
+    }
+
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId> {
+        node_retry::retry("Reading the program ID", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => node.get_program_id(contract_name).await,
+                Node::Unix(node) => node.get_program_id(contract_name).await,
+            }
+        })
+        .await
+    }
+
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash> {
+        match self {
+            Node::Http(node) => node.register_contract(tx).await,
+            Node::Unix(node) => node.register_contract(tx).await,
+        }
+    }
+
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        node_retry::retry("Sending the blob tx", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => NodeTransport::send_tx_blob(node, tx).await,
+                Node::Unix(node) => node.send_tx_blob(tx).await,
+            }
+        })
+        .await
+    }
+
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash> {
+        match self {
+            Node::Http(node) => NodeTransport::send_tx_proof(node, tx).await,
+            Node::Unix(node) => node.send_tx_proof(tx).await,
+        }
+    }
+
+    async fn block_height(&self) -> Result<u64> {
+        node_retry::retry("Reading the block height", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => NodeTransport::block_height(node).await,
+                Node::Unix(node) => node.block_height().await,
+            }
+        })
+        .await
+    }
+
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        node_retry::retry("Reading the tx status", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => node.tx_status(tx_hash).await,
+                Node::Unix(node) => node.tx_status(tx_hash).await,
+            }
+        })
+        .await
+    }
+
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>> {
+        node_retry::retry("Reading the tx context", &Backoff::default(), || async move {
+            match self {
+                Node::Http(node) => node.tx_context(tx_hash).await,
+                Node::Unix(node) => node.tx_context(tx_hash).await,
+            }
+        })
+        .await
+    }
+
+    async fn settlement_cost(&self, proof_size: usize) -> Result<Option<u64>> {
+        match self {
+            Node::Http(node) => node.settlement_cost(proof_size).await,
+            Node::Unix(node) => node.settlement_cost(proof_size).await,
+        }
+    }
+}
+
+/// Whether a blob transaction of `status` settled, a proof of it having
+/// landed, whatever the outcome.
+fn is_settled(status: &TransactionStatus) -> bool {
+    matches!(status, TransactionStatus::Success | TransactionStatus::Failure)
+}
+
+/// Sends `tx`, proving the blob transaction `blob_tx_hash`, retrying on
+/// failure as long as that transaction didn't settle: once it did, an attempt
+/// whose answer was lost landed, and `None` is returned instead of sending
+/// the proof again. Other transactions moving the contract state meanwhile
+/// don't count.
+pub async fn send_proof(
+    node: &impl NodeTransport,
+    tx: &ProofTransaction,
+    blob_tx_hash: &TxHash,
+) -> Result<Option<TxHash>> {
+    node_retry::retry_send(
+        "Sending the proof tx",
+        &Backoff::default(),
+        || node.send_tx_proof(tx),
+        || async move { Ok(is_settled(&node.tx_status(blob_tx_hash).await?)) },
+    )
+    .await
+}
+
+/// How long [`sequenced_context`] waits for a blob transaction to be sequenced.
+const SEQUENCING_TIMEOUT: Duration = Duration::from_secs(60);
+
+const SEQUENCING_POLL: Duration = Duration::from_secs(1);
+
+/// Waits for the blob transaction `tx_hash` to be sequenced, then returns the
+/// context of its block: proofs consuming an ID token must carry it, for the
+/// contract to check the token's times against the block's.
+pub async fn sequenced_context(node: &impl NodeTransport, tx_hash: &TxHash) -> Result<TxContext> {
+    let deadline = tokio::time::Instant::now() + SEQUENCING_TIMEOUT;
+    loop {
+        if let Some(tx_ctx) = node.tx_context(tx_hash).await? {
+            return Ok(tx_ctx);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!(
+                "{} is still not sequenced after {}s",
+                tx_hash,
+                SEQUENCING_TIMEOUT.as_secs()
+            );
+        }
+        tokio::time::sleep(SEQUENCING_POLL).await;
+    }
+}
+
+/// The context a transaction sent now should land in: the next block, at the
+/// current time. Only good for estimates; proofs use [`sequenced_context`].
+pub async fn next_context(node: &impl NodeTransport) -> Result<TxContext> {
+    Ok(TxContext {
+        block_height: BlockHeight(node.block_height().await? + 1),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        ..Default::default()
+    })
+}
+
+/// The context of transactions sequenced in `block`.
+fn block_context(block: APIBlock) -> Result<TxContext> {
+    Ok(TxContext {
+        block_hash: block.hash,
+        block_height: BlockHeight(block.height),
+        timestamp: block.timestamp.try_into()?,
+        ..Default::default()
+    })
+}
+
+impl NodeTransport for NodeApiHttpClient {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest> {
+        Ok(self.get_contract(contract_name).await?.state)
+    }
+
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId> {
+        Ok(self.get_contract(contract_name).await?.program_id)
+    }
+
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash> {
+        NodeApiHttpClient::register_contract(self, tx).await
+    }
+
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        NodeApiHttpClient::send_tx_blob(self, tx).await
+    }
+
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash> {
+        NodeApiHttpClient::send_tx_proof(self, tx).await
+    }
+
+    async fn block_height(&self) -> Result<u64> {
+        Ok(self.get_block_height().await?.0)
+    }
+
+    /// The indexer is served alongside the node's REST API.
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        let indexer = IndexerApiHttpClient::new(self.url.to_string())?;
+        Ok(indexer
+            .get_transaction_with_hash(tx_hash)
+            .await?
+            .transaction_status)
+    }
+
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>> {
+        let indexer = IndexerApiHttpClient::new(self.url.to_string())?;
+        let tx = indexer.get_transaction_with_hash(tx_hash).await?;
+        let Some(block_hash) = tx.block_hash else {
+            return Ok(None);
+        };
+        block_context(indexer.get_block_by_hash(&block_hash).await?).map(Some)
+    }
+}
+
+/// Speaks the node's REST API as plain HTTP/1.1 over a Unix domain socket.
+pub struct UnixSocketNode {
+    path: PathBuf,
+}
+
+impl UnixSocketNode {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixSocketNode { path: path.into() }
+    }
+
+    async fn request(&self, method: &str, route: &str, body: Option<Vec<u8>>) -> Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.path)
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.path.display()))?;
+
+        let body = body.unwrap_or_default();
+        let head = format!(
+            "{method} /{route} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&body).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        let split = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| anyhow!("Malformed HTTP response from node"))?;
+        let head = String::from_utf8_lossy(&response[..split]);
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|status_line| status_line.split_whitespace().nth(1))
+            .ok_or_else(|| anyhow!("Malformed HTTP status line"))?
+            .to_string();
+        let chunked = lines.any(|line| {
+            line.split_once(':').is_some_and(|(name, value)| {
+                name.trim().eq_ignore_ascii_case("transfer-encoding")
+                    && value.trim().eq_ignore_ascii_case("chunked")
+            })
+        });
+        let body = &response[split + 4..];
+        let body = if chunked {
+            decode_chunked(body)?
+        } else {
+            body.to_vec()
+        };
+        if !status.starts_with('2') {
+            bail!(
+                "{} /{} failed with status {}: {}",
+                method,
+                route,
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+        Ok(body)
+    }
+
+    async fn post<T: Serialize, R: DeserializeOwned>(&self, route: &str, body: &T) -> Result<R> {
+        let response = self
+            .request("POST", route, Some(serde_json::to_vec(body)?))
+            .await?;
+        Ok(serde_json::from_slice(&response)?)
+    }
+}
+
+/// Reassembles a body sent with `Transfer-Encoding: chunked`, as the node
+/// sends responses whose length it doesn't know upfront.
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let end = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("Malformed chunk size in HTTP response"))?;
+        let line = std::str::from_utf8(&body[..end])?;
+        // Chunk extensions follow the size after a `;`
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .with_context(|| format!("Malformed chunk size {size:?} in HTTP response"))?;
+        body = &body[end + 2..];
+        if size == 0 {
+            // Trailers, if any, carry nothing the host reads
+            return Ok(decoded);
+        }
+        let chunk = body
+            .get(..size)
+            .ok_or_else(|| anyhow!("Truncated chunk in HTTP response"))?;
+        decoded.extend_from_slice(chunk);
+        body = body[size..]
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| anyhow!("Malformed chunk in HTTP response"))?;
+    }
+}
+
+impl NodeTransport for UnixSocketNode {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest> {
+        let response = self
+            .request("GET", &format!("v1/contract/{}", contract_name), None)
+            .await?;
+        let contract: serde_json::Value = serde_json::from_slice(&response)?;
+        Ok(serde_json::from_value(contract["state"].clone())?)
+    }
+
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId> {
+        let response = self
+            .request("GET", &format!("v1/contract/{}", contract_name), None)
+            .await?;
+        let contract: serde_json::Value = serde_json::from_slice(&response)?;
+        Ok(serde_json::from_value(contract["program_id"].clone())?)
+    }
+
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash> {
+        self.post("v1/contract/register", tx).await
+    }
+
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        self.post("v1/tx/send/blob", tx).await
+    }
+
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash> {
+        self.post("v1/tx/send/proof", tx).await
+    }
+
+    async fn block_height(&self) -> Result<u64> {
+        let response = self.request("GET", "v1/da/block/height", None).await?;
+        Ok(serde_json::from_slice(&response)?)
+    }
+
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        let response = self
+            .request("GET", &format!("v1/indexer/transaction/hash/{}", tx_hash), None)
+            .await?;
+        let tx: serde_json::Value = serde_json::from_slice(&response)?;
+        Ok(serde_json::from_value(tx["transaction_status"].clone())?)
+    }
+
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>> {
+        let response = self
+            .request("GET", &format!("v1/indexer/transaction/hash/{}", tx_hash), None)
+            .await?;
+        let tx: APITransaction = serde_json::from_slice(&response)?;
+        let Some(block_hash) = tx.block_hash else {
+            return Ok(None);
+        };
+        let response = self
+            .request("GET", &format!("v1/indexer/block/hash/{}", block_hash.0), None)
+            .await?;
+        block_context(serde_json::from_slice(&response)?).map(Some)
+    }
+}
+
+/// In-process stand-in for a devnet, for tests.
+///
+/// Registered contracts keep their initial state until the test applies a
+/// new one with [`InProcessNode::set_state`]; proofs are recorded, not
+/// verified, and blob transactions stay sequenced until [`InProcessNode::settle`].
+/// Each transaction gets a block of its own, at the time set with
+/// [`InProcessNode::set_time`].
+#[cfg(test)]
+#[derive(Default)]
+pub struct InProcessNode {
+    contracts: Mutex<HashMap<ContractName, StateDigest>>,
+    program_ids: Mutex<HashMap<ContractName, ProgramId>>,
+    pub blob_txs: Mutex<Vec<BlobTransaction>>,
+    pub proof_txs: Mutex<Vec<ProofTransaction>>,
+    settled: Mutex<HashMap<TxHash, TransactionStatus>>,
+    contexts: Mutex<HashMap<TxHash, TxContext>>,
+    time: Mutex<u64>,
+}
+
+#[cfg(test)]
+impl InProcessNode {
+    pub fn set_state(&self, contract_name: &ContractName, state: StateDigest) {
+        self.contracts
+            .lock()
+            .unwrap()
+            .insert(contract_name.clone(), state);
+    }
+
+    pub fn settle(&self, tx_hash: &TxHash, status: TransactionStatus) {
+        self.settled.lock().unwrap().insert(tx_hash.clone(), status);
+    }
+
+    /// Timestamp of the blocks of the transactions sent from now on.
+    pub fn set_time(&self, timestamp: u64) {
+        *self.time.lock().unwrap() = timestamp;
+    }
+
+    fn next_hash(&self, prefix: &str) -> TxHash {
+        let count = self.blob_txs.lock().unwrap().len() + self.proof_txs.lock().unwrap().len();
+        TxHash(format!("{prefix}{count:08x}"))
+    }
+}
+
+#[cfg(test)]
+impl NodeTransport for InProcessNode {
+    async fn get_contract_state(&self, contract_name: &ContractName) -> Result<StateDigest> {
+        self.contracts
+            .lock()
+            .unwrap()
+            .get(contract_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Contract {} not found", contract_name))
+    }
+
+    async fn get_program_id(&self, contract_name: &ContractName) -> Result<ProgramId> {
+        self.program_ids
+            .lock()
+            .unwrap()
+            .get(contract_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Contract {} not found", contract_name))
+    }
+
+    async fn register_contract(&self, tx: &APIRegisterContract) -> Result<TxHash> {
+        self.set_state(&tx.contract_name, tx.state_digest.clone());
+        self.program_ids
+            .lock()
+            .unwrap()
+            .insert(tx.contract_name.clone(), tx.program_id.clone());
+        Ok(self.next_hash("register"))
+    }
+
+    async fn send_tx_blob(&self, tx: &BlobTransaction) -> Result<TxHash> {
+        let hash = self.next_hash("blob");
+        self.blob_txs.lock().unwrap().push(tx.clone());
+        let tx_ctx = TxContext {
+            block_height: BlockHeight(self.block_height().await?),
+            timestamp: *self.time.lock().unwrap(),
+            ..Default::default()
+        };
+        self.contexts.lock().unwrap().insert(hash.clone(), tx_ctx);
+        Ok(hash)
+    }
+
+    async fn send_tx_proof(&self, tx: &ProofTransaction) -> Result<TxHash> {
+        let hash = self.next_hash("proof");
+        self.proof_txs.lock().unwrap().push(tx.clone());
+        Ok(hash)
+    }
+
+    /// One block per transaction received.
+    async fn block_height(&self) -> Result<u64> {
+        Ok((self.blob_txs.lock().unwrap().len() + self.proof_txs.lock().unwrap().len()) as u64)
+    }
+
+    async fn tx_status(&self, tx_hash: &TxHash) -> Result<TransactionStatus> {
+        Ok(self
+            .settled
+            .lock()
+            .unwrap()
+            .get(tx_hash)
+            .cloned()
+            .unwrap_or(TransactionStatus::Sequenced))
+    }
+
+    async fn tx_context(&self, tx_hash: &TxHash) -> Result<Option<TxContext>> {
+        Ok(self.contexts.lock().unwrap().get(tx_hash).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use e2e::TestIdp;
+    use oidc_identity::OidcIdentity;
+    use oidc_provider::code::ResultCode;
+    use oidc_provider::failure::Failure;
+    use oidc_provider::metadata::AccountMetadata;
+    use oidc_provider::nonce::NonceStrategy;
+    use oidc_provider::{AdminOperation, IdentityAction, PrivateInput};
+    use sdk::{ContractInput, Digestable};
+
+    use super::*;
+
+    const CONTRACT: &str = "oidc_identity";
+    const ALICE: &str = "alice.oidc_identity";
+
+    async fn registered_node() -> InProcessNode {
+        node_with(OidcIdentity::new()).await
+    }
+
+    async fn node_with(initial_state: OidcIdentity) -> InProcessNode {
+        let node = InProcessNode::default();
+        node.register_contract(&APIRegisterContract {
+            verifier: "risc0".into(),
+            program_id: ProgramId(vec![]),
+            state_digest: initial_state.as_digest(),
+            contract_name: CONTRACT.into(),
+        })
+        .await
+        .unwrap();
+        node
+    }
+
+    /// Sends `action` from `account`, then executes it natively with the
+    /// context of the block it was sequenced in, as the host proves it.
+    async fn settle(
+        node: &InProcessNode,
+        account: &str,
+        action: IdentityAction,
+        private_input: PrivateInput,
+    ) -> Result<OidcIdentity, ResultCode> {
+        let blob_tx = BlobTransaction {
+            identity: account.into(),
+            blobs: vec![action.as_blob(CONTRACT.into())],
+        };
+        let initial_state = node.get_contract_state(&CONTRACT.into()).await.unwrap();
+        let tx_hash = node.send_tx_blob(&blob_tx).await.unwrap();
+        let input = ContractInput {
+            initial_state,
+            identity: blob_tx.identity,
+            tx_hash: tx_hash.clone(),
+            private_input: private_input.to_bytes(),
+            tx_ctx: Some(sequenced_context(node, &tx_hash).await.unwrap()),
+            blobs: blob_tx.blobs,
+            index: sdk::BlobIndex(0),
+        };
+        match oidc_identity::execute(input) {
+            Ok((_, state, _)) => {
+                node.set_state(&CONTRACT.into(), state.as_digest());
+                Ok(state)
+            }
+            Err(err) => Err(Failure::parse(&err).expect("undecodable failure").code),
+        }
+    }
+
+    fn register(idp: &TestIdp, account: &str) -> IdentityAction {
+        IdentityAction::RegisterIdentity {
+            account: account.to_string(),
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+            recovery_codes: vec![],
+            metadata: AccountMetadata::default(),
+            nonce_strategy: NonceStrategy::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn proofs_check_tokens_against_the_time_of_their_block() {
+        let idp = TestIdp::new();
+        let node = registered_node().await;
+        let token = PrivateInput::Token(idp.id_token("alice"));
+
+        // The test tokens expire in 2030
+        node.set_time(1_900_000_000);
+        assert_eq!(
+            settle(&node, ALICE, register(&idp, ALICE), token.clone())
+                .await
+                .err(),
+            Some(ResultCode::TokenExpired)
+        );
+        node.set_time(1_700_000_000);
+        let state = settle(&node, ALICE, register(&idp, ALICE), token)
+            .await
+            .unwrap();
+        assert!(state.account(ALICE).is_some());
+    }
+
+    #[tokio::test]
+    async fn pausing_waits_for_the_governance_delay_in_blocks() {
+        let idp = TestIdp::new();
+        let node = node_with(OidcIdentity::new().with_admin(ALICE.to_string(), 2)).await;
+        node.set_time(1_700_000_000);
+        let token = PrivateInput::Token(idp.id_token("alice"));
+        settle(&node, ALICE, register(&idp, ALICE), token.clone())
+            .await
+            .unwrap();
+
+        // Proposed in block 2, so executable from block 4
+        let propose = IdentityAction::ProposeAdminOperation {
+            admin_context: idp.context.clone(),
+            admin_jwk_pub_key: idp.jwk(),
+            operation: AdminOperation::Pause,
+        };
+        settle(&node, ALICE, propose, token.clone()).await.unwrap();
+        let execute = IdentityAction::ExecuteAdminOperation {
+            admin_context: idp.context.clone(),
+            admin_jwk_pub_key: idp.jwk(),
+            id: 0,
+        };
+        assert_eq!(
+            settle(&node, ALICE, execute.clone(), token.clone())
+                .await
+                .err(),
+            Some(ResultCode::AdminOperationTimeLocked)
+        );
+        let state = settle(&node, ALICE, execute, token).await.unwrap();
+        assert!(state.governance().paused);
+    }
+
+    #[tokio::test]
+    async fn session_keys_expire_at_the_block_the_host_computed() {
+        let idp = TestIdp::new();
+        let node = registered_node().await;
+        node.set_time(1_700_000_000);
+        let token = PrivateInput::Token(idp.id_token("alice"));
+        settle(&node, ALICE, register(&idp, ALICE), token.clone())
+            .await
+            .unwrap();
+
+        // Computed in block 1, the key is valid until block 4
+        let key = crate::session_key::generate();
+        let expiry = crate::session_key::expiry(&node, 3).await.unwrap();
+        let authorize = IdentityAction::AuthorizeSessionKey {
+            account: ALICE.to_string(),
+            nonce: 0,
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+            pubkey: crate::session_key::public_key(&key),
+            expiry,
+        };
+        settle(&node, ALICE, authorize, token).await.unwrap();
+        let verify = |nonce| IdentityAction::VerifyWithSessionKey {
+            account: ALICE.to_string(),
+            nonce,
+        };
+        let sign = |nonce| crate::session_key::sign(&key, CONTRACT, ALICE, nonce, &[]);
+        let state = settle(&node, ALICE, verify(1), sign(1)).await.unwrap();
+        assert_eq!(state.get_nonce(ALICE), Ok(2));
+        assert_eq!(
+            settle(&node, ALICE, verify(2), sign(2)).await.err(),
+            Some(ResultCode::SessionKeyNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn recovery_completes_once_its_delay_in_blocks_has_passed() {
+        let idp = TestIdp::new();
+        let node = registered_node().await;
+        node.set_time(1_700_000_000);
+        let token = PrivateInput::Token(idp.id_token("alice"));
+        settle(&node, ALICE, register(&idp, ALICE), token.clone())
+            .await
+            .unwrap();
+        let add_provider = IdentityAction::AddRecoveryProvider {
+            account: ALICE.to_string(),
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+            recovery_context: idp.context.clone(),
+            recovery_jwk_pub_key: idp.jwk(),
+            delay: 2,
+        };
+        let pair = PrivateInput::TokenPair {
+            old_token: idp.id_token("alice"),
+            new_token: idp.id_token("bob"),
+        };
+        settle(&node, ALICE, add_provider, pair).await.unwrap();
+
+        // Started in block 3, so complete from block 5
+        let recover = IdentityAction::RecoverIdentity {
+            account: ALICE.to_string(),
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+        };
+        let recovery_token = PrivateInput::Token(idp.id_token("bob"));
+        settle(&node, ALICE, recover.clone(), recovery_token.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            settle(&node, ALICE, recover.clone(), recovery_token.clone())
+                .await
+                .err(),
+            Some(ResultCode::RecoveryTimeLocked)
+        );
+        let state = settle(&node, ALICE, recover, recovery_token)
+            .await
+            .unwrap();
+        let info = state.account(ALICE).unwrap();
+        assert!(info.recovery_provider.is_none());
+        assert_eq!(info.hashes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pruned_accounts_come_back_with_the_archive_journal() {
+        let idp = TestIdp::new();
+        let node = node_with(OidcIdentity::new().with_pruning(2)).await;
+        node.set_time(1_700_000_000);
+        let token = PrivateInput::Token(idp.id_token("alice"));
+        let state = settle(&node, ALICE, register(&idp, ALICE), token)
+            .await
+            .unwrap();
+
+        // Active in block 1, so prunable from block 3
+        let accounts = vec![ALICE.to_string()];
+        let prune = IdentityAction::PruneInactive {
+            accounts: accounts.clone(),
+        };
+        let no_input = PrivateInput::Batch(vec![]);
+        assert_eq!(
+            settle(&node, ALICE, prune.clone(), no_input.clone())
+                .await
+                .err(),
+            Some(ResultCode::AccountStillActive)
+        );
+        let archived = crate::archive_journal::archived(&state, &accounts).unwrap();
+        let state = settle(&node, ALICE, prune, no_input.clone())
+            .await
+            .unwrap();
+        assert!(state.account(ALICE).is_none());
+
+        let mut journal = crate::archive_journal::ArchiveJournal::default();
+        journal.record(&archived).unwrap();
+        let (record, proof) = journal.proof(&state, ALICE).unwrap();
+        let reactivate = IdentityAction::Reactivate {
+            account: ALICE.to_string(),
+            record,
+            proof,
+        };
+        let state = settle(&node, ALICE, reactivate, no_input).await.unwrap();
+        assert!(state.account(ALICE).is_some());
+    }
+
+    #[test]
+    fn chunked_bodies_are_reassembled() {
+        let body = b"5\r\n{\"a\":\r\n3;ext=1\r\n12}\r\n0\r\n\r\n";
+        assert_eq!(decode_chunked(body).unwrap(), b"{\"a\":12}");
+        assert_eq!(decode_chunked(b"0\r\n\r\n").unwrap(), b"");
+
+        assert!(decode_chunked(b"5\r\n{\"a\"").is_err());
+        assert!(decode_chunked(b"5\r\n{\"a\":1\r\n0\r\n\r\n").is_err());
+        assert!(decode_chunked(b"x\r\n{\r\n0\r\n\r\n").is_err());
+        assert!(decode_chunked(b"{\"a\":12}").is_err());
+    }
+}
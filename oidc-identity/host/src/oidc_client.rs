@@ -1,13 +1,18 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
-use jsonwebtoken::decode_header;
+use anyhow::{anyhow, bail, Context, Result};
+use host_output::say;
+use jsonwebtoken::{decode_header, Algorithm, DecodingKey, Validation};
+use oidc_provider::{token_nonce, JwkPublicKey};
 use openidconnect::{
     core::{
         CoreAuthDisplay,
         CoreAuthPrompt,
         CoreAuthenticationFlow,
         CoreClient,
+        CoreDeviceAuthorizationResponse,
         CoreErrorResponseType,
         CoreGenderClaim,
         CoreIdToken,
@@ -21,11 +26,13 @@ use openidconnect::{
         // CoreUserInfoClaims,
     },
     reqwest, AccessToken, AccessTokenHash, AuthorizationCode, Client, ClientId, ClientSecret,
-    CsrfToken, EmptyAdditionalClaims, EmptyExtraTokenFields, EndpointMaybeSet, EndpointNotSet,
-    EndpointSet, IdTokenFields, IssuerUrl, Nonce, OAuth2TokenResponse, PkceCodeChallenge,
-    PkceCodeVerifier, RedirectUrl, RevocationErrorResponseType, Scope, StandardErrorResponse,
-    StandardTokenIntrospectionResponse, StandardTokenResponse, TokenResponse,
+    CsrfToken, DeviceAuthorizationUrl, EmptyAdditionalClaims, EmptyExtraTokenFields,
+    EndpointMaybeSet, EndpointNotSet, EndpointSet, IdTokenFields, IssuerUrl, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken,
+    RevocationErrorResponseType, Scope, StandardErrorResponse, StandardTokenIntrospectionResponse,
+    StandardTokenResponse, TokenResponse,
 };
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -64,11 +71,185 @@ pub type AuthClient = Client<
 #[derive(Debug, Clone)]
 pub struct OIDCClient {}
 
+/// How long JWKS are kept when the provider sends no `Cache-Control` max-age.
+const DEFAULT_JWKS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long JWKS are kept at most, whatever the provider allows, so that a
+/// removed key stops being trusted.
+const MAX_JWKS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Fetches of a JWKS before giving up.
+const JWKS_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry of a JWKS fetch, doubled on each next one,
+/// plus up to as much random jitter.
+const JWKS_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Provider key sets, cached per JWKS URL for the `max-age` of their
+/// `Cache-Control`. Key sets in the last fifth of their lifetime are still
+/// served while being refreshed in the background, and failed fetches are
+/// retried with jittered exponential backoff, so that repeated verifications
+/// don't hammer the provider. Clones share the cache.
+#[derive(Clone, Default)]
+pub struct JwksCache {
+    entries: Arc<Mutex<HashMap<String, CachedJwks>>>,
+}
+
+struct CachedJwks {
+    keys: HashMap<String, Jwk>,
+    fetched: Instant,
+    ttl: Duration,
+    refreshing: bool,
+}
+
+impl JwksCache {
+    /// The key set at `jwk_url`, from the cache while it is fresh.
+    pub async fn get(&self, jwk_url: &str) -> Result<HashMap<String, Jwk>, String> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(entry) = entries.get_mut(jwk_url) {
+                let age = entry.fetched.elapsed();
+                if age < entry.ttl {
+                    if age >= entry.ttl * 4 / 5 && !entry.refreshing {
+                        entry.refreshing = true;
+                        let (cache, jwk_url) = (self.clone(), jwk_url.to_string());
+                        tokio::spawn(async move { cache.refresh(&jwk_url).await });
+                    }
+                    return Ok(entry.keys.clone());
+                }
+            }
+        }
+        self.refresh(jwk_url).await
+    }
+
+    /// Fetches the key set at `jwk_url` again and caches it. A failed refresh
+    /// leaves the cached keys until they expire.
+    async fn refresh(&self, jwk_url: &str) -> Result<HashMap<String, Jwk>, String> {
+        let fetched = fetch_jwks_with_backoff(jwk_url).await;
+        let mut entries = self.entries.lock().unwrap();
+        match fetched {
+            Ok((keys, ttl)) => {
+                entries.insert(
+                    jwk_url.to_string(),
+                    CachedJwks {
+                        keys: keys.clone(),
+                        fetched: Instant::now(),
+                        ttl,
+                        refreshing: false,
+                    },
+                );
+                Ok(keys)
+            }
+            Err(err) => {
+                if let Some(entry) = entries.get_mut(jwk_url) {
+                    entry.refreshing = false;
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// `OIDCClient::fetch_jwks_with_ttl`, retried up to `JWKS_ATTEMPTS` times.
+async fn fetch_jwks_with_backoff(
+    jwk_url: &str,
+) -> Result<(HashMap<String, Jwk>, Duration), String> {
+    let mut delay = JWKS_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match OIDCClient::fetch_jwks_with_ttl(jwk_url).await {
+            Ok(fetched) => return Ok(fetched),
+            Err(_) if attempt < JWKS_ATTEMPTS => {
+                let jitter = rand::thread_rng().gen_range(0..=delay.as_millis() as u64);
+                tokio::time::sleep(delay + Duration::from_millis(jitter)).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(format!("{} (after {} attempts)", err, attempt)),
+        }
+    }
+}
+
+/// How long a response may be cached, from its `Cache-Control` header.
+fn cache_ttl(cache_control: Option<&str>) -> Duration {
+    let Some(cache_control) = cache_control else {
+        return DEFAULT_JWKS_TTL;
+    };
+    let mut ttl = DEFAULT_JWKS_TTL;
+    for directive in cache_control.split(',').map(str::trim) {
+        if directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("no-cache")
+        {
+            return Duration::ZERO;
+        }
+        if let Some(seconds) = directive
+            .strip_prefix("max-age=")
+            .and_then(|seconds| seconds.parse().ok())
+        {
+            ttl = Duration::from_secs(seconds);
+        }
+    }
+    ttl.min(MAX_JWKS_TTL)
+}
+
+/// How the user is logged in, selected with `--flow`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Flow {
+    /// Authorization code flow, redirecting the browser to `server_url`
+    #[default]
+    Browser,
+    /// Device authorization grant: the user approves a code on any other
+    /// device, for machines without a browser
+    Device,
+}
+
+/// A key of a provider's JWKS: RSA (`n`, `e`), EC (`crv`, `x`, `y`) or OKP
+/// (`crv`, `x`).
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Jwk {
     pub kid: String,
+    pub kty: String,
+    #[serde(default)]
     pub n: String,
+    #[serde(default)]
     pub e: String,
+    #[serde(default)]
+    pub crv: String,
+    #[serde(default)]
+    pub x: String,
+    #[serde(default)]
+    pub y: String,
+}
+
+impl Jwk {
+    /// The key as the contract verifies ID tokens with it.
+    pub fn public_key(&self) -> JwkPublicKey {
+        JwkPublicKey {
+            kid: self.kid.clone(),
+            kty: self.kty.clone(),
+            n: self.n.clone(),
+            e: self.e.clone(),
+            crv: self.crv.clone(),
+            x: self.x.clone(),
+            y: self.y.clone(),
+        }
+    }
+
+    /// Key material, compared to detect a key id reused for another key.
+    pub fn material(&self) -> (String, String) {
+        match self.kty.as_str() {
+            "EC" | "OKP" => (self.x.clone(), self.y.clone()),
+            _ => (self.n.clone(), self.e.clone()),
+        }
+    }
+}
+
+/// Parameters of a JARM authorization response.
+#[derive(Deserialize, Debug)]
+struct JarmResponse {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
 }
 
 pub fn build_http_client() -> reqwest::Client {
@@ -108,28 +289,226 @@ impl OIDCClient {
         Ok(client)
     }
 
-    pub fn generate_auth_url(client: &AuthClient) -> (String, CsrfToken, Nonce, PkceCodeVerifier) {
+    /// With `offline`, also asks for a refresh token (`offline_access`), so
+    /// ID tokens can be renewed without the browser. With `jarm`, asks for the
+    /// response as a signed JWT, see [`OIDCClient::verify_jarm_response`].
+    /// With `bind_nonce`, the ID token is bound to that account nonce, see
+    /// [`oidc_provider::token_nonce`].
+    pub fn generate_auth_url(
+        client: &AuthClient,
+        offline: bool,
+        jarm: bool,
+        bind_nonce: Option<u32>,
+    ) -> (String, CsrfToken, Nonce, PkceCodeVerifier) {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-        let (auth_url, csrf_token, nonce) = client
+        let mut request = client
             .authorize_url(
                 CoreAuthenticationFlow::AuthorizationCode,
                 CsrfToken::new_random,
-                Nonce::new_random,
+                move || match bind_nonce {
+                    Some(nonce) => Nonce::new(token_nonce::bound_nonce(
+                        Nonce::new_random().secret(),
+                        nonce,
+                    )),
+                    None => Nonce::new_random(),
+                },
             )
             .add_scope(Scope::new("openid".to_string()))
             .add_scope(Scope::new("profile".to_string()))
-            .add_scope(Scope::new("email".to_string()))
-            .set_pkce_challenge(pkce_challenge)
-            .url();
+            .add_scope(Scope::new("email".to_string()));
+        if offline {
+            request = request
+                .add_scope(Scope::new("offline_access".to_string()))
+                .add_prompt(CoreAuthPrompt::Consent);
+        }
+        if jarm {
+            request = request.add_extra_param("response_mode", "jwt");
+        }
+        let (auth_url, csrf_token, nonce) = request.set_pkce_challenge(pkce_challenge).url();
 
         (auth_url.to_string(), csrf_token, nonce, pkce_verifier)
     }
 
+    /// The provider's discovery document, for the fields `openidconnect`
+    /// doesn't model, such as the PAR endpoint.
+    pub async fn fetch_discovery(issuer_url: &str) -> Result<Value> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer_url.trim_end_matches('/')
+        );
+        let body = build_http_client()
+            .get(&url)
+            .send()
+            .await
+            .context("Discovery request failed")?
+            .text()
+            .await
+            .context("Failed to read the discovery document")?;
+        serde_json::from_str(&body).context("Invalid discovery document")
+    }
+
+    /// Pushes the parameters of `auth_url` to the provider's pushed
+    /// authorization request endpoint (RFC 9126), authenticating the client,
+    /// and returns the URL to open instead: it only carries the `request_uri`.
+    pub async fn push_authorization_request(
+        issuer_url: &str,
+        client_id: &str,
+        client_secret: &str,
+        auth_url: &str,
+    ) -> Result<String> {
+        let discovery = Self::fetch_discovery(issuer_url).await?;
+        let endpoint = discovery["pushed_authorization_request_endpoint"]
+            .as_str()
+            .ok_or_else(|| anyhow!("{} has no PAR endpoint", issuer_url))?;
+
+        let mut auth_url = Url::parse(auth_url).context("Invalid authorization URL")?;
+        let params: Vec<(String, String)> = auth_url.query_pairs().into_owned().collect();
+        let resp = build_http_client()
+            .post(endpoint)
+            .basic_auth(client_id, Some(client_secret))
+            .form(&params)
+            .send()
+            .await
+            .context("Pushed authorization request failed")?;
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .context("Failed to read the pushed authorization response")?;
+        if !status.is_success() {
+            bail!(
+                "Pushed authorization request failed with status {}: {}",
+                status,
+                body
+            );
+        }
+
+        let pushed: Value =
+            serde_json::from_str(&body).context("Invalid pushed authorization response")?;
+        let request_uri = pushed["request_uri"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Pushed authorization response has no request_uri"))?;
+        auth_url
+            .query_pairs_mut()
+            .clear()
+            .append_pair("client_id", client_id)
+            .append_pair("request_uri", request_uri);
+        Ok(auth_url.to_string())
+    }
+
+    /// Runs the device authorization grant (RFC 8628): prints the URL to open
+    /// and the code to enter there, then polls the provider until the user
+    /// approves. With `offline`, also asks for a refresh token. The ID token
+    /// carries no nonce, see [`OIDCClient::verify_refreshed_id_token`].
+    pub async fn device_flow(
+        client: &AuthClient,
+        issuer_url: &str,
+        offline: bool,
+    ) -> Result<(CoreIdToken, AccessToken, Option<RefreshToken>)> {
+        let discovery = Self::fetch_discovery(issuer_url).await?;
+        let endpoint = discovery["device_authorization_endpoint"]
+            .as_str()
+            .ok_or_else(|| anyhow!("{} has no device authorization endpoint", issuer_url))?;
+        let client = client.clone().set_device_authorization_url(
+            DeviceAuthorizationUrl::new(endpoint.to_string())
+                .context("Invalid device authorization endpoint")?,
+        );
+        let http_client = build_http_client();
+
+        let mut request = client
+            .exchange_device_code()
+            .add_scope(Scope::new("profile".to_string()))
+            .add_scope(Scope::new("email".to_string()));
+        if offline {
+            request = request.add_scope(Scope::new("offline_access".to_string()));
+        }
+        let details: CoreDeviceAuthorizationResponse = request
+            .request_async(&http_client)
+            .await
+            .map_err(|err| anyhow!("Device authorization request failed: {}", err))?;
+
+        say!("On any device, open:");
+        say!("{}", details.verification_uri().as_str());
+        say!("and enter the code {}", details.user_code().secret());
+
+        let token_response = client
+            .exchange_device_access_token(&details)?
+            .request_async(&http_client, tokio::time::sleep, None)
+            .await
+            .map_err(|err| anyhow!("Device authorization failed: {}", err))?;
+
+        let id_token = token_response
+            .id_token()
+            .cloned()
+            .ok_or_else(|| anyhow!("Server did not return an ID token"))?;
+
+        Ok((
+            id_token,
+            token_response.access_token().clone(),
+            token_response.refresh_token().cloned(),
+        ))
+    }
+
+    /// Validates a JARM authorization response against the provider's `keys`:
+    /// signature, issuer, audience (the client id), expiry and `state`, then
+    /// returns the authorization code it carries. Like ID tokens, responses
+    /// must be RSA or P-256 signed; encrypted responses aren't supported.
+    pub fn verify_jarm_response(
+        response: &str,
+        keys: &HashMap<String, Jwk>,
+        issuer_url: &str,
+        client_id: &str,
+        state: &CsrfToken,
+    ) -> Result<String> {
+        let header = decode_header(response).context("Invalid JARM response header")?;
+        if !matches!(
+            header.alg,
+            Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::PS384
+                | Algorithm::PS512
+                | Algorithm::ES256
+                | Algorithm::EdDSA
+        ) {
+            bail!("Unsupported JARM signing algorithm {:?}", header.alg);
+        }
+        let jwk = Self::find_jwk(keys, response).map_err(|err| anyhow!(err))?;
+        let key = match jwk.kty.as_str() {
+            "EC" => DecodingKey::from_ec_components(&jwk.x, &jwk.y)?,
+            "OKP" => DecodingKey::from_ed_components(&jwk.x)?,
+            _ => DecodingKey::from_rsa_components(&jwk.n, &jwk.e)?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer_url, issuer_url.trim_end_matches('/')]);
+        validation.set_audience(&[client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let response = jsonwebtoken::decode::<JarmResponse>(response, &key, &validation)
+            .context("Failed to verify the JARM response")?
+            .claims;
+
+        if let Some(error) = response.error {
+            bail!(
+                "Authorization failed: {} {}",
+                error,
+                response.error_description.unwrap_or_default()
+            );
+        }
+        if response.state.as_deref() != Some(state.secret().as_str()) {
+            bail!("JARM response state does not match the request");
+        }
+        response
+            .code
+            .ok_or_else(|| anyhow!("JARM response carries no authorization code"))
+    }
+
     pub async fn exchange_code_for_tokens(
         client: &AuthClient,
         auth_code: String,
         pkce_verifier: PkceCodeVerifier,
-    ) -> anyhow::Result<(CoreIdToken, AccessToken)> {
+    ) -> anyhow::Result<(CoreIdToken, AccessToken, Option<RefreshToken>)> {
         let token_response = client
             .exchange_code(AuthorizationCode::new(auth_code))?
             .set_pkce_verifier(pkce_verifier)
@@ -142,7 +521,49 @@ impl OIDCClient {
             .cloned()
             .ok_or_else(|| anyhow!("Server did not return an ID token"))?;
 
-        Ok((id_token, token_response.access_token().clone()))
+        Ok((
+            id_token,
+            token_response.access_token().clone(),
+            token_response.refresh_token().cloned(),
+        ))
+    }
+
+    /// Trades a refresh token for a fresh ID token and access token. Returns
+    /// the rotated refresh token when the provider issues a new one.
+    pub async fn refresh_id_token(
+        client: &AuthClient,
+        refresh_token: &RefreshToken,
+    ) -> anyhow::Result<(CoreIdToken, AccessToken, Option<RefreshToken>)> {
+        let token_response = client
+            .exchange_refresh_token(refresh_token)?
+            .request_async(&build_http_client())
+            .await
+            .map_err(|err| anyhow!("Failed to refresh tokens: {}", err))?;
+
+        let id_token = token_response
+            .id_token()
+            .cloned()
+            .ok_or_else(|| anyhow!("Server did not return an ID token on refresh"))?;
+
+        Ok((
+            id_token,
+            token_response.access_token().clone(),
+            token_response.refresh_token().cloned(),
+        ))
+    }
+
+    /// Like `verify_id_token`, for ID tokens obtained by refresh or through
+    /// the device flow, which carry no nonce.
+    pub fn verify_refreshed_id_token(
+        client: &AuthClient,
+        id_token: &CoreIdToken,
+    ) -> anyhow::Result<CoreIdTokenClaims> {
+        let id_token_verifier = client.id_token_verifier();
+
+        id_token
+            .claims(&id_token_verifier, |_: Option<&Nonce>| Ok(()))
+            .cloned()
+            .context("Failed to verify refreshed OpenID Connect ID token")
     }
 
     pub fn verify_id_token(
@@ -194,9 +615,23 @@ impl OIDCClient {
     // }
 
     pub async fn fetch_jwks(jwk_url: &str) -> Result<HashMap<String, Jwk>, String> {
+        Ok(Self::fetch_jwks_with_ttl(jwk_url).await?.0)
+    }
+
+    /// Like `fetch_jwks`, along with how long the keys may be cached for, see
+    /// [`JwksCache`].
+    async fn fetch_jwks_with_ttl(
+        jwk_url: &str,
+    ) -> Result<(HashMap<String, Jwk>, Duration), String> {
         let resp = reqwest::get(jwk_url)
             .await
+            .and_then(|resp| resp.error_for_status())
             .map_err(|e| format!("HTTP request failed: {}", e))?;
+        let ttl = cache_ttl(
+            resp.headers()
+                .get(reqwest::header::CACHE_CONTROL)
+                .and_then(|value| value.to_str().ok()),
+        );
 
         let body = resp
             .text()
@@ -218,15 +653,11 @@ impl OIDCClient {
                 }
             }
         }
-        Ok(keys)
+        Ok((keys, ttl))
     }
 
-    pub async fn match_jwks(access_token: &str, jwk_url: &str) -> Result<Jwk, String> {
-        // Fetch JWKS and return error if the request fails
-        let keys = OIDCClient::fetch_jwks(jwk_url)
-            .await
-            .map_err(|e| format!("Failed to fetch Google JWKS: {:?}", e))?;
-
+    /// Picks the key of an already fetched JWKS that signed `access_token`.
+    pub fn find_jwk(keys: &HashMap<String, Jwk>, access_token: &str) -> Result<Jwk, String> {
         // Decode the JWT header
         let header = decode_header(access_token).map_err(|_| "Invalid JWT header".to_string())?;
 
@@ -235,65 +666,156 @@ impl OIDCClient {
             .kid
             .ok_or("JWT header does not contain a Key ID (kid)".to_string())?;
 
-        // Retrieve (modulus `n`, exponent `e`) pair from the JWKS mapping
+        // Retrieve the key from the JWKS mapping
         keys.get(&kid)
             .cloned() // Clone since we're returning owned values
             .ok_or_else(|| format!("Key ID '{}' not found in JWKS", kid))
     }
 
-    /// Starts a temporary HTTP server to capture the access code from the redirect URL
-    pub async fn capture_access_code(redirect_url: &str) -> String {
-        let parsed_url = Url::parse(redirect_url).expect("Failed to parse URL");
+    /// Revokes a token at the provider's RFC 7009 revocation endpoint.
+    pub async fn revoke_token(
+        revocation_url: &str,
+        client_id: &str,
+        client_secret: &str,
+        token: &str,
+    ) -> anyhow::Result<()> {
+        let resp = build_http_client()
+            .post(revocation_url)
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .context("Token revocation request failed")?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!("Token revocation failed with status {}", resp.status()));
+        }
+        Ok(())
+    }
+
+    /// Starts a temporary HTTP server to capture the access code from the
+    /// redirect URL. The response must carry the `state` of the request, so
+    /// that a login started elsewhere can't be slipped in, and errors such as
+    /// `access_denied` fail the login.
+    pub async fn capture_access_code(redirect_url: &str, state: &CsrfToken) -> Result<String> {
+        Self::capture_callback(redirect_url, "code", |params| {
+            if params.get("state") != Some(state.secret()) {
+                bail!("Authorization response state does not match the request");
+            }
+            Ok(params["code"].clone())
+        })
+        .await
+    }
+
+    /// Same as `capture_access_code`, for the `param` query parameter, e.g.
+    /// the `response` of JARM, whose state is checked once it is verified.
+    pub async fn capture_callback_param(redirect_url: &str, param: &str) -> Result<String> {
+        Self::capture_callback(redirect_url, param, |params| Ok(params[param].clone())).await
+    }
+
+    /// Waits for the redirect carrying `param` or an `error`, checks it with
+    /// `check` and tells the browser whether the login went through. Other
+    /// requests, such as the browser's favicon, get `404 Not Found`.
+    async fn capture_callback(
+        redirect_url: &str,
+        param: &str,
+        check: impl FnOnce(&HashMap<String, String>) -> Result<String>,
+    ) -> Result<String> {
+        let parsed_url = Url::parse(redirect_url).context("Failed to parse URL")?;
         let socket_addr = format!(
             "{}:{}",
-            parsed_url.host_str().expect("Invalid host"),
-            parsed_url.port_or_known_default().expect("Invalid port")
+            parsed_url.host_str().context("Invalid host")?,
+            parsed_url.port_or_known_default().context("Invalid port")?
         );
 
         let listener = TcpListener::bind(&socket_addr)
             .await
-            .unwrap_or_else(|e| panic!("Failed to bind to {:?}: {}", redirect_url, e));
+            .with_context(|| format!("Failed to bind to {:?}", redirect_url))?;
 
         loop {
-            if let Ok((mut stream, _)) = listener.accept().await {
-                let mut buffer = vec![0; 4096]; // Bigger buffer for large OAuth redirects
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let mut buffer = vec![0; 4096]; // Bigger buffer for large OAuth redirects
+            let Ok(read) = stream.read(&mut buffer).await else {
+                continue;
+            };
+            let request = String::from_utf8_lossy(&buffer[..read]);
+
+            // Extracts "/callback?..." from the request line
+            let params: HashMap<String, String> = request
+                .lines()
+                .next()
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|path| Url::parse(&format!("{}{}", redirect_url, path)).ok())
+                .map(|url| url.query_pairs().into_owned().collect())
+                .unwrap_or_default();
+            if !params.contains_key(param) && !params.contains_key("error") {
                 let _ = stream
-                    .read(&mut buffer)
-                    .await
-                    .expect("Failed to read stream");
-
-                let request = String::from_utf8_lossy(&buffer);
-                println!("Received request:\n{}", request);
-
-                // Extract first line from request
-                if let Some(first_line) = request.lines().next() {
-                    let request_path = first_line.split_whitespace().nth(1); // Extracts "/callback?..."
-                    if let Some(query_part) = request_path {
-                        let full_url = format!("{}{}", redirect_url, query_part);
-                        let url = Url::parse(&full_url).expect("Failed to parse URL");
-
-                        if let Some(code) = url
-                            .query_pairs()
-                            .find(|(k, _)| k == "code")
-                            .map(|(_, v)| v.to_string())
-                        {
-                            // Send success response
-                            let response = "HTTP/1.1 200 OK\r\nContent-Length: 25\r\n\r\nAuthentication Complete";
-                            stream
-                                .write_all(response.as_bytes())
-                                .await
-                                .expect("Failed to write response");
-
-                            println!("Extracted Auth Code: {}", code);
-                            return code;
-                        }
-                    }
-                }
+                    .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                    .await;
+                continue;
             }
+
+            let outcome = match params.get("error") {
+                Some(error) => match params.get("error_description") {
+                    Some(description) => Err(anyhow!(
+                        "The provider refused the login: {} ({})",
+                        error,
+                        description
+                    )),
+                    None => Err(anyhow!("The provider refused the login: {}", error)),
+                },
+                None => check(&params),
+            };
+            let (status, page) = match &outcome {
+                Ok(_) => (
+                    "200 OK",
+                    result_page(
+                        "Authentication complete",
+                        "You can close this window and return to the terminal.",
+                    ),
+                ),
+                Err(err) => (
+                    "400 Bad Request",
+                    result_page("Authentication failed", &format!("{:#}", err)),
+                ),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                page.len(),
+                page
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            return outcome;
         }
     }
 }
 
+/// The page shown in the browser once the redirect is captured.
+fn result_page(title: &str, message: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+         <body><h1>{title}</h1><p>{}</p></body></html>",
+        escape_html(message)
+    )
+}
+
+/// Escapes `text` for HTML, as error descriptions come from the provider.
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
 // // Check expiration manually
 // let now = SystemTime::now()
 //     .duration_since(UNIX_EPOCH)
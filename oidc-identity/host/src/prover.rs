@@ -0,0 +1,319 @@
+//! Proving backends: the local zkVM, or a remote proving service.
+//!
+//! Locally, the identity guest is proven with Risc0 or, in hosts built with
+//! `--features sp1`, with SP1 (`--zkvm sp1`). The contract must then have been
+//! registered with the same `--zkvm`, as the node picks its verifier from the
+//! registration. Remote provers return Risc0 receipts only.
+//!
+//! The OIDC guest verifies an RSA signature, which takes far too long to prove
+//! on a laptop. With `--prover remote=<url>` the assembled `ContractInput` is
+//! shipped to a proving service instead, which must speak this small API (the
+//! backend can also be set once with `prover` in the `[proving]` section of
+//! `config.toml`):
+//!
+//! - `PUT /images/<image id>` uploads the guest ELF; idempotent
+//! - `POST /jobs` with `{"image_id", "input"}` returns `{"id"}`
+//! - `GET /jobs/<id>` returns `{"status": "pending" | "done" | "failed"}`, with
+//!   the hex borsh-encoded `receipt` once done or an `error` once failed
+//!
+//! The input carries the ID token, so it is only ever sent over TLS (plain
+//! HTTP is accepted for loopback services) with the `REMOTE_PROVER_TOKEN`
+//! bearer token if set. Receipts are verified locally against the guest's
+//! image id and the submitted input before being handed back.
+
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "sp1")]
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use client_sdk::helpers::risc0::Risc0Prover;
+#[cfg(feature = "sp1")]
+use client_sdk::helpers::sp1::SP1Prover;
+use host_output::say;
+use methods_identity::{GUEST_ELF, GUEST_ID};
+#[cfg(feature = "sp1")]
+use methods_identity::SP1_GUEST_ELF;
+use openidconnect::reqwest;
+use risc0_zkvm::Receipt;
+use sdk::{ContractInput, HyleOutput, ProgramId, ProofData};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::oidc_client::build_http_client;
+
+/// Delay between two polls of a remote job, unless configured.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Remote jobs still pending after this long are given up, unless configured.
+pub const JOB_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[cfg(not(feature = "sp1"))]
+const NO_SP1: &str = "This host was built without SP1: rebuild it with `--features sp1`";
+
+/// Value of `--zkvm`: which zkVM the identity guest is registered and proven
+/// with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Zkvm {
+    #[default]
+    Risc0,
+    /// Needs a host built with `--features sp1`
+    Sp1,
+}
+
+impl Zkvm {
+    /// Verifier the node checks the contract's proofs with.
+    pub fn verifier(self) -> &'static str {
+        match self {
+            Zkvm::Risc0 => "risc0",
+            Zkvm::Sp1 => "sp1",
+        }
+    }
+
+    /// Program id of the local identity guest: the image id for Risc0, the
+    /// verifying key for SP1.
+    pub fn program_id(self) -> Result<ProgramId> {
+        match self {
+            Zkvm::Risc0 => Ok(ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec())),
+            #[cfg(feature = "sp1")]
+            Zkvm::Sp1 => {
+                // Computing the verifying key sets the whole program up
+                static PROGRAM_ID: OnceLock<ProgramId> = OnceLock::new();
+                if let Some(program_id) = PROGRAM_ID.get() {
+                    return Ok(program_id.clone());
+                }
+                let program_id = SP1Prover::new(SP1_GUEST_ELF).program_id()?;
+                Ok(PROGRAM_ID.get_or_init(|| program_id).clone())
+            }
+            #[cfg(not(feature = "sp1"))]
+            Zkvm::Sp1 => bail!(NO_SP1),
+        }
+    }
+}
+
+/// Value of `--prover`: `local`, or `remote=<url>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ProverChoice {
+    Local,
+    Remote(Url),
+}
+
+impl FromStr for ProverChoice {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "local" {
+            return Ok(ProverChoice::Local);
+        }
+        let Some(url) = value.strip_prefix("remote=") else {
+            return Err("expected `local` or `remote=<url>`".to_string());
+        };
+        let mut url = Url::parse(url).map_err(|err| format!("invalid prover url: {err}"))?;
+        // Routes are joined onto the url, which must then be a directory
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        let loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+        if url.scheme() != "https" && !loopback {
+            return Err("remote provers receive ID tokens and must use https".to_string());
+        }
+        Ok(ProverChoice::Remote(url))
+    }
+}
+
+impl TryFrom<String> for ProverChoice {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ProverChoice> for String {
+    fn from(choice: ProverChoice) -> Self {
+        choice.to_string()
+    }
+}
+
+impl fmt::Display for ProverChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverChoice::Local => f.write_str("local"),
+            ProverChoice::Remote(url) => write!(f, "remote={url}"),
+        }
+    }
+}
+
+/// How a remote job is waited for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Polling {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Polling {
+    fn default() -> Self {
+        Polling {
+            interval: POLL_INTERVAL,
+            timeout: JOB_TIMEOUT,
+        }
+    }
+}
+
+/// Proves the executions of the identity guest.
+pub enum Prover<'a> {
+    Local(Risc0Prover<'a>),
+    #[cfg(feature = "sp1")]
+    Sp1(SP1Prover),
+    Remote(RemoteProver<'a>),
+}
+
+impl Prover<'_> {
+    pub fn new(choice: &ProverChoice, polling: Polling, zkvm: Zkvm) -> Result<Self> {
+        Ok(match (choice, zkvm) {
+            (ProverChoice::Local, Zkvm::Risc0) => Prover::Local(Risc0Prover::new(GUEST_ELF)),
+            #[cfg(feature = "sp1")]
+            (ProverChoice::Local, Zkvm::Sp1) => Prover::Sp1(SP1Prover::new(SP1_GUEST_ELF)),
+            #[cfg(not(feature = "sp1"))]
+            (ProverChoice::Local, Zkvm::Sp1) => bail!(NO_SP1),
+            (ProverChoice::Remote(url), Zkvm::Risc0) => Prover::Remote(RemoteProver {
+                url: url.clone(),
+                token: std::env::var("REMOTE_PROVER_TOKEN").ok(),
+                polling,
+                elf: GUEST_ELF,
+                image_id: GUEST_ID,
+            }),
+            (ProverChoice::Remote(_), Zkvm::Sp1) => {
+                bail!("Remote provers return Risc0 receipts: prove SP1 locally")
+            }
+        })
+    }
+
+    pub async fn prove(&self, input: ContractInput) -> Result<ProofData> {
+        match self {
+            Prover::Local(prover) => prover.prove(input).await,
+            #[cfg(feature = "sp1")]
+            Prover::Sp1(prover) => prover.prove(input).await,
+            Prover::Remote(prover) => prover.prove(input).await,
+        }
+    }
+}
+
+pub struct RemoteProver<'a> {
+    url: Url,
+    token: Option<String>,
+    polling: Polling,
+    elf: &'a [u8],
+    image_id: [u32; 8],
+}
+
+#[derive(Serialize)]
+struct JobRequest<'a> {
+    image_id: String,
+    input: &'a ContractInput,
+}
+
+#[derive(Deserialize)]
+struct JobCreated {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum JobStatus {
+    Pending,
+    Done { receipt: String },
+    Failed { error: String },
+}
+
+impl RemoteProver<'_> {
+    pub async fn prove(&self, input: ContractInput) -> Result<ProofData> {
+        let image_id = hex::encode(sdk::to_u8_array(&self.image_id));
+
+        self.request(reqwest::Method::PUT, &format!("images/{image_id}"))?
+            .header("Content-Type", "application/octet-stream")
+            .body(self.elf.to_vec())
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to upload the guest to the remote prover")?;
+
+        let job: JobCreated = json(
+            self.request(reqwest::Method::POST, "jobs")?
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(&JobRequest {
+                    image_id,
+                    input: &input,
+                })?),
+        )
+        .await
+        .context("Failed to submit the proving job")?;
+        say!("⏳ Remote proving job {} submitted", job.id);
+
+        let deadline = tokio::time::Instant::now() + self.polling.timeout;
+        let receipt = loop {
+            let status: Result<JobStatus> =
+                json(self.request(reqwest::Method::GET, &format!("jobs/{}", job.id))?).await;
+            match status {
+                Ok(JobStatus::Done { receipt }) => break receipt,
+                Ok(JobStatus::Failed { error }) => {
+                    bail!("Proving job {} failed: {}", job.id, error)
+                }
+                _ if tokio::time::Instant::now() >= deadline => {
+                    bail!(
+                        "Proving job {} still pending after {:?}",
+                        job.id,
+                        self.polling.timeout
+                    )
+                }
+                Ok(JobStatus::Pending) => {}
+                // The job lives on the service: a failed poll is retried
+                Err(err) => eprintln!("⚠️  Failed to poll proving job {}: {:#}", job.id, err),
+            }
+            tokio::time::sleep(self.polling.interval).await;
+        };
+
+        let receipt = hex::decode(receipt).context("Receipt is not hex")?;
+        self.check(
+            &borsh::from_slice(&receipt).context("Malformed receipt")?,
+            &input,
+        )?;
+        Ok(ProofData(receipt))
+    }
+
+    /// Verifies the receipt and that it proves `input` rather than another
+    /// execution of the same guest.
+    fn check(&self, receipt: &Receipt, input: &ContractInput) -> Result<()> {
+        receipt
+            .verify(self.image_id)
+            .map_err(|err| anyhow!("Remote receipt does not verify: {err}"))?;
+        let output: HyleOutput = receipt
+            .journal
+            .decode()
+            .context("Remote receipt journal is not a program output")?;
+        if output.initial_state != input.initial_state
+            || output.identity != input.identity
+            || output.tx_hash != input.tx_hash
+            || output.index != input.index
+        {
+            bail!("Remote receipt proves another input");
+        }
+        Ok(())
+    }
+
+    fn request(&self, method: reqwest::Method, route: &str) -> Result<reqwest::RequestBuilder> {
+        let url = self.url.join(route)?;
+        let request = build_http_client().request(method, url);
+        Ok(match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+}
+
+async fn json<T: for<'de> Deserialize<'de>>(request: reqwest::RequestBuilder) -> Result<T> {
+    let body = request.send().await?.error_for_status()?.bytes().await?;
+    Ok(serde_json::from_slice(&body)?)
+}
@@ -0,0 +1,80 @@
+//! `providers`: the identity providers of `config.toml`, any of which
+//! `--provider` picks by name.
+
+use clap::Subcommand;
+use host_output::say;
+use serde_json::{json, Value};
+
+use crate::config::AppConfig;
+use crate::doctor::discover;
+
+#[derive(Subcommand)]
+pub enum ProvidersCommand {
+    /// Print the configured providers and check their discovery documents
+    List {},
+}
+
+/// Names of the configured providers, sorted.
+pub fn names(config: &AppConfig) -> Vec<&str> {
+    let mut names: Vec<&str> = config
+        .identity_providers
+        .keys()
+        .map(String::as_str)
+        .collect();
+    names.sort();
+    names
+}
+
+/// Prints every configured provider, marking `selected`, and whether its
+/// discovery document is served and agrees with its settings. Returns whether
+/// every document was fetched.
+pub async fn list(config: &AppConfig, selected: &str) -> bool {
+    let mut healthy = true;
+    for name in names(config) {
+        let provider = &config.identity_providers[name];
+        let marker = if name == selected { "*" } else { " " };
+        let mut text = format!(
+            "{} {}\n    issuer:   {}\n    audience: {}\n    jwks:     {}",
+            marker, name, provider.issuer_url, provider.audience_url, provider.jwk_public_key_url
+        );
+
+        let discovery = match discover(&provider.issuer_url).await {
+            Ok(metadata) => {
+                text += &format!("\n    ✅ discovery: issuer {}", metadata.issuer().as_str());
+                let jwks_uri = metadata.jwks_uri().url().as_str();
+                if jwks_uri != provider.jwk_public_key_url {
+                    text += &format!("\n    ⚠️  discovery advertises the keys at {}", jwks_uri);
+                }
+                json!({ "issuer": metadata.issuer().as_str(), "jwks_uri": jwks_uri })
+            }
+            Err(err) => {
+                healthy = false;
+                text += &format!("\n    ❌ discovery: {:#}", err);
+                json!({ "error": format!("{:#}", err) })
+            }
+        };
+        host_output::report(
+            "provider",
+            json!({
+                "name": name,
+                "selected": name == selected,
+                "issuer": provider.issuer_url,
+                "audience": provider.audience_url,
+                "jwks": provider.jwk_public_key_url,
+                "discovery": discovery,
+            }),
+            text,
+        );
+    }
+    if !config.identity_providers.contains_key(selected) {
+        say!("--provider {} is not among them", selected);
+    }
+    if !healthy {
+        host_output::error(
+            "discovery",
+            "Some discovery documents couldn't be fetched",
+            Value::Null,
+        );
+    }
+    healthy
+}
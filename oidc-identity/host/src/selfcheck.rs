@@ -0,0 +1,192 @@
+//! Startup self-check, see the `host-check` crate.
+//!
+//! Complements `doctor`, which tests one provider in depth: this runs before
+//! every command and only checks what the command is about to rely on.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use host_check::{is_loopback, KeyFile, SelfCheck};
+use url::Url;
+
+use crate::config::{client_secret_var, AppConfig};
+use crate::jwks_pin::PinStore;
+use crate::lifecycle::local_program_id;
+use crate::node::NodeTransport;
+use crate::oidc_client::OIDCClient;
+use crate::prover::Zkvm;
+use crate::{jwks_pins_path, Commands};
+
+/// Checks the settings `command` runs with, read from `config_path`.
+/// `provider` and `zkvm` are the `--provider` and `--zkvm` ones; the node is
+/// only asked when the command submits proofs.
+pub async fn run(
+    command: &Commands,
+    provider: &str,
+    zkvm: Zkvm,
+    config: &AppConfig,
+    config_path: &Path,
+    node: &impl NodeTransport,
+) -> SelfCheck {
+    let mut check = SelfCheck::new();
+
+    check.settings_file(config_path);
+    if let Err(err) = PinStore::load(&jwks_pins_path()) {
+        check.fail(
+            "JWKS pins",
+            format!("{:#}", err),
+            format_args!(
+                "delete {} to pin the providers' keys again on the next login",
+                jwks_pins_path().display()
+            ),
+        );
+    }
+
+    for name in providers(command, provider, config) {
+        check_provider(&mut check, config, name).await;
+    }
+
+    match command {
+        Commands::Watch { listen } => {
+            let tls = config.watch.tls.as_ref();
+            check.plaintext_listener("The watch API", listen, tls.is_some());
+            if let Some(tls) = tls {
+                check.key_file(&tls.key, KeyFile::Pem);
+                let host = listen.rsplit_once(':').map_or(&**listen, |(host, _)| host);
+                if tls.client_ca.is_none() && !is_loopback(host) {
+                    check.warn(
+                        "Listener",
+                        format_args!("the watch API served on {} to any client", listen),
+                        "require client certificates with `[watch.tls] client_ca`",
+                    );
+                }
+            }
+        }
+        Commands::Serve { listen, .. } => {
+            check.plaintext_listener("The REST API", listen, false);
+        }
+        Commands::VerifyIdentity {
+            credential: Some(_),
+            ..
+        } => match &config.credentials.key {
+            Some(key) => check.key_file(key, KeyFile::Pem),
+            None => check.fail(
+                "Credentials",
+                "no key to sign credentials with",
+                "set `[credentials] key` in config.toml",
+            ),
+        },
+        _ => {}
+    }
+
+    if command.submits_proofs() {
+        let contract_name = &config.contract.name;
+        let local = local_program_id(zkvm);
+        match node.get_program_id(&contract_name.clone().into()).await {
+            Ok(program_id) if program_id == local => check.pass(),
+            Ok(program_id) => check.fail(
+                "Program id",
+                format_args!(
+                    "on-chain {} differs from the local guest {}, proofs would be rejected",
+                    hex::encode(&program_id.0),
+                    hex::encode(local.0)
+                ),
+                "build the guest at the deployed revision (RISC0_USE_DOCKER=1), pass the \
+                 `--zkvm` it was registered with, or set `[contract] name` to the contract \
+                 built from this one",
+            ),
+            Err(err) => check.fail(
+                "Node",
+                format_args!(
+                    "can't fetch {} from {}: {:#}",
+                    contract_name, config.server.host, err
+                ),
+                "check `[server] host` and `[contract] name`, or register the contract \
+                 with `contract register`",
+            ),
+        }
+        // Over a Unix socket the node shares this machine's clock
+        if !config.server.host.starts_with("unix://") {
+            match host_check::node_time(&config.server.host).await {
+                Ok(node_time) => check.clock(node_time, SystemTime::now()),
+                Err(err) => check.warn(
+                    "Clock",
+                    format_args!("can't read the node's time: {}", err),
+                    "make sure the local clock is synchronized, ID tokens expire",
+                ),
+            }
+        }
+    }
+
+    check
+}
+
+/// Providers `command` logs in with.
+fn providers<'a>(command: &'a Commands, provider: &'a str, config: &'a AppConfig) -> Vec<&'a str> {
+    match command {
+        Commands::Watch { .. } => config.watch.providers.iter().map(String::as_str).collect(),
+        Commands::Serve { .. } => config
+            .identity_providers
+            .keys()
+            .map(String::as_str)
+            .collect(),
+        Commands::MigrateSubject { from_provider, .. } => vec![provider, from_provider.as_str()],
+        Commands::ProveBatch { .. } => vec![],
+        command if command.submits_proofs() => vec![provider],
+        _ => vec![],
+    }
+}
+
+/// Checks that `name` is configured, has its client secret, is reachable and
+/// gets its login back safely.
+async fn check_provider(check: &mut SelfCheck, config: &AppConfig, name: &str) {
+    let Some(provider) = config.identity_providers.get(name) else {
+        return check.fail(
+            "Provider",
+            format_args!("{} is not set in config.toml", name),
+            format_args!(
+                "add `[identity_providers.{}]`, or pick another --provider",
+                name
+            ),
+        );
+    };
+
+    let secret = client_secret_var(name);
+    if std::env::var(&secret).is_ok() {
+        check.pass();
+    } else {
+        check.fail(
+            "Provider",
+            format_args!("{} has no client secret", name),
+            format_args!("set {} in the environment or .env", secret),
+        );
+    }
+
+    match OIDCClient::fetch_discovery(&provider.issuer_url).await {
+        Ok(_) => check.pass(),
+        Err(err) => check.fail(
+            "Provider",
+            format_args!("{} unreachable at {}: {:#}", name, provider.issuer_url, err),
+            format_args!("check `issuer_url`, then run `doctor {}`", name),
+        ),
+    }
+
+    // The authorization code comes back in the redirect: over plain HTTP
+    // beyond this machine, anyone on the way can exchange it first
+    let local = |url: &str| {
+        Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(is_loopback))
+            .unwrap_or(false)
+    };
+    let redirect = &config.server.server_url;
+    if redirect.starts_with("http://") && !local(redirect) && !local(&provider.issuer_url) {
+        check.unsafe_setting(
+            "Redirect URI",
+            format_args!("{} logins come back to {} over plain HTTP", name, redirect),
+            "serve `[server] server_url` over HTTPS, or on localhost",
+        );
+    } else {
+        check.pass();
+    }
+}
@@ -0,0 +1,735 @@
+//! `serve` mode: the host's commands behind a REST API, for web front ends.
+//!
+//! The browser is sent through the provider's authorization code flow, whose
+//! redirect comes back to the API, and the resulting login is kept as a
+//! session until a transaction spends it:
+//!
+//! - `GET /auth/start?provider=<name>[&nonce=<n>]` returns `{"auth_url"}`;
+//!   with `nonce`, the ID token is bound to that account nonce
+//! - `GET /auth/callback?code=..&state=..` completes the login and returns
+//!   `{"session", "provider", "account"}`
+//! - `POST /register` with `{"session"}`, and optionally `"display_name"` and
+//!   `"nonce_strategy"`, registers the login's account
+//! - `POST /verify` with `{"session", "nonce"}` verifies it at `nonce`
+//! - `GET /nonce/<account>` returns `{"account", "nonce"}`
+//!
+//! Transactions are answered with `{"account", "blob_tx_hash",
+//! "proof_tx_hash"}` once the proof is sent, with a null `proof_tx_hash` when
+//! the blob transaction settled but the answer to its proof was lost, errors
+//! with `{"error"}`. Requests
+//! reaching the node are handled one at a time, in order, by the loop holding
+//! the prover. Sessions are single-use and kept in memory only, but started
+//! authorizations and unproven transactions are also kept in the session
+//! store: a restarted server still completes the logins started before, and
+//! `resume` proves what it left behind. Logins started or completed more than
+//! [`SESSION_TTL`] ago are given up.
+//!
+//! With `--watch-config`, config.toml is polled for changes and its identity
+//! providers and `server_url` swapped in once they pass the offline checks of
+//! `config validate`; the contract, node and proving settings the loop
+//! started with still need a restart.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use host_output::say;
+use methods_identity::GUEST_ELF;
+use oidc_identity::OidcIdentity;
+use oidc_provider::hasher::{AccountHasher, DefaultHasher};
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::{IdentityAction, OpenIdContext, PrivateInput};
+use openidconnect::{Nonce, PkceCodeVerifier};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sdk::{BlobTransaction, ContractInput, Digestable, ProofTransaction};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::{load_config, AppConfig, IdentityProvider};
+use crate::config_validate;
+use crate::node::{next_context, send_proof, sequenced_context, NodeTransport};
+use crate::oidc_client::{AuthClient, JwksCache, OIDCClient};
+use crate::prover::Prover;
+use crate::session_store::{SessionStore, SESSION_TTL};
+use crate::{acting_account, estimate, jwks_pin, jwks_pins_path, refusal_hint, Login};
+
+/// An authorization started by `/auth/start`, keyed by its CSRF state.
+struct Pending {
+    provider_name: String,
+    client: AuthClient,
+    nonce: Nonce,
+    pkce_verifier: PkceCodeVerifier,
+    started: Instant,
+}
+
+/// A completed login, waiting for the transaction that spends it.
+struct Session {
+    provider_name: String,
+    login: Login,
+    completed: Instant,
+}
+
+/// Work for the loop holding the node and the prover.
+enum Job {
+    Nonce {
+        account: String,
+        reply: oneshot::Sender<Result<u32>>,
+    },
+    Submit {
+        account: String,
+        action: IdentityAction,
+        private_input: PrivateInput,
+        reply: oneshot::Sender<Result<Submitted>>,
+    },
+}
+
+/// Hashes of a submitted transaction.
+#[derive(Serialize)]
+struct Submitted {
+    account: String,
+    blob_tx_hash: String,
+    /// `None` when a lost answer hid that the proof settled
+    proof_tx_hash: Option<String>,
+}
+
+/// Delay between two checks of config.toml for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Delay between two sweeps of the expired logins.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Shared {
+    /// Swapped for the reloaded settings with `--watch-config`
+    config: RwLock<AppConfig>,
+    strict_keys: bool,
+    /// Salt every account served is registered and verified with, see
+    /// `register-identity --salted`
+    salt: Option<String>,
+    pending: Mutex<HashMap<String, Pending>>,
+    store: Mutex<SessionStore>,
+    sessions: Mutex<HashMap<String, Session>>,
+    jobs: mpsc::Sender<Job>,
+    jwks: JwksCache,
+}
+
+/// An error answered as `{"error"}` with its status.
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, message.to_string())
+    }
+
+    fn not_found(message: impl ToString) -> Self {
+        ApiError(StatusCode::NOT_FOUND, message.to_string())
+    }
+
+    fn unauthorized(err: impl std::fmt::Display) -> Self {
+        ApiError(StatusCode::UNAUTHORIZED, format!("{:#}", err))
+    }
+
+    fn upstream(err: anyhow::Error) -> Self {
+        ApiError(StatusCode::BAD_GATEWAY, format!("{:#}", err))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Serves the API on `listen` and runs the jobs it hands over, until the
+/// listener fails. Reloads the settings of `config_path`, when given, as it
+/// changes.
+pub async fn run(
+    config: &AppConfig,
+    node: &impl NodeTransport,
+    prover: &Prover<'_>,
+    strict_keys: bool,
+    salt: Option<String>,
+    max_cost: Option<u64>,
+    listen: &str,
+    config_path: Option<&std::path::Path>,
+) -> Result<()> {
+    let (jobs, mut queue) = mpsc::channel(32);
+    let shared = Arc::new(Shared {
+        config: RwLock::new(config.clone()),
+        strict_keys,
+        salt,
+        pending: Mutex::new(HashMap::new()),
+        store: Mutex::new(SessionStore::open()?),
+        sessions: Mutex::new(HashMap::new()),
+        jobs,
+        jwks: JwksCache::default(),
+    });
+    let router = Router::new()
+        .route("/auth/start", get(auth_start))
+        .route("/auth/callback", get(auth_callback))
+        .route("/register", post(register))
+        .route("/verify", post(verify))
+        .route("/nonce/:account", get(nonce))
+        .with_state(shared.clone());
+    tokio::spawn(prune_expired(shared.clone()));
+    if let Some(path) = config_path {
+        tokio::spawn(reload_config(shared, path.to_path_buf()));
+    }
+
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to bind the REST API to {}", listen))?;
+    say!("🌐 REST API on http://{}", listen);
+    let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+    while let Some(job) = queue.recv().await {
+        match job {
+            Job::Nonce { account, reply } => {
+                let _ = reply.send(read_nonce(config, node, &account).await);
+            }
+            Job::Submit {
+                account,
+                action,
+                private_input,
+                reply,
+            } => {
+                let submitted = submit(
+                    config,
+                    node,
+                    prover,
+                    max_cost,
+                    account,
+                    action,
+                    private_input,
+                )
+                .await;
+                if let Err(err) = &submitted {
+                    eprintln!("❌ {:#}", err);
+                }
+                let _ = reply.send(submitted);
+            }
+        }
+    }
+
+    server.await?.context("The REST API stopped")
+}
+
+/// Drops the logins started or completed more than [`SESSION_TTL`] ago, in
+/// memory and in the session store, which `/auth/start` would otherwise pile
+/// up for anyone calling it.
+async fn prune_expired(shared: Arc<Shared>) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        shared
+            .pending
+            .lock()
+            .unwrap()
+            .retain(|_, pending| pending.started.elapsed() < SESSION_TTL);
+        shared
+            .sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.completed.elapsed() < SESSION_TTL);
+        if let Err(err) = shared.store.lock().unwrap().prune_sessions() {
+            eprintln!("⚠️  Failed to drop the expired logins: {:#}", err);
+        }
+    }
+}
+
+fn modified_at(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Polls `path` and swaps in its identity providers and `server_url` when it
+/// changes, unless it can't be loaded or has issues: the previous settings
+/// are then kept.
+async fn reload_config(shared: Arc<Shared>, path: PathBuf) {
+    let mut modified = modified_at(&path);
+    loop {
+        tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+        let latest = modified_at(&path);
+        if latest == modified {
+            continue;
+        }
+        modified = latest;
+
+        let config = match load_config(Some(&path)) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!(
+                    "⚠️  Keeping the previous settings, {} can't be loaded: {:#}",
+                    path.display(),
+                    err
+                );
+                continue;
+            }
+        };
+        let issues = config_validate::static_issues(&config);
+        if !issues.is_empty() {
+            eprintln!(
+                "⚠️  Keeping the previous settings, {} has issues:",
+                path.display()
+            );
+            for issue in issues {
+                eprintln!("   {}", issue);
+            }
+            continue;
+        }
+
+        let mut current = shared.config.write().unwrap();
+        if config.contract != current.contract
+            || config.server.host != current.server.host
+            || config.server.witnesses != current.server.witnesses
+            || config.proving != current.proving
+        {
+            eprintln!("⚠️  The contract, node and proving settings only change on restart");
+        }
+        current.identity_providers = config.identity_providers;
+        current.server.server_url = config.server.server_url;
+        say!("🔄 Reloaded the identity providers of {}", path.display());
+    }
+}
+
+/// The account's next nonce, as the contract state reports it.
+async fn read_nonce(config: &AppConfig, node: &impl NodeTransport, account: &str) -> Result<u32> {
+    let state: OidcIdentity = node
+        .get_contract_state(&config.contract.name.clone().into())
+        .await?
+        .into();
+    Ok(state
+        .account(account)
+        .ok_or_else(|| anyhow!("{} is not registered", account))?
+        .nonce)
+}
+
+/// Estimates `action`, then sends it from `account` and proves it. Actions
+/// the contract refuses are reported without sending anything.
+async fn submit(
+    config: &AppConfig,
+    node: &impl NodeTransport,
+    prover: &Prover<'_>,
+    max_cost: Option<u64>,
+    account: String,
+    action: IdentityAction,
+    private_input: PrivateInput,
+) -> Result<Submitted> {
+    let contract_name = &config.contract.name;
+    let state: OidcIdentity = node
+        .get_contract_state(&contract_name.clone().into())
+        .await?
+        .into();
+    let blob_tx = BlobTransaction {
+        identity: account.clone().into(),
+        blobs: vec![action.as_blob(contract_name.clone().into())],
+    };
+    let mut inputs = ContractInput {
+        initial_state: state.as_digest(),
+        identity: blob_tx.identity.clone(),
+        tx_hash: sdk::TxHash(String::new()),
+        private_input: private_input.to_bytes(),
+        tx_ctx: Some(next_context(node).await?),
+        blobs: blob_tx.blobs.clone(),
+        index: sdk::BlobIndex(0),
+    };
+
+    let estimate = estimate::estimate(node, GUEST_ELF, &inputs, &config.proving).await?;
+    estimate::check_budget(&estimate, max_cost)?;
+    if let Some(failure) = &estimate.failure {
+        match refusal_hint(failure.code) {
+            Some(hint) => bail!(
+                "The contract refuses the action: {}. {}",
+                failure.detail,
+                hint
+            ),
+            None => bail!("The contract refuses the action: {}", failure.detail),
+        }
+    }
+
+    let store = SessionStore::open()?;
+    let blob_tx_hash = node.send_tx_blob(&blob_tx).await?;
+    inputs.tx_hash = blob_tx_hash.clone();
+    store.add_pending(contract_name, &inputs)?;
+    inputs.tx_ctx = Some(sequenced_context(node, &blob_tx_hash).await?);
+    let proof = prover.prove(inputs).await?;
+    let proof_tx = ProofTransaction {
+        proof,
+        contract_name: contract_name.clone().into(),
+    };
+    let proof_tx_hash = send_proof(node, &proof_tx, &blob_tx_hash).await?;
+    store.remove_pending(contract_name, &blob_tx_hash)?;
+    say!("✅ {} sent {} and its proof", account, blob_tx_hash);
+
+    Ok(Submitted {
+        account,
+        blob_tx_hash: blob_tx_hash.0,
+        proof_tx_hash: proof_tx_hash.map(|hash| hash.0),
+    })
+}
+
+/// A copy of the provider's settings, which a reload may replace meanwhile.
+fn identity_provider(shared: &Shared, name: &str) -> Result<IdentityProvider, ApiError> {
+    shared
+        .config
+        .read()
+        .unwrap()
+        .identity_providers
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found(format!("Unknown provider {}", name)))
+}
+
+fn contract_name(shared: &Shared) -> String {
+    shared.config.read().unwrap().contract.name.clone()
+}
+
+fn context(identity_provider: &IdentityProvider) -> OpenIdContext {
+    OpenIdContext {
+        issuer: identity_provider.token_issuer().to_string(),
+        audience: vec![identity_provider.audience_url.to_string()],
+        policy: identity_provider.policy.clone(),
+        algorithms: identity_provider.algorithms.clone(),
+        issuer_match: identity_provider.issuer_match,
+    }
+}
+
+/// The provider's client, redirecting to `/auth/callback`.
+async fn auth_client(shared: &Shared, provider_name: &str) -> Result<AuthClient, ApiError> {
+    let identity_provider = identity_provider(shared, provider_name)?;
+    let redirect_url = format!(
+        "{}/auth/callback",
+        shared.config.read().unwrap().server.server_url
+    );
+    OIDCClient::build(
+        identity_provider.issuer_url.to_string(),
+        identity_provider.audience_url.to_string(),
+        Some(identity_provider.get_client_secret(provider_name)),
+        &redirect_url,
+    )
+    .await
+    .map_err(ApiError::upstream)
+}
+
+#[derive(Deserialize)]
+struct StartQuery {
+    provider: String,
+    nonce: Option<u32>,
+}
+
+async fn auth_start(
+    State(shared): State<Arc<Shared>>,
+    Query(query): Query<StartQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let identity_provider = identity_provider(&shared, &query.provider)?;
+    // The JARM response carries its state signed, where `/auth/callback` can't look it up
+    if identity_provider.jarm {
+        return Err(ApiError::bad_request(
+            "JARM providers can only log in through the CLI",
+        ));
+    }
+    let client_secret = identity_provider.get_client_secret(&query.provider);
+    let client = auth_client(&shared, &query.provider).await?;
+
+    let (auth_url, csrf_token, nonce, pkce_verifier) =
+        OIDCClient::generate_auth_url(&client, false, false, query.nonce);
+    let auth_url = if identity_provider.par {
+        OIDCClient::push_authorization_request(
+            &identity_provider.issuer_url,
+            &identity_provider.audience_url,
+            &client_secret,
+            &auth_url,
+        )
+        .await
+        .map_err(ApiError::upstream)?
+    } else {
+        auth_url
+    };
+
+    shared
+        .store
+        .lock()
+        .unwrap()
+        .save_session(
+            csrf_token.secret(),
+            &query.provider,
+            &nonce,
+            &pkce_verifier,
+        )
+        .map_err(ApiError::upstream)?;
+    shared.pending.lock().unwrap().insert(
+        csrf_token.secret().clone(),
+        Pending {
+            provider_name: query.provider,
+            client,
+            nonce,
+            pkce_verifier,
+            started: Instant::now(),
+        },
+    );
+    Ok(Json(json!({ "auth_url": auth_url })))
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: String,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+async fn auth_callback(
+    State(shared): State<Arc<Shared>>,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let pending = shared
+        .pending
+        .lock()
+        .unwrap()
+        .remove(&query.state)
+        .filter(|pending| pending.started.elapsed() < SESSION_TTL);
+    let stored = shared
+        .store
+        .lock()
+        .unwrap()
+        .take_session(&query.state)
+        .map_err(ApiError::upstream)?;
+    let pending = match (pending, stored) {
+        (Some(pending), _) => pending,
+        // Started before the server restarted: only the client is missing
+        (None, Some(stored)) => Pending {
+            client: auth_client(&shared, &stored.provider_name).await?,
+            provider_name: stored.provider_name,
+            nonce: stored.nonce,
+            pkce_verifier: stored.pkce_verifier,
+            started: Instant::now(),
+        },
+        (None, None) => {
+            return Err(ApiError::bad_request(
+                "Unknown, expired or already used login state",
+            ))
+        }
+    };
+    let identity_provider = identity_provider(&shared, &pending.provider_name)?;
+    let code = match (query.code, query.error) {
+        (_, Some(error)) => {
+            let reason = match query.error_description {
+                Some(description) => format!("{} ({})", error, description),
+                None => error,
+            };
+            return Err(ApiError::unauthorized(format!(
+                "The provider refused the login: {}",
+                reason
+            )));
+        }
+        (Some(code), None) => code,
+        (None, None) => return Err(ApiError::bad_request("No authorization code")),
+    };
+
+    let (id_token, access_token, refresh_token) =
+        OIDCClient::exchange_code_for_tokens(&pending.client, code, pending.pkce_verifier)
+            .await
+            .map_err(ApiError::unauthorized)?;
+    let claims = OIDCClient::verify_id_token(&pending.client, &id_token, &pending.nonce)
+        .map_err(ApiError::unauthorized)?;
+    OIDCClient::verify_access_token(&pending.client, &id_token, &access_token, &claims)
+        .map_err(ApiError::unauthorized)?;
+
+    let keys = shared
+        .jwks
+        .get(&identity_provider.jwk_public_key_url)
+        .await
+        .map_err(|err| ApiError::upstream(anyhow!(err)))?;
+    jwks_pin::verify(
+        &jwks_pins_path(),
+        &identity_provider.issuer_url,
+        &keys,
+        shared.strict_keys,
+    )
+    .map_err(ApiError::unauthorized)?;
+    let jwk = OIDCClient::find_jwk(&keys, &id_token.to_string()).map_err(ApiError::unauthorized)?;
+
+    let login = Login {
+        id_token,
+        access_token,
+        claims,
+        jwk,
+        refresh_token,
+    };
+    let account = acting_account(
+        None,
+        &identity_provider,
+        &login,
+        &contract_name(&shared),
+        shared.salt.as_deref(),
+    );
+
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    let session = hex::encode(bytes);
+    shared.sessions.lock().unwrap().insert(
+        session.clone(),
+        Session {
+            provider_name: pending.provider_name.clone(),
+            login,
+            completed: Instant::now(),
+        },
+    );
+    Ok(Json(json!({
+        "session": session,
+        "provider": pending.provider_name,
+        "account": account,
+    })))
+}
+
+/// Takes the session out, so that its ID token is only submitted once.
+fn take_session(shared: &Shared, session: &str) -> Result<Session, ApiError> {
+    shared
+        .sessions
+        .lock()
+        .unwrap()
+        .remove(session)
+        .filter(|session| session.completed.elapsed() < SESSION_TTL)
+        .ok_or_else(|| ApiError::not_found("Unknown, expired or already used session"))
+}
+
+/// Hands the action over to the loop and waits for its transaction.
+async fn send(
+    shared: &Shared,
+    account: String,
+    action: IdentityAction,
+    private_input: PrivateInput,
+) -> Result<Json<Submitted>, ApiError> {
+    let (reply, submitted) = oneshot::channel();
+    shared
+        .jobs
+        .send(Job::Submit {
+            account,
+            action,
+            private_input,
+            reply,
+        })
+        .await
+        .map_err(|_| ApiError::upstream(anyhow!("The submission loop stopped")))?;
+    match submitted.await {
+        Ok(Ok(submitted)) => Ok(Json(submitted)),
+        Ok(Err(err)) => Err(ApiError::upstream(err)),
+        Err(_) => Err(ApiError::upstream(anyhow!("The submission loop stopped"))),
+    }
+}
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+    session: String,
+    display_name: Option<String>,
+    #[serde(default)]
+    nonce_strategy: NonceStrategy,
+}
+
+async fn register(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<RegisterRequest>,
+) -> Result<Json<Submitted>, ApiError> {
+    let Session {
+        provider_name,
+        login,
+        ..
+    } = take_session(&shared, &request.session)?;
+    let identity_provider = identity_provider(&shared, &provider_name)?;
+    let account = acting_account(
+        None,
+        &identity_provider,
+        &login,
+        &contract_name(&shared),
+        shared.salt.as_deref(),
+    );
+
+    let action = IdentityAction::RegisterIdentity {
+        account: account.clone(),
+        jwk_pub_key: login.jwk.public_key().into(),
+        context: context(&identity_provider),
+        recovery_codes: vec![],
+        metadata: AccountMetadata::new(
+            Some(provider_name),
+            request
+                .display_name
+                .map(|name| DefaultHasher::digest_hex(name.as_bytes())),
+        ),
+        nonce_strategy: request.nonce_strategy,
+    };
+    send(
+        &shared,
+        account,
+        action,
+        PrivateInput::Token(login.id_token.to_string()).salted(shared.salt.clone()),
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    session: String,
+    nonce: u32,
+}
+
+async fn verify(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<Submitted>, ApiError> {
+    let Session {
+        provider_name,
+        login,
+        ..
+    } = take_session(&shared, &request.session)?;
+    let identity_provider = identity_provider(&shared, &provider_name)?;
+    let account = acting_account(
+        None,
+        &identity_provider,
+        &login,
+        &contract_name(&shared),
+        shared.salt.as_deref(),
+    );
+
+    let action = IdentityAction::VerifyIdentity {
+        account: account.clone(),
+        nonce: request.nonce,
+        jwk_pub_key: login.jwk.public_key().into(),
+        context: context(&identity_provider),
+    };
+    send(
+        &shared,
+        account,
+        action,
+        PrivateInput::Token(login.id_token.to_string()).salted(shared.salt.clone()),
+    )
+    .await
+}
+
+async fn nonce(
+    State(shared): State<Arc<Shared>>,
+    Path(account): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let (reply, nonce) = oneshot::channel();
+    shared
+        .jobs
+        .send(Job::Nonce {
+            account: account.clone(),
+            reply,
+        })
+        .await
+        .map_err(|_| ApiError::upstream(anyhow!("The submission loop stopped")))?;
+    match nonce.await {
+        Ok(Ok(nonce)) => Ok(Json(json!({ "account": account, "nonce": nonce }))),
+        Ok(Err(err)) => Err(ApiError::not_found(format!("{:#}", err))),
+        Err(_) => Err(ApiError::upstream(anyhow!("The submission loop stopped"))),
+    }
+}
@@ -0,0 +1,75 @@
+//! Session keys, see [`oidc_provider::session`]: generated here, printed once
+//! and never written anywhere; only the public key is sent to the contract.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use oidc_provider::session::{check_expiry, signing_message};
+use oidc_provider::PrivateInput;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::rngs::OsRng;
+
+use crate::node::NodeTransport;
+
+pub fn generate() -> SigningKey {
+    SigningKey::random(&mut OsRng)
+}
+
+/// Hex of the SEC1-encoded public key, as `AuthorizeSessionKey` takes it.
+pub fn public_key(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().to_encoded_point(true).as_bytes())
+}
+
+/// Expiry of a key valid for `blocks` from now. Sequenced in a later block,
+/// the key is left with fewer, within the contract's bounds if these are.
+pub async fn expiry(node: &impl NodeTransport, blocks: u64) -> Result<u64> {
+    let height = node.block_height().await?;
+    let expiry = height.saturating_add(blocks);
+    check_expiry(expiry, height).map_err(|err| anyhow!(err))?;
+    Ok(expiry)
+}
+
+pub fn print(key: &SigningKey, expiry: u64) {
+    let secret = hex::encode(key.to_bytes());
+    host_output::report(
+        "session_key",
+        serde_json::json!({
+            "secret_key": secret,
+            "public_key": public_key(key),
+            "expiry": expiry,
+        }),
+        format_args!(
+            "🔑 Session key, valid until block {}. It isn't stored: keep it now.\n   {}",
+            expiry, secret
+        ),
+    );
+}
+
+/// The `SessionKeySignature` verifying `account` at `nonce` with `key`, with
+/// the contract `contract_name` in a transaction carrying `blobs` besides the
+/// action's.
+pub fn sign(
+    key: &SigningKey,
+    contract_name: &str,
+    account: &str,
+    nonce: u32,
+    blobs: &[sdk::Blob],
+) -> PrivateInput {
+    let message = signing_message(contract_name, account, nonce, blobs);
+    let signature: Signature = key.sign(message.as_bytes());
+    PrivateInput::SessionKeySignature {
+        public_key: public_key(key),
+        signature: hex::encode(signature.to_bytes()),
+    }
+}
+
+/// Reads a secret key from stdin rather than the command line, keeping it out
+/// of the shell history. The prompt goes to stderr, stdout being for results.
+pub fn prompt() -> Result<SigningKey> {
+    eprint!("Session key: ");
+    io::stderr().flush()?;
+    let mut secret = String::new();
+    io::stdin().lock().read_line(&mut secret)?;
+    let bytes = hex::decode(secret.trim()).map_err(|_| anyhow!("The session key isn't hex"))?;
+    SigningKey::from_slice(&bytes).map_err(|_| anyhow!("Invalid P-256 session key"))
+}
@@ -0,0 +1,316 @@
+//! Flows in progress, kept in SQLite so that they survive the process.
+//!
+//! Two tables live in `sessions.sqlite` under the state directory:
+//!
+//! - `sessions`: authorizations started by `serve` and not completed yet,
+//!   with their PKCE verifier and nonce, so that a restarted server still
+//!   accepts the provider's redirect
+//! - `pending_proofs`: the contract input of every blob transaction sent but
+//!   not proven yet, with its initial state, which `resume` proves and sends
+//!
+//! Pending proofs carry the ID token in their private input, so the file is
+//! only readable by its owner, and entries are deleted as soon as their proof
+//! is sent. Expired sessions are dropped by `resume`, and regularly by
+//! `serve`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use client_sdk::helpers::risc0::Risc0Prover;
+use host_output::say;
+use methods_identity::{ALIAS_GUEST_ELF, ATTESTATION_GUEST_ELF};
+use openidconnect::{Nonce, PkceCodeVerifier};
+use rusqlite::{params, Connection, OptionalExtension};
+use sdk::{ContractInput, ProofTransaction, TxHash};
+use serde_json::json;
+
+use crate::config::AppConfig;
+use crate::node::{send_proof, sequenced_context, NodeTransport};
+use crate::prover::Prover;
+
+/// Started authorizations older than this are given up: providers expire
+/// their authorization codes long before.
+pub const SESSION_TTL: Duration = Duration::from_secs(10 * 60);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        state TEXT PRIMARY KEY,
+        provider TEXT NOT NULL,
+        nonce TEXT NOT NULL,
+        pkce_verifier TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS pending_proofs (
+        tx_hash TEXT NOT NULL,
+        contract_name TEXT NOT NULL,
+        input TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (tx_hash, contract_name)
+    );
+";
+
+/// An authorization started by `serve`, keyed by its CSRF state.
+pub struct StoredSession {
+    pub provider_name: String,
+    pub nonce: Nonce,
+    pub pkce_verifier: PkceCodeVerifier,
+}
+
+/// A blob sent to `contract_name` and still waiting for its proof.
+pub struct PendingProof {
+    pub contract_name: String,
+    pub input: ContractInput,
+    /// Unix time the blob transaction was sent at
+    pub created_at: u64,
+}
+
+pub struct SessionStore {
+    connection: Connection,
+}
+
+/// Where the store is kept.
+pub fn store_path() -> Result<PathBuf> {
+    Ok(app_dirs::get()
+        .state()
+        .context("Failed to create the state directory")?
+        .join("sessions.sqlite"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+impl SessionStore {
+    /// Opens the store of the state directory, creating it if needed.
+    pub fn open() -> Result<Self> {
+        Self::open_at(&store_path()?)
+    }
+
+    pub fn open_at(path: &Path) -> Result<Self> {
+        create_private(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let connection =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .context("Failed to set up the session store")?;
+        Ok(SessionStore { connection })
+    }
+
+    pub fn save_session(
+        &self,
+        state: &str,
+        provider_name: &str,
+        nonce: &Nonce,
+        pkce_verifier: &PkceCodeVerifier,
+    ) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO sessions VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                state,
+                provider_name,
+                nonce.secret(),
+                pkce_verifier.secret(),
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Takes the session started with `state` out, unless it expired.
+    pub fn take_session(&self, state: &str) -> Result<Option<StoredSession>> {
+        let session = self
+            .connection
+            .query_row(
+                "DELETE FROM sessions WHERE state = ?1 \
+                 RETURNING provider, nonce, pkce_verifier, created_at",
+                params![state],
+                |row| {
+                    Ok((
+                        StoredSession {
+                            provider_name: row.get(0)?,
+                            nonce: Nonce::new(row.get(1)?),
+                            pkce_verifier: PkceCodeVerifier::new(row.get(2)?),
+                        },
+                        row.get::<_, u64>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+        Ok(session
+            .filter(|(_, created_at)| now().saturating_sub(*created_at) < SESSION_TTL.as_secs())
+            .map(|(session, _)| session))
+    }
+
+    /// Deletes the expired sessions, returning how many there were.
+    pub fn prune_sessions(&self) -> Result<usize> {
+        let cutoff = now().saturating_sub(SESSION_TTL.as_secs());
+        Ok(self.connection.execute(
+            "DELETE FROM sessions WHERE created_at < ?1",
+            params![cutoff],
+        )?)
+    }
+
+    /// Records `input`, whose blob transaction was just sent to
+    /// `contract_name`, until `remove_pending`.
+    pub fn add_pending(&self, contract_name: &str, input: &ContractInput) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO pending_proofs VALUES (?1, ?2, ?3, ?4)",
+            params![
+                input.tx_hash.0,
+                contract_name,
+                serde_json::to_string(input)?,
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_pending(&self, contract_name: &str, tx_hash: &TxHash) -> Result<()> {
+        self.connection.execute(
+            "DELETE FROM pending_proofs WHERE tx_hash = ?1 AND contract_name = ?2",
+            params![tx_hash.0, contract_name],
+        )?;
+        Ok(())
+    }
+
+    /// The pending proofs, oldest first.
+    pub fn pending(&self) -> Result<Vec<PendingProof>> {
+        let mut statement = self.connection.prepare(
+            "SELECT contract_name, input, created_at FROM pending_proofs ORDER BY created_at",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u64>(2)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (contract_name, input, created_at) = row?;
+            Ok(PendingProof {
+                input: serde_json::from_str(&input)
+                    .with_context(|| format!("Malformed pending proof for {}", contract_name))?,
+                contract_name,
+                created_at,
+            })
+        })
+        .collect()
+    }
+}
+
+/// Creates `path` readable by its owner only, keeping its content.
+fn create_private(path: &Path) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path).map(drop)
+}
+
+/// Proves and sends the pending proofs, identity ones with `prover` and
+/// companion registry ones locally, then drops the expired sessions. Proofs
+/// that fail again are kept for the next run unless `drop_failed`. Returns
+/// whether every proof was sent.
+pub async fn resume(
+    config: &AppConfig,
+    node: &impl NodeTransport,
+    prover: &Prover<'_>,
+    drop_failed: bool,
+) -> Result<bool> {
+    let store = SessionStore::open()?;
+    let pending = store.pending()?;
+    if pending.is_empty() {
+        say!("✅ No pending proof");
+    }
+
+    let mut all_sent = true;
+    for PendingProof {
+        contract_name,
+        input,
+        created_at,
+    } in pending
+    {
+        let tx_hash = input.tx_hash.clone();
+        say!(
+            "⏳ Proving {} for {}, sent {}s ago",
+            tx_hash,
+            contract_name,
+            now().saturating_sub(created_at)
+        );
+        // Inputs are recorded as soon as their blob tx is sent, before the
+        // context of its block is known
+        let proof = match sequenced_context(node, &tx_hash).await {
+            Ok(tx_ctx) => {
+                let input = ContractInput {
+                    tx_ctx: Some(tx_ctx),
+                    ..input
+                };
+                if contract_name == config.contract.name {
+                    prover.prove(input).await
+                } else if contract_name == config.contract.attestation_name {
+                    Risc0Prover::new(ATTESTATION_GUEST_ELF).prove(input).await
+                } else if contract_name == config.contract.alias_name {
+                    Risc0Prover::new(ALIAS_GUEST_ELF).prove(input).await
+                } else {
+                    Err(anyhow!("{} is no longer configured", contract_name))
+                }
+            }
+            Err(err) => Err(err),
+        };
+        let sent = match proof {
+            Ok(proof) => {
+                let proof_tx = ProofTransaction {
+                    proof,
+                    contract_name: contract_name.clone().into(),
+                };
+                send_proof(node, &proof_tx, &tx_hash).await
+            }
+            Err(err) => Err(err),
+        };
+
+        match sent {
+            Ok(proof_tx_hash) => {
+                match proof_tx_hash {
+                    Some(proof_tx_hash) => host_output::report(
+                        "proof_tx_sent",
+                        json!({
+                            "contract": contract_name,
+                            "blob_tx_hash": tx_hash,
+                            "tx_hash": proof_tx_hash,
+                        }),
+                        format_args!("✅ Proof tx sent. Tx hash: {}", proof_tx_hash),
+                    ),
+                    None => host_output::report(
+                        "proof_settled",
+                        json!({ "contract": contract_name, "blob_tx_hash": tx_hash }),
+                        "✅ Proof tx already settled",
+                    ),
+                }
+                store.remove_pending(&contract_name, &tx_hash)?;
+            }
+            Err(err) => {
+                all_sent = false;
+                let message = format!("Failed to prove {}: {:#}", tx_hash, err);
+                eprintln!("❌ {}", message);
+                host_output::error(
+                    "proof",
+                    message,
+                    json!({ "contract": contract_name, "blob_tx_hash": tx_hash }),
+                );
+                if drop_failed {
+                    store.remove_pending(&contract_name, &tx_hash)?;
+                    eprintln!("🗑️  Dropped {}", tx_hash);
+                }
+            }
+        }
+    }
+
+    let expired = store.prune_sessions()?;
+    if expired > 0 {
+        say!("🗑️  Dropped {} expired login(s)", expired);
+    }
+    Ok(all_sent)
+}
@@ -0,0 +1,126 @@
+//! Transaction templates: a repeatable flow kept in a YAML, JSON or TOML file
+//! and executed with `run <template>`, instead of a long command line.
+//!
+//! ```yaml
+//! scheme: oidc
+//! provider: google
+//! prover: remote=https://prover.example.com/
+//! flow: device
+//! action: verify-identity
+//! args: ["0", "--attest", "email"]
+//! max_cost: 5
+//! output: ./runs/verify.json
+//! ```
+//!
+//! `action` and `args` are a subcommand and its arguments, so blobs are
+//! composed with the action's own options (`--attest`, `alias ...`). Options
+//! left out of the template keep the values given on the command line.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use config::{Config, File};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    /// Identity scheme the flow is written for; this host only runs `oidc`
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+    /// Subcommand to run, e.g. `verify-identity`
+    pub action: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub provider: Option<String>,
+    /// `local` or `remote=<url>`, as `--prover`
+    pub prover: Option<String>,
+    /// `risc0` or `sp1`, as `--zkvm`
+    pub zkvm: Option<String>,
+    /// `browser` or `device`, as `--flow`
+    pub flow: Option<String>,
+    pub max_cost: Option<u64>,
+    #[serde(default)]
+    pub estimate: bool,
+    #[serde(default)]
+    pub strict_keys: bool,
+    /// Where to write a JSON record of the run once it completes
+    pub output: Option<PathBuf>,
+}
+
+fn default_scheme() -> String {
+    "oidc".to_string()
+}
+
+/// Run record written to a template's `output`.
+#[derive(Serialize)]
+struct RunRecord<'a> {
+    template: &'a Path,
+    args: &'a [String],
+    /// Unix time the run completed at
+    completed_at: u64,
+}
+
+impl Template {
+    /// Reads a template, its format given by the file extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        let template: Template = Config::builder()
+            .add_source(File::from(path).required(true))
+            .build()
+            .and_then(Config::try_deserialize)
+            .with_context(|| format!("Invalid template {}", path.display()))?;
+        if template.scheme != "oidc" {
+            bail!(
+                "Template is for the {} scheme, run it with that project's host",
+                template.scheme
+            );
+        }
+        if template.action == "run" {
+            bail!("Templates can't run other templates");
+        }
+        Ok(template)
+    }
+
+    /// Command line the template stands for, `provider` and `prover` being
+    /// the current values used where the template sets none.
+    pub fn args(&self, program: &str, provider: &str, prover: &str) -> Vec<String> {
+        let mut args = vec![
+            program.to_string(),
+            "--provider".to_string(),
+            self.provider.as_deref().unwrap_or(provider).to_string(),
+            "--prover".to_string(),
+            self.prover.as_deref().unwrap_or(prover).to_string(),
+        ];
+        for (option, value) in [("--zkvm", &self.zkvm), ("--flow", &self.flow)] {
+            if let Some(value) = value {
+                args.extend([option.to_string(), value.clone()]);
+            }
+        }
+        if let Some(max_cost) = self.max_cost {
+            args.extend(["--max-cost".to_string(), max_cost.to_string()]);
+        }
+        if self.estimate {
+            args.push("--estimate".to_string());
+        }
+        if self.strict_keys {
+            args.push("--strict-keys".to_string());
+        }
+        args.push(self.action.clone());
+        args.extend(self.args.iter().cloned());
+        args
+    }
+
+    /// Writes the run record to `output`, if set.
+    pub fn record(&self, template: &Path, args: &[String]) -> Result<()> {
+        let Some(output) = &self.output else {
+            return Ok(());
+        };
+        let record = RunRecord {
+            template,
+            args,
+            completed_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        };
+        std::fs::write(output, serde_json::to_vec_pretty(&record)?)
+            .with_context(|| format!("Failed to write {}", output.display()))
+    }
+}
@@ -0,0 +1,114 @@
+//! Refresh tokens kept across runs for the providers with `remember_login`,
+//! so that later commands get a fresh ID token without the browser.
+//!
+//! Each token is encrypted with AES-256-GCM under a key derived from a
+//! password with PBKDF2, like ecdsa-identity's account keys, but with a random
+//! salt per file: `salt || nonce || ciphertext`. The password is read from
+//! `OIDC_TOKEN_PASSWORD`, or prompted for.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, Context, Result};
+use openidconnect::RefreshToken;
+use pbkdf2::pbkdf2_hmac;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Where the refresh token of `provider_name` is kept.
+fn token_path(provider_name: &str) -> Result<PathBuf> {
+    Ok(app_dirs::get()
+        .keys()
+        .context("Failed to create the keys directory")?
+        .join(format!("{}.refresh", provider_name)))
+}
+
+/// The password tokens are encrypted with, from `OIDC_TOKEN_PASSWORD` or
+/// stdin, keeping it out of the shell history.
+pub fn password(provider_name: &str) -> Result<String> {
+    if let Ok(password) = std::env::var("OIDC_TOKEN_PASSWORD") {
+        return Ok(password);
+    }
+    print!("Password of the saved {} login: ", provider_name);
+    io::stdout().flush()?;
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Encrypts and keeps `refresh_token`, replacing the one kept before.
+pub fn save(provider_name: &str, refresh_token: &RefreshToken, password: &str) -> Result<()> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(password, &salt);
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), refresh_token.secret().as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt the refresh token"))?;
+
+    let path = token_path(provider_name)?;
+    let mut file =
+        create_private(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(&salt)?;
+    file.write_all(&nonce)?;
+    file.write_all(&ciphertext)?;
+    Ok(())
+}
+
+/// The refresh token kept for `provider_name`, `None` if there is none.
+pub fn load(provider_name: &str, password: &str) -> Result<Option<RefreshToken>> {
+    let path = token_path(provider_name)?;
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(anyhow!("{} is truncated", path.display()));
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(password, salt);
+    let plaintext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Wrong password, or {} is corrupted", path.display()))?;
+    let secret = String::from_utf8(plaintext).context("Invalid refresh token")?;
+    Ok(Some(RefreshToken::new(secret)))
+}
+
+/// Deletes the refresh token kept for `provider_name`. Returns whether there
+/// was one.
+pub fn forget(provider_name: &str) -> Result<bool> {
+    let path = token_path(provider_name)?;
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err).with_context(|| format!("Failed to delete {}", path.display())),
+    }
+}
+
+/// Creates `path` readable by its owner only.
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
@@ -0,0 +1,425 @@
+//! `watch` mode: keeps the accounts of the `[watch]` providers verified on-chain.
+//!
+//! Each provider is logged in once in the browser, asking for a refresh token.
+//! From then on ID tokens are renewed without interaction and a verification
+//! is proven every `interval` seconds, or on demand through a small local API:
+//!
+//! - `GET /status` returns the state of every watched account as JSON
+//! - `POST /verify/<provider>` re-proves that provider's account right away
+//!
+//! With `[watch.tls]` the API is served over TLS. When it also names a client
+//! CA, each account is bound to the certificate of the first client that asks
+//! to re-prove it, and other certificates get `403 Forbidden` for it.
+//!
+//! A verification that fails or doesn't settle within `settle_timeout` raises
+//! an alert on stderr and, if configured, at `alert_url`.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use host_output::say;
+use host_tls::{Acceptor, ClientCert};
+use oidc_identity::OidcIdentity;
+use oidc_provider::account::{derive_account, AccountScheme};
+use oidc_provider::{IdentityAction, OpenIdContext, PrivateInput};
+use openidconnect::RefreshToken;
+use sdk::{BlobTransaction, ContractInput, Digestable, ProofTransaction};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use crate::config::{AppConfig, IdentityProvider};
+use crate::node::{send_proof, sequenced_context, NodeTransport};
+use crate::oidc_client::{build_http_client, AuthClient, Flow, JwksCache, OIDCClient};
+use crate::prover::Prover;
+use crate::session_store::SessionStore;
+use crate::{authorize, jwks_pin, jwks_pins_path};
+
+/// What the local API reports for one watched account.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct AccountStatus {
+    pub account: String,
+    /// Unix time of the last verification seen settled
+    pub last_verified: Option<u64>,
+    pub last_error: Option<String>,
+    /// Thumbprint of the client certificate the account is bound to
+    pub bound_client: Option<String>,
+}
+
+type Statuses = Arc<Mutex<BTreeMap<String, AccountStatus>>>;
+
+/// A provider account kept verified.
+struct Watched<'a> {
+    provider_name: String,
+    identity_provider: &'a IdentityProvider,
+    client: AuthClient,
+    refresh_token: RefreshToken,
+    account: String,
+}
+
+pub async fn run(
+    config: &AppConfig,
+    node: &impl NodeTransport,
+    prover: &Prover<'_>,
+    strict_keys: bool,
+    flow: Flow,
+    listen: &str,
+) -> Result<()> {
+    if config.watch.providers.is_empty() {
+        bail!("No providers to watch, set `[watch] providers` in config.toml");
+    }
+
+    let mut watched = BTreeMap::new();
+    let statuses = Statuses::default();
+    let jwks = JwksCache::default();
+    for provider_name in &config.watch.providers {
+        let identity_provider = config
+            .identity_providers
+            .get(provider_name)
+            .ok_or_else(|| anyhow!("{} not set in config.toml", provider_name))?;
+
+        say!(
+            "Log in with {} to start watching its account",
+            provider_name
+        );
+        let login = authorize(
+            config,
+            provider_name,
+            identity_provider,
+            strict_keys,
+            flow,
+            true,
+            None,
+        )
+        .await;
+        let refresh_token = login
+            .refresh_token
+            .ok_or_else(|| anyhow!("{} did not issue a refresh token", provider_name))?;
+        let account = derive_account(AccountScheme::Oidc {
+            issuer: &identity_provider.issuer_url,
+            subject: login.claims.subject(),
+            salt: None,
+            contract_name: &config.contract.name,
+        })
+        .account;
+
+        let client = OIDCClient::build(
+            identity_provider.issuer_url.to_string(),
+            identity_provider.audience_url.to_string(),
+            Some(identity_provider.get_client_secret(provider_name)),
+            &format!("{}/callback", config.server.server_url),
+        )
+        .await?;
+
+        statuses.lock().unwrap().insert(
+            provider_name.clone(),
+            AccountStatus {
+                account: account.clone(),
+                ..Default::default()
+            },
+        );
+        watched.insert(
+            provider_name.clone(),
+            Watched {
+                provider_name: provider_name.clone(),
+                identity_provider,
+                client,
+                refresh_token,
+                account,
+            },
+        );
+    }
+
+    let (requests, mut on_demand) = mpsc::channel(16);
+    let acceptor = config
+        .watch
+        .tls
+        .as_ref()
+        .map(|tls| Acceptor::new(&tls.settings()))
+        .transpose()
+        .context("Invalid watch API TLS settings")?;
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to bind the watch API to {}", listen))?;
+    say!(
+        "👀 Watching {} account(s), API on {}://{}",
+        watched.len(),
+        if acceptor.is_some() { "https" } else { "http" },
+        listen
+    );
+    tokio::spawn(serve(listener, acceptor, statuses.clone(), requests));
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.watch.interval.max(1)));
+    loop {
+        let due: Vec<String> = tokio::select! {
+            _ = ticker.tick() => watched.keys().cloned().collect(),
+            Some(provider_name) = on_demand.recv() => vec![provider_name],
+        };
+
+        for provider_name in due {
+            let Some(entry) = watched.get_mut(&provider_name) else {
+                continue;
+            };
+            let result =
+                refresh_verification(config, node, prover, &jwks, strict_keys, entry).await;
+
+            let mut statuses = statuses.lock().unwrap();
+            let status = statuses.entry(provider_name).or_default();
+            match result {
+                Ok(()) => {
+                    say!("✅ {} verified", entry.account);
+                    status.last_verified = Some(now());
+                    status.last_error = None;
+                }
+                Err(err) => {
+                    status.last_error = Some(format!("{:#}", err));
+                    let status = status.clone();
+                    drop(statuses);
+                    alert(config, &status).await;
+                }
+            }
+        }
+    }
+}
+
+/// Renews the ID token, proves a verification at the account's current nonce
+/// and waits for the node to settle it.
+async fn refresh_verification(
+    config: &AppConfig,
+    node: &impl NodeTransport,
+    prover: &Prover<'_>,
+    jwks: &JwksCache,
+    strict_keys: bool,
+    entry: &mut Watched<'_>,
+) -> Result<()> {
+    let (id_token, _, rotated) =
+        OIDCClient::refresh_id_token(&entry.client, &entry.refresh_token).await?;
+    if let Some(rotated) = rotated {
+        entry.refresh_token = rotated;
+    }
+    let claims = OIDCClient::verify_refreshed_id_token(&entry.client, &id_token)?;
+    let account = derive_account(AccountScheme::Oidc {
+        issuer: &entry.identity_provider.issuer_url,
+        subject: claims.subject(),
+        salt: None,
+        contract_name: &config.contract.name,
+    })
+    .account;
+    if account != entry.account {
+        bail!(
+            "{} now returns tokens for another subject",
+            entry.provider_name
+        );
+    }
+
+    let keys = jwks
+        .get(&entry.identity_provider.jwk_public_key_url)
+        .await
+        .map_err(|err| anyhow!(err))?;
+    jwks_pin::verify(
+        &jwks_pins_path(),
+        &entry.identity_provider.issuer_url,
+        &keys,
+        strict_keys,
+    )?;
+    let jwk = OIDCClient::find_jwk(&keys, &id_token.to_string()).map_err(|err| anyhow!(err))?;
+
+    let contract_name = &config.contract.name;
+    let state: OidcIdentity = node
+        .get_contract_state(&contract_name.clone().into())
+        .await?
+        .into();
+    let nonce = state
+        .account(&entry.account)
+        .ok_or_else(|| anyhow!("{} is not registered", entry.account))?
+        .nonce;
+
+    let action = IdentityAction::VerifyIdentity {
+        account: entry.account.clone(),
+        nonce,
+        context: OpenIdContext {
+            issuer: entry.identity_provider.token_issuer().to_string(),
+            audience: vec![entry.identity_provider.audience_url.to_string()],
+            policy: entry.identity_provider.policy.clone(),
+            algorithms: entry.identity_provider.algorithms.clone(),
+            issuer_match: entry.identity_provider.issuer_match,
+        },
+        jwk_pub_key: jwk.public_key().into(),
+    };
+    let blob_tx = BlobTransaction {
+        identity: entry.account.clone().into(),
+        blobs: vec![action.as_blob(contract_name.clone().into())],
+    };
+    let store = SessionStore::open()?;
+    let tx_hash = node.send_tx_blob(&blob_tx).await?;
+
+    let mut input = ContractInput {
+        initial_state: state.as_digest(),
+        identity: blob_tx.identity,
+        tx_hash: tx_hash.clone(),
+        private_input: PrivateInput::Token(id_token.to_string()).to_bytes(),
+        tx_ctx: None,
+        blobs: blob_tx.blobs,
+        index: sdk::BlobIndex(0),
+    };
+    store.add_pending(contract_name, &input)?;
+    input.tx_ctx = Some(sequenced_context(node, &tx_hash).await?);
+    let proof = prover.prove(input).await?;
+    let proof_tx = ProofTransaction {
+        proof,
+        contract_name: contract_name.clone().into(),
+    };
+    send_proof(node, &proof_tx, &tx_hash).await?;
+    store.remove_pending(contract_name, &tx_hash)?;
+
+    wait_for_nonce(node, config, &entry.account, nonce + 1)
+        .await
+        .with_context(|| format!("Verification tx {} did not settle", tx_hash))
+}
+
+/// Polls the contract until the account's nonce reaches `expected`, for at
+/// most `watch.settle_timeout` seconds.
+pub async fn wait_for_nonce(
+    node: &impl NodeTransport,
+    config: &AppConfig,
+    account: &str,
+    expected: u32,
+) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(config.watch.settle_timeout);
+    while tokio::time::Instant::now() < deadline {
+        let state: OidcIdentity = node
+            .get_contract_state(&config.contract.name.clone().into())
+            .await?
+            .into();
+        if state
+            .account(account)
+            .is_some_and(|info| info.nonce >= expected)
+        {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    bail!("timed out after {}s", config.watch.settle_timeout)
+}
+
+async fn alert(config: &AppConfig, status: &AccountStatus) {
+    eprintln!(
+        "🚨 {}: {}",
+        status.account,
+        status.last_error.as_deref().unwrap_or("unknown error")
+    );
+    let Some(alert_url) = &config.watch.alert_url else {
+        return;
+    };
+    let sent = build_http_client()
+        .post(alert_url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(status).unwrap_or_default())
+        .send()
+        .await;
+    if let Err(err) = sent.and_then(|resp| resp.error_for_status()) {
+        eprintln!("⚠️ Failed to deliver alert to {}: {}", alert_url, err);
+    }
+}
+
+/// Serves the local API, over TLS when an acceptor is given.
+async fn serve(
+    listener: TcpListener,
+    acceptor: Option<Acceptor>,
+    statuses: Statuses,
+    requests: mpsc::Sender<String>,
+) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let (acceptor, statuses, requests) = (acceptor.clone(), statuses.clone(), requests.clone());
+        tokio::spawn(async move {
+            match acceptor {
+                Some(acceptor) => {
+                    if let Ok((stream, client)) = acceptor.accept(stream).await {
+                        answer(stream, client, &statuses, &requests).await;
+                    }
+                }
+                None => answer(stream, None, &statuses, &requests).await,
+            }
+        });
+    }
+}
+
+/// Answers one request. Requests are tiny, so a single read is enough.
+async fn answer<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    client: Option<ClientCert>,
+    statuses: &Statuses,
+    requests: &mpsc::Sender<String>,
+) {
+    let mut buffer = vec![0; 4096];
+    let Ok(read) = stream.read(&mut buffer).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+
+    let (code, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/status")) => {
+            let statuses = statuses.lock().unwrap().clone();
+            (
+                "200 OK",
+                serde_json::to_string(&statuses).unwrap_or_default(),
+            )
+        }
+        (Some("POST"), Some(path)) => match path.strip_prefix("/verify/") {
+            Some(provider) => match bind(statuses, provider, client.as_ref()) {
+                Some(true) => {
+                    let _ = requests.send(provider.to_string()).await;
+                    ("202 Accepted", String::new())
+                }
+                Some(false) => ("403 Forbidden", String::new()),
+                None => ("404 Not Found", String::new()),
+            },
+            None => ("404 Not Found", String::new()),
+        },
+        _ => ("404 Not Found", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        code,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// Binds the account watched for `provider` to the client certificate on
+/// first use. `None` if the provider isn't watched, `Some(false)` if its
+/// account is bound to another certificate.
+fn bind(statuses: &Statuses, provider: &str, client: Option<&ClientCert>) -> Option<bool> {
+    let mut statuses = statuses.lock().unwrap();
+    let status = statuses.get_mut(provider)?;
+    let Some(client) = client else {
+        return Some(true);
+    };
+    match &status.bound_client {
+        Some(bound) => Some(*bound == client.thumbprint),
+        None => {
+            status.bound_client = Some(client.thumbprint.clone());
+            Some(true)
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
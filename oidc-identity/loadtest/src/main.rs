@@ -0,0 +1,185 @@
+//! Load generator for the OIDC identity pipeline.
+//!
+//! Synthetic users get ID tokens from the e2e test identity provider and run
+//! register followed by verifications. By default every action is executed
+//! natively, which is what the mock prover does, measuring the contract alone.
+//! With `--node` the actions also go through a real node: blob transaction,
+//! proof (set `RISC0_DEV_MODE=1` for fake receipts) and proof transaction.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use client_sdk::helpers::risc0::Risc0Prover;
+use client_sdk::rest_client::NodeApiHttpClient;
+use e2e::{local_tx_context, unique_contract_name, wait_for_tx_context, TestIdp};
+use methods_identity::{GUEST_ELF, GUEST_ID};
+use oidc_identity::OidcIdentity;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::{IdentityAction, PrivateInput};
+use sdk::api::APIRegisterContract;
+use sdk::{BlobTransaction, ContractInput, ContractName, Digestable, ProofTransaction};
+
+mod stats;
+
+use stats::Stats;
+
+#[derive(Parser)]
+#[command(about = "Drive register/verify load through the OIDC identity contract")]
+struct Args {
+    /// Number of synthetic users
+    #[arg(long, default_value_t = 100)]
+    users: usize,
+    /// Verifications per user after registration
+    #[arg(long, default_value_t = 4)]
+    verifies: u32,
+    /// Target rate in actions per second (0 for as fast as possible)
+    #[arg(long, default_value_t = 0)]
+    rate: u64,
+    /// Node URL; without it actions are only executed natively
+    #[arg(long)]
+    node: Option<String>,
+}
+
+/// Where actions are sent, besides the native execution.
+struct Target {
+    client: NodeApiHttpClient,
+    prover: Risc0Prover<'static>,
+}
+
+impl Target {
+    async fn submit(&self, input: &mut ContractInput) -> Result<()> {
+        let contract_name = input.blobs[0].contract_name.clone();
+        input.tx_hash = self
+            .client
+            .send_tx_blob(&BlobTransaction {
+                identity: input.identity.clone(),
+                blobs: input.blobs.clone(),
+            })
+            .await?;
+        input.tx_ctx = Some(wait_for_tx_context(&self.client, &input.tx_hash).await?);
+        let proof = self.prover.prove(input.clone()).await?;
+        self.client
+            .send_tx_proof(&ProofTransaction {
+                proof,
+                contract_name,
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+async fn run_action(
+    target: Option<&Target>,
+    state: OidcIdentity,
+    contract_name: &ContractName,
+    account: &str,
+    action: IdentityAction,
+    token: String,
+) -> Result<OidcIdentity> {
+    let mut input = ContractInput {
+        initial_state: state.as_digest(),
+        identity: account.into(),
+        tx_hash: sdk::TxHash(String::new()),
+        private_input: PrivateInput::Token(token).to_bytes(),
+        // Replaced by the context of the block the action lands in, if sent
+        tx_ctx: Some(local_tx_context(0)),
+        blobs: vec![action.as_blob(contract_name.clone())],
+        index: sdk::BlobIndex(0),
+    };
+    if let Some(target) = target {
+        target.submit(&mut input).await?;
+    }
+    let (_, next_state, _) = oidc_identity::execute(input).map_err(anyhow::Error::msg)?;
+    Ok(next_state)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let idp = TestIdp::new();
+    let contract_name = unique_contract_name("oidc_identity_load");
+    let mut state = OidcIdentity::new();
+
+    let target = match &args.node {
+        Some(url) => {
+            let client = NodeApiHttpClient::new(url.clone()).context("Invalid node URL")?;
+            client
+                .register_contract(&APIRegisterContract {
+                    verifier: "risc0".into(),
+                    program_id: sdk::ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec()),
+                    state_digest: state.as_digest(),
+                    contract_name: contract_name.clone(),
+                })
+                .await?;
+            Some(Target {
+                client,
+                prover: Risc0Prover::new(GUEST_ELF),
+            })
+        }
+        None => None,
+    };
+
+    let mut ticker = (args.rate > 0)
+        .then(|| tokio::time::interval(Duration::from_secs_f64(1.0 / args.rate as f64)));
+    let mut stats = Stats::default();
+    let started = Instant::now();
+
+    for user in 0..args.users {
+        let subject = format!("load-user-{user}");
+        let account = format!("{subject}.{contract_name}");
+
+        let mut actions = vec![(
+            "register",
+            IdentityAction::RegisterIdentity {
+                account: account.clone(),
+                context: idp.context.clone(),
+                jwk_pub_key: idp.jwk(),
+                recovery_codes: vec![],
+                metadata: AccountMetadata::default(),
+                nonce_strategy: NonceStrategy::default(),
+            },
+        )];
+        actions.extend((0..args.verifies).map(|nonce| {
+            (
+                "verify",
+                IdentityAction::VerifyIdentity {
+                    account: account.clone(),
+                    nonce,
+                    context: idp.context.clone(),
+                    jwk_pub_key: idp.jwk(),
+                },
+            )
+        }));
+
+        for (kind, action) in actions {
+            if let Some(ticker) = ticker.as_mut() {
+                ticker.tick().await;
+            }
+            let begin = Instant::now();
+            match run_action(
+                target.as_ref(),
+                state.clone(),
+                &contract_name,
+                &account,
+                action,
+                idp.id_token(&subject),
+            )
+            .await
+            {
+                Ok(next_state) => {
+                    stats.record(kind, begin.elapsed());
+                    state = next_state;
+                }
+                Err(err) => {
+                    eprintln!("{kind} failed for {account}: {err:#}");
+                    stats.record_failure(kind);
+                }
+            }
+        }
+    }
+
+    stats.report(started.elapsed());
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Latencies recorded per operation kind.
+#[derive(Default)]
+pub struct Stats {
+    samples: BTreeMap<&'static str, Vec<Duration>>,
+    failures: BTreeMap<&'static str, usize>,
+}
+
+impl Stats {
+    pub fn record(&mut self, kind: &'static str, latency: Duration) {
+        self.samples.entry(kind).or_default().push(latency);
+    }
+
+    pub fn record_failure(&mut self, kind: &'static str) {
+        *self.failures.entry(kind).or_default() += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.samples.values().map(Vec::len).sum()
+    }
+
+    pub fn report(&mut self, elapsed: Duration) {
+        println!(
+            "{:<10} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10}",
+            "action", "ok", "failed", "p50", "p90", "p99", "max"
+        );
+        for (kind, samples) in self.samples.iter_mut() {
+            samples.sort();
+            println!(
+                "{:<10} {:>7} {:>7} {:>10?} {:>10?} {:>10?} {:>10?}",
+                kind,
+                samples.len(),
+                self.failures.get(kind).copied().unwrap_or(0),
+                percentile(samples, 50),
+                percentile(samples, 90),
+                percentile(samples, 99),
+                samples.last().copied().unwrap_or_default(),
+            );
+        }
+        println!(
+            "throughput: {:.1} ops/s over {:?}",
+            self.total() as f64 / elapsed.as_secs_f64(),
+            elapsed
+        );
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
@@ -0,0 +1,22 @@
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::guest::entry!(main);
+
+/// Folds several identity receipts into one: each inner journal is verified
+/// against the identity guest's image id (resolved by the host through
+/// assumptions), then all journals are committed in order.
+fn main() {
+    let (image_id, journals): ([u32; 8], Vec<Vec<u8>>) = env::read();
+
+    for journal in &journals {
+        env::verify(image_id, journal).expect("inner receipt verification failed");
+    }
+
+    env::commit(&journals);
+}
@@ -1,3 +1,32 @@
+use std::collections::HashMap;
+
+use risc0_build::GuestOptions;
+
 fn main() {
-    risc0_build::embed_methods();
+    // `--features instrument` and `--features serde-claims` build the identity
+    // guest with its own feature of the same name
+    let features: Vec<String> = [
+        ("CARGO_FEATURE_INSTRUMENT", "instrument"),
+        ("CARGO_FEATURE_SERDE_CLAIMS", "serde-claims"),
+    ]
+    .into_iter()
+    .filter(|(var, _)| std::env::var_os(var).is_some())
+    .map(|(_, feature)| feature.to_string())
+    .collect();
+    let mut options = HashMap::new();
+    if !features.is_empty() {
+        options.insert(
+            "guest",
+            GuestOptions {
+                features,
+                ..Default::default()
+            },
+        );
+    }
+    risc0_build::embed_methods_with_options(options);
+
+    // `--features sp1` also builds `sp1-guest`, whose ELF path the build
+    // exports as `SP1_ELF_sp1-guest`
+    #[cfg(feature = "sp1")]
+    sp1_build::build_program("sp1-guest");
 }
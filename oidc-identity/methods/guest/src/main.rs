@@ -7,7 +7,10 @@ use sdk::guest::commit;
 use sdk::guest::GuestEnv;
 use sdk::guest::Risc0Env;
 
+#[cfg(not(feature = "merkle-state"))]
 use oidc_identity::execute;
+#[cfg(feature = "merkle-state")]
+use oidc_identity::merkle::execute;
 use sdk::ContractInput;
 
 risc0_zkvm::guest::entry!(main);
@@ -15,5 +18,46 @@ risc0_zkvm::guest::entry!(main);
 fn main() {
     let env = Risc0Env {};
     let input: ContractInput = env.read();
+    #[cfg(feature = "instrument")]
+    let start = instrument::Mark::now();
     commit(env, input.clone(), execute(input));
+    #[cfg(feature = "instrument")]
+    instrument::write_stats(start);
+}
+
+#[cfg(feature = "instrument")]
+mod instrument {
+    use alloc::boxed::Box;
+    use oidc_identity::limits::GuestStats;
+    use risc0_zkvm::guest::env;
+
+    pub struct Mark {
+        cycles: u64,
+        heap: u64,
+    }
+
+    impl Mark {
+        pub fn now() -> Self {
+            Mark {
+                cycles: env::cycle_count(),
+                heap: heap_top(),
+            }
+        }
+    }
+
+    /// The zkVM's bump allocator never frees, so a fresh allocation lands on
+    /// top of everything allocated so far: its address is the peak heap.
+    fn heap_top() -> u64 {
+        let probe = Box::new(0u8);
+        &*probe as *const u8 as u64
+    }
+
+    /// Writes what was spent since `start` to the host's stdout, leaving the
+    /// journal to the program output.
+    pub fn write_stats(start: Mark) {
+        env::write(&GuestStats {
+            cycles: env::cycle_count() - start.cycles,
+            heap: heap_top() - start.heap,
+        });
+    }
 }
@@ -0,0 +1,20 @@
+#![no_main]
+
+use sdk::guest::commit;
+use sdk::guest::GuestEnv;
+use sdk::guest::SP1Env;
+
+#[cfg(not(feature = "merkle-state"))]
+use oidc_identity::execute;
+#[cfg(feature = "merkle-state")]
+use oidc_identity::merkle::execute;
+use sdk::ContractInput;
+
+sp1_zkvm::entrypoint!(main);
+
+// Same program as `guest`, for the SP1 zkVM.
+fn main() {
+    let env = SP1Env {};
+    let input: ContractInput = env.read();
+    commit(env, input.clone(), execute(input));
+}
@@ -0,0 +1,307 @@
+//! Runs the contracts inside the risc0 executor rather than natively.
+//!
+//! Native unit tests don't catch guest-only regressions (no_std builds,
+//! allocation limits, cycle blow-ups). Each case here executes the guest ELF
+//! without proving, checks its journal against the native `execute`, and
+//! fails when the cycle count exceeds the case's budget.
+//!
+//! Raise a budget deliberately when a change is expected to cost more cycles.
+
+use alias_registry::{AliasAction, AliasRegistry, IdentityScheme};
+use attestation_registry::{AttestationAction, AttestationRegistry};
+use e2e::TestIdp;
+use methods_identity::{ALIAS_GUEST_ELF, ATTESTATION_GUEST_ELF, GUEST_ELF};
+use oidc_identity::OidcIdentity;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::{IdentityAction, PrivateInput};
+use risc0_zkvm::{default_executor, ExecutorEnv};
+use sdk::{Blob, BlobIndex, ContractInput, Digestable, HyleOutput, RunResult, StateDigest};
+
+const IDENTITY_CONTRACT: &str = "oidc_identity";
+const ATTESTATION_CONTRACT: &str = "attestation_registry";
+const ALIAS_CONTRACT: &str = "alias_registry";
+
+/// Cycle budgets, with headroom over what the guests take today.
+const REGISTER_BUDGET: u64 = 8_000_000;
+const VERIFY_BUDGET: u64 = 8_000_000;
+const COMPANION_BUDGET: u64 = 1_000_000;
+
+/// Executes `elf` on `input` and returns its journal with the user cycles spent.
+fn run_guest(elf: &[u8], input: &ContractInput) -> (HyleOutput, u64) {
+    let env = ExecutorEnv::builder()
+        .write(input)
+        .expect("Failed to write guest input")
+        .build()
+        .expect("Failed to build executor env");
+    let session = default_executor()
+        .execute(env, elf)
+        .expect("Guest execution failed");
+    let cycles = session.segments.iter().map(|s| s.cycles as u64).sum();
+    let output = session
+        .journal
+        .decode()
+        .expect("Failed to decode guest journal");
+    (output, cycles)
+}
+
+/// Runs `input` in the guest, checks the journal agrees with the native
+/// execution and that it fits in `budget`, and returns the next state.
+fn assert_guest_matches<State: Digestable>(
+    elf: &[u8],
+    input: ContractInput,
+    native: fn(ContractInput) -> RunResult<State>,
+    budget: u64,
+) -> StateDigest {
+    let (output, cycles) = run_guest(elf, &input);
+    let expected = native(input);
+
+    match expected {
+        Ok((program_output, state, _)) => {
+            assert!(
+                output.success,
+                "guest failed: {}",
+                String::from_utf8_lossy(&output.program_outputs)
+            );
+            assert_eq!(output.program_outputs, program_output.into_bytes());
+            assert_eq!(output.next_state, state.as_digest());
+        }
+        Err(err) => {
+            assert!(
+                !output.success,
+                "guest succeeded where native execution failed"
+            );
+            assert_eq!(output.program_outputs, err.into_bytes());
+        }
+    }
+    assert!(
+        cycles <= budget,
+        "{cycles} cycles exceed the budget of {budget}"
+    );
+    output.next_state
+}
+
+/// Context of the block the test transactions land in, before the test
+/// tokens expire.
+fn tx_ctx() -> Option<sdk::TxContext> {
+    Some(sdk::TxContext {
+        block_height: sdk::BlockHeight(1),
+        timestamp: 1_700_000_000,
+        ..Default::default()
+    })
+}
+
+fn identity_input(
+    state: StateDigest,
+    account: &str,
+    action: IdentityAction,
+    token: String,
+) -> ContractInput {
+    ContractInput {
+        initial_state: state,
+        identity: account.into(),
+        tx_hash: sdk::TxHash("guest-test".to_string()),
+        private_input: PrivateInput::Token(token).to_bytes(),
+        tx_ctx: tx_ctx(),
+        blobs: vec![action.as_blob(IDENTITY_CONTRACT.into())],
+        index: BlobIndex(0),
+    }
+}
+
+/// Registers `subject` in a fresh identity contract, returning the new state.
+fn registered(idp: &TestIdp, subject: &str, account: &str) -> StateDigest {
+    let action = IdentityAction::RegisterIdentity {
+        account: account.to_string(),
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+        recovery_codes: vec![],
+        metadata: AccountMetadata::default(),
+        nonce_strategy: NonceStrategy::default(),
+    };
+    assert_guest_matches(
+        GUEST_ELF,
+        identity_input(
+            OidcIdentity::new().as_digest(),
+            account,
+            action,
+            idp.id_token(subject),
+        ),
+        oidc_identity::execute,
+        REGISTER_BUDGET,
+    )
+}
+
+/// A verification blob followed by `companion`, as sent when attesting or aliasing.
+fn with_identity_blob(idp: &TestIdp, account: &str, companion: Blob) -> Vec<Blob> {
+    let verify = IdentityAction::VerifyIdentity {
+        account: account.to_string(),
+        nonce: 0,
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+    };
+    vec![verify.as_blob(IDENTITY_CONTRACT.into()), companion]
+}
+
+#[test]
+fn identity_register_and_verify() {
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let state = registered(&idp, "alice", &account);
+
+    let verify = IdentityAction::VerifyIdentity {
+        account: account.clone(),
+        nonce: 0,
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+    };
+    assert_guest_matches(
+        GUEST_ELF,
+        identity_input(state, &account, verify, idp.id_token("alice")),
+        oidc_identity::execute,
+        VERIFY_BUDGET,
+    );
+}
+
+/// Prints the cycles of a verification, which parses the token's claims once.
+/// Run it with and without `--features serde-claims` to compare the scanner
+/// with `serde_json`.
+#[test]
+fn claims_parse_cycles() {
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let state = registered(&idp, "alice", &account);
+
+    let verify = IdentityAction::VerifyIdentity {
+        account: account.clone(),
+        nonce: 0,
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+    };
+    let input = identity_input(state, &account, verify, idp.id_token("alice"));
+    let (output, cycles) = run_guest(GUEST_ELF, &input);
+    assert!(output.success);
+    assert!(
+        cycles <= VERIFY_BUDGET,
+        "{cycles} cycles exceed the budget of {VERIFY_BUDGET}"
+    );
+
+    let parser = if cfg!(feature = "serde-claims") {
+        "serde_json"
+    } else {
+        "scanner"
+    };
+    println!("verify with the {parser} claims parser: {cycles} cycles");
+}
+
+#[test]
+fn identity_rejects_malformed_state() {
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let action = IdentityAction::GetIdentityInfo {
+        account: account.clone(),
+    };
+    assert_guest_matches(
+        GUEST_ELF,
+        identity_input(
+            StateDigest(vec![0xff; 7]),
+            &account,
+            action,
+            idp.id_token("alice"),
+        ),
+        oidc_identity::execute,
+        VERIFY_BUDGET,
+    );
+}
+
+#[test]
+fn attestation_attest() {
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let attest = AttestationAction::Attest {
+        claim: "google-login".to_string(),
+    };
+    let input = ContractInput {
+        initial_state: AttestationRegistry::new([IDENTITY_CONTRACT.to_string()]).as_digest(),
+        identity: account.as_str().into(),
+        tx_hash: sdk::TxHash("guest-test".to_string()),
+        private_input: vec![],
+        tx_ctx: None,
+        blobs: with_identity_blob(&idp, &account, attest.as_blob(ATTESTATION_CONTRACT.into())),
+        index: BlobIndex(1),
+    };
+    assert_guest_matches(
+        ATTESTATION_GUEST_ELF,
+        input,
+        attestation_registry::execute,
+        COMPANION_BUDGET,
+    );
+}
+
+#[test]
+fn alias_link() {
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let link = AliasAction::Link {
+        handle: "alice".to_string(),
+    };
+    let input = ContractInput {
+        initial_state: AliasRegistry::new([(IDENTITY_CONTRACT.to_string(), IdentityScheme::Oidc)])
+            .as_digest(),
+        identity: account.as_str().into(),
+        tx_hash: sdk::TxHash("guest-test".to_string()),
+        private_input: vec![],
+        tx_ctx: None,
+        blobs: with_identity_blob(&idp, &account, link.as_blob(ALIAS_CONTRACT.into())),
+        index: BlobIndex(1),
+    };
+    assert_guest_matches(
+        ALIAS_GUEST_ELF,
+        input,
+        alias_registry::execute,
+        COMPANION_BUDGET,
+    );
+}
+
+#[cfg(feature = "instrument")]
+#[test]
+fn identity_stats_within_budgets() {
+    use oidc_identity::limits::GuestStats;
+
+    let idp = TestIdp::new();
+    let account = format!("alice.{IDENTITY_CONTRACT}");
+    let action = IdentityAction::RegisterIdentity {
+        account: account.clone(),
+        context: idp.context.clone(),
+        jwk_pub_key: idp.jwk(),
+        recovery_codes: vec![],
+        metadata: AccountMetadata::default(),
+        nonce_strategy: NonceStrategy::default(),
+    };
+    let input = identity_input(
+        OidcIdentity::new().as_digest(),
+        &account,
+        action,
+        idp.id_token("alice"),
+    );
+    let mut stdout = Vec::new();
+    let env = ExecutorEnv::builder()
+        .write(&input)
+        .expect("Failed to write guest input")
+        .stdout(&mut stdout)
+        .build()
+        .expect("Failed to build executor env");
+    let session = default_executor()
+        .execute(env, GUEST_ELF)
+        .expect("Guest execution failed");
+
+    // The journal is the program output alone, the stats go to stdout
+    let output: HyleOutput = session
+        .journal
+        .decode()
+        .expect("Failed to decode instrumented journal");
+    assert!(output.success);
+    let stats: GuestStats =
+        risc0_zkvm::serde::from_slice(&stdout).expect("Failed to decode guest stats");
+    assert!(stats.cycles > 0 && stats.heap > 0);
+    assert_eq!(stats.check_budget(), Ok(()));
+}
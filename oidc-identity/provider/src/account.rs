@@ -0,0 +1,154 @@
+//! Account identifier derivation, shared by the contracts and their clients so
+//! that anyone can predict an identity before sending a transaction.
+
+use alloc::{format, string::String};
+
+use crate::hasher::{AccountHasher, DefaultHasher, Sha256Hasher};
+
+/// Inputs identifying an account, per identity scheme.
+#[derive(Debug, Clone, Copy)]
+pub enum AccountScheme<'a> {
+    /// OIDC: the token's `sub` and `iss` claims, and the account's salt if
+    /// registered with one. A salted account is named after its hash rather
+    /// than the subject, which the salt keeps private.
+    Oidc {
+        issuer: &'a str,
+        subject: &'a str,
+        salt: Option<&'a str>,
+        contract_name: &'a str,
+    },
+    /// ECDSA: the hex-encoded uncompressed SEC1 public key.
+    Ecdsa {
+        pub_key: &'a str,
+        contract_name: &'a str,
+    },
+    /// MetaMask: the hex-encoded Ethereum address, with or without `0x`.
+    Metamask {
+        address: &'a str,
+        contract_name: &'a str,
+    },
+}
+
+/// The identity used as blob transaction sender, and the hash the contract stores.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DerivedAccount {
+    pub account: String,
+    pub hash: String,
+}
+
+/// Hash stored by the OIDC contract for a `sub`/`iss` pair.
+pub fn oidc_account_hash(subject: &str, issuer: &str) -> String {
+    DefaultHasher::digest_hex(format!("{subject}:{issuer}").as_bytes())
+}
+
+/// [`oidc_account_hash`] of an account registered with a private `salt`: it
+/// can't be recomputed from a known subject, nor matched across contracts
+/// registered with different salts.
+pub fn oidc_salted_account_hash(salt: &str, subject: &str, issuer: &str) -> String {
+    DefaultHasher::digest_hex(format!("{salt}:{subject}:{issuer}").as_bytes())
+}
+
+/// Key of an account's email in the OIDC contract's email index: the same hash
+/// as a hashed `DiscloseClaim` of the email.
+pub fn email_hash(email: &str) -> String {
+    DefaultHasher::digest_hex(email.as_bytes())
+}
+
+pub fn derive_account(scheme: AccountScheme) -> DerivedAccount {
+    match scheme {
+        AccountScheme::Oidc {
+            issuer,
+            subject,
+            salt,
+            contract_name,
+        } => match salt {
+            Some(salt) => {
+                let hash = oidc_salted_account_hash(salt, subject, issuer);
+                DerivedAccount {
+                    account: format!("{hash}.{contract_name}"),
+                    hash,
+                }
+            }
+            None => DerivedAccount {
+                account: format!("{subject}.{contract_name}"),
+                hash: oidc_account_hash(subject, issuer),
+            },
+        },
+        AccountScheme::Ecdsa {
+            pub_key,
+            contract_name,
+        } => DerivedAccount {
+            account: format!("{pub_key}.{contract_name}"),
+            hash: Sha256Hasher::digest_hex(pub_key.as_bytes()),
+        },
+        AccountScheme::Metamask {
+            address,
+            contract_name,
+        } => {
+            use sha3::{Digest, Keccak256};
+            let address = address.strip_prefix("0x").unwrap_or(address);
+            let account = format!("{address}.{contract_name}");
+            let hash = hex::encode(Keccak256::digest(account.as_bytes()));
+            DerivedAccount { account, hash }
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use super::{derive_account, AccountScheme};
+    use alloc::string::String;
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    pub struct WasmDerivedAccount {
+        account: String,
+        hash: String,
+    }
+
+    #[wasm_bindgen]
+    impl WasmDerivedAccount {
+        #[wasm_bindgen(getter)]
+        pub fn account(&self) -> String {
+            self.account.clone()
+        }
+
+        #[wasm_bindgen(getter)]
+        pub fn hash(&self) -> String {
+            self.hash.clone()
+        }
+    }
+
+    /// `scheme` is one of `oidc`, `ecdsa` or `metamask`. `key` is the issuer
+    /// for OIDC and ignored otherwise; `id` is the subject, public key or address.
+    #[wasm_bindgen(js_name = deriveAccount)]
+    pub fn derive_account_js(
+        scheme: &str,
+        key: &str,
+        id: &str,
+        contract_name: &str,
+    ) -> Result<WasmDerivedAccount, JsError> {
+        let scheme = match scheme {
+            "oidc" => AccountScheme::Oidc {
+                issuer: key,
+                subject: id,
+                salt: None,
+                contract_name,
+            },
+            "ecdsa" => AccountScheme::Ecdsa {
+                pub_key: id,
+                contract_name,
+            },
+            "metamask" => AccountScheme::Metamask {
+                address: id,
+                contract_name,
+            },
+            _ => return Err(JsError::new("unknown identity scheme")),
+        };
+        let derived = derive_account(scheme);
+        Ok(WasmDerivedAccount {
+            account: derived.account,
+            hash: derived.hash,
+        })
+    }
+}
@@ -0,0 +1,163 @@
+//! Append-only Merkle tree of pruned accounts.
+//!
+//! The contract only keeps the root, the number of leaves and the frontier
+//! (the last left node of each level): enough to append leaves and, given an
+//! inclusion proof, clear one. Clients rebuild proofs with [`proof`] from the
+//! entries published in the `PruneInactive` program outputs.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::hasher::{AccountHasher, DefaultHasher};
+
+/// Depth of the tree, so at most 2^32 accounts are ever archived.
+pub const ARCHIVE_DEPTH: usize = 32;
+
+pub type Node = [u8; 32];
+
+/// Position of an archived account and the sibling of each node on its path.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct ArchiveProof {
+    pub index: u32,
+    pub siblings: Vec<Node>,
+}
+
+/// Leaf of an account archived with the encoded `record` of its state.
+pub fn leaf(account: &str, record: &[u8]) -> Node {
+    let mut data = Vec::with_capacity(4 + account.len() + record.len());
+    data.extend_from_slice(&(account.len() as u32).to_le_bytes());
+    data.extend_from_slice(account.as_bytes());
+    data.extend_from_slice(record);
+    DefaultHasher::digest(&data)
+}
+
+/// Root of an all-empty subtree, per level. Cleared leaves are zero too.
+fn zeros() -> [Node; ARCHIVE_DEPTH + 1] {
+    let mut zeros = [[0; 32]; ARCHIVE_DEPTH + 1];
+    for level in 0..ARCHIVE_DEPTH {
+        zeros[level + 1] = DefaultHasher::hash_pair(&zeros[level], &zeros[level]);
+    }
+    zeros
+}
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct MerkleArchive {
+    root: Node,
+    size: u64,
+    frontier: Vec<Node>,
+}
+
+impl Default for MerkleArchive {
+    fn default() -> Self {
+        MerkleArchive {
+            root: zeros()[ARCHIVE_DEPTH],
+            size: 0,
+            frontier: vec![[0; 32]; ARCHIVE_DEPTH],
+        }
+    }
+}
+
+impl MerkleArchive {
+    pub fn root(&self) -> Node {
+        self.root
+    }
+
+    /// Number of leaves ever appended, cleared ones included.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Appends `leaf`, returning its index, or `None` once the tree is full.
+    pub fn append(&mut self, leaf: Node) -> Option<u32> {
+        let index = u32::try_from(self.size).ok()?;
+        let zeros = zeros();
+        let mut node = leaf;
+        for level in 0..ARCHIVE_DEPTH {
+            if (index >> level) & 1 == 0 {
+                self.frontier[level] = node;
+                node = DefaultHasher::hash_pair(&node, &zeros[level]);
+            } else {
+                node = DefaultHasher::hash_pair(&self.frontier[level], &node);
+            }
+        }
+        self.root = node;
+        self.size += 1;
+        Some(index)
+    }
+
+    /// Clears `leaf` if `proof` shows it is in the tree, so it can't be
+    /// restored twice. Returns whether it was.
+    pub fn remove(&mut self, leaf: Node, proof: &ArchiveProof) -> bool {
+        if u64::from(proof.index) >= self.size
+            || proof.siblings.len() != ARCHIVE_DEPTH
+            || leaf == [0; 32]
+        {
+            return false;
+        }
+        if path(leaf, proof)[ARCHIVE_DEPTH] != self.root {
+            return false;
+        }
+
+        let cleared = path([0; 32], proof);
+        // The frontier holds the newest left node of each level, which may sit
+        // on the cleared leaf's path
+        let last = self.size - 1;
+        for level in 0..ARCHIVE_DEPTH {
+            if u64::from(proof.index >> level) == (last >> level) & !1 {
+                self.frontier[level] = cleared[level];
+            }
+        }
+        self.root = cleared[ARCHIVE_DEPTH];
+        true
+    }
+}
+
+/// Nodes from `leaf` up to the root, following `proof`.
+fn path(leaf: Node, proof: &ArchiveProof) -> [Node; ARCHIVE_DEPTH + 1] {
+    let mut nodes = [leaf; ARCHIVE_DEPTH + 1];
+    for (level, sibling) in proof.siblings.iter().enumerate() {
+        nodes[level + 1] = if (proof.index >> level) & 1 == 0 {
+            DefaultHasher::hash_pair(&nodes[level], sibling)
+        } else {
+            DefaultHasher::hash_pair(sibling, &nodes[level])
+        };
+    }
+    nodes
+}
+
+/// Builds the inclusion proof of leaf `index` from every leaf of the archive,
+/// in order, with cleared ones as zero.
+pub fn proof(leaves: &[Node], index: u32) -> ArchiveProof {
+    let zeros = zeros();
+    let mut level_nodes = leaves.to_vec();
+    let mut position = index as usize;
+    let mut siblings = Vec::with_capacity(ARCHIVE_DEPTH);
+    for zero in &zeros[..ARCHIVE_DEPTH] {
+        siblings.push(level_nodes.get(position ^ 1).copied().unwrap_or(*zero));
+        level_nodes = level_nodes
+            .chunks(2)
+            .map(|pair| DefaultHasher::hash_pair(&pair[0], pair.get(1).unwrap_or(zero)))
+            .collect();
+        position >>= 1;
+    }
+    ArchiveProof { index, siblings }
+}
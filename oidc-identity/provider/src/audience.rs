@@ -0,0 +1,28 @@
+//! Audiences, which OIDC lets be a single string or a list: the `aud` claim
+//! of providers issuing tokens for several clients (an API audience, an
+//! audience mapper), and [`crate::OpenIdContext::audience`] for contexts
+//! accepting tokens of several clients. JSON reads either form.
+
+use alloc::{string::String, vec, vec::Vec};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Deserializes a string or a list of strings, for `deserialize_with`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(audience) => vec![audience],
+        OneOrMany::Many(audiences) => audiences,
+    })
+}
+
+/// Whether a token issued for `aud` is meant for a context accepting
+/// `accepted`: any audience in common.
+pub fn matches(accepted: &[String], aud: &[String]) -> bool {
+    aud.iter().any(|audience| accepted.contains(audience))
+}
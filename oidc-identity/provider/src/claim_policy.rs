@@ -0,0 +1,71 @@
+//! Requirements on ID token claims besides issuer and audience, for
+//! organizations restricting who can bind a subject, e.g. to their Google
+//! Workspace domain. A contract enforces the policy it was configured with for
+//! the token's issuer, along with the one of the action's [`OpenIdContext`],
+//! which can only narrow it.
+//!
+//! [`OpenIdContext`]: crate::OpenIdContext
+
+use alloc::{string::String, vec::Vec};
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Every requirement is optional; the default policy accepts any token.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(default)]
+pub struct ClaimPolicy {
+    /// Google Workspace domain (`hd`) the account must belong to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosted_domain: Option<String>,
+    /// Refuse tokens without `email_verified: true`
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub email_verified: bool,
+    /// Clients (`azp`) the token may have been issued to; empty accepts any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authorized_parties: Vec<String>,
+}
+
+impl ClaimPolicy {
+    pub fn is_empty(&self) -> bool {
+        *self == ClaimPolicy::default()
+    }
+
+    /// Whether a token with these claims meets the policy. Without `azp`,
+    /// the token was issued to its sole audience, if it has only one.
+    pub fn allows(
+        &self,
+        hosted_domain: Option<&str>,
+        email_verified: Option<bool>,
+        authorized_party: Option<&str>,
+        aud: &[String],
+    ) -> bool {
+        let authorized_party = match (authorized_party, aud) {
+            (Some(party), _) => Some(party),
+            (None, [audience]) => Some(audience.as_str()),
+            (None, _) => None,
+        };
+        self.hosted_domain
+            .as_deref()
+            .map_or(true, |domain| hosted_domain == Some(domain))
+            && (!self.email_verified || email_verified == Some(true))
+            && (self.authorized_parties.is_empty()
+                || authorized_party.is_some_and(|party| {
+                    self.authorized_parties
+                        .iter()
+                        .any(|allowed| allowed == party)
+                }))
+    }
+}
@@ -0,0 +1,518 @@
+//! Allocation-light extraction of the ID token claims contracts use.
+//!
+//! Parsing the payload with `serde_json` builds a generic deserializer for every
+//! field, known or not, which is a sizeable share of the guest cycles. This
+//! scanner walks the payload once, borrows strings that need no unescaping and
+//! skips unknown members without materializing them. Build with the
+//! `serde-claims` feature to go back to `serde_json`, e.g. to compare cycles.
+
+#[cfg(not(feature = "serde-claims"))]
+use alloc::{borrow::Cow, vec};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Claims {
+    pub sub: String,
+    pub email: String,
+    pub exp: u64,
+    /// A single audience is read as a list of one
+    #[serde(deserialize_with = "crate::audience::deserialize")]
+    pub aud: Vec<String>,
+    pub iss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
+    /// See [`crate::token_nonce`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Google Workspace domain, see [`crate::claim_policy`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    /// Client the token was issued to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
+    /// Tenant the token was issued from, see [`crate::issuer`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tid: Option<String>,
+}
+
+const INVALID: &str = "Failed to parse JWT claims";
+
+#[cfg(feature = "serde-claims")]
+pub fn parse(payload: &[u8]) -> Result<Claims, &'static str> {
+    serde_json::from_slice(payload).map_err(|_| INVALID)
+}
+
+/// The members of the claims object named in `wanted`, whichever they are:
+/// strings as they are, other values as compact JSON. Absent and `null`
+/// members are left out. Only contracts asking for custom claims pay for this
+/// generic parse.
+pub fn extract(payload: &[u8], wanted: &[&str]) -> Result<BTreeMap<String, String>, &'static str> {
+    let members: BTreeMap<String, serde_json::Value> =
+        serde_json::from_slice(payload).map_err(|_| INVALID)?;
+    Ok(members
+        .into_iter()
+        .filter(|(name, _)| wanted.contains(&name.as_str()))
+        .filter_map(|(name, value)| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some((name, value)),
+            value => Some((name, value.to_string())),
+        })
+        .collect())
+}
+
+/// Parses the claims. Like the `serde_json` path, it rejects missing or
+/// mistyped fields and duplicate keys, reads `null` optional claims as absent,
+/// and ignores unknown members, checking their syntax all the same. As
+/// `serde_json` does for a struct, it also reads the claims as an array of
+/// their values in declaration order.
+#[cfg(not(feature = "serde-claims"))]
+pub fn parse(payload: &[u8]) -> Result<Claims, &'static str> {
+    let mut scanner = Scanner {
+        bytes: payload,
+        pos: 0,
+    };
+    let fields = if scanner.peek()? == b'[' {
+        scanner.sequence()?
+    } else {
+        scanner.object()?
+    };
+    scanner.skip_whitespace();
+    if scanner.pos != payload.len() {
+        return Err(INVALID);
+    }
+
+    Ok(Claims {
+        sub: fields.sub.ok_or(INVALID)?.into_owned(),
+        email: fields.email.ok_or(INVALID)?.into_owned(),
+        exp: fields.exp.ok_or(INVALID)?,
+        aud: fields.aud.ok_or(INVALID)?,
+        iss: fields.iss.ok_or(INVALID)?.into_owned(),
+        iat: fields.iat.flatten(),
+        nbf: fields.nbf.flatten(),
+        nonce: fields.nonce.flatten().map(Cow::into_owned),
+        hd: fields.hd.flatten().map(Cow::into_owned),
+        email_verified: fields.email_verified.flatten(),
+        azp: fields.azp.flatten().map(Cow::into_owned),
+        tid: fields.tid.flatten().map(Cow::into_owned),
+    })
+}
+
+/// Claims read so far. An optional claim read as `Some(None)` was `null`:
+/// absent from the [`Claims`], but a second one is still a duplicate.
+#[cfg(not(feature = "serde-claims"))]
+#[derive(Default)]
+struct Fields<'a> {
+    sub: Option<Cow<'a, str>>,
+    email: Option<Cow<'a, str>>,
+    exp: Option<u64>,
+    aud: Option<Vec<String>>,
+    iss: Option<Cow<'a, str>>,
+    iat: Option<Option<u64>>,
+    nbf: Option<Option<u64>>,
+    nonce: Option<Option<Cow<'a, str>>>,
+    hd: Option<Option<Cow<'a, str>>>,
+    email_verified: Option<Option<bool>>,
+    azp: Option<Option<Cow<'a, str>>>,
+    tid: Option<Option<Cow<'a, str>>>,
+}
+
+#[cfg(not(feature = "serde-claims"))]
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+#[cfg(not(feature = "serde-claims"))]
+impl<'a> Scanner<'a> {
+    fn skip_whitespace(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8, &'static str> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied().ok_or(INVALID)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), &'static str> {
+        if self.peek()? != byte {
+            return Err(INVALID);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// The claims object, member by member.
+    fn object(&mut self) -> Result<Fields<'a>, &'static str> {
+        let mut fields = Fields::default();
+        self.expect(b'{')?;
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Ok(fields);
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(b':')?;
+            let duplicate = match key.as_ref() {
+                "sub" => fields.sub.replace(self.string()?).is_some(),
+                "email" => fields.email.replace(self.string()?).is_some(),
+                "aud" => fields.aud.replace(self.audience()?).is_some(),
+                "iss" => fields.iss.replace(self.string()?).is_some(),
+                "exp" => fields.exp.replace(self.number()?).is_some(),
+                "iat" => fields.iat.replace(self.nullable(Self::number)?).is_some(),
+                "nbf" => fields.nbf.replace(self.nullable(Self::number)?).is_some(),
+                "nonce" => fields.nonce.replace(self.nullable(Self::string)?).is_some(),
+                "hd" => fields.hd.replace(self.nullable(Self::string)?).is_some(),
+                "email_verified" => fields
+                    .email_verified
+                    .replace(self.nullable(Self::boolean)?)
+                    .is_some(),
+                "azp" => fields.azp.replace(self.nullable(Self::string)?).is_some(),
+                "tid" => fields.tid.replace(self.nullable(Self::string)?).is_some(),
+                _ => {
+                    self.skip_value()?;
+                    false
+                }
+            };
+            if duplicate {
+                return Err(INVALID);
+            }
+            if !self.next_member()? {
+                return Ok(fields);
+            }
+        }
+    }
+
+    /// The claims as an array of their values, in the order of [`Claims`].
+    /// Trailing optional claims can be left out.
+    fn sequence(&mut self) -> Result<Fields<'a>, &'static str> {
+        let mut fields = Fields::default();
+        self.expect(b'[')?;
+        if self.peek()? != b']' {
+            for index in 0.. {
+                match index {
+                    0 => fields.sub = Some(self.string()?),
+                    1 => fields.email = Some(self.string()?),
+                    2 => fields.exp = Some(self.number()?),
+                    3 => fields.aud = Some(self.audience()?),
+                    4 => fields.iss = Some(self.string()?),
+                    5 => fields.iat = Some(self.nullable(Self::number)?),
+                    6 => fields.nbf = Some(self.nullable(Self::number)?),
+                    7 => fields.nonce = Some(self.nullable(Self::string)?),
+                    8 => fields.hd = Some(self.nullable(Self::string)?),
+                    9 => fields.email_verified = Some(self.nullable(Self::boolean)?),
+                    10 => fields.azp = Some(self.nullable(Self::string)?),
+                    11 => fields.tid = Some(self.nullable(Self::string)?),
+                    _ => return Err(INVALID),
+                }
+                if self.peek()? != b',' {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+        self.expect(b']')?;
+        Ok(fields)
+    }
+
+    /// Consumes the separator after an object member: `true` on `,`, `false` on `}`.
+    fn next_member(&mut self) -> Result<bool, &'static str> {
+        match self.peek()? {
+            b',' => {
+                self.pos += 1;
+                Ok(true)
+            }
+            b'}' => {
+                self.pos += 1;
+                Ok(false)
+            }
+            _ => Err(INVALID),
+        }
+    }
+
+    fn string(&mut self) -> Result<Cow<'a, str>, &'static str> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        // Fast path: no escapes, borrow the bytes as they are
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            match byte {
+                b'"' => {
+                    let raw = &self.bytes[start..self.pos];
+                    self.pos += 1;
+                    return core::str::from_utf8(raw)
+                        .map(Cow::Borrowed)
+                        .map_err(|_| INVALID);
+                }
+                b'\\' => break,
+                0..=0x1f => return Err(INVALID),
+                _ => self.pos += 1,
+            }
+        }
+
+        let mut out = core::str::from_utf8(&self.bytes[start..self.pos])
+            .map_err(|_| INVALID)?
+            .to_string();
+        loop {
+            let byte = *self.bytes.get(self.pos).ok_or(INVALID)?;
+            self.pos += 1;
+            match byte {
+                b'"' => return Ok(Cow::Owned(out)),
+                b'\\' => {
+                    let escape = *self.bytes.get(self.pos).ok_or(INVALID)?;
+                    self.pos += 1;
+                    match escape {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => out.push(self.unicode_escape()?),
+                        _ => return Err(INVALID),
+                    }
+                }
+                0..=0x1f => return Err(INVALID),
+                _ => {
+                    // Copy the whole UTF-8 sequence starting at this byte
+                    let start = self.pos - 1;
+                    while matches!(self.bytes.get(self.pos), Some(0x80..=0xbf)) {
+                        self.pos += 1;
+                    }
+                    out.push_str(
+                        core::str::from_utf8(&self.bytes[start..self.pos]).map_err(|_| INVALID)?,
+                    );
+                }
+            }
+        }
+    }
+
+    /// A string or an array of strings.
+    fn audience(&mut self) -> Result<Vec<String>, &'static str> {
+        if self.peek()? != b'[' {
+            return Ok(vec![self.string()?.into_owned()]);
+        }
+        self.pos += 1;
+        let mut audiences = Vec::new();
+        if self.peek()? == b']' {
+            self.pos += 1;
+            return Ok(audiences);
+        }
+        loop {
+            audiences.push(self.string()?.into_owned());
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    return Ok(audiences);
+                }
+                _ => return Err(INVALID),
+            }
+        }
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex4(&mut self) -> Result<u32, &'static str> {
+        let digits = self.bytes.get(self.pos..self.pos + 4).ok_or(INVALID)?;
+        self.pos += 4;
+        digits
+            .iter()
+            .try_fold(0, |code, &digit| Some((code << 4) | char::from(digit).to_digit(16)?))
+            .ok_or(INVALID)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, &'static str> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.bytes.get(self.pos..self.pos + 2) != Some(b"\\u") {
+                return Err(INVALID);
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(INVALID);
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or(INVALID)
+    }
+
+    /// A `u64` as `serde_json` reads one: no sign, leading zeros, fraction
+    /// or exponent.
+    fn number(&mut self) -> Result<u64, &'static str> {
+        self.skip_whitespace();
+        let digits = self.integer()?;
+        let digits = core::str::from_utf8(digits).map_err(|_| INVALID)?;
+        digits.parse().map_err(|_| INVALID)
+    }
+
+    /// The digits of a JSON integer: at least one, and no leading zero.
+    fn integer(&mut self) -> Result<&'a [u8], &'static str> {
+        let digits = self.digits();
+        match digits {
+            [] | [b'0', _, ..] => Err(INVALID),
+            _ => Ok(digits),
+        }
+    }
+
+    fn digits(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while matches!(self.bytes.get(self.pos), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+
+    fn boolean(&mut self) -> Result<bool, &'static str> {
+        if self.literal(b"true")? {
+            Ok(true)
+        } else if self.literal(b"false")? {
+            Ok(false)
+        } else {
+            Err(INVALID)
+        }
+    }
+
+    /// `None` on `null`, else the value read by `value`.
+    fn nullable<T>(
+        &mut self,
+        value: impl FnOnce(&mut Self) -> Result<T, &'static str>,
+    ) -> Result<Option<T>, &'static str> {
+        if self.literal(b"null")? {
+            Ok(None)
+        } else {
+            value(self).map(Some)
+        }
+    }
+
+    /// Consumes `word` if it comes next.
+    fn literal(&mut self, word: &[u8]) -> Result<bool, &'static str> {
+        self.peek()?;
+        if !self.bytes[self.pos..].starts_with(word) {
+            return Ok(false);
+        }
+        self.pos += word.len();
+        Ok(true)
+    }
+
+    /// Skips any JSON value without keeping it, checking its syntax as
+    /// `serde_json` does: the closing brackets of the containers it is in are
+    /// kept on a stack, so a mismatched or missing one fails.
+    fn skip_value(&mut self) -> Result<(), &'static str> {
+        let mut closing = Vec::new();
+        loop {
+            match self.peek()? {
+                open @ (b'{' | b'[') => {
+                    self.pos += 1;
+                    let close = if open == b'{' { b'}' } else { b']' };
+                    if self.peek()? == close {
+                        self.pos += 1;
+                    } else {
+                        closing.push(close);
+                        if close == b'}' {
+                            self.skip_key()?;
+                        }
+                        continue;
+                    }
+                }
+                b'"' => self.skip_string()?,
+                _ => self.skip_scalar()?,
+            }
+            // The value ends the containers it closes, or is followed by the
+            // next element of the innermost one
+            loop {
+                let Some(&close) = closing.last() else {
+                    return Ok(());
+                };
+                match self.peek()? {
+                    b',' => {
+                        self.pos += 1;
+                        if close == b'}' {
+                            self.skip_key()?;
+                        }
+                        break;
+                    }
+                    byte if byte == close => {
+                        self.pos += 1;
+                        closing.pop();
+                    }
+                    _ => return Err(INVALID),
+                }
+            }
+        }
+    }
+
+    /// Skips an object key and its colon.
+    fn skip_key(&mut self) -> Result<(), &'static str> {
+        self.skip_string()?;
+        self.expect(b':')
+    }
+
+    /// Skips a string as `serde_json` ignores one: escapes are checked, but
+    /// neither decoded nor paired, and the bytes aren't checked to be UTF-8.
+    fn skip_string(&mut self) -> Result<(), &'static str> {
+        self.expect(b'"')?;
+        loop {
+            let byte = *self.bytes.get(self.pos).ok_or(INVALID)?;
+            self.pos += 1;
+            match byte {
+                b'"' => return Ok(()),
+                b'\\' => {
+                    let escape = *self.bytes.get(self.pos).ok_or(INVALID)?;
+                    self.pos += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {}
+                        b'u' => {
+                            self.hex4()?;
+                        }
+                        _ => return Err(INVALID),
+                    }
+                }
+                0..=0x1f => return Err(INVALID),
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips a number, `true`, `false` or `null`.
+    fn skip_scalar(&mut self) -> Result<(), &'static str> {
+        for word in [&b"true"[..], b"false", b"null"] {
+            if self.literal(word)? {
+                return Ok(());
+            }
+        }
+        if self.bytes.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        self.integer()?;
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if self.digits().is_empty() {
+                return Err(INVALID);
+            }
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if self.digits().is_empty() {
+                return Err(INVALID);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+use crate::event::{self, IdentityEvent};
+use crate::failure::FailureKind;
+use crate::work::Work;
+
+/// Stable result code of an identity action, leading its program output.
+///
+/// Clients match on the number, never on the message: messages are for humans
+/// and may be reworded. Codes are never renumbered or reused, new ones are
+/// appended. The hundreds give the [`FailureKind`]: `0` is success, `1xx`
+/// invalid input, `2xx` malformed state and `3xx` rejected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResultCode {
+    Ok,
+    InvalidInput,
+    MissingToken,
+    MissingTokenPair,
+    MissingTxContext,
+    MissingRecoveryCode,
+    InvalidRealmName,
+    InvalidBatch,
+    MissingSessionKeySignature,
+    IdentityMismatch,
+    MalformedState,
+    IdentityNotFound,
+    IdentityExists,
+    InvalidNonce,
+    InvalidToken,
+    TokenAccountMismatch,
+    AccountFrozen,
+    IssuerNotAllowed,
+    AudienceNotAllowed,
+    NonceRangeInUse,
+    NonceBatchOutOfBounds,
+    NonceOverflow,
+    SubjectAlreadyBound,
+    DisclosureForbidden,
+    GovernanceDisabled,
+    AdminNotRegistered,
+    UnknownAdminOperation,
+    AdminOperationTimeLocked,
+    PruningDisabled,
+    AccountStillActive,
+    InvalidArchiveProof,
+    ContractPaused,
+    InvalidRecoveryCode,
+    RealmNotFound,
+    RealmExists,
+    NestedRealm,
+    TokenExpired,
+    TokenNotYetValid,
+    TokenNonceMismatch,
+    AdminCannotUnregister,
+    ProviderNotLinked,
+    LastProviderLinked,
+    ClaimPolicyViolation,
+    NoRecoveryProvider,
+    RecoveryTimeLocked,
+    SessionKeyNotFound,
+    InvalidSessionSignature,
+    TooManySessionKeys,
+    UntrustedKey,
+}
+
+impl ResultCode {
+    const ALL: [ResultCode; 49] = [
+        ResultCode::Ok,
+        ResultCode::InvalidInput,
+        ResultCode::MissingToken,
+        ResultCode::MissingTokenPair,
+        ResultCode::MissingTxContext,
+        ResultCode::MissingRecoveryCode,
+        ResultCode::InvalidRealmName,
+        ResultCode::InvalidBatch,
+        ResultCode::MissingSessionKeySignature,
+        ResultCode::IdentityMismatch,
+        ResultCode::MalformedState,
+        ResultCode::IdentityNotFound,
+        ResultCode::IdentityExists,
+        ResultCode::InvalidNonce,
+        ResultCode::InvalidToken,
+        ResultCode::TokenAccountMismatch,
+        ResultCode::AccountFrozen,
+        ResultCode::IssuerNotAllowed,
+        ResultCode::AudienceNotAllowed,
+        ResultCode::NonceRangeInUse,
+        ResultCode::NonceBatchOutOfBounds,
+        ResultCode::NonceOverflow,
+        ResultCode::SubjectAlreadyBound,
+        ResultCode::DisclosureForbidden,
+        ResultCode::GovernanceDisabled,
+        ResultCode::AdminNotRegistered,
+        ResultCode::UnknownAdminOperation,
+        ResultCode::AdminOperationTimeLocked,
+        ResultCode::PruningDisabled,
+        ResultCode::AccountStillActive,
+        ResultCode::InvalidArchiveProof,
+        ResultCode::ContractPaused,
+        ResultCode::InvalidRecoveryCode,
+        ResultCode::RealmNotFound,
+        ResultCode::RealmExists,
+        ResultCode::NestedRealm,
+        ResultCode::TokenExpired,
+        ResultCode::TokenNotYetValid,
+        ResultCode::TokenNonceMismatch,
+        ResultCode::AdminCannotUnregister,
+        ResultCode::ProviderNotLinked,
+        ResultCode::LastProviderLinked,
+        ResultCode::ClaimPolicyViolation,
+        ResultCode::NoRecoveryProvider,
+        ResultCode::RecoveryTimeLocked,
+        ResultCode::SessionKeyNotFound,
+        ResultCode::InvalidSessionSignature,
+        ResultCode::TooManySessionKeys,
+        ResultCode::UntrustedKey,
+    ];
+
+    pub fn code(self) -> u16 {
+        match self {
+            ResultCode::Ok => 0,
+            ResultCode::InvalidInput => 100,
+            ResultCode::MissingToken => 101,
+            ResultCode::MissingTokenPair => 102,
+            ResultCode::MissingTxContext => 103,
+            ResultCode::MissingRecoveryCode => 104,
+            ResultCode::InvalidRealmName => 105,
+            ResultCode::InvalidBatch => 106,
+            ResultCode::MissingSessionKeySignature => 107,
+            ResultCode::IdentityMismatch => 108,
+            ResultCode::MalformedState => 200,
+            ResultCode::IdentityNotFound => 300,
+            ResultCode::IdentityExists => 301,
+            ResultCode::InvalidNonce => 302,
+            ResultCode::InvalidToken => 303,
+            ResultCode::TokenAccountMismatch => 304,
+            ResultCode::AccountFrozen => 305,
+            ResultCode::IssuerNotAllowed => 306,
+            ResultCode::AudienceNotAllowed => 307,
+            ResultCode::NonceRangeInUse => 308,
+            ResultCode::NonceBatchOutOfBounds => 309,
+            ResultCode::NonceOverflow => 310,
+            ResultCode::SubjectAlreadyBound => 311,
+            ResultCode::DisclosureForbidden => 312,
+            ResultCode::GovernanceDisabled => 313,
+            ResultCode::AdminNotRegistered => 314,
+            ResultCode::UnknownAdminOperation => 315,
+            ResultCode::AdminOperationTimeLocked => 316,
+            ResultCode::PruningDisabled => 317,
+            ResultCode::AccountStillActive => 318,
+            ResultCode::InvalidArchiveProof => 319,
+            ResultCode::ContractPaused => 320,
+            ResultCode::InvalidRecoveryCode => 321,
+            ResultCode::RealmNotFound => 322,
+            ResultCode::RealmExists => 323,
+            ResultCode::NestedRealm => 324,
+            ResultCode::TokenExpired => 325,
+            ResultCode::TokenNotYetValid => 326,
+            ResultCode::TokenNonceMismatch => 327,
+            ResultCode::AdminCannotUnregister => 328,
+            ResultCode::ProviderNotLinked => 329,
+            ResultCode::LastProviderLinked => 330,
+            ResultCode::ClaimPolicyViolation => 331,
+            ResultCode::NoRecoveryProvider => 332,
+            ResultCode::RecoveryTimeLocked => 333,
+            ResultCode::SessionKeyNotFound => 334,
+            ResultCode::InvalidSessionSignature => 335,
+            ResultCode::TooManySessionKeys => 336,
+            ResultCode::UntrustedKey => 337,
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|result| result.code() == code)
+    }
+
+    /// Human-readable description; not part of the stable interface.
+    pub fn message(self) -> &'static str {
+        match self {
+            ResultCode::Ok => "Ok",
+            ResultCode::InvalidInput => "Invalid input",
+            ResultCode::MissingToken => "This action requires an ID token as private input",
+            ResultCode::MissingTokenPair => "This action requires both ID tokens as private input",
+            ResultCode::MissingTxContext => "This action requires the tx context",
+            ResultCode::MissingRecoveryCode => "This action requires a recovery code",
+            ResultCode::InvalidRealmName => "Invalid realm name",
+            ResultCode::InvalidBatch => {
+                "A batch needs one private input per action and can't be nested"
+            }
+            ResultCode::MissingSessionKeySignature => {
+                "This action requires a session key signature"
+            }
+            ResultCode::IdentityMismatch => {
+                "The transaction's identity is not the action's account"
+            }
+            ResultCode::MalformedState => "Could not decode identity state",
+            ResultCode::IdentityNotFound => "Identity not found",
+            ResultCode::IdentityExists => "Identity already exists",
+            ResultCode::InvalidNonce => "Invalid nonce",
+            ResultCode::InvalidToken => "Failed to verify ID token JWT",
+            ResultCode::TokenAccountMismatch => "ID token does not belong to this account",
+            ResultCode::AccountFrozen => "Account is frozen",
+            ResultCode::IssuerNotAllowed => "Issuer not allowed",
+            ResultCode::AudienceNotAllowed => "Audience not allowed for this issuer",
+            ResultCode::NonceRangeInUse => "Previous nonce range is still in use",
+            ResultCode::NonceBatchOutOfBounds => "Nonce batch size out of bounds",
+            ResultCode::NonceOverflow => "Nonce overflow",
+            ResultCode::SubjectAlreadyBound => "New subject is already bound to an account",
+            ResultCode::DisclosureForbidden => "Disclosure policy forbids this claim",
+            ResultCode::GovernanceDisabled => "Governance is disabled",
+            ResultCode::AdminNotRegistered => "Admin account not registered",
+            ResultCode::UnknownAdminOperation => "Unknown admin operation",
+            ResultCode::AdminOperationTimeLocked => "Admin operation is still time-locked",
+            ResultCode::PruningDisabled => "Pruning is disabled",
+            ResultCode::AccountStillActive => "Account has been active too recently to be pruned",
+            ResultCode::InvalidArchiveProof => "Account is not in the archive",
+            ResultCode::ContractPaused => "Contract is paused",
+            ResultCode::InvalidRecoveryCode => "Recovery code unknown or already used",
+            ResultCode::RealmNotFound => "Realm not found",
+            ResultCode::RealmExists => "Realm already exists",
+            ResultCode::NestedRealm => "Realms can't be nested",
+            ResultCode::TokenExpired => "ID token has expired",
+            ResultCode::TokenNotYetValid => "ID token is not valid yet",
+            ResultCode::TokenNonceMismatch => "ID token is not bound to this nonce",
+            ResultCode::AdminCannotUnregister => "The admin account can't be unregistered",
+            ResultCode::ProviderNotLinked => "Provider is not linked to this account",
+            ResultCode::LastProviderLinked => "Can't unlink the account's last provider",
+            ResultCode::ClaimPolicyViolation => "ID token claims don't meet the issuer's policy",
+            ResultCode::NoRecoveryProvider => "Account has no recovery provider",
+            ResultCode::RecoveryTimeLocked => "Recovery is still time-locked",
+            ResultCode::SessionKeyNotFound => "Session key unknown, expired or revoked",
+            ResultCode::InvalidSessionSignature => "Invalid session key signature",
+            ResultCode::TooManySessionKeys => "Account holds too many session keys",
+            ResultCode::UntrustedKey => "Key is not one stored for the issuer",
+        }
+    }
+
+    /// `None` for [`ResultCode::Ok`].
+    pub fn kind(self) -> Option<FailureKind> {
+        match self.code() / 100 {
+            0 => None,
+            1 => Some(FailureKind::InvalidInput),
+            2 => Some(FailureKind::MalformedState),
+            _ => Some(FailureKind::Rejected),
+        }
+    }
+}
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Formats a program output: the numeric code, then `:` and the detail.
+pub fn output(code: ResultCode, detail: &str) -> String {
+    format!("{}:{}", code.code(), detail)
+}
+
+/// Formats a successful program output, with the action's [`Work`] between
+/// the code and the detail: `<code>/<work>:<detail>`. The detail may hold any
+/// character, so the work goes before it.
+pub fn output_with_work(code: ResultCode, work: Work, detail: &str) -> String {
+    format!("{}/{}:{}", code.code(), work, detail)
+}
+
+/// Formats a successful program output with the action's events after its
+/// [`Work`], see [`event`](crate::event): `<code>/<work>/<events>:<detail>`.
+pub fn output_with_events(
+    code: ResultCode,
+    work: Work,
+    events: &[IdentityEvent],
+    detail: &str,
+) -> String {
+    format!(
+        "{}/{}/{}:{}",
+        code.code(),
+        work,
+        event::encode(events),
+        detail
+    )
+}
+
+/// Splits a program output written by [`output`], [`output_with_work`] or
+/// [`output_with_events`] into
+/// its code and detail. Outputs from older guests give `None`.
+pub fn parse_output(output: &str) -> Option<(ResultCode, &str)> {
+    let (header, detail) = output.split_once(':')?;
+    let code = header.split_once('/').map_or(header, |(code, _)| code);
+    Some((ResultCode::from_code(code.parse().ok()?)?, detail))
+}
+
+/// The work reported by an output written by [`output_with_work`] or
+/// [`output_with_events`]; `None` for failures and outputs from older guests.
+pub fn parse_work(output: &str) -> Option<Work> {
+    let (header, _) = output.split_once(':')?;
+    Work::parse(header.split('/').nth(1)?)
+}
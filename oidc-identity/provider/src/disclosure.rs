@@ -0,0 +1,111 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::hasher::{AccountHasher, DefaultHasher};
+
+/// ID token claims that may be echoed into the public program output.
+///
+/// Outputs never contain claims by default: actions only report the account id.
+/// A claim is published solely through `IdentityAction::DiscloseClaim`, and
+/// only as far as the contract's [`DisclosurePolicy`] allows: the custom claims
+/// of `IdentityAction::ExtractClaims` can't name these.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Claim {
+    Email,
+    Subject,
+    Issuer,
+}
+
+impl Claim {
+    /// The claim's member in the ID token.
+    pub fn name(self) -> &'static str {
+        match self {
+            Claim::Email => "email",
+            Claim::Subject => "sub",
+            Claim::Issuer => "iss",
+        }
+    }
+
+    /// The claim whose member is `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Claim::Email, Claim::Subject, Claim::Issuer]
+            .into_iter()
+            .find(|claim| claim.name() == name)
+    }
+}
+
+/// How a disclosed claim appears in the output. `Plain` implies `Hashed`.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Disclosure {
+    Hashed,
+    Plain,
+}
+
+impl Disclosure {
+    pub fn render(self, value: &str) -> String {
+        match self {
+            Disclosure::Hashed => DefaultHasher::digest_hex(value.as_bytes()),
+            Disclosure::Plain => String::from(value),
+        }
+    }
+}
+
+/// The most a contract lets each claim be disclosed. Claims missing from the
+/// policy are never disclosed.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct DisclosurePolicy {
+    pub claims: BTreeMap<Claim, Disclosure>,
+}
+
+impl DisclosurePolicy {
+    pub fn allows(&self, claim: Claim, disclosure: Disclosure) -> bool {
+        self.claims
+            .get(&claim)
+            .is_some_and(|allowed| disclosure <= *allowed)
+    }
+}
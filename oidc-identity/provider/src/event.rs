@@ -0,0 +1,70 @@
+//! Typed events of an action, so that indexers don't have to read them out of
+//! the detail of program outputs. The sdk's `RunResult` has no room for them,
+//! its third element being the node's on-chain effects: successful outputs
+//! carry them in their header instead, see [`output_with_events`], and a
+//! failure reads as a single [`IdentityEvent::Failed`].
+//!
+//! [`output_with_events`]: crate::code::output_with_events
+
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::failure::Failure;
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum IdentityEvent {
+    Registered {
+        account: String,
+    },
+    /// The account was verified at `nonce`, with an ID token or a session key
+    Verified {
+        account: String,
+        nonce: u32,
+    },
+    /// The account's next nonce moved to `nonce`, past a reserved range
+    NonceBumped {
+        account: String,
+        nonce: u32,
+    },
+    /// The action failed with the result code `code`
+    Failed {
+        code: u16,
+        reason: String,
+    },
+    /// `event` happened in `realm`, whose account names are its own
+    InRealm {
+        realm: String,
+        event: Box<IdentityEvent>,
+    },
+}
+
+impl IdentityEvent {
+    /// The events of a program output: those a successful output carries, or
+    /// the failure of a failed one. Outputs of older guests give `None`.
+    pub fn parse(output: &str) -> Option<Vec<Self>> {
+        if let Some(failure) = Failure::parse(output) {
+            return Some(vec![IdentityEvent::Failed {
+                code: failure.code.code(),
+                reason: failure.detail,
+            }]);
+        }
+        let (header, _) = output.split_once(':')?;
+        decode(header.split('/').nth(2)?)
+    }
+}
+
+/// The header field of `events`: their bincode encoding, in hex.
+pub fn encode(events: &[IdentityEvent]) -> String {
+    let bytes = bincode::encode_to_vec(events, bincode::config::standard())
+        .expect("Failed to encode events");
+    hex::encode(bytes)
+}
+
+fn decode(field: &str) -> Option<Vec<IdentityEvent>> {
+    let bytes = hex::decode(field).ok()?;
+    match bincode::decode_from_slice(&bytes, bincode::config::standard()) {
+        Ok((events, read)) if read == bytes.len() => Some(events),
+        _ => None,
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use crate::code::{self, ResultCode};
+
+/// Why an execution failed, as far as the guest can tell.
+///
+/// Failed executions still produce a journal: the error string ends up in the
+/// program output, led by a [`ResultCode`] whose hundreds give the kind. Hosts
+/// and indexers can tell a rejected action (bad signature, wrong nonce) from an
+/// input that could not be decoded without matching on messages. A bug still
+/// panics the guest and produces no journal at all.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FailureKind {
+    /// Oversized or undecodable blob or private input
+    InvalidInput,
+    /// The initial state digest doesn't decode
+    MalformedState,
+    /// The action was understood and refused by the contract
+    Rejected,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Failure {
+    pub code: ResultCode,
+    /// Free-form detail for humans
+    pub detail: String,
+}
+
+impl Failure {
+    pub fn new(code: ResultCode, detail: impl ToString) -> Self {
+        Failure {
+            code,
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn invalid_input(detail: impl ToString) -> Self {
+        Self::new(ResultCode::InvalidInput, detail)
+    }
+
+    pub fn malformed_state(detail: impl ToString) -> Self {
+        Self::new(ResultCode::MalformedState, detail)
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        self.code.kind().unwrap_or(FailureKind::Rejected)
+    }
+
+    /// Reads back a failure from a program output written by [`fmt::Display`].
+    /// Outputs of successful runs, or from older guests, give `None`.
+    pub fn parse(output: &str) -> Option<Self> {
+        match code::parse_output(output)? {
+            (ResultCode::Ok, _) => None,
+            (code, detail) => Some(Failure::new(code, detail)),
+        }
+    }
+}
+
+impl From<ResultCode> for Failure {
+    fn from(code: ResultCode) -> Self {
+        Failure::new(code, code.message())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&code::output(self.code, &self.detail))
+    }
+}
+
+impl From<Failure> for String {
+    fn from(failure: Failure) -> Self {
+        failure.to_string()
+    }
+}
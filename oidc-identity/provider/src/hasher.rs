@@ -0,0 +1,48 @@
+use alloc::string::String;
+
+/// Hash function used for account commitments (and, once the state is
+/// merklized, for tree nodes).
+///
+/// SHA-256 is the default. Enabling the `blake3` feature switches
+/// [`DefaultHasher`] to BLAKE3, which is considerably cheaper inside the zkVM.
+/// Every prover and verifier of a given deployment must agree on the choice,
+/// as it changes the stored account hashes.
+pub trait AccountHasher {
+    fn digest(data: &[u8]) -> [u8; 32];
+
+    fn digest_hex(data: &[u8]) -> String {
+        hex::encode(Self::digest(data))
+    }
+
+    fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        let mut buf = [0u8; 64];
+        buf[..32].copy_from_slice(left);
+        buf[32..].copy_from_slice(right);
+        Self::digest(&buf)
+    }
+}
+
+pub struct Sha256Hasher;
+
+impl AccountHasher for Sha256Hasher {
+    fn digest(data: &[u8]) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(data).into()
+    }
+}
+
+#[cfg(feature = "blake3")]
+pub struct Blake3Hasher;
+
+#[cfg(feature = "blake3")]
+impl AccountHasher for Blake3Hasher {
+    fn digest(data: &[u8]) -> [u8; 32] {
+        blake3::hash(data).into()
+    }
+}
+
+#[cfg(not(feature = "blake3"))]
+pub type DefaultHasher = Sha256Hasher;
+
+#[cfg(feature = "blake3")]
+pub type DefaultHasher = Blake3Hasher;
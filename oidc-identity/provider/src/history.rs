@@ -0,0 +1,88 @@
+//! Control history of an account: a rolling hash over the events that change
+//! who controls it, or whether it can be used. The contracts keep it next to
+//! each account and only ever extend it, so a relying party that recorded it
+//! can tell whether the account was rebound, recovered or frozen since: any
+//! event changes the head, and none can be taken back.
+
+use alloc::{format, string::String};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::hasher::{AccountHasher, DefaultHasher};
+
+/// Security-relevant events of an account.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SecurityEvent<'a> {
+    /// Registered, bound to the subject hashing to `hash`
+    Registered {
+        hash: &'a str,
+    },
+    /// Rebound to `hash` by the current credential, see `MigrateSubject`
+    SubjectMigrated {
+        hash: &'a str,
+    },
+    /// The subject hashing to `hash` linked as another credential
+    ProviderLinked {
+        hash: &'a str,
+    },
+    /// The subject hashing to `hash` no longer a credential
+    ProviderUnlinked {
+        hash: &'a str,
+    },
+    /// Rebound to `hash` with a recovery code
+    Recovered {
+        hash: &'a str,
+    },
+    /// `count` recovery codes committed, replacing the previous ones
+    RecoveryCodesSet {
+        count: usize,
+    },
+    /// The subject hashing to `hash` set as recovery provider
+    RecoveryProviderSet {
+        hash: &'a str,
+    },
+    /// Rebound to `hash` by the recovery provider
+    RecoveredByProvider {
+        hash: &'a str,
+    },
+    Frozen,
+    Unfrozen,
+}
+
+impl SecurityEvent<'_> {
+    /// Canonical text of the event, as hashed into the history.
+    pub fn describe(&self) -> String {
+        match self {
+            SecurityEvent::Registered { hash } => format!("registered {hash}"),
+            SecurityEvent::SubjectMigrated { hash } => format!("migrated {hash}"),
+            SecurityEvent::ProviderLinked { hash } => format!("linked {hash}"),
+            SecurityEvent::ProviderUnlinked { hash } => format!("unlinked {hash}"),
+            SecurityEvent::Recovered { hash } => format!("recovered {hash}"),
+            SecurityEvent::RecoveryCodesSet { count } => format!("recovery codes {count}"),
+            SecurityEvent::RecoveryProviderSet { hash } => format!("recovery provider {hash}"),
+            SecurityEvent::RecoveredByProvider { hash } => format!("provider recovered {hash}"),
+            SecurityEvent::Frozen => "frozen".into(),
+            SecurityEvent::Unfrozen => "unfrozen".into(),
+        }
+    }
+}
+
+/// Head of an account's history, and how many events it covers.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct ControlHistory {
+    /// Hex; empty before the first event
+    pub head: String,
+    pub events: u32,
+}
+
+impl ControlHistory {
+    pub fn record(&mut self, event: SecurityEvent) {
+        self.head = next_head(&self.head, event);
+        self.events += 1;
+    }
+}
+
+/// Head following `head` once `event` is recorded: `H("{head}:{event}")`.
+pub fn next_head(head: &str, event: SecurityEvent) -> String {
+    DefaultHasher::digest_hex(format!("{head}:{}", event.describe()).as_bytes())
+}
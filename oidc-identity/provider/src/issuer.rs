@@ -0,0 +1,81 @@
+//! How a token's `iss` must match the issuer of an [`OpenIdContext`]. Most
+//! providers have a single issuer, matched exactly. Multi-tenant ones issue
+//! each user's tokens from their tenant: Microsoft Entra ID's `common`
+//! endpoint documents its issuer as
+//! `https://login.microsoftonline.com/{tenantid}/v2.0`, the `tid` claim naming
+//! the tenant the token was issued from.
+//!
+//! An allow-listing contract records the rule of each issuer along with it, so
+//! a context can't widen an exact issuer into a prefix.
+//!
+//! [`OpenIdContext`]: crate::OpenIdContext
+
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Placeholder of the tenant in a [`IssuerMatch::Template`] issuer.
+pub const TENANT_PLACEHOLDER: &str = "{tenantid}";
+
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum IssuerMatch {
+    /// `iss` is the issuer
+    #[default]
+    Exact,
+    /// `iss` starts with the issuer, which must end with `/` so that it can't
+    /// match another host or path segment
+    Prefix,
+    /// `iss` is the issuer with [`TENANT_PLACEHOLDER`] replaced by the token's
+    /// `tid`
+    Template,
+}
+
+impl IssuerMatch {
+    pub fn is_exact(&self) -> bool {
+        *self == IssuerMatch::Exact
+    }
+
+    /// Whether a token issued by `iss`, from tenant `tenant` if it names one,
+    /// matches `issuer`.
+    pub fn matches(self, issuer: &str, iss: &str, tenant: Option<&str>) -> bool {
+        match self {
+            IssuerMatch::Exact => iss == issuer,
+            IssuerMatch::Prefix => issuer.ends_with('/') && iss.starts_with(issuer),
+            IssuerMatch::Template => {
+                let (Some((before, after)), Some(tenant)) =
+                    (issuer.split_once(TENANT_PLACEHOLDER), tenant)
+                else {
+                    return false;
+                };
+                is_tenant_id(tenant)
+                    && iss
+                        .strip_prefix(before)
+                        .and_then(|rest| rest.strip_suffix(after))
+                        == Some(tenant)
+            }
+        }
+    }
+}
+
+/// Tenant ids are GUIDs; anything else, a `/` in particular, could make the
+/// issuer point elsewhere.
+fn is_tenant_id(tenant: &str) -> bool {
+    !tenant.is_empty()
+        && tenant
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
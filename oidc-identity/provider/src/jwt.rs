@@ -0,0 +1,186 @@
+//! ID token verification, for any guest. The JWT format, the choice of key and
+//! the claim checks are done here; the signature schemes are left to a
+//! [`SignatureVerifier`], so that each guest brings the crypto crates (or
+//! zkVM precompiles) it already has. A guest verifying only some algorithms
+//! implements only their methods: the others refuse every token.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+
+use crate::claims::{self, Claims};
+use crate::{audience, code::ResultCode, JwkPublicKey, JwkSet, OpenIdContext};
+
+/// Seconds the block time may be off from the issuer's clock, by default.
+pub const DEFAULT_MAX_CLOCK_SKEW: u64 = 60;
+
+/// Signature checks of the JWS algorithms a token may use. Each gets the
+/// token's signing input (`header.payload`), its decoded signature and the key
+/// picked for it, whose `kty` is the one of the algorithm.
+pub trait SignatureVerifier {
+    /// RSASSA-PKCS1-v1_5 with SHA-256, with the key's `n` and `e`.
+    fn verify_rs256(
+        &self,
+        _key: &JwkPublicKey,
+        _signing_input: &[u8],
+        _signature: &[u8],
+    ) -> Result<(), String> {
+        Err("RS256 is not supported".to_string())
+    }
+
+    /// ECDSA over P-256 with SHA-256, with the key's `x` and `y`. JWS
+    /// signatures are `r || s`, not DER.
+    fn verify_es256(
+        &self,
+        _key: &JwkPublicKey,
+        _signing_input: &[u8],
+        _signature: &[u8],
+    ) -> Result<(), String> {
+        Err("ES256 is not supported".to_string())
+    }
+
+    /// Ed25519, with the key's `x`.
+    fn verify_eddsa(
+        &self,
+        _key: &JwkPublicKey,
+        _signing_input: &[u8],
+        _signature: &[u8],
+    ) -> Result<(), String> {
+        Err("EdDSA is not supported".to_string())
+    }
+}
+
+fn split_jwt(token: &str) -> Result<(&str, &str, &str), String> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err("Invalid JWT structure".to_string());
+    }
+    Ok((parts[0], parts[1], parts[2]))
+}
+
+/// Decodes the unpadded base64url of JWT segments and JWK members.
+pub fn decode_b64(input: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(input)
+        .map_err(|_| "Failed to decode Base64".to_string())
+}
+
+/// The members of the JWT header verification reads.
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// The algorithm a key verifies with. The key decides, so a token can't pick
+/// its own check: the header's `alg` must only agree.
+fn key_algorithm(key: &JwkPublicKey) -> Result<&'static str, String> {
+    match key.kty.as_str() {
+        "RSA" => Ok("RS256"),
+        "EC" => Ok("ES256"),
+        "OKP" => Ok("EdDSA"),
+        kty => Err(format!("Unsupported JWK key type `{}`", kty)),
+    }
+}
+
+/// The key of `keys` named by the token's header, once the header's `alg` is
+/// checked against it and against the algorithms `context` accepts.
+fn select_key<'a>(
+    header_b64: &str,
+    keys: &'a JwkSet,
+    context: &OpenIdContext,
+) -> Result<&'a JwkPublicKey, String> {
+    let header: Header = serde_json::from_slice(&decode_b64(header_b64)?)
+        .map_err(|_| "Failed to parse JWT header".to_string())?;
+    let key = keys
+        .key(header.kid.as_deref())
+        .ok_or_else(|| match &header.kid {
+            Some(kid) => format!("Unknown key id `{}`", kid),
+            None => "Token has no key id to pick a key with".to_string(),
+        })?;
+    // `none` and HMAC algorithms are never a key's, so they end here
+    if header.alg != key_algorithm(key)? {
+        return Err(format!("Algorithm `{}` doesn't match the key", header.alg));
+    }
+    if !context.algorithms.is_empty() && !context.algorithms.contains(&header.alg) {
+        return Err(format!("Algorithm `{}` is not accepted", header.alg));
+    }
+    Ok(key)
+}
+
+/// Verifies `token`'s signature with the key of `keys` its header names, and
+/// its audience and issuer against `context`, returning its claims.
+pub fn verify_jwt_signature(
+    token: &str,
+    keys: &JwkSet,
+    context: &OpenIdContext,
+    verifier: &impl SignatureVerifier,
+) -> Result<Claims, String> {
+    let (header_b64, payload_b64, signature_b64) = split_jwt(token)?;
+
+    let jwk_pub_key = select_key(header_b64, keys, context)?;
+
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+
+    let signature = decode_b64(signature_b64)?;
+
+    match key_algorithm(jwk_pub_key)? {
+        "RS256" => verifier.verify_rs256(jwk_pub_key, signing_input.as_bytes(), &signature)?,
+        "ES256" => verifier.verify_es256(jwk_pub_key, signing_input.as_bytes(), &signature)?,
+        _ => verifier.verify_eddsa(jwk_pub_key, signing_input.as_bytes(), &signature)?,
+    }
+
+    let payload_bytes = decode_b64(payload_b64)?;
+
+    let claims = claims::parse(&payload_bytes)?;
+
+    if !audience::matches(&context.audience, &claims.aud) {
+        return Err(format!(
+            "Invalid Audience: expected one of `{:?}`, got `{:?}`",
+            context.audience, claims.aud
+        ));
+    }
+    if !context
+        .issuer_match
+        .matches(&context.issuer, &claims.iss, claims.tid.as_deref())
+    {
+        return Err(format!(
+            "Invalid Issuer: expected `{}`, got `{}`",
+            context.issuer, claims.iss
+        ));
+    }
+
+    Ok(claims)
+}
+
+/// The claims of `token` named in `wanted`, see [`claims::extract`]. Only the
+/// payload is read: callers verify the token with [`verify_jwt_signature`]
+/// first.
+pub fn extract_claims(token: &str, wanted: &[&str]) -> Result<BTreeMap<String, String>, String> {
+    let (_, payload_b64, _) = split_jwt(token)?;
+    let payload = decode_b64(payload_b64)?;
+    Ok(claims::extract(&payload, wanted)?)
+}
+
+/// Checks `token`'s `exp`, and `nbf` and `iat` when present, against `now`
+/// (Unix seconds), allowing `max_skew` seconds either way. Only the payload is
+/// read: the action verifies the signature of the same token.
+pub fn check_token_time(token: &str, now: u64, max_skew: u64) -> Result<(), ResultCode> {
+    let (_, payload_b64, _) = split_jwt(token).map_err(|_| ResultCode::InvalidToken)?;
+    let payload = decode_b64(payload_b64).map_err(|_| ResultCode::InvalidToken)?;
+    let claims = claims::parse(&payload).map_err(|_| ResultCode::InvalidToken)?;
+
+    if claims.exp.saturating_add(max_skew) < now {
+        return Err(ResultCode::TokenExpired);
+    }
+    let valid_from = claims.nbf.max(claims.iat);
+    if valid_from.is_some_and(|time| time > now.saturating_add(max_skew)) {
+        return Err(ResultCode::TokenNotYetValid);
+    }
+    Ok(())
+}
@@ -2,125 +2,1453 @@
 
 extern crate alloc;
 use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
-use hyle_model::{Blob, BlobData, BlobIndex, ContractAction, ContractName, Digestable};
+use hyle_model::{
+    Blob, BlobData, BlobIndex, ContractAction, ContractName, Digestable, StructuredBlobData,
+};
 use sdk::RunResult;
 
-use alloc::{format, string::String, vec::Vec};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 
-#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub mod account;
+pub mod archive;
+pub mod audience;
+pub mod claim_policy;
+pub mod claims;
+pub mod code;
+pub mod disclosure;
+pub mod event;
+pub mod failure;
+pub mod hasher;
+pub mod history;
+pub mod issuer;
+pub mod jwt;
+pub mod metadata;
+pub mod nonce;
+pub mod realm;
+pub mod recovery;
+pub mod session;
+pub mod smt;
+pub mod token_nonce;
+pub mod work;
+
+use archive::ArchiveProof;
+use claim_policy::ClaimPolicy;
+use code::ResultCode;
+use disclosure::{Claim, Disclosure};
+use event::IdentityEvent;
+use failure::Failure;
+use issuer::IssuerMatch;
+use metadata::AccountMetadata;
+use nonce::NonceStrategy;
+use realm::RealmConfig;
+use work::Work;
+
+/// Public key an ID token is verified with, as published in the provider's
+/// JWKS. The contract picks the algorithm from `kty`, never from the token:
+/// RS256 for `RSA` keys (`n`, `e`), ES256 for `EC` keys on `P-256` (`crv`,
+/// `x`, `y`), EdDSA for `OKP` keys on `Ed25519` (`crv`, `x`). The other key
+/// types' members are left empty.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
 pub struct JwkPublicKey {
+    /// Key id, matched against the `kid` of the token's header; empty for a
+    /// key only verifying tokens without one
+    #[serde(default)]
+    pub kid: String,
+    pub kty: String,
     pub n: String,
     pub e: String,
+    pub crv: String,
+    pub x: String,
+    pub y: String,
 }
 
-#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+impl JwkPublicKey {
+    pub fn rsa(n: String, e: String) -> Self {
+        JwkPublicKey {
+            kid: String::new(),
+            kty: "RSA".to_string(),
+            n,
+            e,
+            crv: String::new(),
+            x: String::new(),
+            y: String::new(),
+        }
+    }
+
+    pub fn ec(crv: String, x: String, y: String) -> Self {
+        JwkPublicKey {
+            kid: String::new(),
+            kty: "EC".to_string(),
+            n: String::new(),
+            e: String::new(),
+            crv,
+            x,
+            y,
+        }
+    }
+
+    pub fn okp(crv: String, x: String) -> Self {
+        JwkPublicKey {
+            kid: String::new(),
+            kty: "OKP".to_string(),
+            n: String::new(),
+            e: String::new(),
+            crv,
+            x,
+            y: String::new(),
+        }
+    }
+
+    pub fn with_kid(mut self, kid: String) -> Self {
+        self.kid = kid;
+        self
+    }
+}
+
+/// Keys an ID token may be signed with, such as a provider's JWKS or the
+/// part of it a host picked. The contract verifies the token with the key
+/// named by its header's `kid`, and refuses it if the set has no such key.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct JwkSet {
+    pub keys: Vec<JwkPublicKey>,
+}
+
+impl JwkSet {
+    /// The key for a token whose header names `kid`. A token without `kid`
+    /// can only be verified by a set of one key without one.
+    pub fn key(&self, kid: Option<&str>) -> Option<&JwkPublicKey> {
+        match (kid, self.keys.as_slice()) {
+            (Some(kid), keys) => keys.iter().find(|key| key.kid == kid),
+            (None, [key]) if key.kid.is_empty() => Some(key),
+            (None, _) => None,
+        }
+    }
+}
+
+impl From<JwkPublicKey> for JwkSet {
+    fn from(key: JwkPublicKey) -> Self {
+        JwkSet {
+            keys: alloc::vec![key],
+        }
+    }
+}
+
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
 pub struct OpenIdContext {
     pub issuer: String,
-    pub audience: String,
+    /// Client ids accepted: the token's `aud` must name one of them
+    #[serde(deserialize_with = "audience::deserialize")]
+    pub audience: Vec<String>,
+    /// Further requirements on the token, see [`claim_policy`]
+    #[serde(default, skip_serializing_if = "ClaimPolicy::is_empty")]
+    pub policy: ClaimPolicy,
+    /// JWS algorithms (`RS256`, `ES256`, `EdDSA`) accepted; empty accepts
+    /// all. The token's `alg` must also be the one of the key verifying it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub algorithms: Vec<String>,
+    /// How the token's `iss` must match `issuer`, see [`issuer`]
+    #[serde(default, skip_serializing_if = "IssuerMatch::is_exact")]
+    pub issuer_match: IssuerMatch,
+}
+
+/// Private data handed to the guest alongside the blob, bincode-encoded in
+/// `ContractInput::private_input`.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum PrivateInput {
+    /// The raw OIDC ID token (JWT).
+    Token(String),
+    /// An ID token together with a password, for contracts that require one.
+    TokenWithPassword { token: String, password: String },
+    /// A signature by one of the account's session keys, over
+    /// [`session::signing_message`], for `VerifyWithSessionKey`.
+    SessionKeySignature {
+        public_key: String,
+        signature: String,
+    },
+    /// ID tokens for the current and the new subject of an account, for
+    /// `MigrateSubject` and `LinkProvider`.
+    TokenPair {
+        old_token: String,
+        new_token: String,
+    },
+    /// The ID token of an account's new credential and one of its recovery
+    /// codes, for `RecoverWithCode`.
+    TokenWithRecoveryCode { token: String, code: String },
+    /// The inputs of the actions of an `IdentityAction::Batch`, in order.
+    Batch(Vec<PrivateInput>),
+    /// `input`, for an account whose subjects are hashed with `salt`, see
+    /// [`account::oidc_salted_account_hash`]. The salt applies to every token
+    /// of `input`.
+    Salted {
+        salt: String,
+        input: Box<PrivateInput>,
+    },
+}
+
+/// An ID token handed to a contract, with the salt of the account it proves
+/// control of, if any.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IdToken<'a> {
+    pub jwt: &'a str,
+    pub salt: Option<&'a str>,
+}
+
+impl<'a> IdToken<'a> {
+    pub fn new(jwt: &'a str, salt: Option<&'a str>) -> Self {
+        IdToken { jwt, salt }
+    }
+
+    /// The hash an account stores for the token's `subject` and `issuer`.
+    pub fn subject_hash(&self, subject: &str, issuer: &str) -> String {
+        match self.salt {
+            Some(salt) => account::oidc_salted_account_hash(salt, subject, issuer),
+            None => account::oidc_account_hash(subject, issuer),
+        }
+    }
+}
+
+/// An unsalted token.
+impl<'a, S: AsRef<str> + ?Sized> From<&'a S> for IdToken<'a> {
+    fn from(jwt: &'a S) -> Self {
+        IdToken::new(jwt.as_ref(), None)
+    }
+}
+
+impl PrivateInput {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::encode_to_vec(self, bincode::config::standard())
+            .expect("failed to encode private input")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        bincode::decode_from_slice(bytes, bincode::config::standard())
+            .map(|(input, _)| input)
+            .map_err(|_| "Failed to decode private input")
+    }
+
+    /// Wraps this input with the account's `salt`, if any.
+    pub fn salted(self, salt: Option<String>) -> Self {
+        match salt {
+            Some(salt) => PrivateInput::Salted {
+                salt,
+                input: Box::new(self),
+            },
+            None => self,
+        }
+    }
+
+    /// Returns the salt of the account this input proves control of, if any.
+    pub fn salt(&self) -> Option<&str> {
+        match self {
+            PrivateInput::Salted { salt, .. } => Some(salt),
+            _ => None,
+        }
+    }
+
+    /// Returns this input without its salt.
+    pub fn unsalted(&self) -> &PrivateInput {
+        match self {
+            PrivateInput::Salted { input, .. } => input.unsalted(),
+            input => input,
+        }
+    }
+
+    /// Returns the ID token carried by this input, if any.
+    pub fn token(&self) -> Option<&str> {
+        match self.unsalted() {
+            PrivateInput::Token(token)
+            | PrivateInput::TokenWithPassword { token, .. }
+            | PrivateInput::TokenWithRecoveryCode { token, .. } => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Returns every ID token carried by this input.
+    pub fn tokens(&self) -> Vec<&str> {
+        match self.unsalted() {
+            PrivateInput::TokenPair {
+                old_token,
+                new_token,
+            } => alloc::vec![old_token.as_str(), new_token.as_str()],
+            PrivateInput::Batch(inputs) => inputs.iter().flat_map(PrivateInput::tokens).collect(),
+            input => input.token().into_iter().collect(),
+        }
+    }
+
+    /// The ID token of [`PrivateInput::token`], with the input's salt.
+    fn require_token(&self) -> Result<IdToken<'_>, ResultCode> {
+        let jwt = self.token().ok_or(ResultCode::MissingToken)?;
+        Ok(IdToken::new(jwt, self.salt()))
+    }
+
+    /// Returns the password carried by this input, if any.
+    pub fn password(&self) -> Option<&str> {
+        match self.unsalted() {
+            PrivateInput::TokenWithPassword { password, .. } => Some(password),
+            _ => None,
+        }
+    }
 }
 
 pub trait IdentityVerification {
-    fn register_identity(
+    fn register_identity<'t>(
         &mut self,
         account: &str,
         context: &OpenIdContext,
-        jwk_pub_key: &JwkPublicKey,
-        private_input: &str,
-    ) -> Result<(), &'static str>;
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+    ) -> Result<(), ResultCode>;
 
-    fn verify_identity(
+    fn verify_identity<'t>(
         &mut self,
         account: &str,
         nonce: u32,
         context: &OpenIdContext,
-        jwk_pub_key: &JwkPublicKey,
-        private_input: &str,
-    ) -> Result<bool, &'static str>;
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+    ) -> Result<bool, ResultCode>;
+
+    /// Sets the descriptive metadata of `account`, see [`metadata`].
+    fn set_account_metadata(
+        &mut self,
+        account: &str,
+        metadata: AccountMetadata,
+    ) -> Result<(), ResultCode>;
+
+    /// Sets which nonces `account` accepts, see [`nonce`].
+    fn set_nonce_strategy(
+        &mut self,
+        account: &str,
+        strategy: NonceStrategy,
+    ) -> Result<(), ResultCode>;
+
+    /// Bumps the account's session epoch, invalidating everything minted
+    /// under the previous one. Returns the new epoch.
+    fn logout<'t>(
+        &mut self,
+        account: &str,
+        context: &OpenIdContext,
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+    ) -> Result<u32, ResultCode>;
 
-    fn get_identity_info(&self, account: &str) -> Result<String, &'static str>;
+    /// Deletes the account, proven with a fresh ID token of its subject. The
+    /// admin account can't be unregistered.
+    fn unregister_identity<'t>(
+        &mut self,
+        account: &str,
+        context: &OpenIdContext,
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+    ) -> Result<(), ResultCode>;
+
+    /// Rebinds the account to a new `sub:iss` pair. Both tokens must be valid,
+    /// and the old one must match the account's current binding.
+    fn migrate_subject<'t>(
+        &mut self,
+        account: &str,
+        old: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        new: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+    ) -> Result<(), ResultCode>;
+
+    /// Binds the account to one more `sub:iss` pair, so that ID tokens of
+    /// either subject control it. The current token must match one of the
+    /// account's bindings.
+    fn link_provider<'t>(
+        &mut self,
+        account: &str,
+        current: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        new: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+    ) -> Result<(), ResultCode>;
+
+    /// Removes the binding hashing to `hash`, proven with an ID token of any
+    /// subject of the account. The last binding can't be removed.
+    fn unlink_provider<'t>(
+        &mut self,
+        account: &str,
+        auth: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        hash: &str,
+    ) -> Result<(), ResultCode>;
+
+    /// Sets aside the next `count` nonces so that many verifications can be
+    /// proved in parallel and settle in any order. Returns the reserved range.
+    fn reserve_nonces<'t>(
+        &mut self,
+        account: &str,
+        count: u32,
+        context: &OpenIdContext,
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+    ) -> Result<core::ops::Range<u32>, ResultCode>;
+
+    fn get_identity_info(&self, account: &str) -> Result<String, ResultCode>;
+
+    /// The account whose email hashes to `email_hash`, see
+    /// [`account::email_hash`], if the contract indexes emails.
+    fn account_by_email_hash(&self, email_hash: &str) -> Result<String, ResultCode>;
+
+    /// Returns the claim of the account's ID token, rendered as `disclosure`,
+    /// if the contract's disclosure policy allows it.
+    fn disclose_claim<'t>(
+        &self,
+        account: &str,
+        context: &OpenIdContext,
+        jwk_pub_key: &JwkSet,
+        private_input: impl Into<IdToken<'t>>,
+        claim: Claim,
+        disclosure: Disclosure,
+    ) -> Result<String, ResultCode>;
+
+    /// Returns the claims of the account's ID token named in `wanted`, such
+    /// as `groups` or `org_id`, for downstream contracts to consume; see
+    /// [`claims::extract`]. Contracts committing to no custom claims keep
+    /// this default, refusing them all.
+    fn extract_claims<'t>(
+        &self,
+        _account: &str,
+        _context: &OpenIdContext,
+        _jwk_pub_key: &JwkSet,
+        _private_input: impl Into<IdToken<'t>>,
+        _wanted: &[&str],
+    ) -> Result<BTreeMap<String, String>, ResultCode> {
+        Err(ResultCode::DisclosureForbidden)
+    }
+}
+
+/// One-time recovery codes (see [`recovery`]), committed as hashes at
+/// registration. Each rebinds the account to a new credential once, for users
+/// who lost access to their identity provider account.
+pub trait AccountRecovery {
+    /// Commits the hashes of `account`'s recovery codes, replacing any.
+    fn set_recovery_codes(
+        &mut self,
+        account: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), ResultCode>;
+
+    /// Consumes `code` and rebinds `account` to the subject of the `new` token.
+    fn recover_with_code<'t>(
+        &mut self,
+        account: &str,
+        code: &str,
+        new: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+    ) -> Result<(), ResultCode>;
+
+    /// Sets the subject of the `recovery` token as the account's recovery
+    /// provider, replacing any, with `delay` blocks between starting and
+    /// completing a recovery. The current token must match one of the
+    /// account's bindings.
+    fn add_recovery_provider<'t>(
+        &mut self,
+        account: &str,
+        current: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        recovery: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        delay: u64,
+    ) -> Result<(), ResultCode>;
+
+    /// With a token of the account's recovery provider, starts a recovery, or
+    /// completes the pending one once its delay has passed: the recovery
+    /// subject then replaces all of the account's bindings. Returns the block
+    /// a started recovery can complete at, `None` once completed.
+    fn recover_identity<'t>(
+        &mut self,
+        account: &str,
+        recovery: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        block_height: u64,
+    ) -> Result<Option<u64>, ResultCode>;
+}
+
+/// Administrative changes, applied only after the contract's minimum delay.
+/// `Pause` is the emergency stop: until `Unpause`, every action but reads and
+/// governance fails with [`ResultCode::ContractPaused`]. Pausing the root
+/// state stops every realm. `CreateRealm` is only proposable by the root
+/// admin, see [`realm`]. `AllowIssuer` stores the issuer's `keys`, its JWKS:
+/// its tokens are only verified with them, so a provider rotating its keys
+/// must be allowed again with the new ones.
+#[derive(
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum AdminOperation {
+    AllowIssuer { context: OpenIdContext, keys: JwkSet },
+    DisallowIssuer { issuer: String },
+    FreezeAccount { account: String },
+    UnfreezeAccount { account: String },
+    Pause,
+    Unpause,
+    CreateRealm { realm: String, config: RealmConfig },
+    AllowOrigin { origin: String },
+    DisallowOrigin { origin: String },
+}
+
+/// Two-phase governance: operations are proposed, become executable after a
+/// minimum number of blocks, and can be cancelled in between. Every call is
+/// authenticated with an ID token of the admin account.
+pub trait AdminGovernance {
+    /// Returns the id of the pending operation.
+    fn propose_admin_operation<'t>(
+        &mut self,
+        admin_auth: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        operation: AdminOperation,
+        block_height: u64,
+    ) -> Result<u32, ResultCode>;
+
+    fn execute_admin_operation<'t>(
+        &mut self,
+        admin_auth: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        id: u32,
+        block_height: u64,
+    ) -> Result<(), ResultCode>;
+
+    fn cancel_admin_operation<'t>(
+        &mut self,
+        admin_auth: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        id: u32,
+    ) -> Result<(), ResultCode>;
+
+    /// Whether an executed [`AdminOperation::Pause`] is in effect.
+    fn is_paused(&self) -> bool;
+}
+
+/// Keeps the live state small: accounts inactive for the contract's configured
+/// number of blocks are moved into a Merkle archive (see [`archive`]), from
+/// which anyone can restore them with an inclusion proof.
+pub trait AccountArchive {
+    /// Records that `account` acted at `block_height`.
+    fn record_activity(&mut self, account: &str, block_height: u64);
+
+    /// Archives `accounts`, which must all be inactive. Returns the archived
+    /// entries, to be kept by whoever will need their proofs.
+    fn prune_inactive(
+        &mut self,
+        accounts: &[String],
+        block_height: u64,
+    ) -> Result<String, ResultCode>;
+
+    /// Restores `account` from the `record` it was archived with.
+    fn reactivate(
+        &mut self,
+        account: &str,
+        record: &[u8],
+        proof: &ArchiveProof,
+        block_height: u64,
+    ) -> Result<(), ResultCode>;
+}
+
+/// Short-lived keys verifying an account without an ID token, see [`session`].
+pub trait SessionKeys {
+    /// Verifies the account at `nonce` with an ID token, as
+    /// [`IdentityVerification::verify_identity`] does, then authorizes the
+    /// P-256 `public_key` until block `expiry`. The account's expired or
+    /// revoked keys are dropped first.
+    fn authorize_session_key<'t>(
+        &mut self,
+        account: &str,
+        nonce: u32,
+        auth: (&OpenIdContext, &JwkSet, impl Into<IdToken<'t>>),
+        public_key: String,
+        expiry: u64,
+        block_height: u64,
+    ) -> Result<(), ResultCode>;
+
+    /// Verifies the account at `nonce` with `signature` by `public_key`, one
+    /// of its valid session keys, over [`session::signing_message`] for the
+    /// contract and transaction of `block`.
+    fn verify_with_session_key(
+        &mut self,
+        account: &str,
+        nonce: u32,
+        public_key: &str,
+        signature: &str,
+        block: BlockContext<'_>,
+    ) -> Result<(), ResultCode>;
+}
+
+/// Namespaced realms, see [`realm`]. A realm's state has the same type as the
+/// root state, and every action applies to it as it would to the root.
+pub trait Realms {
+    /// The state of `realm`, created by [`AdminOperation::CreateRealm`].
+    fn realm_mut(&mut self, realm: &str) -> Result<&mut Self, ResultCode>;
 }
 
 /// Enum representing the actions that can be performed by the IdentityVerification contract.
-#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone)]
+#[derive(
+    Serialize, Deserialize, Encode, Decode, Debug, Clone, BorshSerialize, BorshDeserialize,
+)]
 pub enum IdentityAction {
     RegisterIdentity {
         account: String,
         context: OpenIdContext,
-        jwk_pub_key: JwkPublicKey,
+        jwk_pub_key: JwkSet,
+        /// Hashes of the account's recovery codes, see [`recovery`]; may be empty
+        recovery_codes: Vec<String>,
+        /// See [`metadata`]; the registration time sent is ignored
+        metadata: AccountMetadata,
+        /// Which nonces `VerifyIdentity` accepts, see [`nonce`]
+        nonce_strategy: NonceStrategy,
     },
     VerifyIdentity {
         account: String,
         nonce: u32,
         context: OpenIdContext,
-        jwk_pub_key: JwkPublicKey,
+        jwk_pub_key: JwkSet,
     },
     GetIdentityInfo {
         account: String,
     },
+    Logout {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+    },
+    UnregisterIdentity {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+    },
+    ProposeAdminOperation {
+        admin_context: OpenIdContext,
+        admin_jwk_pub_key: JwkSet,
+        operation: AdminOperation,
+    },
+    ExecuteAdminOperation {
+        admin_context: OpenIdContext,
+        admin_jwk_pub_key: JwkSet,
+        id: u32,
+    },
+    CancelAdminOperation {
+        admin_context: OpenIdContext,
+        admin_jwk_pub_key: JwkSet,
+        id: u32,
+    },
+    ReserveNonces {
+        account: String,
+        count: u32,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+    },
+    /// Publishes one claim of the account's ID token in the program output.
+    DiscloseClaim {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        claim: Claim,
+        disclosure: Disclosure,
+    },
+    /// Publishes the named custom claims of the account's ID token, as a JSON
+    /// object of the ones it has. Requires `PrivateInput::Token`.
+    ExtractClaims {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        claims: Vec<String>,
+    },
+    /// Requires `PrivateInput::TokenPair`.
+    MigrateSubject {
+        account: String,
+        old_context: OpenIdContext,
+        old_jwk_pub_key: JwkSet,
+        new_context: OpenIdContext,
+        new_jwk_pub_key: JwkSet,
+    },
+    /// Requires `PrivateInput::TokenPair`, the old token being one of the
+    /// account's current subjects.
+    LinkProvider {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        new_context: OpenIdContext,
+        new_jwk_pub_key: JwkSet,
+    },
+    UnlinkProvider {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        /// `sub:iss` hash of the subject to unlink, as listed in the account info
+        hash: String,
+    },
+    /// Archives inactive accounts. Anyone can send it; needs the tx context.
+    PruneInactive {
+        accounts: Vec<String>,
+    },
+    /// Restores an archived account. Anyone can send it; needs the tx context.
+    Reactivate {
+        account: String,
+        /// The record published when the account was archived
+        record: Vec<u8>,
+        proof: ArchiveProof,
+    },
+    /// Rebinds the account to a new credential with one of its recovery codes.
+    /// Requires `PrivateInput::TokenWithRecoveryCode`, with the new credential's token.
+    RecoverWithCode {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+    },
+    /// Sets the account's recovery provider, see [`recovery`]. Requires
+    /// `PrivateInput::TokenPair`: the old token being one of the account's
+    /// current subjects, the new one the recovery subject.
+    AddRecoveryProvider {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        recovery_context: OpenIdContext,
+        recovery_jwk_pub_key: JwkSet,
+        /// Blocks between starting a recovery and completing it
+        delay: u64,
+    },
+    /// Verifies the account with an ID token, then authorizes a session key,
+    /// see [`session`]. Needs the tx context.
+    AuthorizeSessionKey {
+        account: String,
+        nonce: u32,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+        /// Hex of the SEC1-encoded P-256 public key
+        pubkey: String,
+        /// Block height the key expires at
+        expiry: u64,
+    },
+    /// Verifies the account with a session key instead of an ID token.
+    /// Requires `PrivateInput::SessionKeySignature`. Needs the tx context.
+    VerifyWithSessionKey {
+        account: String,
+        nonce: u32,
+    },
+    /// Starts a recovery with the recovery provider's token, outputting the
+    /// block it can complete at, or completes it once its delay has passed,
+    /// outputting the account. Needs the tx context.
+    RecoverIdentity {
+        account: String,
+        context: OpenIdContext,
+        jwk_pub_key: JwkSet,
+    },
+    /// Outputs the account registered with the email hashing to `email_hash`.
+    GetAccountByEmailHash {
+        email_hash: String,
+    },
+    /// Applies `action` to the state of `realm` instead of the root state.
+    InRealm {
+        realm: String,
+        action: Box<IdentityAction>,
+    },
+    /// Applies each action in order, in a single state transition: one proof
+    /// for a relayer registering or verifying many accounts. Requires
+    /// `PrivateInput::Batch`, with the input of each action. If any action
+    /// fails, the whole batch does.
+    Batch(Vec<IdentityAction>),
 }
 
+/// Largest number of actions in an [`IdentityAction::Batch`]. In practice the
+/// blob and private input limits of the contract allow fewer.
+pub const MAX_BATCH_ACTIONS: usize = 32;
+
 impl IdentityAction {
+    /// The account acting, for actions that count as its activity. Accounts
+    /// of a realm are only known within it: `None` for [`IdentityAction::InRealm`].
+    pub fn account(&self) -> Option<&str> {
+        match self {
+            IdentityAction::RegisterIdentity { account, .. }
+            | IdentityAction::VerifyIdentity { account, .. }
+            | IdentityAction::Logout { account, .. }
+            | IdentityAction::UnregisterIdentity { account, .. }
+            | IdentityAction::ReserveNonces { account, .. }
+            | IdentityAction::DiscloseClaim { account, .. }
+            | IdentityAction::ExtractClaims { account, .. }
+            | IdentityAction::MigrateSubject { account, .. }
+            | IdentityAction::LinkProvider { account, .. }
+            | IdentityAction::UnlinkProvider { account, .. }
+            | IdentityAction::Reactivate { account, .. }
+            | IdentityAction::RecoverWithCode { account, .. }
+            | IdentityAction::AddRecoveryProvider { account, .. }
+            | IdentityAction::RecoverIdentity { account, .. }
+            | IdentityAction::AuthorizeSessionKey { account, .. }
+            | IdentityAction::VerifyWithSessionKey { account, .. } => Some(account),
+            IdentityAction::GetIdentityInfo { .. }
+            | IdentityAction::GetAccountByEmailHash { .. }
+            | IdentityAction::ProposeAdminOperation { .. }
+            | IdentityAction::ExecuteAdminOperation { .. }
+            | IdentityAction::CancelAdminOperation { .. }
+            | IdentityAction::PruneInactive { .. }
+            | IdentityAction::InRealm { .. }
+            | IdentityAction::Batch(_) => None,
+        }
+    }
+
+    /// The account whose login the action proves: registrations and
+    /// verifications of the root state, sent with the account as the
+    /// transaction's identity. What companion contracts rely on.
+    pub fn authenticates(&self) -> Option<&str> {
+        match self {
+            IdentityAction::RegisterIdentity { account, .. }
+            | IdentityAction::VerifyIdentity { account, .. }
+            | IdentityAction::VerifyWithSessionKey { account, .. } => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Whether the action is a batch, possibly within a realm.
+    fn is_batch(&self) -> bool {
+        match self {
+            IdentityAction::Batch(_) => true,
+            IdentityAction::InRealm { action, .. } => action.is_batch(),
+            _ => false,
+        }
+    }
+
+    /// The realm the action applies to; `None` for the root state.
+    pub fn realm(&self) -> Option<&str> {
+        match self {
+            IdentityAction::InRealm { realm, .. } => Some(realm),
+            _ => None,
+        }
+    }
+
+    /// Whether the action still goes through while the contract is paused:
+    /// reads, and the governance actions needed to unpause it.
+    pub fn allowed_while_paused(&self) -> bool {
+        match self {
+            IdentityAction::InRealm { action, .. } => action.allowed_while_paused(),
+            IdentityAction::Batch(actions) => actions.iter().all(Self::allowed_while_paused),
+            _ => matches!(
+                self,
+                IdentityAction::GetIdentityInfo { .. }
+                    | IdentityAction::GetAccountByEmailHash { .. }
+                    | IdentityAction::ProposeAdminOperation { .. }
+                    | IdentityAction::ExecuteAdminOperation { .. }
+                    | IdentityAction::CancelAdminOperation { .. }
+            ),
+        }
+    }
+
+    /// Signatures and hashes the action costs when it succeeds. Failures stop
+    /// early and don't report any work.
+    pub fn work(&self) -> Work {
+        match self {
+            IdentityAction::GetIdentityInfo { .. }
+            | IdentityAction::GetAccountByEmailHash { .. } => Work::new(0, 0),
+            IdentityAction::MigrateSubject { .. }
+            | IdentityAction::LinkProvider { .. }
+            | IdentityAction::AddRecoveryProvider { .. } => Work::new(2, 2),
+            IdentityAction::RecoverWithCode { .. } => Work::new(1, 2),
+            // A leaf and its path up to the root per pruned account
+            IdentityAction::PruneInactive { accounts } => Work::new(
+                0,
+                accounts.len() as u32 * (archive::ARCHIVE_DEPTH as u32 + 1),
+            ),
+            // The leaf, then its path checked against the old root and
+            // recomputed for the new one
+            IdentityAction::Reactivate { .. } => {
+                Work::new(0, 2 * archive::ARCHIVE_DEPTH as u32 + 1)
+            }
+            IdentityAction::InRealm { action, .. } => action.work(),
+            IdentityAction::Batch(actions) => actions
+                .iter()
+                .map(Self::work)
+                .fold(Work::default(), |sum, work| {
+                    Work::new(sum.signatures + work.signatures, sum.hashes + work.hashes)
+                }),
+            _ => Work::new(1, 1),
+        }
+    }
+
     pub fn as_blob(&self, contract_name: ContractName) -> Blob {
         <Self as ContractAction>::as_blob(self, contract_name, None, None)
     }
+
+    /// Decodes the data of an action blob: bincode, as [`Self::as_blob`]
+    /// encodes it, or Borsh, as contracts composing with this one (such as
+    /// ecdsa-identity) encode their own blobs. A composed blob gives its
+    /// action; see [`parse_composed_blob`] for its caller and callees.
+    pub fn from_blob_data(data: &BlobData) -> Option<Self> {
+        Self::from_structured_blob_data(data).map(|structured| structured.parameters)
+    }
+
+    /// Decodes the data of an action blob along with its place in a
+    /// composition: plain blobs have neither caller nor callees.
+    pub fn from_structured_blob_data(data: &BlobData) -> Option<StructuredBlobData<Self>> {
+        let plain = |parameters| StructuredBlobData {
+            caller: None,
+            callees: None,
+            parameters,
+        };
+        if let Some(action) = decode_whole(&data.0) {
+            return Some(plain(action));
+        }
+        if let Some(structured) = decode_whole(&data.0) {
+            return Some(structured);
+        }
+        borsh::from_slice(&data.0).ok().map(plain)
+    }
+}
+
+/// `bytes` decoded with bincode, if that reads all of them.
+fn decode_whole<T: Decode>(bytes: &[u8]) -> Option<T> {
+    match bincode::decode_from_slice(bytes, bincode::config::standard()) {
+        Ok((value, read)) if read == bytes.len() => Some(value),
+        _ => None,
+    }
+}
+
+/// The action of the blob at `index` among a transaction's `blobs`, with its
+/// caller and callees if it was composed with other blobs, such as a token
+/// transfer the identity authorizes. They must name other blobs of the
+/// transaction.
+pub fn parse_composed_blob(
+    blobs: &[Blob],
+    index: &BlobIndex,
+) -> Option<StructuredBlobData<IdentityAction>> {
+    let structured = IdentityAction::from_structured_blob_data(&blobs.get(index.0)?.data)?;
+    let composes = |other: &BlobIndex| other.0 != index.0 && other.0 < blobs.len();
+    let callees_compose = structured
+        .callees
+        .as_ref()
+        .map_or(true, |callees| callees.iter().all(composes));
+    (structured.caller.as_ref().map_or(true, composes) && callees_compose).then_some(structured)
 }
 
 impl ContractAction for IdentityAction {
+    /// A plain bincode blob, or a [`StructuredBlobData`] one when composed
+    /// with a caller or callees.
     fn as_blob(
         &self,
         contract_name: ContractName,
-        _caller: Option<BlobIndex>,
-        _callees: Option<Vec<BlobIndex>>,
+        caller: Option<BlobIndex>,
+        callees: Option<Vec<BlobIndex>>,
     ) -> Blob {
+        let data = if caller.is_none() && callees.is_none() {
+            bincode::encode_to_vec(self, bincode::config::standard())
+        } else {
+            bincode::encode_to_vec(
+                StructuredBlobData {
+                    caller,
+                    callees,
+                    parameters: self.clone(),
+                },
+                bincode::config::standard(),
+            )
+        };
         Blob {
             contract_name,
-            data: BlobData(
-                bincode::encode_to_vec(self, bincode::config::standard())
-                    .expect("failed to encode program inputs"),
-            ),
+            data: BlobData(data.expect("failed to encode program inputs")),
         }
     }
 }
 
-pub fn execute_action<T: IdentityVerification + Digestable>(
+/// The block an action is proved in, from the proof's tx context, and the
+/// transaction it is sent in.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BlockContext<'a> {
+    pub height: u64,
+    /// Block timestamp, as the tx context carries it
+    pub time: u64,
+    /// Contract the action's blob is sent to
+    pub contract_name: &'a str,
+    /// Blobs of the transaction, the action's at `index`
+    pub blobs: &'a [sdk::Blob],
+    pub index: usize,
+}
+
+impl<'a> BlockContext<'a> {
+    /// Blobs of the transaction besides the action's.
+    pub fn other_blobs(self) -> impl Iterator<Item = &'a sdk::Blob> {
+        let index = self.index;
+        self.blobs
+            .iter()
+            .enumerate()
+            .filter(move |(position, _)| *position != index)
+            .map(|(_, blob)| blob)
+    }
+}
+
+/// Applies `action` to `state`. Program outputs start with a [`ResultCode`]
+/// (see [`code::output`]), followed by the action's detail payload: the
+/// account, the new session epoch, the reserved range, the disclosed value...
+/// Successes also carry the action's [`Work`].
+pub fn execute_action<
+    T: IdentityVerification
+        + AdminGovernance
+        + AccountArchive
+        + AccountRecovery
+        + SessionKeys
+        + Realms
+        + Digestable,
+>(
     mut state: T,
     action: IdentityAction,
-    private_input: &str,
+    private_input: &PrivateInput,
+    block: Option<BlockContext<'_>>,
 ) -> RunResult<T> {
-    let program_output = match action {
+    let mut work = action.work();
+    let mut events = Vec::new();
+    let detail = apply_action(&mut state, action, private_input, block, &mut events)?;
+    work.state_bytes = state.as_digest().0.len() as u64;
+    Ok((
+        code::output_with_events(ResultCode::Ok, work, &events, &detail),
+        state,
+        alloc::vec![],
+    ))
+}
+
+fn apply_action<
+    T: IdentityVerification
+        + AdminGovernance
+        + AccountArchive
+        + AccountRecovery
+        + SessionKeys
+        + Realms,
+>(
+    state: &mut T,
+    action: IdentityAction,
+    private_input: &PrivateInput,
+    block: Option<BlockContext<'_>>,
+    events: &mut Vec<IdentityEvent>,
+) -> Result<String, Failure> {
+    if state.is_paused() && !action.allowed_while_paused() {
+        return Err(ResultCode::ContractPaused.into());
+    }
+    let block_height = block.map(|block| block.height);
+    let require_block_height = || block_height.ok_or(ResultCode::MissingTxContext);
+    let acting = action.account().map(ToString::to_string);
+
+    let detail = match action {
         IdentityAction::RegisterIdentity {
             account,
             context,
             jwk_pub_key,
-        } => match state.register_identity(&account, &context, &jwk_pub_key, private_input) {
-            Ok(()) => Ok(format!(
-                "Successfully registered identity for account: {}",
-                account
-            )),
-            Err(err) => Err(format!("Failed to register identity: {}", err)),
-        },
+            recovery_codes,
+            metadata,
+            nonce_strategy,
+        } => {
+            if recovery_codes.len() > recovery::MAX_RECOVERY_CODES {
+                return Err(Failure::invalid_input(format!(
+                    "Too many recovery codes (max {})",
+                    recovery::MAX_RECOVERY_CODES
+                )));
+            }
+            metadata.check().map_err(Failure::invalid_input)?;
+            if !nonce_strategy.is_valid() {
+                return Err(Failure::invalid_input(format!(
+                    "Nonce window must be 1 to {}",
+                    nonce::MAX_NONCE_WINDOW
+                )));
+            }
+            state.register_identity(
+                &account,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+            )?;
+            state.set_recovery_codes(&account, recovery_codes)?;
+            state.set_account_metadata(
+                &account,
+                AccountMetadata {
+                    registered_at: block.map(|block| block.time),
+                    ..metadata
+                },
+            )?;
+            state.set_nonce_strategy(&account, nonce_strategy)?;
+            events.push(IdentityEvent::Registered {
+                account: account.clone(),
+            });
+            account
+        }
         IdentityAction::VerifyIdentity {
             account,
             nonce,
             context,
             jwk_pub_key,
-        } => match state.verify_identity(&account, nonce, &context, &jwk_pub_key, private_input) {
-            Ok(true) => Ok(format!("Identity verified for account: {}", account)),
-            Ok(false) => Err(format!(
-                "Identity verification failed for account: {}",
-                account
-            )),
-            Err(err) => Err(format!("Error verifying identity: {}", err)),
-        },
-        IdentityAction::GetIdentityInfo { account } => match state.get_identity_info(&account) {
-            Ok(info) => Ok(format!(
-                "Retrieved identity info for account: {}: {}",
-                account, info
-            )),
-            Err(err) => Err(format!("Failed to get identity info: {}", err)),
-        },
+        } => {
+            let verified = state.verify_identity(
+                &account,
+                nonce,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+            )?;
+            if !verified {
+                return Err(ResultCode::TokenAccountMismatch.into());
+            }
+            events.push(IdentityEvent::Verified {
+                account: account.clone(),
+                nonce,
+            });
+            account
+        }
+        IdentityAction::GetIdentityInfo { account } => state.get_identity_info(&account)?,
+        IdentityAction::GetAccountByEmailHash { email_hash } => {
+            state.account_by_email_hash(&email_hash)?
+        }
+        IdentityAction::Logout {
+            account,
+            context,
+            jwk_pub_key,
+        } => state
+            .logout(
+                &account,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+            )?
+            .to_string(),
+        IdentityAction::UnregisterIdentity {
+            account,
+            context,
+            jwk_pub_key,
+        } => {
+            state.unregister_identity(
+                &account,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+            )?;
+            account
+        }
+        IdentityAction::ProposeAdminOperation {
+            admin_context,
+            admin_jwk_pub_key,
+            operation,
+        } => state
+            .propose_admin_operation(
+                (&admin_context, &admin_jwk_pub_key, private_input.require_token()?),
+                operation,
+                require_block_height()?,
+            )?
+            .to_string(),
+        IdentityAction::ExecuteAdminOperation {
+            admin_context,
+            admin_jwk_pub_key,
+            id,
+        } => {
+            state.execute_admin_operation(
+                (&admin_context, &admin_jwk_pub_key, private_input.require_token()?),
+                id,
+                require_block_height()?,
+            )?;
+            id.to_string()
+        }
+        IdentityAction::CancelAdminOperation {
+            admin_context,
+            admin_jwk_pub_key,
+            id,
+        } => {
+            state.cancel_admin_operation(
+                (&admin_context, &admin_jwk_pub_key, private_input.require_token()?),
+                id,
+            )?;
+            id.to_string()
+        }
+        IdentityAction::ReserveNonces {
+            account,
+            count,
+            context,
+            jwk_pub_key,
+        } => {
+            let range = state.reserve_nonces(
+                &account,
+                count,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+            )?;
+            events.push(IdentityEvent::NonceBumped {
+                account,
+                nonce: range.end,
+            });
+            format!("{}..{}", range.start, range.end)
+        }
+        IdentityAction::DiscloseClaim {
+            account,
+            context,
+            jwk_pub_key,
+            claim,
+            disclosure,
+        } => state.disclose_claim(
+            &account,
+            &context,
+            &jwk_pub_key,
+            private_input.require_token()?,
+            claim,
+            disclosure,
+        )?,
+        IdentityAction::ExtractClaims {
+            account,
+            context,
+            jwk_pub_key,
+            claims,
+        } => {
+            let wanted: Vec<&str> = claims.iter().map(String::as_str).collect();
+            let extracted = state.extract_claims(
+                &account,
+                &context,
+                &jwk_pub_key,
+                private_input.require_token()?,
+                &wanted,
+            )?;
+            serde_json::to_string(&extracted).expect("Failed to encode claims")
+        }
+        IdentityAction::MigrateSubject {
+            account,
+            old_context,
+            old_jwk_pub_key,
+            new_context,
+            new_jwk_pub_key,
+        } => {
+            let PrivateInput::TokenPair {
+                old_token,
+                new_token,
+            } = private_input.unsalted()
+            else {
+                return Err(ResultCode::MissingTokenPair.into());
+            };
+            let salt = private_input.salt();
+            let (old_token, new_token) =
+                (IdToken::new(old_token, salt), IdToken::new(new_token, salt));
+            state.migrate_subject(
+                &account,
+                (&old_context, &old_jwk_pub_key, old_token),
+                (&new_context, &new_jwk_pub_key, new_token),
+            )?;
+            account
+        }
+        IdentityAction::LinkProvider {
+            account,
+            context,
+            jwk_pub_key,
+            new_context,
+            new_jwk_pub_key,
+        } => {
+            let PrivateInput::TokenPair {
+                old_token,
+                new_token,
+            } = private_input.unsalted()
+            else {
+                return Err(ResultCode::MissingTokenPair.into());
+            };
+            let salt = private_input.salt();
+            let (old_token, new_token) =
+                (IdToken::new(old_token, salt), IdToken::new(new_token, salt));
+            state.link_provider(
+                &account,
+                (&context, &jwk_pub_key, old_token),
+                (&new_context, &new_jwk_pub_key, new_token),
+            )?;
+            account
+        }
+        IdentityAction::UnlinkProvider {
+            account,
+            context,
+            jwk_pub_key,
+            hash,
+        } => {
+            state.unlink_provider(
+                &account,
+                (&context, &jwk_pub_key, private_input.require_token()?),
+                &hash,
+            )?;
+            account
+        }
+        IdentityAction::PruneInactive { accounts } => {
+            state.prune_inactive(&accounts, require_block_height()?)?
+        }
+        IdentityAction::Reactivate {
+            account,
+            record,
+            proof,
+        } => {
+            state.reactivate(&account, &record, &proof, require_block_height()?)?;
+            account
+        }
+        IdentityAction::RecoverWithCode {
+            account,
+            context,
+            jwk_pub_key,
+        } => {
+            let PrivateInput::TokenWithRecoveryCode { token, code } = private_input.unsalted()
+            else {
+                return Err(ResultCode::MissingRecoveryCode.into());
+            };
+            let token = IdToken::new(token, private_input.salt());
+            state.recover_with_code(&account, code, (&context, &jwk_pub_key, token))?;
+            account
+        }
+        IdentityAction::AddRecoveryProvider {
+            account,
+            context,
+            jwk_pub_key,
+            recovery_context,
+            recovery_jwk_pub_key,
+            delay,
+        } => {
+            let PrivateInput::TokenPair {
+                old_token,
+                new_token,
+            } = private_input.unsalted()
+            else {
+                return Err(ResultCode::MissingTokenPair.into());
+            };
+            let salt = private_input.salt();
+            let (token, recovery_token) =
+                (IdToken::new(old_token, salt), IdToken::new(new_token, salt));
+            state.add_recovery_provider(
+                &account,
+                (&context, &jwk_pub_key, token),
+                (&recovery_context, &recovery_jwk_pub_key, recovery_token),
+                delay,
+            )?;
+            account
+        }
+        IdentityAction::AuthorizeSessionKey {
+            account,
+            nonce,
+            context,
+            jwk_pub_key,
+            pubkey,
+            expiry,
+        } => {
+            let block_height = require_block_height()?;
+            session::check_expiry(expiry, block_height).map_err(Failure::invalid_input)?;
+            state.authorize_session_key(
+                &account,
+                nonce,
+                (&context, &jwk_pub_key, private_input.require_token()?),
+                pubkey,
+                expiry,
+                block_height,
+            )?;
+            events.push(IdentityEvent::Verified {
+                account: account.clone(),
+                nonce,
+            });
+            account
+        }
+        IdentityAction::VerifyWithSessionKey { account, nonce } => {
+            let PrivateInput::SessionKeySignature {
+                public_key,
+                signature,
+            } = private_input.unsalted()
+            else {
+                return Err(ResultCode::MissingSessionKeySignature.into());
+            };
+            let block = block.ok_or(ResultCode::MissingTxContext)?;
+            state.verify_with_session_key(&account, nonce, public_key, signature, block)?;
+            events.push(IdentityEvent::Verified {
+                account: account.clone(),
+                nonce,
+            });
+            account
+        }
+        IdentityAction::RecoverIdentity {
+            account,
+            context,
+            jwk_pub_key,
+        } => {
+            let ready_at = state.recover_identity(
+                &account,
+                (&context, &jwk_pub_key, private_input.require_token()?),
+                require_block_height()?,
+            )?;
+            match ready_at {
+                Some(ready_at) => ready_at.to_string(),
+                None => account,
+            }
+        }
+        IdentityAction::Batch(actions) => {
+            if actions.len() > MAX_BATCH_ACTIONS {
+                return Err(Failure::invalid_input(format!(
+                    "Too many actions in a batch (max {})",
+                    MAX_BATCH_ACTIONS
+                )));
+            }
+            let PrivateInput::Batch(inputs) = private_input else {
+                return Err(ResultCode::InvalidBatch.into());
+            };
+            if inputs.len() != actions.len() || actions.iter().any(IdentityAction::is_batch) {
+                return Err(ResultCode::InvalidBatch.into());
+            }
+            let mut details = Vec::with_capacity(actions.len());
+            for (action, input) in actions.into_iter().zip(inputs) {
+                details.push(apply_action(state, action, input, block, events)?);
+            }
+            details.join("\n")
+        }
+        IdentityAction::InRealm { realm, action } => {
+            realm::check_name(&realm)?;
+            let nested = match action.as_ref() {
+                IdentityAction::InRealm { .. } => true,
+                IdentityAction::ProposeAdminOperation { operation, .. } => {
+                    matches!(operation, AdminOperation::CreateRealm { .. })
+                }
+                _ => false,
+            };
+            if nested {
+                return Err(ResultCode::NestedRealm.into());
+            }
+            let mut realm_events = Vec::new();
+            let state = state.realm_mut(&realm)?;
+            let detail = apply_action(state, *action, private_input, block, &mut realm_events)?;
+            for event in realm_events {
+                events.push(IdentityEvent::InRealm {
+                    realm: realm.clone(),
+                    event: Box::new(event),
+                });
+            }
+            detail
+        }
     };
-    program_output.map(|output| (output, state, alloc::vec![]))
+    if let (Some(account), Some(block_height)) = (acting, block_height) {
+        state.record_activity(&account, block_height);
+    }
+    Ok(detail)
 }
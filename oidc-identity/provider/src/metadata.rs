@@ -0,0 +1,72 @@
+//! Descriptive account metadata for indexers and wallets listing accounts.
+//! None of it is checked against the ID token or used to authorize anything:
+//! the provider label and display hash are whatever the registering client
+//! sent, only bounded in size, and the registration time is the block's.
+
+use alloc::{format, string::String};
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Longest provider label.
+pub const MAX_PROVIDER_LABEL: usize = 64;
+
+/// Length of a display hash, the hex of a 32-byte hash.
+pub const DISPLAY_HASH_LEN: usize = 64;
+
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct AccountMetadata {
+    /// Block time the account was registered at, set by the contract from the
+    /// tx context; `None` when registered in a proof without one
+    pub registered_at: Option<u64>,
+    /// Name of the identity provider, as configured by the client, e.g. `google`
+    pub provider: Option<String>,
+    /// Hex of a hash of the user's chosen display name or avatar, kept off
+    /// chain by the wallet showing it
+    pub display_hash: Option<String>,
+}
+
+impl AccountMetadata {
+    /// The metadata sent by a client, which doesn't choose the registration time.
+    pub fn new(provider: Option<String>, display_hash: Option<String>) -> Self {
+        AccountMetadata {
+            registered_at: None,
+            provider,
+            display_hash,
+        }
+    }
+
+    /// Bounds on the client-supplied fields.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(provider) = &self.provider {
+            if provider.is_empty() || provider.len() > MAX_PROVIDER_LABEL {
+                return Err(format!(
+                    "Provider label must be 1 to {MAX_PROVIDER_LABEL} bytes"
+                ));
+            }
+        }
+        if let Some(hash) = &self.display_hash {
+            let is_hex = hash
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+            if hash.len() != DISPLAY_HASH_LEN || !is_hex {
+                return Err(format!(
+                    "Display hash must be {DISPLAY_HASH_LEN} lowercase hex characters"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Which nonces an account accepts, chosen at registration. Strict accounts
+//! take their nonces in order, one transaction at a time. A window lets a
+//! client have several transactions in flight, settling in any order, without
+//! reserving nonces first. Timestamp nonces only have to increase, so a
+//! client needs no nonce read before signing.
+
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Largest window of [`NonceStrategy::Window`].
+pub const MAX_NONCE_WINDOW: u32 = 64;
+
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceStrategy {
+    /// The next nonce only
+    #[default]
+    Strict,
+    /// Any unused nonce of the `size` ones from the lowest unused
+    Window { size: u32 },
+    /// Any nonce above the last used, such as a Unix time
+    Timestamp,
+}
+
+impl NonceStrategy {
+    pub fn is_strict(&self) -> bool {
+        *self == NonceStrategy::Strict
+    }
+
+    /// Whether the strategy can be registered with.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            NonceStrategy::Window { size } => (1..=MAX_NONCE_WINDOW).contains(size),
+            _ => true,
+        }
+    }
+
+    /// Whether `nonce` is usable by an account whose next nonce is `next`.
+    /// Bit `i` of `window` marks `next + i` used, for windows.
+    pub fn accepts(&self, next: u32, window: u64, nonce: u32) -> bool {
+        match self {
+            NonceStrategy::Strict => nonce == next,
+            NonceStrategy::Window { size } => nonce
+                .checked_sub(next)
+                .is_some_and(|offset| offset < *size && window & (1 << offset) == 0),
+            // The last nonce is never usable, so `next` always follows it
+            NonceStrategy::Timestamp => nonce >= next && nonce < u32::MAX,
+        }
+    }
+
+    /// Uses up `nonce`, accepted by [`Self::accepts`], moving `next` past
+    /// every used nonce.
+    pub fn consume(&self, next: &mut u32, window: &mut u64, nonce: u32) {
+        match self {
+            NonceStrategy::Strict => *next += 1,
+            NonceStrategy::Window { .. } => {
+                *window |= 1 << (nonce - *next);
+                while *window & 1 == 1 {
+                    *window >>= 1;
+                    *next += 1;
+                }
+            }
+            NonceStrategy::Timestamp => *next = nonce + 1,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Identity realms: namespaces that let one deployment serve many
+//! applications. Each realm has its own accounts, issuer allow-list, origins
+//! and policies, and its own admin account governing them. An action applies
+//! to a realm when wrapped in `IdentityAction::InRealm`; unwrapped actions
+//! apply to the root state, whose admin creates the realms.
+
+use alloc::{string::String, vec::Vec};
+use bincode::{Decode, Encode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::code::ResultCode;
+use crate::disclosure::DisclosurePolicy;
+use crate::{JwkSet, OpenIdContext};
+
+/// Longest realm name.
+pub const MAX_REALM_NAME: usize = 64;
+
+/// Everything a realm is created with. The admin and min delay are fixed for
+/// the realm's life; the rest can be changed by its admin operations.
+#[derive(
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct RealmConfig {
+    /// Account, to be registered in the realm, governing it
+    pub admin: String,
+    /// Blocks between proposing and executing the realm's admin operations
+    pub min_delay: u64,
+    /// Issuer/audience pairs accepted at registration, with the keys of
+    /// their issuer; empty accepts any
+    pub allowed_issuers: Vec<(OpenIdContext, JwkSet)>,
+    /// Web origins of the realm's applications. The contract can't see
+    /// origins: they are published for hosts and relayers to enforce.
+    pub origins: Vec<String>,
+    pub disclosure_policy: DisclosurePolicy,
+    /// See the contract's pruning; `None` disables it in the realm
+    pub max_inactivity: Option<u64>,
+}
+
+/// Realm names are short identifiers: ASCII letters, digits, `-` and `_`.
+pub fn check_name(realm: &str) -> Result<(), ResultCode> {
+    let valid = !realm.is_empty()
+        && realm.len() <= MAX_REALM_NAME
+        && realm
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ResultCode::InvalidRealmName)
+    }
+}
@@ -0,0 +1,67 @@
+//! One-time recovery codes. The client generates them at registration, shows
+//! them to the user once and only sends their hashes, which the contract keeps
+//! with the account. A code travels in the private input when used, never in
+//! a blob, so it doesn't leak on-chain before being consumed.
+//!
+//! Accounts can also name a recovery provider: a subject of another issuer,
+//! which doesn't control the account but can take it over after a delay. Any
+//! verification of the account during the delay cancels the takeover, so a
+//! user who still has their credentials can stop a stolen recovery login.
+
+use alloc::{format, string::String};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::hasher::{AccountHasher, DefaultHasher};
+
+/// Most recovery codes an account can hold.
+pub const MAX_RECOVERY_CODES: usize = 10;
+
+/// Hash committed for `code`. Salting with the account keeps a hash from
+/// matching the same code on another account; dashes and case are ignored so
+/// codes can be shown grouped.
+pub fn recovery_code_hash(account: &str, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    DefaultHasher::digest_hex(format!("recovery:{account}:{code}").as_bytes())
+}
+
+/// A subject that can take its account over, see the [module docs](self).
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RecoveryProvider {
+    /// `sub:iss` hash of the recovery subject
+    pub hash: String,
+    /// Blocks between starting a recovery and completing it
+    pub delay: u64,
+    pub pending: Option<PendingRecovery>,
+}
+
+/// A recovery started with the recovery subject's ID token.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PendingRecovery {
+    /// First block the recovery can complete at
+    pub ready_at: u64,
+    /// Nonce of the account when the recovery started; the recovery is
+    /// cancelled once the account verifies with it
+    pub nonce: u32,
+}
+
+impl RecoveryProvider {
+    pub fn new(hash: String, delay: u64) -> Self {
+        RecoveryProvider {
+            hash,
+            delay,
+            pending: None,
+        }
+    }
+
+    /// The recovery pending for an account now at `nonce`, if not cancelled.
+    pub fn pending_at(&self, nonce: u32) -> Option<&PendingRecovery> {
+        self.pending
+            .as_ref()
+            .filter(|pending| pending.nonce == nonce)
+    }
+}
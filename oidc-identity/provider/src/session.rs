@@ -0,0 +1,62 @@
+//! Session keys. After an OIDC verification an account can authorize a P-256
+//! key, held by the client, to verify its next actions by signature only: far
+//! cheaper to prove than an ID token. Keys are short-lived, expiring at most
+//! [`MAX_SESSION_KEY_LIFETIME`] blocks after being authorized, and belong to
+//! the session epoch they were authorized in, so a logout revokes them all.
+
+use alloc::{format, string::String, vec::Vec};
+use bincode::{Decode, Encode};
+use sdk::Blob;
+use serde::{Deserialize, Serialize};
+
+/// Most blocks a session key can stay valid for.
+pub const MAX_SESSION_KEY_LIFETIME: u64 = 7200;
+
+/// Most session keys an account can hold at once.
+pub const MAX_SESSION_KEYS: usize = 4;
+
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SessionKey {
+    /// Hex of the SEC1-encoded P-256 public key
+    pub public_key: String,
+    /// First block height the key is no longer valid at
+    pub expiry: u64,
+    /// Session epoch of the account when the key was authorized
+    pub epoch: u32,
+}
+
+impl SessionKey {
+    /// Whether the key still verifies an account at `session_epoch`.
+    pub fn is_valid(&self, session_epoch: u32, block_height: u64) -> bool {
+        self.epoch == session_epoch && block_height < self.expiry
+    }
+}
+
+/// Bounds on the expiry of a key authorized at `block_height`.
+pub fn check_expiry(expiry: u64, block_height: u64) -> Result<(), String> {
+    if expiry <= block_height || expiry - block_height > MAX_SESSION_KEY_LIFETIME {
+        return Err(format!(
+            "Session key expiry must be within {MAX_SESSION_KEY_LIFETIME} blocks"
+        ));
+    }
+    Ok(())
+}
+
+/// Message a session key signs, with ECDSA over SHA-256, to verify `account`
+/// at `nonce` with the contract `contract_name`, in a transaction carrying
+/// `blobs` besides the action's. Naming both, as the ECDSA contract's
+/// verification message does, keeps a signature from being replayed on
+/// another deployment or attached to another transaction.
+pub fn signing_message<'b>(
+    contract_name: &str,
+    account: &str,
+    nonce: u32,
+    blobs: impl IntoIterator<Item = &'b Blob>,
+) -> String {
+    let blobs = blobs
+        .into_iter()
+        .map(|blob| format!("{} {:?}", blob.contract_name.0, blob.data.0))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!("session {contract_name} {account} {nonce} {blobs}")
+}
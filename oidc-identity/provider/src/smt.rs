@@ -0,0 +1,167 @@
+//! Sparse Merkle tree over a keyed map of a state, such as its accounts.
+//!
+//! Each entry sits at the leaf indexed by the hash of its key, 256 levels
+//! down, and absent entries are zero leaves: the same proof shows that an
+//! entry is in the tree or that it isn't. Proofs only carry the siblings
+//! that aren't the root of an empty subtree, flagged in a bitmap, so they stay
+//! small as long as the tree is sparse. Nodes are hashed with the
+//! [`DefaultHasher`].
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::Node;
+use crate::hasher::{AccountHasher, DefaultHasher};
+
+pub const SMT_DEPTH: usize = 256;
+
+/// Leaf of an absent entry.
+pub const EMPTY_LEAF: Node = [0; 32];
+
+/// Siblings of a leaf's path, from the leaf level up, omitting empty subtrees.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct SmtProof {
+    /// Bit `level` is set when the sibling at that level is in `siblings`
+    pub bitmap: [u8; 32],
+    pub siblings: Vec<Node>,
+}
+
+/// Position of the entry keyed by `key` in the tree.
+pub fn key(key: &str) -> Node {
+    DefaultHasher::digest(key.as_bytes())
+}
+
+/// Root of an all-empty subtree, per height.
+pub fn zeros() -> Vec<Node> {
+    let mut zeros = vec![EMPTY_LEAF; SMT_DEPTH + 1];
+    for height in 0..SMT_DEPTH {
+        zeros[height + 1] = DefaultHasher::hash_pair(&zeros[height], &zeros[height]);
+    }
+    zeros
+}
+
+/// Whether the path to `key` goes right below the node at `height`.
+fn goes_right(key: &Node, height: usize) -> bool {
+    let bit = SMT_DEPTH - height;
+    (key[bit / 8] >> (7 - bit % 8)) & 1 == 1
+}
+
+/// Root of the tree holding `leaves`, by key.
+pub fn root(leaves: &BTreeMap<Node, Node>) -> Node {
+    let leaves: Vec<(Node, Node)> = leaves.iter().map(|(key, leaf)| (*key, *leaf)).collect();
+    subtree(&leaves, SMT_DEPTH, &zeros())
+}
+
+/// Root of the subtree at `height` holding `leaves`, sorted by key.
+fn subtree(leaves: &[(Node, Node)], height: usize, zeros: &[Node]) -> Node {
+    match leaves {
+        [] => zeros[height],
+        [(_, leaf)] if height == 0 => *leaf,
+        _ => {
+            let split = leaves.partition_point(|(key, _)| !goes_right(key, height));
+            DefaultHasher::hash_pair(
+                &subtree(&leaves[..split], height - 1, zeros),
+                &subtree(&leaves[split..], height - 1, zeros),
+            )
+        }
+    }
+}
+
+/// Proof of the leaf at `key`, present or not, in the tree holding `leaves`.
+pub fn proof(leaves: &BTreeMap<Node, Node>, key: &Node) -> SmtProof {
+    let zeros = zeros();
+    let mut leaves: &[(Node, Node)] = &leaves
+        .iter()
+        .map(|(key, leaf)| (*key, *leaf))
+        .collect::<Vec<_>>();
+    let mut proof = SmtProof::default();
+    for height in (1..=SMT_DEPTH).rev() {
+        let split = leaves.partition_point(|(key, _)| !goes_right(key, height));
+        let (left, right) = leaves.split_at(split);
+        let (on_path, sibling) = if goes_right(key, height) {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        if !sibling.is_empty() {
+            let level = height - 1;
+            proof.bitmap[level / 8] |= 1 << (level % 8);
+            proof.siblings.push(subtree(sibling, level, &zeros));
+        }
+        leaves = on_path;
+    }
+    // Collected from the root down
+    proof.siblings.reverse();
+    proof
+}
+
+/// Every sibling of the path, empty subtrees included, from the leaf level
+/// up; `None` if the proof is malformed.
+fn expand(proof: &SmtProof, zeros: &[Node]) -> Option<Vec<Node>> {
+    let mut siblings = proof.siblings.iter();
+    let expanded = (0..SMT_DEPTH)
+        .map(|level| match (proof.bitmap[level / 8] >> (level % 8)) & 1 {
+            1 => siblings.next().copied(),
+            _ => Some(zeros[level]),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    siblings.next().is_none().then_some(expanded)
+}
+
+/// Nodes from `leaf` up to the root, following `siblings`.
+fn path(key: &Node, leaf: Node, siblings: &[Node]) -> Vec<Node> {
+    let mut nodes = Vec::with_capacity(SMT_DEPTH + 1);
+    nodes.push(leaf);
+    for (level, sibling) in siblings.iter().enumerate() {
+        let node = nodes[level];
+        nodes.push(if goes_right(key, level + 1) {
+            DefaultHasher::hash_pair(sibling, &node)
+        } else {
+            DefaultHasher::hash_pair(&node, sibling)
+        });
+    }
+    nodes
+}
+
+/// Root of a tree where `key` holds `leaf`, according to `proof`; `None` if
+/// the proof is malformed.
+pub fn root_from(key: &Node, leaf: Node, proof: &SmtProof) -> Option<Node> {
+    let siblings = expand(proof, &zeros())?;
+    Some(path(key, leaf, &siblings)[SMT_DEPTH])
+}
+
+/// A leaf to replace, and its proof in the tree before any replacement.
+pub struct LeafUpdate<'a> {
+    pub key: Node,
+    pub leaf: Node,
+    pub proof: &'a SmtProof,
+}
+
+/// Root once every leaf of `updates` is replaced, each proved against the
+/// same tree; `None` if a proof is malformed or a key repeats. The proofs must
+/// have been checked against the current root beforehand.
+pub fn update(root: Node, updates: &[LeafUpdate]) -> Option<Node> {
+    let zeros = zeros();
+    let mut siblings = updates
+        .iter()
+        .map(|update| expand(update.proof, &zeros))
+        .collect::<Option<Vec<_>>>()?;
+    let mut root = root;
+    for (index, update) in updates.iter().enumerate() {
+        let nodes = path(&update.key, update.leaf, &siblings[index]);
+        root = nodes[SMT_DEPTH];
+        // Later paths meet this one where the keys first differ: below that,
+        // their sibling is the subtree just rewritten
+        for (other, later) in updates.iter().zip(siblings.iter_mut()).skip(index + 1) {
+            let height = (1..=SMT_DEPTH).rev().find(|&height| {
+                goes_right(&update.key, height) != goes_right(&other.key, height)
+            })?;
+            later[height - 1] = nodes[height - 1];
+        }
+    }
+    Some(root)
+}
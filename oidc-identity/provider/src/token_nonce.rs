@@ -0,0 +1,23 @@
+//! Single-use ID tokens for `VerifyIdentity`. The client logs in with a
+//! `nonce` naming the account nonce it is about to consume, and the contract
+//! refuses a token whose `nonce` names another: once the account nonce moves
+//! on, a stolen token is worthless. A random salt before it keeps the login's
+//! nonce unpredictable, as OIDC expects.
+
+use alloc::{format, string::String};
+
+/// `nonce` to log in with for the account nonce `nonce`: `<salt>.<nonce>`.
+/// `salt` must not contain a dot.
+pub fn bound_nonce(salt: &str, nonce: u32) -> String {
+    format!("{salt}.{nonce}")
+}
+
+/// The account nonce a token's `nonce` claim is bound to; `None` if it isn't
+/// a [`bound_nonce`].
+pub fn bound_to(claim: &str) -> Option<u32> {
+    let (salt, nonce) = claim.rsplit_once('.')?;
+    if salt.is_empty() || salt.contains('.') {
+        return None;
+    }
+    nonce.parse().ok()
+}
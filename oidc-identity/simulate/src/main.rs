@@ -0,0 +1,228 @@
+//! Interactive simulator for the OIDC identity contract.
+//!
+//! Starts from an empty state, a snapshot file or a contract on a node, then
+//! applies actions typed on stdin by running the contract natively, exactly as
+//! the guest would. ID tokens are fabricated by the e2e test identity provider,
+//! so they are structurally valid and correctly signed, just not by a real IdP.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use client_sdk::rest_client::NodeApiHttpClient;
+use e2e::{local_tx_context, TestIdp};
+use oidc_identity::OidcIdentity;
+use oidc_provider::disclosure::{Claim, Disclosure};
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::{IdentityAction, PrivateInput};
+use sdk::{ContractInput, ContractName, Digestable, StateDigest};
+
+#[derive(Parser)]
+#[command(about = "Apply identity actions to a contract state interactively")]
+struct Args {
+    /// Hex-encoded state digest to start from, as written by `save`
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+    /// Fetch the starting state of `--contract` from this node instead
+    #[arg(long)]
+    node: Option<String>,
+    #[arg(long, default_value = "oidc_identity")]
+    contract: String,
+}
+
+const HELP: &str = "\
+register <subject>                 register <subject>.<contract>
+verify <subject> <nonce>           verify the account with a fresh token
+logout <subject>                   bump the account's session epoch
+unregister <subject>               delete the account
+disclose <subject> <claim> [plain] publish email, subject or issuer
+info <subject>                     GetIdentityInfo
+raw <subject> <json>               any JSON-encoded IdentityAction, with a token of <subject>
+state                              print the current state
+undo                               revert the last applied action
+save <path>                        write the state digest as hex
+help | quit";
+
+enum Step {
+    Output(String),
+    Nothing,
+    Quit,
+}
+
+struct Simulator {
+    idp: TestIdp,
+    contract_name: ContractName,
+    history: Vec<OidcIdentity>,
+}
+
+impl Simulator {
+    fn state(&self) -> &OidcIdentity {
+        self.history.last().expect("history is never empty")
+    }
+
+    fn account(&self, subject: &str) -> String {
+        format!("{}.{}", subject, self.contract_name)
+    }
+
+    /// Runs `action` on the current state and keeps the result on success.
+    fn apply(&mut self, subject: &str, action: IdentityAction) -> Result<String> {
+        let input = ContractInput {
+            initial_state: self.state().as_digest(),
+            identity: self.account(subject).into(),
+            tx_hash: sdk::TxHash(String::new()),
+            private_input: PrivateInput::Token(self.idp.id_token(subject)).to_bytes(),
+            // One block per applied action
+            tx_ctx: Some(local_tx_context(self.history.len() as u64)),
+            blobs: vec![action.as_blob(self.contract_name.clone())],
+            index: sdk::BlobIndex(0),
+        };
+        let (output, next_state, _) = oidc_identity::execute(input).map_err(|err| anyhow!(err))?;
+        self.history.push(next_state);
+        Ok(output)
+    }
+
+    fn handle(&mut self, line: &str) -> Result<Step> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        let context = self.idp.context.clone();
+        let jwk_pub_key = self.idp.jwk();
+
+        let output = match (command, args.as_slice()) {
+            ("", _) => return Ok(Step::Nothing),
+            ("quit" | "exit", _) => return Ok(Step::Quit),
+            ("help", _) => HELP.to_string(),
+            ("state", _) => serde_json::to_string_pretty(self.state())?,
+            ("undo", _) => {
+                if self.history.len() == 1 {
+                    bail!("Nothing to undo");
+                }
+                self.history.pop();
+                "Reverted".to_string()
+            }
+            ("save", [path]) => {
+                std::fs::write(path, hex::encode(&self.state().as_digest().0))?;
+                format!("Saved to {}", path)
+            }
+            ("register", [subject]) => self.apply(
+                subject,
+                IdentityAction::RegisterIdentity {
+                    account: self.account(subject),
+                    context,
+                    jwk_pub_key,
+                    recovery_codes: vec![],
+                    metadata: AccountMetadata::default(),
+                    nonce_strategy: NonceStrategy::default(),
+                },
+            )?,
+            ("verify", [subject, nonce]) => self.apply(
+                subject,
+                IdentityAction::VerifyIdentity {
+                    account: self.account(subject),
+                    nonce: nonce.parse().context("Invalid nonce")?,
+                    context,
+                    jwk_pub_key,
+                },
+            )?,
+            ("logout", [subject]) => self.apply(
+                subject,
+                IdentityAction::Logout {
+                    account: self.account(subject),
+                    context,
+                    jwk_pub_key,
+                },
+            )?,
+            ("unregister", [subject]) => self.apply(
+                subject,
+                IdentityAction::UnregisterIdentity {
+                    account: self.account(subject),
+                    context,
+                    jwk_pub_key,
+                },
+            )?,
+            ("disclose", [subject, claim, rest @ ..]) => {
+                let claim: Claim = serde_json::from_value(serde_json::Value::String(
+                    claim.to_string(),
+                ))
+                .context("Unknown claim")?;
+                let disclosure = match rest {
+                    ["plain"] => Disclosure::Plain,
+                    _ => Disclosure::Hashed,
+                };
+                self.apply(
+                    subject,
+                    IdentityAction::DiscloseClaim {
+                        account: self.account(subject),
+                        context,
+                        jwk_pub_key,
+                        claim,
+                        disclosure,
+                    },
+                )?
+            }
+            ("info", [subject]) => self.apply(
+                subject,
+                IdentityAction::GetIdentityInfo {
+                    account: self.account(subject),
+                },
+            )?,
+            ("raw", [subject, ..]) => {
+                let json = line
+                    .splitn(3, char::is_whitespace)
+                    .nth(2)
+                    .ok_or_else(|| anyhow!("Missing action"))?;
+                let action: IdentityAction =
+                    serde_json::from_str(json).context("Invalid IdentityAction JSON")?;
+                self.apply(subject, action)?
+            }
+            _ => bail!("Unknown command, try `help`"),
+        };
+        Ok(Step::Output(output))
+    }
+}
+
+fn load_state(args: &Args) -> Result<OidcIdentity> {
+    let digest = if let Some(path) = &args.snapshot {
+        let hex_state = std::fs::read_to_string(path).context("Failed to read snapshot")?;
+        StateDigest(hex::decode(hex_state.trim()).context("Snapshot is not hex")?)
+    } else if let Some(url) = &args.node {
+        let client = NodeApiHttpClient::new(url.clone())?;
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime
+            .block_on(client.get_contract(&args.contract.clone().into()))?
+            .state
+    } else {
+        return Ok(OidcIdentity::new());
+    };
+    OidcIdentity::from_bytes(&digest.0).map_err(|err| anyhow!(err))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut simulator = Simulator {
+        idp: TestIdp::new(),
+        contract_name: args.contract.clone().into(),
+        history: vec![load_state(&args)?],
+    };
+    println!("Test IdP issuer: {}", simulator.idp.context.issuer);
+    println!("{}", HELP);
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        match simulator.handle(line.trim()) {
+            Ok(Step::Output(output)) => println!("{}", output),
+            Ok(Step::Nothing) => {}
+            Ok(Step::Quit) => break,
+            Err(err) => println!("error: {:#}", err),
+        }
+    }
+    Ok(())
+}
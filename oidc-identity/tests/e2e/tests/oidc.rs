@@ -0,0 +1,114 @@
+use client_sdk::helpers::risc0::Risc0Prover;
+use e2e::{node_client, unique_contract_name, wait_for_state, wait_for_tx_context, TestIdp};
+use methods_identity::{GUEST_ELF, GUEST_ID};
+use oidc_identity::OidcIdentity;
+use oidc_provider::metadata::AccountMetadata;
+use oidc_provider::nonce::NonceStrategy;
+use oidc_provider::{IdentityAction, PrivateInput};
+use sdk::api::APIRegisterContract;
+use sdk::{BlobTransaction, ContractInput, Digestable, ProofTransaction};
+
+async fn settle(
+    client: &client_sdk::rest_client::NodeApiHttpClient,
+    contract_name: &sdk::ContractName,
+    state: &OidcIdentity,
+    account: &str,
+    action: IdentityAction,
+    private_input: PrivateInput,
+) -> OidcIdentity {
+    let blobs = vec![action.as_blob(contract_name.clone())];
+    let blob_tx = BlobTransaction {
+        identity: account.into(),
+        blobs: blobs.clone(),
+    };
+    let tx_hash = client.send_tx_blob(&blob_tx).await.unwrap();
+    let tx_ctx = wait_for_tx_context(client, &tx_hash).await.unwrap();
+
+    let inputs = ContractInput {
+        initial_state: state.as_digest(),
+        identity: blob_tx.identity,
+        tx_hash,
+        private_input: private_input.to_bytes(),
+        tx_ctx: Some(tx_ctx),
+        blobs,
+        index: sdk::BlobIndex(0),
+    };
+
+    // Run the contract natively first so we know what the node should end up with
+    let (_, next_state, _) = oidc_identity::execute(inputs.clone()).expect("native execution");
+
+    let proof = Risc0Prover::new(GUEST_ELF).prove(inputs).await.unwrap();
+    client
+        .send_tx_proof(&ProofTransaction {
+            proof,
+            contract_name: contract_name.clone(),
+        })
+        .await
+        .unwrap();
+
+    wait_for_state(client, contract_name, &next_state.as_digest())
+        .await
+        .unwrap();
+    next_state
+}
+
+#[tokio::test]
+async fn oidc_register_then_verify() {
+    let Some(client) = node_client() else {
+        eprintln!("HYLE_NODE_URL not set, skipping");
+        return;
+    };
+    let contract_name = unique_contract_name("oidc_identity_e2e");
+    let idp = TestIdp::new();
+
+    let state = OidcIdentity::new();
+    client
+        .register_contract(&APIRegisterContract {
+            verifier: "risc0".into(),
+            program_id: sdk::ProgramId(sdk::to_u8_array(&GUEST_ID).to_vec()),
+            state_digest: state.as_digest(),
+            contract_name: contract_name.clone(),
+        })
+        .await
+        .unwrap();
+    wait_for_state(&client, &contract_name, &state.as_digest())
+        .await
+        .unwrap();
+
+    let account = format!("alice.{contract_name}");
+    let token = idp.id_token("alice");
+
+    let state = settle(
+        &client,
+        &contract_name,
+        &state,
+        &account,
+        IdentityAction::RegisterIdentity {
+            account: account.clone(),
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+            recovery_codes: vec![],
+            metadata: AccountMetadata::default(),
+            nonce_strategy: NonceStrategy::default(),
+        },
+        PrivateInput::Token(token.clone()),
+    )
+    .await;
+    assert_eq!(state.get_nonce(&account), Ok(0));
+
+    let state = settle(
+        &client,
+        &contract_name,
+        &state,
+        &account,
+        IdentityAction::VerifyIdentity {
+            account: account.clone(),
+            nonce: 0,
+            context: idp.context.clone(),
+            jwk_pub_key: idp.jwk(),
+        },
+        PrivateInput::Token(token),
+    )
+    .await;
+    assert_eq!(state.get_nonce(&account), Ok(1));
+}